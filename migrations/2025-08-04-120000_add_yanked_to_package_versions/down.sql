@@ -0,0 +1,2 @@
+-- Remove yanked column from package_versions table
+ALTER TABLE package_versions DROP COLUMN IF EXISTS yanked;
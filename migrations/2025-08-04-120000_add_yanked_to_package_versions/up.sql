@@ -0,0 +1,2 @@
+-- Add yanked column to package_versions table
+ALTER TABLE package_versions ADD COLUMN yanked BOOLEAN NOT NULL DEFAULT FALSE;
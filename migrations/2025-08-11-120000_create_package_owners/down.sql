@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_package_owner_invites_invited_user_id;
+DROP INDEX IF EXISTS idx_package_owners_user_id;
+
+DROP TABLE IF EXISTS package_owner_invites;
+DROP TABLE IF EXISTS package_owners;
@@ -0,0 +1,30 @@
+-- Owners of a package, who are allowed to publish new versions and manage ownership
+CREATE TABLE package_owners (
+    id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    package_id uuid NOT NULL REFERENCES packages(id) ON DELETE CASCADE,
+    user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    -- A user can only own a package once
+    UNIQUE (package_id, user_id)
+);
+
+-- Pending invitations for users to become owners of a package
+CREATE TABLE package_owner_invites (
+    id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    package_id uuid NOT NULL REFERENCES packages(id) ON DELETE CASCADE,
+    invited_user_id uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    invited_by uuid NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    -- A user can only have one pending invite per package
+    UNIQUE (package_id, invited_user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_package_owners_user_id
+ON package_owners(user_id);
+
+CREATE INDEX IF NOT EXISTS idx_package_owner_invites_invited_user_id
+ON package_owner_invites(invited_user_id);
+
+-- Existing packages are owned by the user who first published them
+INSERT INTO package_owners (package_id, user_id)
+SELECT id, user_owner FROM packages;
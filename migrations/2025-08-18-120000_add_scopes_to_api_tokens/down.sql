@@ -0,0 +1,3 @@
+-- Remove scopes and package_glob columns from api_tokens table
+ALTER TABLE api_tokens DROP COLUMN IF EXISTS package_glob;
+ALTER TABLE api_tokens DROP COLUMN IF EXISTS scopes;
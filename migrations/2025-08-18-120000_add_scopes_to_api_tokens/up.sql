@@ -0,0 +1,4 @@
+-- Add scopes and an optional package name glob to api_tokens table.
+-- Existing tokens keep all scopes and can be used for any package.
+ALTER TABLE api_tokens ADD COLUMN scopes TEXT[] NOT NULL DEFAULT '{publish-new,publish-update,yank}';
+ALTER TABLE api_tokens ADD COLUMN package_glob VARCHAR;
@@ -0,0 +1,3 @@
+-- Remove workspace_upload_id column from uploads table
+DROP INDEX IF EXISTS idx_uploads_workspace_upload_id;
+ALTER TABLE uploads DROP COLUMN IF EXISTS workspace_upload_id;
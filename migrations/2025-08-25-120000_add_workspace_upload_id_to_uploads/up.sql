@@ -0,0 +1,5 @@
+-- Add workspace_upload_id column to uploads table
+ALTER TABLE uploads ADD COLUMN workspace_upload_id uuid DEFAULT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_uploads_workspace_upload_id
+ON uploads(workspace_upload_id);
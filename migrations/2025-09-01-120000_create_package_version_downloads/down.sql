@@ -0,0 +1,3 @@
+ALTER TABLE packages DROP COLUMN downloads;
+
+DROP TABLE IF EXISTS package_version_downloads;
@@ -0,0 +1,14 @@
+-- Daily download counts for each package version
+CREATE TABLE package_version_downloads (
+    package_version_id uuid NOT NULL REFERENCES package_versions(id) ON DELETE CASCADE,
+    date DATE NOT NULL DEFAULT CURRENT_DATE,
+    downloads BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (package_version_id, date)
+);
+
+CREATE INDEX IF NOT EXISTS idx_package_version_downloads_date
+ON package_version_downloads(date);
+
+-- Total downloads of all versions of a package, kept alongside the daily rollups
+-- so search results can be ranked by popularity without aggregating them.
+ALTER TABLE packages ADD COLUMN downloads BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,25 @@
+-- Jobs that build and upload a project tarball in the background. The ID of a job is
+-- the ID of the upload it creates.
+CREATE TABLE upload_jobs (
+    id uuid PRIMARY KEY,
+    forc_version VARCHAR NOT NULL,
+    -- The uploaded tarball, cleared once the job has finished
+    tarball BYTEA,
+    status VARCHAR NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'running', 'succeeded', 'failed')),
+    -- The latest progress message of the job
+    message TEXT,
+    -- The JSON encoded error of the last failed attempt
+    error TEXT,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL DEFAULT 3,
+    -- The earliest time a pending job can run, used to back off retries
+    run_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    -- When a worker last claimed or reported progress on a running job
+    locked_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_upload_jobs_status_run_at
+ON upload_jobs(status, run_at);
@@ -0,0 +1,2 @@
+-- The compiler output of a failed build
+ALTER TABLE upload_jobs ADD COLUMN diagnostics TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE upload_jobs DROP COLUMN report;
+ALTER TABLE upload_jobs DROP COLUMN dry_run;
@@ -0,0 +1,4 @@
+-- Dry-run jobs validate and build a project without storing it, and record the uploads
+-- that would have been created in the report
+ALTER TABLE upload_jobs ADD COLUMN dry_run BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE upload_jobs ADD COLUMN report TEXT;
@@ -0,0 +1,4 @@
+DROP TABLE package_name_flags;
+DROP FUNCTION package_name_lookalike(VARCHAR);
+DROP INDEX IF EXISTS idx_packages_normalized_name;
+ALTER TABLE packages DROP COLUMN normalized_name;
@@ -0,0 +1,54 @@
+-- The normalized form of each package name, which package names are compared by to detect
+-- collisions. Only names that differ by case and separators collide. This must match
+-- `normalize_package_name` for the ASCII names that forc allows.
+ALTER TABLE packages ADD COLUMN normalized_name VARCHAR;
+UPDATE packages SET normalized_name = replace(lower(package_name), '_', '-');
+ALTER TABLE packages ALTER COLUMN normalized_name SET NOT NULL;
+
+-- Existing packages whose names collide can't be renamed here, since their names are
+-- published in the index. An admin must remove or rename all but one of them before this
+-- migration can run.
+DO $$
+DECLARE
+    collisions TEXT;
+BEGIN
+    SELECT string_agg(names, '; ') INTO collisions
+    FROM (
+        SELECT string_agg(package_name, ', ' ORDER BY created_at) AS names
+        FROM packages
+        GROUP BY normalized_name
+        HAVING count(*) > 1
+    ) AS colliding;
+    IF collisions IS NOT NULL THEN
+        RAISE EXCEPTION 'Package names collide when normalized: %', collisions
+            USING HINT = 'Remove or rename all but one package of each group before migrating.';
+    END IF;
+END
+$$;
+
+CREATE UNIQUE INDEX idx_packages_normalized_name
+ON packages(normalized_name);
+
+-- The lookalike form of a normalized package name, where ASCII lookalikes are folded
+-- together. New packages that share it with an existing package are flagged for review.
+-- This must match `lookalike_package_name`.
+CREATE FUNCTION package_name_lookalike(normalized_name VARCHAR) RETURNS VARCHAR
+LANGUAGE SQL IMMUTABLE STRICT
+AS $$
+    SELECT replace(replace(translate(normalized_name, '01i', 'oll'), 'rn', 'm'), 'vv', 'w')
+$$;
+
+-- New packages whose names are near-duplicates of popular packages, for admin review
+CREATE TABLE package_name_flags (
+    id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    package_id uuid NOT NULL REFERENCES packages(id) ON DELETE CASCADE,
+    similar_package_id uuid NOT NULL REFERENCES packages(id) ON DELETE CASCADE,
+    similarity REAL NOT NULL,
+    reviewed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    -- A package is only flagged once for each similar package
+    UNIQUE (package_id, similar_package_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_package_name_flags_reviewed_at
+ON package_name_flags(reviewed_at);
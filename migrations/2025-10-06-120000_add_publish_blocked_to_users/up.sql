@@ -0,0 +1,2 @@
+-- Users who are blocked by an admin can't publish packages
+ALTER TABLE users ADD COLUMN publish_blocked BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,18 @@
+-- The audit log of security-relevant events, such as logins, token changes and publishes.
+-- Actions taken by admins are recorded as `admin-*` events, with the target of the action
+-- kept in the payload.
+CREATE TABLE audit_events (
+    id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id uuid NOT NULL REFERENCES users(id) ON DELETE RESTRICT,
+    event VARCHAR NOT NULL,
+    ip_address VARCHAR,
+    user_agent VARCHAR,
+    payload JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_events_user_id_created_at
+ON audit_events(user_id, created_at DESC);
+
+CREATE INDEX IF NOT EXISTS idx_audit_events_created_at
+ON audit_events(created_at DESC);
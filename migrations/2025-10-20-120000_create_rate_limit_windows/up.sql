@@ -0,0 +1,11 @@
+-- Request counts of the current rate limit window of each bucket, for the Postgres
+-- rate limit store
+CREATE TABLE rate_limit_windows (
+    key VARCHAR PRIMARY KEY,
+    window_start TIMESTAMPTZ NOT NULL,
+    window_end TIMESTAMPTZ NOT NULL,
+    count BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_rate_limit_windows_window_end
+ON rate_limit_windows(window_end);
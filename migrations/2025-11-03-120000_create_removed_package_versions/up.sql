@@ -0,0 +1,9 @@
+-- The versions that admins removed, which can't be published again
+CREATE TABLE removed_package_versions (
+    id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    package_id uuid NOT NULL REFERENCES packages(id) ON DELETE CASCADE,
+    num VARCHAR NOT NULL,
+    removed_by uuid NOT NULL REFERENCES users(id) ON DELETE RESTRICT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (package_id, num)
+);
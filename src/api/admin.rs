@@ -0,0 +1,104 @@
+use crate::handlers::toolchain::InstalledToolchain;
+use crate::models::{self, PackageNameFlagInfo};
+use chrono::{DateTime, Utc};
+use rocket::serde::{Deserialize, Serialize};
+
+/// A user as seen by admins.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminUser {
+    pub full_name: String,
+    pub github_login: String,
+    pub github_url: String,
+    pub avatar_url: Option<String>,
+    pub email: Option<String>,
+    pub is_admin: bool,
+    pub publish_blocked: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<models::User> for AdminUser {
+    fn from(user: models::User) -> Self {
+        AdminUser {
+            full_name: user.full_name,
+            github_login: user.github_login,
+            github_url: user.github_url,
+            avatar_url: user.avatar_url,
+            email: user.email,
+            is_admin: user.is_admin,
+            publish_blocked: user.publish_blocked,
+            created_at: user.created_at,
+        }
+    }
+}
+
+/// The response to a request that changes a user.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminUserResponse {
+    pub user: AdminUser,
+}
+
+/// The TransferOwnership request.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferOwnershipRequest {
+    pub github_login: String,
+}
+
+/// The response to a TransferOwnership request.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferOwnershipResponse {
+    pub name: String,
+    pub owner: String,
+    pub previous_owners: Vec<String>,
+}
+
+/// The response to a request to remove a package version.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoveVersionResponse {
+    pub name: String,
+    pub version: String,
+}
+
+/// The response to a package name flags request.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageNameFlagsResponse {
+    pub flags: Vec<PackageNameFlagInfo>,
+}
+
+/// An installed version of forc.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolchainInfo {
+    pub version: String,
+    pub size_bytes: u64,
+    pub last_used: DateTime<Utc>,
+}
+
+impl From<InstalledToolchain> for ToolchainInfo {
+    fn from(toolchain: InstalledToolchain) -> Self {
+        ToolchainInfo {
+            version: toolchain.version,
+            size_bytes: toolchain.size_bytes,
+            last_used: toolchain.last_used,
+        }
+    }
+}
+
+/// The response to a request for the installed versions of forc.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolchainsResponse {
+    pub toolchains: Vec<ToolchainInfo>,
+}
+
+/// The response to a request to install a version of forc.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolchainResponse {
+    pub toolchain: ToolchainInfo,
+}
@@ -1,3 +1,4 @@
+use crate::db::api_token::TokenScope;
 use crate::models;
 use chrono::{DateTime, Utc};
 use rocket::serde::{Deserialize, Serialize};
@@ -8,6 +9,9 @@ pub struct Token {
     pub id: String,
     pub name: String,
     pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub scopes: Vec<String>,
+    pub package_glob: Option<String>,
     pub token: Option<String>,
 }
 
@@ -17,15 +21,23 @@ impl From<models::ApiToken> for Token {
             id: token.id.to_string(),
             name: token.friendly_name,
             created_at: token.created_at,
+            expires_at: token.expires_at,
+            scopes: token.scopes,
+            package_glob: token.package_glob,
             // We don't return the hashed token, as it's a secret.
             token: None,
         }
     }
 }
-/// The CreateToken request.
+/// The CreateToken request. If `scopes` is omitted, the token has all scopes.
+/// If `package_glob` is omitted, the token can be used for any package.
 #[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct CreateTokenRequest {
     pub name: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub scopes: Option<Vec<TokenScope>>,
+    pub package_glob: Option<String>,
 }
 
 /// The response to a CreateToken request.
@@ -0,0 +1,441 @@
+use crate::file_uploader::storage::FileStream;
+use crate::handlers::upload::UploadError;
+use crate::models::{DailyVersionDownloads, VersionDownloads};
+use rocket::{
+    http::{ContentType, Header, Status},
+    request::{self, FromRequest},
+    response::Responder,
+    Request, Response,
+};
+use serde::Serialize;
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use tokio::io::{sink, AsyncRead, AsyncReadExt};
+
+/// The number of days of daily download counts returned by default.
+pub const DEFAULT_STATS_DAYS: u32 = 90;
+
+/// The maximum number of days of daily download counts that can be requested.
+pub const MAX_STATS_DAYS: u32 = 365;
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageStatsResponse {
+    pub name: String,
+    /// Total downloads of all versions of the package.
+    pub downloads: i64,
+    /// Total downloads of each version, highest version first.
+    pub versions: Vec<VersionDownloads>,
+    /// Downloads of each version per day, oldest day first.
+    pub daily_downloads: Vec<DailyVersionDownloads>,
+}
+
+/// A file of a published package version that can be downloaded from the registry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageFile {
+    /// The source code tarball.
+    Tarball,
+    /// The ABI JSON file.
+    Abi,
+    /// The documentation tarball.
+    Docs,
+}
+
+impl PackageFile {
+    pub fn content_type(&self) -> ContentType {
+        match self {
+            PackageFile::Tarball | PackageFile::Docs => ContentType::GZIP,
+            PackageFile::Abi => ContentType::JSON,
+        }
+    }
+
+    pub fn file_name(&self, package_name: &str, version: &str) -> String {
+        match self {
+            PackageFile::Tarball => format!("{package_name}-{version}.tgz"),
+            PackageFile::Abi => format!("{package_name}-{version}-abi.json"),
+            PackageFile::Docs => format!("{package_name}-{version}-docs.tgz"),
+        }
+    }
+}
+
+/// A byte range of a file, with an inclusive end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// The part of a file requested by a `Range` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeRequest {
+    /// The whole file, for headers that aren't a single byte range.
+    Full,
+    /// A single byte range within the file.
+    Partial(ByteRange),
+    /// A byte range that starts after the end of the file.
+    Unsatisfiable,
+}
+
+/// Parses a `Range` header for a file of the given length.
+pub fn parse_range_header(header: &str, len: usize) -> RangeRequest {
+    let Some(spec) = header.trim().strip_prefix("bytes=") else {
+        return RangeRequest::Full;
+    };
+    // Multiple ranges are not supported, so the whole file is served instead.
+    if spec.contains(',') {
+        return RangeRequest::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return RangeRequest::Full;
+    };
+
+    let range = match (start.parse::<usize>().ok(), end.parse::<usize>().ok()) {
+        // The last `suffix` bytes of the file.
+        (None, Some(suffix)) if start.is_empty() => {
+            if suffix == 0 || len == 0 {
+                return RangeRequest::Unsatisfiable;
+            }
+            ByteRange {
+                start: len.saturating_sub(suffix),
+                end: len - 1,
+            }
+        }
+        (Some(start), None) if end.is_empty() => ByteRange {
+            start,
+            end: len.saturating_sub(1),
+        },
+        (Some(start), Some(end)) if start <= end => ByteRange {
+            start,
+            end: end.min(len.saturating_sub(1)),
+        },
+        _ => return RangeRequest::Full,
+    };
+
+    if range.start >= len {
+        return RangeRequest::Unsatisfiable;
+    }
+    RangeRequest::Partial(range)
+}
+
+/// Returns the ETag of a stored file. Files are stored by their IPFS hash, so the hash is
+/// a strong ETag.
+pub fn file_etag(ipfs_hash: &str) -> String {
+    format!("\"{ipfs_hash}\"")
+}
+
+/// The conditional and range headers of a request for a file.
+#[derive(Debug, Clone, Default)]
+pub struct FileRequest {
+    pub if_none_match: Option<String>,
+    pub if_range: Option<String>,
+    pub range: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for FileRequest {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let header = |name| request.headers().get_one(name).map(str::to_string);
+        request::Outcome::Success(FileRequest {
+            if_none_match: header("If-None-Match"),
+            if_range: header("If-Range"),
+            range: header("Range"),
+        })
+    }
+}
+
+impl FileRequest {
+    /// Returns true if the client already has the file with the given ETag.
+    pub fn is_not_modified(&self, etag: &str) -> bool {
+        self.if_none_match.as_deref().is_some_and(|tags| {
+            tags.split(',')
+                .any(|tag| tag.trim() == etag || tag.trim() == "*")
+        })
+    }
+
+    /// Returns the part of a file of the given length that is requested. A range is only
+    /// served if it is for this version of the file.
+    pub fn range(&self, etag: &str, len: usize) -> RangeRequest {
+        let if_range_matches = self
+            .if_range
+            .as_deref()
+            .is_none_or(|tag| tag.trim() == etag);
+        match self.range.as_deref() {
+            Some(header) if if_range_matches => parse_range_header(header, len),
+            _ => RangeRequest::Full,
+        }
+    }
+
+    /// Returns the body of the response for a stored file. The file is only fetched if the
+    /// client doesn't have it already, and is skipped to the start of the requested range.
+    pub async fn body(
+        &self,
+        ipfs_hash: &str,
+        fetch: impl Future<Output = Result<FileStream, UploadError>>,
+    ) -> Result<FileBody, UploadError> {
+        let etag = file_etag(ipfs_hash);
+        if self.is_not_modified(&etag) {
+            return Ok(FileBody::NotModified);
+        }
+
+        let stream = fetch.await?;
+        // Ranges can't be served if the backend doesn't report the size of the file.
+        let Some(len) = stream.len.and_then(|len| usize::try_from(len).ok()) else {
+            return Ok(FileBody::Full(stream));
+        };
+        match self.range(&etag, len) {
+            RangeRequest::Full => Ok(FileBody::Full(stream)),
+            RangeRequest::Unsatisfiable => Ok(FileBody::Unsatisfiable { len }),
+            RangeRequest::Partial(range) => {
+                let mut reader = stream.reader;
+                tokio::io::copy(&mut (&mut reader).take(range.start as u64), &mut sink())
+                    .await
+                    .map_err(|err| UploadError::IpfsFetchFailed(err.to_string()))?;
+                let range_len = (range.end - range.start + 1) as u64;
+                Ok(FileBody::Partial {
+                    range,
+                    len,
+                    reader: Box::pin(reader.take(range_len)),
+                })
+            }
+        }
+    }
+}
+
+/// The body of a response with a stored file.
+pub enum FileBody {
+    /// The client already has the file.
+    NotModified,
+    /// The whole file.
+    Full(FileStream),
+    /// A byte range of a file of length `len`.
+    Partial {
+        range: ByteRange,
+        len: usize,
+        reader: Pin<Box<dyn AsyncRead + Send>>,
+    },
+    /// The requested range starts after the end of the file of length `len`.
+    Unsatisfiable { len: usize },
+}
+
+impl FileBody {
+    /// Returns true if the whole file is sent.
+    pub fn is_full(&self) -> bool {
+        matches!(self, FileBody::Full(_))
+    }
+}
+
+/// A response with the contents of a file in the storage backend, which are streamed
+/// from the backend as they are sent.
+///
+/// The IPFS hash of the file is used as its ETag, and the response can be cached forever.
+/// Single byte range requests are supported.
+pub struct FileResponse {
+    pub body: FileBody,
+    pub content_type: ContentType,
+    pub file_name: String,
+    pub ipfs_hash: String,
+}
+
+impl<'r, 'o: 'r> Responder<'r, 'o> for FileResponse {
+    fn respond_to(self, _request: &'r Request<'_>) -> rocket::response::Result<'o> {
+        let mut response = Response::build();
+        response
+            .header(Header::new("ETag", file_etag(&self.ipfs_hash)))
+            .header(Header::new(
+                "Cache-Control",
+                "public, max-age=31536000, immutable",
+            ))
+            .header(Header::new("Accept-Ranges", "bytes"));
+
+        if !matches!(self.body, FileBody::NotModified) {
+            response.header(self.content_type).header(Header::new(
+                "Content-Disposition",
+                format!("attachment; filename=\"{}\"", self.file_name),
+            ));
+        }
+
+        // The body is streamed, so its length is set from the size of the file.
+        match self.body {
+            FileBody::NotModified => response.status(Status::NotModified).ok(),
+            FileBody::Full(stream) => {
+                if let Some(len) = stream.len {
+                    response.raw_header("Content-Length", len.to_string());
+                }
+                response.streamed_body(stream.reader).ok()
+            }
+            FileBody::Partial { range, len, reader } => response
+                .status(Status::PartialContent)
+                .header(Header::new(
+                    "Content-Range",
+                    format!("bytes {}-{}/{len}", range.start, range.end),
+                ))
+                .raw_header("Content-Length", (range.end - range.start + 1).to_string())
+                .streamed_body(reader)
+                .ok(),
+            FileBody::Unsatisfiable { len } => response
+                .status(Status::RangeNotSatisfiable)
+                .header(Header::new("Content-Range", format!("bytes */{len}")))
+                .ok(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range_header() {
+        let range = |start, end| RangeRequest::Partial(ByteRange { start, end });
+
+        assert_eq!(parse_range_header("bytes=0-9", 100), range(0, 9));
+        assert_eq!(parse_range_header("bytes=90-", 100), range(90, 99));
+        assert_eq!(parse_range_header("bytes=-10", 100), range(90, 99));
+        assert_eq!(parse_range_header("bytes=-200", 100), range(0, 99));
+        assert_eq!(parse_range_header("bytes=50-200", 100), range(50, 99));
+    }
+
+    #[test]
+    fn test_parse_range_header_serves_whole_file() {
+        assert_eq!(parse_range_header("items=0-9", 100), RangeRequest::Full);
+        assert_eq!(
+            parse_range_header("bytes=0-9,20-29", 100),
+            RangeRequest::Full
+        );
+        assert_eq!(parse_range_header("bytes=9-0", 100), RangeRequest::Full);
+        assert_eq!(parse_range_header("bytes=abc", 100), RangeRequest::Full);
+    }
+
+    #[test]
+    fn test_parse_range_header_unsatisfiable() {
+        assert_eq!(
+            parse_range_header("bytes=100-", 100),
+            RangeRequest::Unsatisfiable
+        );
+        assert_eq!(
+            parse_range_header("bytes=-0", 100),
+            RangeRequest::Unsatisfiable
+        );
+        assert_eq!(
+            parse_range_header("bytes=0-", 0),
+            RangeRequest::Unsatisfiable
+        );
+    }
+
+    const CONTENTS: &[u8] = b"0123456789";
+
+    fn file_response(body: FileBody) -> FileResponse {
+        FileResponse {
+            body,
+            content_type: ContentType::JSON,
+            file_name: "pkg-0.1.0-abi.json".to_string(),
+            ipfs_hash: "bafkreitest".to_string(),
+        }
+    }
+
+    #[rocket::get("/file")]
+    async fn file(request: FileRequest) -> FileResponse {
+        let fetch = async { Ok(FileStream::from(CONTENTS.to_vec())) };
+        file_response(request.body("bafkreitest", fetch).await.unwrap())
+    }
+
+    /// Serves a file that can't be fetched, so only cached responses succeed.
+    #[rocket::get("/missing")]
+    async fn missing(request: FileRequest) -> Result<FileResponse, Status> {
+        let fetch = async { Err(UploadError::IpfsFetchFailed("missing".to_string())) };
+        match request.body("bafkreitest", fetch).await {
+            Ok(body) => Ok(file_response(body)),
+            Err(_) => Err(Status::BadGateway),
+        }
+    }
+
+    /// Serves a file from a backend that doesn't report its size.
+    #[rocket::get("/unsized")]
+    async fn unsized_file(request: FileRequest) -> FileResponse {
+        let fetch = async {
+            Ok(FileStream {
+                len: None,
+                reader: Box::pin(CONTENTS),
+            })
+        };
+        file_response(request.body("bafkreitest", fetch).await.unwrap())
+    }
+
+    fn client() -> rocket::local::blocking::Client {
+        let rocket = rocket::build().mount("/", rocket::routes![file, missing, unsized_file]);
+        rocket::local::blocking::Client::tracked(rocket).expect("valid rocket instance")
+    }
+
+    #[test]
+    fn test_file_response() {
+        let client = client();
+
+        let response = client.get("/file").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+        assert_eq!(response.headers().get_one("ETag"), Some("\"bafkreitest\""));
+        assert_eq!(response.headers().get_one("Content-Length"), Some("10"));
+        assert_eq!(response.into_bytes(), Some(CONTENTS.to_vec()));
+
+        let response = client
+            .get("/file")
+            .header(Header::new("If-None-Match", "\"bafkreitest\""))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotModified);
+    }
+
+    #[test]
+    fn test_file_response_not_modified_without_fetching() {
+        let client = client();
+
+        let response = client
+            .get("/missing")
+            .header(Header::new("If-None-Match", "\"bafkreitest\""))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotModified);
+
+        let response = client.get("/missing").dispatch();
+        assert_eq!(response.status(), Status::BadGateway);
+    }
+
+    #[test]
+    fn test_file_response_range() {
+        let client = client();
+
+        let response = client
+            .get("/file")
+            .header(Header::new("Range", "bytes=2-4"))
+            .dispatch();
+        assert_eq!(response.status(), Status::PartialContent);
+        assert_eq!(
+            response.headers().get_one("Content-Range"),
+            Some("bytes 2-4/10")
+        );
+        assert_eq!(response.headers().get_one("Content-Length"), Some("3"));
+        assert_eq!(response.into_bytes(), Some(b"234".to_vec()));
+
+        let response = client
+            .get("/file")
+            .header(Header::new("Range", "bytes=2-4"))
+            .header(Header::new("If-Range", "\"other\""))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let response = client
+            .get("/file")
+            .header(Header::new("Range", "bytes=20-"))
+            .dispatch();
+        assert_eq!(response.status(), Status::RangeNotSatisfiable);
+
+        // Files of unknown size are served whole.
+        let response = client
+            .get("/unsized")
+            .header(Header::new("Range", "bytes=2-4"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_bytes(), Some(CONTENTS.to_vec()));
+    }
+}
@@ -1,9 +1,18 @@
+pub mod admin;
 pub mod api_token;
 pub mod auth;
+pub mod download;
+pub mod owners;
 pub mod pagination;
 pub mod publish;
+pub mod readiness;
 pub mod search;
 
+use crate::db::error::DatabaseError;
+use crate::handlers::admin::AdminError;
+use crate::handlers::package_name::PackageNameError;
+use crate::handlers::publish::PublishError;
+use crate::handlers::upload::UploadError;
 use rocket::{
     http::{ContentType, Status},
     response::Responder,
@@ -29,17 +38,26 @@ pub enum ApiError {
 
     #[error("Database error: {0}")]
     #[serde(skip)]
-    Database(#[from] crate::db::error::DatabaseError),
+    Database(#[from] DatabaseError),
 
     #[error("GitHub error: {0}")]
     #[serde(skip)]
     Github(#[from] crate::github::GithubError),
 
     #[error("Upload error: {0}")]
-    Upload(#[from] crate::handlers::upload::UploadError),
+    Upload(#[from] UploadError),
 
     #[error("Publish error: {0}")]
-    Publish(#[from] crate::handlers::publish::PublishError),
+    Publish(#[from] PublishError),
+
+    #[error("Yank error: {0}")]
+    Yank(#[from] crate::handlers::yank::YankError),
+
+    #[error("Admin error: {0}")]
+    Admin(#[from] AdminError),
+
+    #[error("Too many requests, retry after {0} seconds")]
+    RateLimited(u64),
 }
 
 impl<'r, 'o: 'r> Responder<'r, 'o> for ApiError {
@@ -47,24 +65,97 @@ impl<'r, 'o: 'r> Responder<'r, 'o> for ApiError {
         error!("API error: {self}");
         let (status, message) = match self {
             ApiError::Generic(ref err, ref status) => (*status, err.to_string()),
+            ApiError::Database(
+                ref err @ (DatabaseError::NotPackageOwner(_)
+                | DatabaseError::TokenScopeDenied(..)
+                | DatabaseError::PublishBlocked),
+            ) => (Status::Forbidden, format!("Database error: {err}")),
+            ApiError::Database(
+                ref err @ (DatabaseError::AlreadyPackageOwner(..)
+                | DatabaseError::LastPackageOwner(_)
+                | DatabaseError::InvalidPackageGlob(_)),
+            ) => (Status::BadRequest, format!("Database error: {err}")),
+            ApiError::Database(ref err @ DatabaseError::NotFound(..)) => {
+                (Status::NotFound, format!("Database error: {err}"))
+            }
             ApiError::Database(ref err) => (
                 Status::InternalServerError,
                 format!("Database error: {err}"),
             ),
             ApiError::Github(ref err) => (Status::Unauthorized, format!("GitHub error: {err}")),
             ApiError::Upload(ref err) => (Status::BadRequest, format!("Upload error: {err}")),
+            ApiError::Publish(
+                ref err @ (PublishError::VersionExists { .. }
+                | PublishError::VersionRemoved { .. }
+                | PublishError::VersionNotIncreasing { .. }
+                | PublishError::PackageName(PackageNameError::Taken { .. })),
+            ) => (Status::Conflict, format!("Publish error: {err}")),
+            ApiError::Publish(ref err @ PublishError::Database(DatabaseError::PublishBlocked)) => {
+                (Status::Forbidden, format!("Publish error: {err}"))
+            }
             ApiError::Publish(ref err) => (Status::BadRequest, format!("Publish error: {err}")),
+            ApiError::Yank(ref err) => (Status::BadRequest, format!("Yank error: {err}")),
+            ApiError::Admin(ref err @ AdminError::Database(DatabaseError::NotFound(..))) => {
+                (Status::NotFound, format!("Admin error: {err}"))
+            }
+            ApiError::Admin(
+                ref err @ (AdminError::InvalidVersion(_)
+                | AdminError::Upload(UploadError::InvalidForcVersion(_))),
+            ) => (Status::BadRequest, format!("Admin error: {err}")),
+            ApiError::Admin(ref err @ AdminError::Index(_)) => {
+                (Status::BadGateway, format!("Admin error: {err}"))
+            }
+            ApiError::Admin(ref err) => {
+                (Status::InternalServerError, format!("Admin error: {err}"))
+            }
+            ApiError::RateLimited(_) => (Status::TooManyRequests, self.to_string()),
         };
-        let body = json!({
+        let mut body = json!({
             "status": status.code,
             "error": message,
-        })
-        .to_string();
+        });
+        if let ApiError::Publish(PublishError::UnsatisfiableDependencies(ref deps)) = self {
+            body["unsatisfiedDependencies"] = json!(deps);
+        }
+        match self {
+            ApiError::Publish(
+                PublishError::VersionExists {
+                    ref name,
+                    ref version,
+                }
+                | PublishError::VersionRemoved {
+                    ref name,
+                    ref version,
+                },
+            ) => {
+                body["versionConflict"] = json!({ "name": name, "version": version });
+            }
+            ApiError::Publish(PublishError::VersionNotIncreasing {
+                ref name,
+                ref version,
+                ref latest,
+            }) => {
+                body["versionConflict"] =
+                    json!({ "name": name, "version": version, "latest": latest });
+            }
+            _ => {}
+        }
+        if let ApiError::Upload(UploadError::FailedToCompile(ref diagnostics)) = self {
+            body["diagnostics"] = json!(diagnostics);
+        }
+        if let ApiError::RateLimited(retry_after) = self {
+            body["retryAfter"] = json!(retry_after);
+        }
+        let body = body.to_string();
 
-        rocket::Response::build()
+        let mut response = rocket::Response::build();
+        response
             .status(status)
             .sized_body(body.len(), Cursor::new(body))
-            .header(ContentType::JSON)
-            .ok()
+            .header(ContentType::JSON);
+        if let ApiError::RateLimited(retry_after) = self {
+            response.raw_header("Retry-After", retry_after.to_string());
+        }
+        response.ok()
     }
 }
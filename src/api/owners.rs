@@ -0,0 +1,23 @@
+use crate::models::{AuthorInfo, PackageOwnerInviteInfo};
+use rocket::serde::{Deserialize, Serialize};
+
+/// The response to a package owners request.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OwnersResponse {
+    pub owners: Vec<AuthorInfo>,
+}
+
+/// The InviteOwner request.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct InviteOwnerRequest {
+    pub github_login: String,
+}
+
+/// The response to an owner invites request.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OwnerInvitesResponse {
+    pub invites: Vec<PackageOwnerInviteInfo>,
+}
@@ -1,3 +1,7 @@
+use crate::db::error::DatabaseError;
+use crate::db::upload_job::UploadJobStatus;
+use crate::models::{NewUpload, UploadJob};
+use chrono::{DateTime, Utc};
 use rocket::serde::{Deserialize, Serialize};
 use semver::Version;
 use url::Url;
@@ -8,6 +12,9 @@ use uuid::Uuid;
 pub struct PublishRequest {
     pub upload_id: Uuid,
     pub urls: Option<Vec<Url>>,
+    /// Validate the request without publishing anything.
+    #[serde(default)]
+    pub dry_run: bool,
 }
 
 /// The publish response.
@@ -17,8 +24,110 @@ pub struct PublishResponse {
     pub version: Version,
 }
 
+/// The response to a publish request for a workspace upload, with the published
+/// members in publish order.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WorkspacePublishResponse {
+    pub members: Vec<PublishResponse>,
+}
+
+/// A dependency of a package that a dry-run publish request would publish.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DryRunDependency {
+    pub name: String,
+    pub version_req: String,
+}
+
+/// A package that a dry-run publish request would publish.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DryRunPackage {
+    pub name: String,
+    pub version: Version,
+    pub upload_id: Uuid,
+    pub forc_version: String,
+    pub source_code_ipfs_hash: String,
+    pub abi_ipfs_hash: Option<String>,
+    pub docs_ipfs_hash: Option<String>,
+    pub bytecode_identifier: Option<String>,
+    pub description: Option<String>,
+    pub license: Option<String>,
+    pub repository: Option<Url>,
+    pub documentation: Option<Url>,
+    pub homepage: Option<Url>,
+    pub urls: Vec<Url>,
+    pub readme: Option<String>,
+    pub categories: Vec<String>,
+    pub keywords: Vec<String>,
+    pub dependencies: Vec<DryRunDependency>,
+}
+
+/// The response to a dry-run publish request, with the packages that would be published
+/// in publish order.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DryRunPublishResponse {
+    pub packages: Vec<DryRunPackage>,
+}
+
+/// The response to a publish request for either a package or a workspace upload.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum PublishResult {
+    Package(PublishResponse),
+    Workspace(WorkspacePublishResponse),
+    DryRun(DryRunPublishResponse),
+}
+
+/// The response to a yank or unyank request.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct YankResponse {
+    pub name: String,
+    pub version: String,
+    pub yanked: bool,
+}
+
 /// The response to an upload_project request.
 #[derive(Serialize, Debug)]
 pub struct UploadResponse {
     pub upload_id: Uuid,
+    /// For a dry run, the uploads that would have been created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report: Option<Vec<NewUpload>>,
+}
+
+/// The status of the background job that builds and uploads a project.
+#[derive(Serialize, Debug)]
+pub struct UploadStatusResponse {
+    pub upload_id: Uuid,
+    pub status: UploadJobStatus,
+    /// The latest progress message of the job.
+    pub message: Option<String>,
+    /// The error of the last failed attempt, if any.
+    pub error: Option<String>,
+    /// The compiler output if the build failed.
+    pub diagnostics: Option<String>,
+    pub dry_run: bool,
+    /// For a succeeded dry run, the uploads that would have been created.
+    pub report: Option<Vec<NewUpload>>,
+    pub attempts: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl TryFrom<UploadJob> for UploadStatusResponse {
+    type Error = DatabaseError;
+
+    fn try_from(job: UploadJob) -> Result<Self, Self::Error> {
+        Ok(UploadStatusResponse {
+            upload_id: job.id,
+            status: job.status()?,
+            error: job.upload_error().map(|err| err.to_string()),
+            report: job.dry_run_report(),
+            message: job.message,
+            diagnostics: job.diagnostics,
+            dry_run: job.dry_run,
+            attempts: job.attempts,
+            created_at: job.created_at,
+            updated_at: job.updated_at,
+        })
+    }
 }
@@ -0,0 +1,72 @@
+use serde::Serialize;
+
+/// The status of a component that the server depends on.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ComponentStatus {
+    Ok,
+    Unavailable,
+    /// The component isn't used in this environment, so it wasn't checked.
+    Skipped,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ComponentReadiness {
+    pub status: ComponentStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ComponentReadiness {
+    pub fn ok() -> Self {
+        Self {
+            status: ComponentStatus::Ok,
+            error: None,
+        }
+    }
+
+    pub fn unavailable(error: String) -> Self {
+        Self {
+            status: ComponentStatus::Unavailable,
+            error: Some(error),
+        }
+    }
+
+    pub fn skipped() -> Self {
+        Self {
+            status: ComponentStatus::Skipped,
+            error: None,
+        }
+    }
+}
+
+/// The response of the readiness probe. The server is ready if the database is available,
+/// and the storage backend too if it stores files on the local filesystem. The status of a
+/// remote storage backend and of the index repo is informational, so that an outage of an
+/// external service doesn't take every instance out of rotation.
+#[derive(Serialize, Debug)]
+pub struct ReadinessResponse {
+    /// Whether the components that the server can't serve requests without are available.
+    pub ready: bool,
+    pub database: ComponentReadiness,
+    pub storage: ComponentReadiness,
+    pub index: ComponentReadiness,
+}
+
+impl ReadinessResponse {
+    pub fn new(
+        database: ComponentReadiness,
+        storage: ComponentReadiness,
+        index: ComponentReadiness,
+        local_storage: bool,
+    ) -> Self {
+        let ready = database.status != ComponentStatus::Unavailable
+            && !(local_storage && storage.status == ComponentStatus::Unavailable);
+        Self {
+            ready,
+            database,
+            storage,
+            index,
+        }
+    }
+}
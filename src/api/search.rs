@@ -1,7 +1,4 @@
-use crate::{
-    file_uploader::pinata::{ipfs_hash_to_abi_url, ipfs_hash_to_docs_url, ipfs_hash_to_tgz_url},
-    models::PackagePreview,
-};
+use crate::{api::download::PackageFile, file_uploader::storage::FileUrls, models::PackagePreview};
 use serde::Serialize;
 use url::Url;
 
@@ -35,6 +32,7 @@ pub struct DownloadLinksResponse {
 pub struct FullPackage {
     #[serde(flatten)]
     pub package_preview: PackagePreview,
+    pub version_downloads: i64,
 
     // Metadata from Uploads table
     pub bytecode_identifier: Option<String>,
@@ -60,12 +58,30 @@ pub struct FullPackage {
     pub keywords: Vec<String>,
 }
 
-impl From<crate::models::FullPackageWithCategories> for FullPackage {
-    fn from(full_package_with_categories: crate::models::FullPackageWithCategories) -> Self {
+impl FullPackage {
+    /// Returns the package with links to its files.
+    pub fn new(
+        full_package_with_categories: crate::models::FullPackageWithCategories,
+        file_urls: &FileUrls,
+    ) -> Self {
         let full_package = full_package_with_categories.package;
         fn string_to_url(s: String) -> Option<Url> {
             Url::parse(&s).ok()
         }
+        let file_url = |file, hash: &str| {
+            file_urls.file_url(file, &full_package.name, &full_package.version, hash)
+        };
+
+        let source_code_ipfs_url =
+            file_url(PackageFile::Tarball, &full_package.source_code_ipfs_hash);
+        let abi_ipfs_url = full_package
+            .abi_ipfs_hash
+            .as_deref()
+            .map(|hash| file_url(PackageFile::Abi, hash));
+        let docs_ipfs_url = full_package
+            .docs_ipfs_hash
+            .as_deref()
+            .map(|hash| file_url(PackageFile::Docs, hash));
 
         FullPackage {
             package_preview: PackagePreview {
@@ -74,16 +90,14 @@ impl From<crate::models::FullPackageWithCategories> for FullPackage {
                 description: full_package.description,
                 created_at: full_package.created_at,
                 updated_at: full_package.updated_at,
+                downloads: full_package.downloads,
             },
+            version_downloads: full_package.version_downloads,
             bytecode_identifier: full_package.bytecode_identifier,
             forc_version: full_package.forc_version,
-            source_code_ipfs_url: ipfs_hash_to_tgz_url(&full_package.source_code_ipfs_hash),
-            abi_ipfs_url: full_package
-                .abi_ipfs_hash
-                .map(|hash| ipfs_hash_to_abi_url(&hash)),
-            docs_ipfs_url: full_package
-                .docs_ipfs_hash
-                .map(|hash| ipfs_hash_to_docs_url(&hash)),
+            source_code_ipfs_url,
+            abi_ipfs_url,
+            docs_ipfs_url,
             abi: None, // Will be populated when inline_abi is requested
             repository: full_package.repository.and_then(string_to_url),
             documentation: full_package.documentation.and_then(string_to_url),
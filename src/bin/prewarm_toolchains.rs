@@ -0,0 +1,15 @@
+//! Installs the versions of forc in `FORC_TOOLCHAIN_PREWARM` into `FORC_TOOLCHAIN_DIR`, and
+//! exits with an error if any of them failed. The Docker image runs it at build time, so
+//! that it ships with the toolchains and the server doesn't download them when it starts.
+
+use forc_pub::handlers::toolchain::ToolchainManager;
+use std::process::ExitCode;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    tracing_subscriber::fmt().init();
+    match ToolchainManager::from_env().prewarm().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(_) => ExitCode::FAILURE,
+    }
+}
@@ -0,0 +1,212 @@
+use super::error::DatabaseError;
+use super::{models, schema, string_to_uuid, DbConn};
+use crate::api::admin::AdminUser;
+use crate::api::pagination::{PaginatedResponse, Pagination};
+use crate::models::PackageNameFlagInfo;
+use chrono::Utc;
+use diesel::prelude::*;
+use uuid::Uuid;
+
+impl DbConn<'_> {
+    /// Fetch the users whose GitHub login, name or email contains the query, or all users
+    /// if there is no query, most recent first.
+    pub fn search_users(
+        &mut self,
+        query: Option<String>,
+        pagination: Pagination,
+    ) -> Result<PaginatedResponse<AdminUser>, DatabaseError> {
+        let pattern = format!("%{}%", query.unwrap_or_default());
+        let users = || {
+            schema::users::table.filter(
+                schema::users::github_login
+                    .ilike(pattern.clone())
+                    .or(schema::users::full_name.ilike(pattern.clone()))
+                    .or(schema::users::email.ilike(pattern.clone())),
+            )
+        };
+
+        let total_count = users()
+            .count()
+            .get_result::<i64>(self.inner())
+            .map_err(|err| DatabaseError::QueryFailed(pattern.clone(), err))?;
+
+        let data = users()
+            .order_by(schema::users::created_at.desc())
+            .select(models::User::as_select())
+            .limit(pagination.limit())
+            .offset(pagination.offset())
+            .load::<models::User>(self.inner())
+            .map_err(|err| DatabaseError::QueryFailed(pattern.clone(), err))?
+            .into_iter()
+            .map(AdminUser::from)
+            .collect();
+
+        Ok(PaginatedResponse {
+            data,
+            total_count,
+            total_pages: ((total_count as f64) / (pagination.limit() as f64)).ceil() as i64,
+            current_page: pagination.page(),
+            per_page: pagination.limit(),
+        })
+    }
+
+    /// Block or unblock the user with the given GitHub login from publishing, and return
+    /// the updated user.
+    pub fn set_user_publish_blocked(
+        &mut self,
+        github_login: String,
+        blocked: bool,
+    ) -> Result<models::User, DatabaseError> {
+        diesel::update(schema::users::table.filter(schema::users::github_login.eq(&github_login)))
+            .set(schema::users::publish_blocked.eq(blocked))
+            .returning(models::User::as_returning())
+            .get_result(self.inner())
+            .map_err(|err| DatabaseError::NotFound(github_login, err))
+    }
+
+    /// Delete a version of a package and return it. The version number is kept in
+    /// `removed_package_versions`, so it can't be published again. The package's default
+    /// version is updated to the remaining versions.
+    pub fn remove_package_version(
+        &mut self,
+        admin_id: Uuid,
+        pkg_name: String,
+        version: String,
+    ) -> Result<models::PackageVersion, DatabaseError> {
+        let package = self.get_package_by_name(pkg_name.clone())?;
+        let package_version = self.get_package_version(pkg_name.clone(), version)?;
+
+        diesel::insert_into(schema::removed_package_versions::table)
+            .values((
+                schema::removed_package_versions::package_id.eq(package.id),
+                schema::removed_package_versions::num.eq(&package_version.num),
+                schema::removed_package_versions::removed_by.eq(admin_id),
+            ))
+            .execute(self.inner())
+            .map_err(|err| DatabaseError::UpdatePackageFailed(pkg_name.clone(), err))?;
+
+        // The package can't reference the version as its default version once it's gone.
+        if package.default_version == Some(package_version.id) {
+            diesel::update(schema::packages::table.filter(schema::packages::id.eq(package.id)))
+                .set(schema::packages::default_version.eq(None::<Uuid>))
+                .execute(self.inner())
+                .map_err(|err| DatabaseError::UpdatePackageFailed(pkg_name.clone(), err))?;
+        }
+
+        diesel::delete(
+            schema::package_versions::table
+                .filter(schema::package_versions::id.eq(package_version.id)),
+        )
+        .execute(self.inner())
+        .map_err(|err| DatabaseError::UpdatePackageFailed(pkg_name, err))?;
+
+        self.update_default_version(&package)?;
+
+        Ok(package_version)
+    }
+
+    /// Make the user with the given GitHub login the only owner of the package. Pending
+    /// owner invites for the package are dropped. Returns the GitHub logins of the
+    /// previous owners.
+    pub fn transfer_package_ownership(
+        &mut self,
+        pkg_name: String,
+        new_owner_github_login: String,
+    ) -> Result<Vec<String>, DatabaseError> {
+        let package = self.get_package_by_name(pkg_name.clone())?;
+        let new_owner = schema::users::table
+            .filter(schema::users::github_login.eq(new_owner_github_login.clone()))
+            .select(models::User::as_returning())
+            .first::<models::User>(self.inner())
+            .map_err(|err| DatabaseError::NotFound(new_owner_github_login, err))?;
+
+        let previous_owners = self
+            .get_package_owners(pkg_name.clone())?
+            .into_iter()
+            .map(|owner| owner.github_login)
+            .collect();
+
+        diesel::delete(
+            schema::package_owners::table.filter(schema::package_owners::package_id.eq(package.id)),
+        )
+        .execute(self.inner())
+        .map_err(|err| DatabaseError::UpdatePackageFailed(pkg_name.clone(), err))?;
+
+        diesel::delete(
+            schema::package_owner_invites::table
+                .filter(schema::package_owner_invites::package_id.eq(package.id)),
+        )
+        .execute(self.inner())
+        .map_err(|err| DatabaseError::UpdatePackageFailed(pkg_name.clone(), err))?;
+
+        self.add_package_owner(package.id, new_owner.id)?;
+
+        diesel::update(schema::packages::table.filter(schema::packages::id.eq(package.id)))
+            .set(schema::packages::user_owner.eq(new_owner.id))
+            .execute(self.inner())
+            .map_err(|err| DatabaseError::UpdatePackageFailed(pkg_name, err))?;
+
+        Ok(previous_owners)
+    }
+
+    /// Fetch the most recent upload jobs.
+    pub fn get_recent_upload_jobs(
+        &mut self,
+        pagination: Pagination,
+    ) -> Result<PaginatedResponse<models::UploadJob>, DatabaseError> {
+        let total_count = schema::upload_jobs::table
+            .count()
+            .get_result::<i64>(self.inner())
+            .map_err(|err| DatabaseError::QueryFailed("upload jobs".to_string(), err))?;
+
+        let data = schema::upload_jobs::table
+            .order_by(schema::upload_jobs::created_at.desc())
+            .select(models::UploadJob::as_select())
+            .limit(pagination.limit())
+            .offset(pagination.offset())
+            .load(self.inner())
+            .map_err(|err| DatabaseError::QueryFailed("upload jobs".to_string(), err))?;
+
+        Ok(PaginatedResponse {
+            data,
+            total_count,
+            total_pages: ((total_count as f64) / (pagination.limit() as f64)).ceil() as i64,
+            current_page: pagination.page(),
+            per_page: pagination.limit(),
+        })
+    }
+
+    /// Fetch the package name flags that haven't been reviewed along with the names of
+    /// the packages, most recent first.
+    pub fn get_package_name_flags_for_review(
+        &mut self,
+    ) -> Result<Vec<PackageNameFlagInfo>, DatabaseError> {
+        diesel::sql_query(
+            r#"SELECT f.id, p.package_name, sp.package_name AS similar_package_name,
+                f.similarity, f.created_at
+            FROM package_name_flags f
+            JOIN packages p ON p.id = f.package_id
+            JOIN packages sp ON sp.id = f.similar_package_id
+            WHERE f.reviewed_at IS NULL
+            ORDER BY f.created_at DESC
+            "#,
+        )
+        .load::<PackageNameFlagInfo>(self.inner())
+        .map_err(|err| DatabaseError::QueryFailed("package name flags".to_string(), err))
+    }
+
+    /// Mark a package name flag as reviewed and return it.
+    pub fn review_package_name_flag(
+        &mut self,
+        flag_id: String,
+    ) -> Result<models::PackageNameFlag, DatabaseError> {
+        let flag_uuid = string_to_uuid(flag_id.clone())?;
+        diesel::update(
+            schema::package_name_flags::table.filter(schema::package_name_flags::id.eq(flag_uuid)),
+        )
+        .set(schema::package_name_flags::reviewed_at.eq(Some(Utc::now())))
+        .returning(models::PackageNameFlag::as_returning())
+        .get_result(self.inner())
+        .map_err(|err| DatabaseError::NotFound(flag_id, err))
+    }
+}
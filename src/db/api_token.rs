@@ -1,9 +1,14 @@
 use super::error::DatabaseError;
 use super::string_to_uuid;
 use super::{models, schema, DbConn};
+use chrono::{DateTime, Utc};
 use diesel::prelude::*;
 use rand::{distributions::Uniform, rngs::OsRng, Rng};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
 use uuid::Uuid;
 
 /// NEVER CHANGE THE PREFIX OF EXISTING TOKENS!!! Doing so will implicitly
@@ -51,13 +56,112 @@ impl From<PlainToken> for String {
     }
 }
 
+/// An action that an API token can be allowed to perform.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TokenScope {
+    /// Publish the first version of a package that doesn't exist yet.
+    PublishNew,
+    /// Publish a new version of an existing package.
+    PublishUpdate,
+    /// Yank or un-yank a package version.
+    Yank,
+}
+
+impl TokenScope {
+    pub const ALL: [TokenScope; 3] = [
+        TokenScope::PublishNew,
+        TokenScope::PublishUpdate,
+        TokenScope::Yank,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenScope::PublishNew => "publish-new",
+            TokenScope::PublishUpdate => "publish-update",
+            TokenScope::Yank => "yank",
+        }
+    }
+}
+
+impl fmt::Display for TokenScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TokenScope {
+    type Err = DatabaseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TokenScope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == s)
+            .ok_or_else(|| DatabaseError::InvalidTokenScope(s.to_string()))
+    }
+}
+
+/// Returns true if the package name matches the glob pattern, where `*` matches
+/// any sequence of characters and `?` matches a single character.
+fn package_glob_matches(glob: &str, package_name: &str) -> bool {
+    let pattern = regex::escape(glob).replace(r"\*", ".*").replace(r"\?", ".");
+    Regex::new(&format!("^{pattern}$")).is_ok_and(|re| re.is_match(package_name))
+}
+
+/// Returns an error if the glob contains characters that can't appear in a package name.
+pub fn validate_package_glob(glob: &str) -> Result<(), DatabaseError> {
+    let is_valid = !glob.is_empty()
+        && glob
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '*' | '?'));
+    if !is_valid {
+        return Err(DatabaseError::InvalidPackageGlob(glob.to_string()));
+    }
+    Ok(())
+}
+
+impl models::ApiToken {
+    /// Returns an error unless the token has the scope and is allowed to act on the package.
+    pub fn authorize(&self, scope: TokenScope, package_name: &str) -> Result<(), DatabaseError> {
+        let has_scope = self.scopes.iter().any(|s| s == scope.as_str());
+        let matches_package = self
+            .package_glob
+            .as_ref()
+            .is_none_or(|glob| package_glob_matches(glob, package_name));
+        if !has_scope || !matches_package {
+            return Err(DatabaseError::TokenScopeDenied(
+                scope.to_string(),
+                package_name.to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
 impl DbConn<'_> {
-    /// Creates an API token for the user and returns the token.
+    /// Creates an API token for the user with all scopes and no expiry, and returns the token.
     pub fn new_token(
         &mut self,
         user_id: Uuid,
         friendly_name: String,
     ) -> Result<(models::ApiToken, PlainToken), DatabaseError> {
+        self.new_scoped_token(user_id, friendly_name, None, &TokenScope::ALL, None)
+    }
+
+    /// Creates an API token for the user that expires at `expires_at`, is limited to
+    /// `scopes`, and can only act on packages matching `package_glob`. Returns the token.
+    pub fn new_scoped_token(
+        &mut self,
+        user_id: Uuid,
+        friendly_name: String,
+        expires_at: Option<DateTime<Utc>>,
+        scopes: &[TokenScope],
+        package_glob: Option<String>,
+    ) -> Result<(models::ApiToken, PlainToken), DatabaseError> {
+        if let Some(glob) = &package_glob {
+            validate_package_glob(glob)?;
+        }
+
         let plain_token = PlainToken::new();
         let token = plain_token.hash();
 
@@ -65,7 +169,9 @@ impl DbConn<'_> {
             user_id,
             friendly_name,
             token,
-            expires_at: None,
+            expires_at,
+            scopes: scopes.iter().map(|scope| scope.to_string()).collect(),
+            package_glob,
         };
 
         // Insert new session
@@ -135,4 +241,54 @@ mod tests {
         let token = PlainToken::from("123456".to_string());
         assert_eq!(token.hash(), Sha256::digest(token.0.as_bytes()).as_slice());
     }
+
+    #[test]
+    fn test_token_scope_from_str() {
+        for scope in TokenScope::ALL {
+            assert_eq!(TokenScope::from_str(scope.as_str()).unwrap(), scope);
+        }
+        assert!(TokenScope::from_str("admin").is_err());
+    }
+
+    #[test]
+    fn test_package_glob_matches() {
+        assert!(package_glob_matches("*", "std"));
+        assert!(package_glob_matches("std", "std"));
+        assert!(!package_glob_matches("std", "std-lib"));
+        assert!(package_glob_matches("my-org-*", "my-org-token"));
+        assert!(!package_glob_matches("my-org-*", "other-token"));
+        assert!(package_glob_matches("lib_?", "lib_a"));
+        assert!(!package_glob_matches("lib_?", "lib_ab"));
+    }
+
+    #[test]
+    fn test_validate_package_glob() {
+        assert!(validate_package_glob("my-org-*").is_ok());
+        assert!(validate_package_glob("lib_?").is_ok());
+        assert!(validate_package_glob("").is_err());
+        assert!(validate_package_glob("my.org/*").is_err());
+    }
+
+    #[test]
+    fn test_authorize() {
+        let token = models::ApiToken {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            friendly_name: "ci".to_string(),
+            expires_at: None,
+            created_at: Utc::now(),
+            scopes: vec![TokenScope::PublishUpdate.to_string()],
+            package_glob: Some("my-org-*".to_string()),
+        };
+        assert!(token
+            .authorize(TokenScope::PublishUpdate, "my-org-token")
+            .is_ok());
+        assert!(token
+            .authorize(TokenScope::PublishNew, "my-org-token")
+            .is_err());
+        assert!(token.authorize(TokenScope::Yank, "my-org-token").is_err());
+        assert!(token
+            .authorize(TokenScope::PublishUpdate, "other-token")
+            .is_err());
+    }
 }
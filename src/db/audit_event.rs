@@ -0,0 +1,173 @@
+use super::error::DatabaseError;
+use super::{models, schema, DbConn};
+use crate::api::pagination::{PaginatedResponse, Pagination};
+use crate::middleware::client_info::ClientInfo;
+use crate::models::{AuditEventInfo, AuthorInfo};
+use diesel::prelude::*;
+use serde::Serialize;
+use std::fmt;
+use uuid::Uuid;
+
+/// A security-relevant event, as recorded in the audit log.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuditEventKind {
+    Login,
+    Logout,
+    NewToken,
+    DeleteToken,
+    Publish,
+    Yank,
+    Unyank,
+    InviteOwner,
+    AcceptOwnerInvite,
+    DeclineOwnerInvite,
+    RemoveOwner,
+    AdminBlockUser,
+    AdminUnblockUser,
+    AdminYank,
+    AdminUnyank,
+    AdminRemoveVersion,
+    AdminTransferOwnership,
+    AdminReviewPackageName,
+    AdminInstallToolchain,
+}
+
+/// The prefix of the events of actions taken by admins.
+const ADMIN_EVENT_PREFIX: &str = "admin-";
+
+impl AuditEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEventKind::Login => "login",
+            AuditEventKind::Logout => "logout",
+            AuditEventKind::NewToken => "new-token",
+            AuditEventKind::DeleteToken => "delete-token",
+            AuditEventKind::Publish => "publish",
+            AuditEventKind::Yank => "yank",
+            AuditEventKind::Unyank => "unyank",
+            AuditEventKind::InviteOwner => "invite-owner",
+            AuditEventKind::AcceptOwnerInvite => "accept-owner-invite",
+            AuditEventKind::DeclineOwnerInvite => "decline-owner-invite",
+            AuditEventKind::RemoveOwner => "remove-owner",
+            AuditEventKind::AdminBlockUser => "admin-block-user",
+            AuditEventKind::AdminUnblockUser => "admin-unblock-user",
+            AuditEventKind::AdminYank => "admin-yank-version",
+            AuditEventKind::AdminUnyank => "admin-unyank-version",
+            AuditEventKind::AdminRemoveVersion => "admin-remove-version",
+            AuditEventKind::AdminTransferOwnership => "admin-transfer-ownership",
+            AuditEventKind::AdminReviewPackageName => "admin-review-package-name",
+            AuditEventKind::AdminInstallToolchain => "admin-install-toolchain",
+        }
+    }
+}
+
+impl fmt::Display for AuditEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl DbConn<'_> {
+    /// Record an event of the user in the audit log, along with the client that made the
+    /// request and the details of the event.
+    pub fn record_audit_event(
+        &mut self,
+        user_id: Uuid,
+        event: AuditEventKind,
+        client: &ClientInfo,
+        payload: serde_json::Value,
+    ) -> Result<models::AuditEvent, DatabaseError> {
+        let new_event = models::NewAuditEvent {
+            user_id,
+            event: event.to_string(),
+            ip_address: client.ip_address.clone(),
+            user_agent: client.user_agent.clone(),
+            payload,
+        };
+
+        diesel::insert_into(schema::audit_events::table)
+            .values(&new_event)
+            .returning(models::AuditEvent::as_returning())
+            .get_result(self.inner())
+            .map_err(|err| DatabaseError::QueryFailed(user_id.to_string(), err))
+    }
+
+    /// Fetch the audit events of the user, or of all users if no user is given, most
+    /// recent first.
+    pub fn get_audit_events(
+        &mut self,
+        user_id: Option<Uuid>,
+        pagination: Pagination,
+    ) -> Result<PaginatedResponse<AuditEventInfo>, DatabaseError> {
+        self.load_audit_events(user_id, false, pagination)
+    }
+
+    /// Fetch the audit events of the actions taken by admins, most recent first.
+    pub fn get_admin_audit_events(
+        &mut self,
+        pagination: Pagination,
+    ) -> Result<PaginatedResponse<AuditEventInfo>, DatabaseError> {
+        self.load_audit_events(None, true, pagination)
+    }
+
+    fn load_audit_events(
+        &mut self,
+        user_id: Option<Uuid>,
+        admin_only: bool,
+        pagination: Pagination,
+    ) -> Result<PaginatedResponse<AuditEventInfo>, DatabaseError> {
+        let events = || {
+            let mut query = schema::audit_events::table
+                .inner_join(schema::users::table)
+                .into_boxed();
+            if let Some(user_id) = user_id {
+                query = query.filter(schema::audit_events::user_id.eq(user_id));
+            }
+            if admin_only {
+                query = query
+                    .filter(schema::audit_events::event.like(format!("{ADMIN_EVENT_PREFIX}%")));
+            }
+            query
+        };
+
+        let total_count = events()
+            .count()
+            .get_result::<i64>(self.inner())
+            .map_err(|err| DatabaseError::QueryFailed("audit events".to_string(), err))?;
+
+        let data = events()
+            .order_by(schema::audit_events::created_at.desc())
+            .select((
+                models::AuditEvent::as_select(),
+                schema::users::full_name,
+                schema::users::github_login,
+            ))
+            .limit(pagination.limit())
+            .offset(pagination.offset())
+            .load::<(models::AuditEvent, String, String)>(self.inner())
+            .map_err(|err| DatabaseError::QueryFailed("audit events".to_string(), err))?
+            .into_iter()
+            .map(|(event, full_name, github_login)| AuditEventInfo {
+                id: event.id,
+                user: AuthorInfo {
+                    full_name,
+                    github_login,
+                },
+                event: event.event,
+                ip_address: event.ip_address,
+                user_agent: event.user_agent,
+                payload: event.payload,
+                created_at: event.created_at,
+            })
+            .collect();
+
+        Ok(PaginatedResponse {
+            data,
+            total_count,
+            total_pages: ((total_count as f64) / (pagination.limit() as f64)).ceil() as i64,
+            current_page: pagination.page(),
+            per_page: pagination.limit(),
+        })
+    }
+}
@@ -0,0 +1,118 @@
+use super::error::DatabaseError;
+use super::{schema, DbConn};
+use crate::models::{DailyVersionDownloads, VersionDownloads};
+use chrono::{NaiveDate, Utc};
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::BigInt;
+use diesel::upsert::excluded;
+use semver::Version;
+use uuid::Uuid;
+
+impl DbConn<'_> {
+    /// Record a download of the given package version, counted towards today's rollup
+    /// for the version and the total downloads of the package.
+    pub fn record_download(
+        &mut self,
+        pkg_name: String,
+        version: String,
+    ) -> Result<(), DatabaseError> {
+        let (package_id, package_version_id) = schema::package_versions::table
+            .inner_join(
+                schema::packages::table
+                    .on(schema::packages::id.eq(schema::package_versions::package_id)),
+            )
+            .filter(schema::packages::package_name.eq(pkg_name.clone()))
+            .filter(schema::package_versions::num.eq(version.clone()))
+            .select((schema::packages::id, schema::package_versions::id))
+            .first::<(Uuid, Uuid)>(self.inner())
+            .map_err(|err| DatabaseError::NotFound(format!("{pkg_name}@{version}"), err))?;
+
+        diesel::insert_into(schema::package_version_downloads::table)
+            .values((
+                schema::package_version_downloads::package_version_id.eq(package_version_id),
+                schema::package_version_downloads::date.eq(Utc::now().date_naive()),
+                schema::package_version_downloads::downloads.eq(1),
+            ))
+            .on_conflict((
+                schema::package_version_downloads::package_version_id,
+                schema::package_version_downloads::date,
+            ))
+            .do_update()
+            .set(
+                schema::package_version_downloads::downloads
+                    .eq(schema::package_version_downloads::downloads
+                        + excluded(schema::package_version_downloads::downloads)),
+            )
+            .execute(self.inner())
+            .map_err(|err| DatabaseError::InsertDownloadFailed(pkg_name.clone(), err))?;
+
+        diesel::update(schema::packages::table.filter(schema::packages::id.eq(package_id)))
+            .set(schema::packages::downloads.eq(schema::packages::downloads + 1))
+            .execute(self.inner())
+            .map_err(|err| DatabaseError::UpdatePackageFailed(pkg_name, err))?;
+
+        Ok(())
+    }
+
+    /// Fetch the total downloads of each version of a package, highest version first.
+    pub fn get_version_downloads(
+        &mut self,
+        pkg_name: String,
+    ) -> Result<Vec<VersionDownloads>, DatabaseError> {
+        let package = self.get_package_by_name(pkg_name.clone())?;
+
+        let versions = schema::package_versions::table
+            .left_join(schema::package_version_downloads::table)
+            .filter(schema::package_versions::package_id.eq(package.id))
+            .group_by((schema::package_versions::id, schema::package_versions::num))
+            .select((
+                schema::package_versions::num,
+                // SUM of a BIGINT is a NUMERIC in Postgres.
+                sql::<BigInt>("COALESCE(SUM(package_version_downloads.downloads), 0)::BIGINT"),
+            ))
+            .load::<(String, i64)>(self.inner())
+            .map_err(|err| DatabaseError::QueryFailed(pkg_name, err))?;
+
+        let mut versions: Vec<VersionDownloads> = versions
+            .into_iter()
+            .map(|(version, downloads)| VersionDownloads { version, downloads })
+            .collect();
+        versions.sort_by_cached_key(|v| std::cmp::Reverse(Version::parse(&v.version).ok()));
+        Ok(versions)
+    }
+
+    /// Fetch the daily downloads of each version of a package since the given date,
+    /// oldest day and lowest version first. Days without downloads are omitted.
+    pub fn get_daily_downloads(
+        &mut self,
+        pkg_name: String,
+        since: NaiveDate,
+    ) -> Result<Vec<DailyVersionDownloads>, DatabaseError> {
+        let package = self.get_package_by_name(pkg_name.clone())?;
+
+        let downloads = schema::package_version_downloads::table
+            .inner_join(schema::package_versions::table)
+            .filter(schema::package_versions::package_id.eq(package.id))
+            .filter(schema::package_version_downloads::date.ge(since))
+            .order_by(schema::package_version_downloads::date.asc())
+            .select((
+                schema::package_version_downloads::date,
+                schema::package_versions::num,
+                schema::package_version_downloads::downloads,
+            ))
+            .load::<(NaiveDate, String, i64)>(self.inner())
+            .map_err(|err| DatabaseError::QueryFailed(pkg_name, err))?;
+
+        let mut downloads: Vec<DailyVersionDownloads> = downloads
+            .into_iter()
+            .map(|(date, version, downloads)| DailyVersionDownloads {
+                date,
+                version,
+                downloads,
+            })
+            .collect();
+        downloads.sort_by_cached_key(|d| (d.date, Version::parse(&d.version).ok()));
+        Ok(downloads)
+    }
+}
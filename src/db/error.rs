@@ -32,6 +32,30 @@ pub enum DatabaseError {
     #[error("Invalid publish token")]
     InvalidPublishToken,
 
+    #[error("User is blocked from publishing")]
+    PublishBlocked,
+
+    #[error("Invalid token scope: {0}")]
+    InvalidTokenScope(String),
+
+    #[error("Invalid package name glob: {0}")]
+    InvalidPackageGlob(String),
+
+    #[error("API token is not allowed to {0} package: {1}")]
+    TokenScopeDenied(String, String),
+
+    #[error("User is not an owner of package: {0}")]
+    NotPackageOwner(String),
+
+    #[error("User {0} is already an owner of package: {1}")]
+    AlreadyPackageOwner(String, String),
+
+    #[error("Cannot remove the last owner of package: {0}")]
+    LastPackageOwner(String),
+
+    #[error("Failed to save package owner: {0}: {1}")]
+    InsertPackageOwnerFailed(String, diesel::result::Error),
+
     #[error("Failed to save package version: {0} {1}: {2}")]
     InsertPackageVersionFailed(String, String, diesel::result::Error),
 
@@ -44,6 +68,15 @@ pub enum DatabaseError {
     #[error("Failed to save package keywords: {0}")]
     InsertPackageKeywordsFailed(diesel::result::Error),
 
+    #[error("Invalid upload job status: {0}")]
+    InvalidUploadJobStatus(String),
+
+    #[error("Invalid report for upload job: {0}: {1}")]
+    InvalidUploadReport(String, serde_json::Error),
+
+    #[error("Failed to record download: {0}: {1}")]
+    InsertDownloadFailed(String, diesel::result::Error),
+
     #[error("Failed to query: {0}: {1}")]
     QueryFailed(String, diesel::result::Error),
 }
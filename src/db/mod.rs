@@ -1,9 +1,16 @@
+pub mod admin;
 pub mod api_token;
+pub mod audit_event;
+pub mod download;
 pub mod error;
 pub mod package_category_keyword;
 pub mod package_dependency;
+pub mod package_name;
+pub mod package_owner;
 pub mod package_version;
+pub mod rate_limit;
 pub mod upload;
+pub mod upload_job;
 mod user_session;
 
 use self::error::DatabaseError;
@@ -11,9 +18,10 @@ use crate::util::load_env;
 use crate::{api, models, schema};
 use diesel::pg::PgConnection;
 use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
-use diesel::Connection;
+use diesel::{Connection, RunQueryDsl};
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use std::env;
+use tokio::task;
 use tracing::info;
 use uuid::Uuid;
 
@@ -21,6 +29,7 @@ pub type DbPool = Pool<ConnectionManager<PgConnection>>;
 pub type DbConnection = PooledConnection<ConnectionManager<PgConnection>>;
 
 /// The representation of a SQL database connection pool and its operations.
+#[derive(Clone)]
 pub struct Database {
     pub pool: DbPool,
 }
@@ -43,6 +52,14 @@ impl<'a> DbConn<'a> {
     pub fn inner(&mut self) -> &mut DbConnection {
         self.0
     }
+
+    /// Runs a trivial query, to check that the database responds.
+    pub fn ping(&mut self) -> Result<(), DatabaseError> {
+        diesel::sql_query("SELECT 1")
+            .execute(self.inner())
+            .map(|_| ())
+            .map_err(|err| DatabaseError::QueryFailed("ping".to_string(), err))
+    }
 }
 
 impl Database {
@@ -74,6 +91,20 @@ impl Database {
         let mut conn = self.pool.get().expect("db connection");
         conn.transaction(|conn| f(&mut DbConn::new(conn)))
     }
+
+    /// Runs [Database::transaction] on the blocking thread pool, so that an async task
+    /// doesn't stall the runtime while it waits for a connection or a query.
+    pub async fn transaction_blocking<F, T, E>(&self, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut DbConn<'_>) -> Result<T, E> + Send + 'static,
+        T: Send + 'static,
+        E: std::convert::From<diesel::result::Error> + Send + 'static,
+    {
+        let db = self.clone();
+        task::spawn_blocking(move || db.transaction(f))
+            .await
+            .expect("db transaction")
+    }
 }
 
 pub(crate) fn string_to_uuid(s: String) -> Result<Uuid, DatabaseError> {
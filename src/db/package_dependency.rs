@@ -1,6 +1,7 @@
 use super::error::DatabaseError;
 use super::{models, schema, DbConn};
-use crate::models::NewPackageDep;
+use crate::api::pagination::{PaginatedResponse, Pagination};
+use crate::models::{NewPackageDep, PackageDependent};
 use diesel::prelude::*;
 use uuid::Uuid;
 
@@ -29,4 +30,90 @@ impl DbConn<'_> {
             .load::<models::PackageDep>(self.inner())
             .map_err(|err| DatabaseError::NotFound(package_version_id.to_string(), err))
     }
+
+    /// Fetch the packages whose latest version depends on the given package, along with
+    /// the version requirement each of them uses for it.
+    pub fn get_package_dependents(
+        &mut self,
+        pkg_name: String,
+        pagination: Pagination,
+    ) -> Result<PaginatedResponse<PackageDependent>, DatabaseError> {
+        use schema::{package_dependencies, package_versions, packages};
+
+        // Ensure the package exists.
+        let _ = self.get_package_by_name(pkg_name.clone())?;
+
+        let dependents = || {
+            packages::table
+                .inner_join(
+                    package_versions::table
+                        .on(packages::default_version.eq(package_versions::id.nullable())),
+                )
+                .inner_join(package_dependencies::table.on(
+                    package_dependencies::dependent_package_version_id.eq(package_versions::id),
+                ))
+                .filter(package_dependencies::dependency_package_name.eq(pkg_name.clone()))
+        };
+
+        let total_count = dependents()
+            .count()
+            .get_result::<i64>(self.inner())
+            .map_err(|err| DatabaseError::QueryFailed(pkg_name.clone(), err))?;
+
+        let data = dependents()
+            .order_by(packages::package_name.asc())
+            .select((
+                packages::package_name,
+                package_versions::num,
+                package_dependencies::dependency_version_req,
+            ))
+            .limit(pagination.limit())
+            .offset(pagination.offset())
+            .load::<(String, String, String)>(self.inner())
+            .map_err(|err| DatabaseError::QueryFailed(pkg_name, err))?
+            .into_iter()
+            .map(|(name, version, version_req)| PackageDependent {
+                name,
+                version,
+                version_req,
+            })
+            .collect();
+
+        Ok(PaginatedResponse {
+            data,
+            total_count,
+            total_pages: ((total_count as f64) / (pagination.limit() as f64)).ceil() as i64,
+            current_page: pagination.page(),
+            per_page: pagination.limit(),
+        })
+    }
+
+    /// Fetch the version numbers and yanked state of every published version of a package.
+    /// Returns an empty list if the package doesn't exist.
+    pub fn get_published_versions(
+        &mut self,
+        pkg_name: String,
+    ) -> Result<Vec<(String, bool)>, DatabaseError> {
+        use schema::{package_versions, packages};
+
+        package_versions::table
+            .inner_join(packages::table.on(packages::id.eq(package_versions::package_id)))
+            .filter(packages::package_name.eq(pkg_name.clone()))
+            .select((package_versions::num, package_versions::yanked))
+            .load::<(String, bool)>(self.inner())
+            .map_err(|err| DatabaseError::QueryFailed(pkg_name, err))
+    }
+
+    /// Fetch the version numbers of the versions of a package that were removed by an admin.
+    /// Returns an empty list if the package doesn't exist.
+    pub fn get_removed_versions(&mut self, pkg_name: String) -> Result<Vec<String>, DatabaseError> {
+        use schema::{packages, removed_package_versions};
+
+        removed_package_versions::table
+            .inner_join(packages::table)
+            .filter(packages::package_name.eq(pkg_name.clone()))
+            .select(removed_package_versions::num)
+            .load::<String>(self.inner())
+            .map_err(|err| DatabaseError::QueryFailed(pkg_name, err))
+    }
 }
@@ -0,0 +1,59 @@
+use super::error::DatabaseError;
+use super::{models, schema, DbConn};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Float4, Uuid as SqlUuid};
+use uuid::Uuid;
+
+/// Packages with at least this many downloads are protected from typosquatting.
+pub const POPULAR_PACKAGE_MIN_DOWNLOADS: i64 = 1000;
+
+/// The trigram similarity above which the name of a new package is flagged as a
+/// near-duplicate of a popular package.
+pub const TYPOSQUAT_MIN_SIMILARITY: f32 = 0.5;
+
+impl DbConn<'_> {
+    /// Fetch the package whose name has the given normalized form, if any.
+    pub fn get_package_by_normalized_name(
+        &mut self,
+        normalized_name: &str,
+    ) -> Result<Option<models::Package>, DatabaseError> {
+        schema::packages::table
+            .filter(schema::packages::normalized_name.eq(normalized_name))
+            .select(models::Package::as_returning())
+            .first::<models::Package>(self.inner())
+            .optional()
+            .map_err(|err| DatabaseError::QueryFailed(normalized_name.to_string(), err))
+    }
+
+    /// Flag the package for admin review if its name has the same lookalike form as the
+    /// name of any other package, or is similar to the names of popular packages, using
+    /// trigram similarity of the normalized names. Returns the new flags.
+    pub fn flag_similar_package_names(
+        &mut self,
+        package_id: Uuid,
+    ) -> Result<Vec<models::PackageNameFlag>, DatabaseError> {
+        diesel::sql_query(
+            r#"INSERT INTO package_name_flags (package_id, similar_package_id, similarity)
+            SELECT np.id, p.id, CASE
+                WHEN package_name_lookalike(np.normalized_name)
+                    = package_name_lookalike(p.normalized_name) THEN 1
+                ELSE similarity(np.normalized_name, p.normalized_name)
+            END
+            FROM packages np
+            JOIN packages p ON p.id <> np.id
+            WHERE np.id = $1
+                AND (package_name_lookalike(np.normalized_name)
+                        = package_name_lookalike(p.normalized_name)
+                    OR (p.downloads >= $2
+                        AND similarity(np.normalized_name, p.normalized_name) >= $3))
+            ON CONFLICT (package_id, similar_package_id) DO NOTHING
+            RETURNING id, package_id, similar_package_id, similarity, reviewed_at, created_at
+            "#,
+        )
+        .bind::<SqlUuid, _>(package_id)
+        .bind::<BigInt, _>(POPULAR_PACKAGE_MIN_DOWNLOADS)
+        .bind::<Float4, _>(TYPOSQUAT_MIN_SIMILARITY)
+        .load::<models::PackageNameFlag>(self.inner())
+        .map_err(|err| DatabaseError::QueryFailed(package_id.to_string(), err))
+    }
+}
@@ -0,0 +1,246 @@
+use super::error::DatabaseError;
+use super::{models, schema, string_to_uuid, DbConn};
+use crate::models::{AuthorInfo, PackageOwnerInviteInfo};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+
+impl DbConn<'_> {
+    /// Returns true if the user is one of the owners of the package.
+    pub fn is_package_owner(
+        &mut self,
+        package_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<bool, DatabaseError> {
+        diesel::select(diesel::dsl::exists(
+            schema::package_owners::table
+                .filter(schema::package_owners::package_id.eq(package_id))
+                .filter(schema::package_owners::user_id.eq(user_id)),
+        ))
+        .get_result(self.inner())
+        .map_err(|err| DatabaseError::QueryFailed("is package owner".to_string(), err))
+    }
+
+    /// Add the user as an owner of the package and return the owner record.
+    pub fn add_package_owner(
+        &mut self,
+        package_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<models::PackageOwner, DatabaseError> {
+        let new_owner = models::NewPackageOwner {
+            package_id,
+            user_id,
+        };
+
+        diesel::insert_into(schema::package_owners::table)
+            .values(&new_owner)
+            .returning(models::PackageOwner::as_returning())
+            .get_result(self.inner())
+            .map_err(|err| DatabaseError::InsertPackageOwnerFailed(package_id.to_string(), err))
+    }
+
+    /// Fetch the owners of a package given the package name.
+    pub fn get_package_owners(
+        &mut self,
+        pkg_name: String,
+    ) -> Result<Vec<AuthorInfo>, DatabaseError> {
+        let package = self.get_package_by_name(pkg_name.clone())?;
+
+        let owners = schema::package_owners::table
+            .inner_join(schema::users::table)
+            .filter(schema::package_owners::package_id.eq(package.id))
+            .order_by(schema::package_owners::created_at.asc())
+            .select((schema::users::full_name, schema::users::github_login))
+            .load::<(String, String)>(self.inner())
+            .map_err(|err| DatabaseError::QueryFailed(pkg_name, err))?;
+
+        Ok(owners
+            .into_iter()
+            .map(|(full_name, github_login)| AuthorInfo {
+                full_name,
+                github_login,
+            })
+            .collect())
+    }
+
+    /// Invite the user with the given GitHub login to become an owner of the package.
+    /// Only existing owners of the package can invite new owners.
+    pub fn new_package_owner_invite(
+        &mut self,
+        inviter_id: Uuid,
+        pkg_name: String,
+        invitee_github_login: String,
+    ) -> Result<models::PackageOwnerInvite, DatabaseError> {
+        let package = self.get_package_by_name(pkg_name.clone())?;
+        if !self.is_package_owner(package.id, inviter_id)? {
+            return Err(DatabaseError::NotPackageOwner(pkg_name));
+        }
+
+        let invitee = schema::users::table
+            .filter(schema::users::github_login.eq(invitee_github_login.clone()))
+            .select(models::User::as_returning())
+            .first::<models::User>(self.inner())
+            .map_err(|err| DatabaseError::NotFound(invitee_github_login.clone(), err))?;
+
+        if self.is_package_owner(package.id, invitee.id)? {
+            return Err(DatabaseError::AlreadyPackageOwner(
+                invitee_github_login,
+                pkg_name,
+            ));
+        }
+
+        let new_invite = models::NewPackageOwnerInvite {
+            package_id: package.id,
+            invited_user_id: invitee.id,
+            invited_by: inviter_id,
+        };
+
+        // Re-inviting a user refreshes the existing invite.
+        diesel::insert_into(schema::package_owner_invites::table)
+            .values(&new_invite)
+            .on_conflict((
+                schema::package_owner_invites::package_id,
+                schema::package_owner_invites::invited_user_id,
+            ))
+            .do_update()
+            .set((
+                schema::package_owner_invites::invited_by.eq(inviter_id),
+                schema::package_owner_invites::created_at.eq(Utc::now()),
+            ))
+            .returning(models::PackageOwnerInvite::as_returning())
+            .get_result(self.inner())
+            .map_err(|err| DatabaseError::InsertPackageOwnerFailed(pkg_name, err))
+    }
+
+    /// Fetch the pending package owner invites for the given user.
+    pub fn get_package_owner_invites_for_user(
+        &mut self,
+        user_id: Uuid,
+    ) -> Result<Vec<PackageOwnerInviteInfo>, DatabaseError> {
+        let invites = schema::package_owner_invites::table
+            .inner_join(schema::packages::table)
+            .inner_join(
+                schema::users::table
+                    .on(schema::users::id.eq(schema::package_owner_invites::invited_by)),
+            )
+            .filter(schema::package_owner_invites::invited_user_id.eq(user_id))
+            .order_by(schema::package_owner_invites::created_at.desc())
+            .select((
+                schema::package_owner_invites::id,
+                schema::packages::package_name,
+                schema::users::full_name,
+                schema::users::github_login,
+                schema::package_owner_invites::created_at,
+            ))
+            .load::<(Uuid, String, String, String, DateTime<Utc>)>(self.inner())
+            .map_err(|err| DatabaseError::QueryFailed(user_id.to_string(), err))?;
+
+        Ok(invites
+            .into_iter()
+            .map(
+                |(id, package_name, full_name, github_login, created_at)| PackageOwnerInviteInfo {
+                    id,
+                    package_name,
+                    invited_by: AuthorInfo {
+                        full_name,
+                        github_login,
+                    },
+                    created_at,
+                },
+            )
+            .collect())
+    }
+
+    /// Accept a pending package owner invite for the user and return the new owner record.
+    pub fn accept_package_owner_invite(
+        &mut self,
+        user_id: Uuid,
+        invite_id: String,
+    ) -> Result<models::PackageOwner, DatabaseError> {
+        let invite_uuid = string_to_uuid(invite_id.clone())?;
+        let invite = diesel::delete(
+            schema::package_owner_invites::table
+                .filter(schema::package_owner_invites::id.eq(invite_uuid))
+                .filter(schema::package_owner_invites::invited_user_id.eq(user_id)),
+        )
+        .returning(models::PackageOwnerInvite::as_returning())
+        .get_result(self.inner())
+        .map_err(|err| DatabaseError::NotFound(invite_id, err))?;
+
+        if self.is_package_owner(invite.package_id, user_id)? {
+            let package = self.get_package_by_id(invite.package_id)?;
+            let github_login = schema::users::table
+                .filter(schema::users::id.eq(user_id))
+                .select(schema::users::github_login)
+                .first::<String>(self.inner())
+                .map_err(|err| DatabaseError::NotFound(user_id.to_string(), err))?;
+            return Err(DatabaseError::AlreadyPackageOwner(
+                github_login,
+                package.package_name,
+            ));
+        }
+
+        self.add_package_owner(invite.package_id, user_id)
+    }
+
+    /// Decline a pending package owner invite for the user. Returns `NotFound` if the
+    /// user has no such invite.
+    pub fn decline_package_owner_invite(
+        &mut self,
+        user_id: Uuid,
+        invite_id: String,
+    ) -> Result<(), DatabaseError> {
+        let invite_uuid = string_to_uuid(invite_id.clone())?;
+        let deleted = diesel::delete(
+            schema::package_owner_invites::table
+                .filter(schema::package_owner_invites::id.eq(invite_uuid))
+                .filter(schema::package_owner_invites::invited_user_id.eq(user_id)),
+        )
+        .execute(self.inner())
+        .map_err(|err| DatabaseError::QueryFailed(invite_id.clone(), err))?;
+        if deleted == 0 {
+            return Err(DatabaseError::NotFound(invite_id, diesel::NotFound));
+        }
+        Ok(())
+    }
+
+    /// Remove the user with the given GitHub login from the owners of the package.
+    /// Only existing owners of the package can remove owners, and the last owner
+    /// of a package cannot be removed.
+    pub fn remove_package_owner(
+        &mut self,
+        remover_id: Uuid,
+        pkg_name: String,
+        owner_github_login: String,
+    ) -> Result<(), DatabaseError> {
+        let package = self.get_package_by_name(pkg_name.clone())?;
+        if !self.is_package_owner(package.id, remover_id)? {
+            return Err(DatabaseError::NotPackageOwner(pkg_name));
+        }
+
+        let owner_ids = schema::package_owners::table
+            .inner_join(schema::users::table)
+            .filter(schema::package_owners::package_id.eq(package.id))
+            .select((schema::package_owners::id, schema::users::github_login))
+            .load::<(Uuid, String)>(self.inner())
+            .map_err(|err| DatabaseError::QueryFailed(pkg_name.clone(), err))?;
+
+        let owner_id = owner_ids
+            .iter()
+            .find(|(_, github_login)| *github_login == owner_github_login)
+            .map(|(id, _)| *id)
+            .ok_or_else(|| DatabaseError::NotFound(owner_github_login, diesel::NotFound))?;
+
+        if owner_ids.len() <= 1 {
+            return Err(DatabaseError::LastPackageOwner(pkg_name));
+        }
+
+        diesel::delete(
+            schema::package_owners::table.filter(schema::package_owners::id.eq(owner_id)),
+        )
+        .execute(self.inner())
+        .map_err(|err| DatabaseError::NotFound(owner_id.to_string(), err))?;
+
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
+use super::api_token::TokenScope;
 use super::error::DatabaseError;
 use super::{models, schema, DbConn};
 use crate::api::pagination::{PaginatedResponse, Pagination};
+use crate::handlers::package_name::normalize_package_name;
 use crate::handlers::publish::PublishInfo;
 use crate::models::{
     ApiToken, AuthorInfo, CountResult, FullPackage, FullPackageWithCategories, PackagePreview,
@@ -13,6 +15,45 @@ use std::collections::HashMap;
 use uuid::Uuid;
 
 impl DbConn<'_> {
+    /// Check that the API token can publish the package and return the package if it exists.
+    /// Users who are blocked by an admin can't publish.
+    /// Publishing a new package requires the `publish-new` scope, while publishing a version
+    /// of an existing package requires the `publish-update` scope and package ownership.
+    pub fn authorize_publish(
+        &mut self,
+        api_token: &ApiToken,
+        pkg_name: &str,
+    ) -> Result<Option<models::Package>, DatabaseError> {
+        let package = schema::packages::table
+            .filter(schema::packages::package_name.eq(pkg_name))
+            .select(schema::packages::all_columns)
+            .first::<models::Package>(self.inner())
+            .optional()
+            .map_err(|err| DatabaseError::QueryFailed(pkg_name.to_string(), err))?;
+
+        let publish_blocked = schema::users::table
+            .filter(schema::users::id.eq(api_token.user_id))
+            .select(schema::users::publish_blocked)
+            .first::<bool>(self.inner())
+            .map_err(|err| DatabaseError::NotFound(api_token.user_id.to_string(), err))?;
+        if publish_blocked {
+            return Err(DatabaseError::PublishBlocked);
+        }
+
+        match &package {
+            Some(existing_package) => {
+                if !self.is_package_owner(existing_package.id, api_token.user_id)? {
+                    // The package exists but the user is not an owner.
+                    return Err(DatabaseError::InvalidPublishToken);
+                }
+                api_token.authorize(TokenScope::PublishUpdate, pkg_name)?;
+            }
+            None => api_token.authorize(TokenScope::PublishNew, pkg_name)?,
+        }
+
+        Ok(package)
+    }
+
     /// Insert a package version into the database and return the package version.
     /// If the package doesn't exist, insert the package as well.
     pub fn new_package_version(
@@ -20,35 +61,30 @@ impl DbConn<'_> {
         api_token: &ApiToken,
         publish_info: &PublishInfo,
     ) -> Result<models::PackageVersion, DatabaseError> {
-        // Check if the package exists.
+        // Check if the package exists and the token is allowed to publish it.
         let pkg_name = publish_info.package_name.clone();
-        let package = if let Some(existing_package) = schema::packages::table
-            .filter(schema::packages::package_name.eq(pkg_name.clone()))
-            .select(schema::packages::all_columns)
-            .first::<models::Package>(self.inner())
-            .optional()
-            .map_err(|err| DatabaseError::QueryFailed(pkg_name.clone(), err))?
-        {
-            if existing_package.user_owner != api_token.user_id {
-                // The package exists but the user is not the owner.
-                return Err(DatabaseError::InvalidPublishToken);
-            }
-            Ok::<_, DatabaseError>(existing_package)
-        } else {
-            // Insert a new package.
-            let new_package: models::NewPackage = models::NewPackage {
-                user_owner: api_token.user_id,
-                package_name: pkg_name.clone(),
+        let package =
+            if let Some(existing_package) = self.authorize_publish(api_token, &pkg_name)? {
+                existing_package
+            } else {
+                // Insert a new package.
+                let new_package: models::NewPackage = models::NewPackage {
+                    user_owner: api_token.user_id,
+                    package_name: pkg_name.clone(),
+                    normalized_name: normalize_package_name(&pkg_name),
+                };
+
+                let saved_package = diesel::insert_into(schema::packages::table)
+                    .values(&new_package)
+                    .returning(models::Package::as_returning())
+                    .get_result(self.inner())
+                    .map_err(|err| DatabaseError::InsertPackageFailed(pkg_name.clone(), err))?;
+
+                // The publisher of a new package becomes its first owner.
+                self.add_package_owner(saved_package.id, api_token.user_id)?;
+                saved_package
             };
 
-            let saved_package = diesel::insert_into(schema::packages::table)
-                .values(&new_package)
-                .returning(models::Package::as_returning())
-                .get_result(self.inner())
-                .map_err(|err| DatabaseError::InsertPackageFailed(pkg_name.clone(), err))?;
-            Ok(saved_package)
-        }?;
-
         let urls = publish_info
             .urls
             .iter()
@@ -135,6 +171,77 @@ impl DbConn<'_> {
             })
     }
 
+    /// Set the yanked state of a package version and return the updated package version.
+    /// The package's default version is updated to the most recent non-yanked version.
+    pub fn set_package_version_yanked(
+        &mut self,
+        api_token: &ApiToken,
+        pkg_name: String,
+        version: String,
+        yanked: bool,
+    ) -> Result<models::PackageVersion, DatabaseError> {
+        let package = self.get_package_by_name(pkg_name.clone())?;
+        if !self.is_package_owner(package.id, api_token.user_id)? {
+            // The package exists but the user is not an owner.
+            return Err(DatabaseError::InvalidPublishToken);
+        }
+        api_token.authorize(TokenScope::Yank, &pkg_name)?;
+
+        self.update_package_version_yanked(&package, version, yanked)
+    }
+
+    /// Set the yanked state of a version of the package, without checking permissions.
+    pub(crate) fn update_package_version_yanked(
+        &mut self,
+        package: &models::Package,
+        version: String,
+        yanked: bool,
+    ) -> Result<models::PackageVersion, DatabaseError> {
+        let pkg_name = &package.package_name;
+        let updated_version = diesel::update(
+            schema::package_versions::table
+                .filter(schema::package_versions::package_id.eq(package.id))
+                .filter(schema::package_versions::num.eq(version.clone())),
+        )
+        .set(schema::package_versions::yanked.eq(yanked))
+        .returning(models::PackageVersion::as_returning())
+        .get_result(self.inner())
+        .map_err(|err| {
+            DatabaseError::NotFound(format!("Package {pkg_name} version {version}"), err)
+        })?;
+
+        self.update_default_version(package)?;
+
+        Ok(updated_version)
+    }
+
+    /// Set the package's default version to its most recent non-yanked version, or to its
+    /// most recent version if all of them are yanked. Packages without versions have no
+    /// default version.
+    pub(crate) fn update_default_version(
+        &mut self,
+        package: &models::Package,
+    ) -> Result<(), DatabaseError> {
+        let pkg_name = &package.package_name;
+        let default_version = schema::package_versions::table
+            .filter(schema::package_versions::package_id.eq(package.id))
+            .order_by((
+                schema::package_versions::yanked.asc(),
+                schema::package_versions::created_at.desc(),
+            ))
+            .select(schema::package_versions::id)
+            .first::<Uuid>(self.inner())
+            .optional()
+            .map_err(|err| DatabaseError::QueryFailed(pkg_name.clone(), err))?;
+
+        diesel::update(schema::packages::table.filter(schema::packages::id.eq(package.id)))
+            .set(schema::packages::default_version.eq(default_version))
+            .execute(self.inner())
+            .map_err(|err| DatabaseError::UpdatePackageFailed(pkg_name.clone(), err))?;
+
+        Ok(())
+    }
+
     /// Fetch the most recently updated packages.
     pub fn get_recently_updated(
         &mut self,
@@ -148,6 +255,7 @@ impl DbConn<'_> {
                     pv.package_description AS description, 
                     p.created_at AS created_at, 
                     pv.created_at AS updated_at,
+                    p.downloads AS downloads,
                     u.docs_ipfs_hash AS docs_ipfs_hash,
                     ROW_NUMBER() OVER (PARTITION BY p.id ORDER BY pv.created_at DESC) AS rank
                 FROM package_versions pv
@@ -160,6 +268,7 @@ impl DbConn<'_> {
                 description, 
                 created_at, 
                 updated_at,
+                downloads,
                 docs_ipfs_hash
             FROM ranked_versions
             WHERE rank = 1
@@ -186,6 +295,7 @@ impl DbConn<'_> {
                     pv.package_description AS description, 
                     p.created_at AS created_at, 
                     pv.created_at AS updated_at,
+                    p.downloads AS downloads,
                     u.docs_ipfs_hash AS docs_ipfs_hash,
                     ROW_NUMBER() OVER (PARTITION BY p.id ORDER BY pv.created_at DESC) AS rank
                 FROM package_versions pv
@@ -198,6 +308,7 @@ impl DbConn<'_> {
                 description, 
                 created_at, 
                 updated_at,
+                downloads,
                 docs_ipfs_hash
             FROM ranked_versions
             WHERE rank = 1
@@ -245,6 +356,12 @@ impl DbConn<'_> {
                 pv.package_description AS description,
                 p.created_at AS created_at,
                 pv.created_at AS updated_at,
+                p.downloads AS downloads,
+                (
+                    SELECT COALESCE(SUM(d.downloads), 0)::BIGINT
+                    FROM package_version_downloads d
+                    WHERE d.package_version_id = pv.id
+                ) AS version_downloads,
         
                 u.bytecode_identifier AS bytecode_identifier,
                 u.forc_version AS forc_version,
@@ -305,6 +422,12 @@ impl DbConn<'_> {
                     pv.package_description AS description,
                     p.created_at AS created_at,
                     pv.created_at AS updated_at,
+                    p.downloads AS downloads,
+                    (
+                        SELECT COALESCE(SUM(d.downloads), 0)::BIGINT
+                        FROM package_version_downloads d
+                        WHERE d.package_version_id = pv.id
+                    ) AS version_downloads,
             
                     u.bytecode_identifier AS bytecode_identifier,
                     u.forc_version AS forc_version,
@@ -349,7 +472,7 @@ impl DbConn<'_> {
     ) -> Result<Vec<PackageVersionInfo>, DatabaseError> {
         use schema::package_versions;
         use schema::package_versions::columns::{
-            created_at as pv_created_at, license, num, package_id, published_by,
+            created_at as pv_created_at, license, num, package_id, published_by, yanked,
         };
         use schema::users;
         use schema::users::columns::{full_name, github_login};
@@ -360,14 +483,21 @@ impl DbConn<'_> {
             .inner_join(users::table.on(published_by.eq(users::id)))
             .filter(package_id.eq(package.id))
             .order_by(package_versions::created_at.desc())
-            .select((num, full_name, github_login, license, pv_created_at))
-            .load::<(String, String, String, Option<String>, DateTime<Utc>)>(self.inner())
+            .select((num, full_name, github_login, license, pv_created_at, yanked))
+            .load::<(String, String, String, Option<String>, DateTime<Utc>, bool)>(self.inner())
             .map_err(|err| DatabaseError::QueryFailed(pkg_name, err))?;
 
         Ok(results
             .into_iter()
             .map(
-                |(version, author_full_name, author_github_login, pkg_license, created_at)| {
+                |(
+                    version,
+                    author_full_name,
+                    author_github_login,
+                    pkg_license,
+                    created_at,
+                    is_yanked,
+                )| {
                     PackageVersionInfo {
                         version,
                         author: AuthorInfo {
@@ -376,6 +506,7 @@ impl DbConn<'_> {
                         },
                         license: pkg_license,
                         created_at,
+                        yanked: is_yanked,
                     }
                 },
             )
@@ -399,6 +530,7 @@ impl DbConn<'_> {
                     pv.package_description AS description, 
                     p.created_at AS created_at, 
                     pv.created_at AS updated_at,
+                    p.downloads AS downloads,
                     ROW_NUMBER() OVER (PARTITION BY p.id ORDER BY pv.created_at DESC) AS rank,
                     -- Combined relevance scoring including categories and keywords
                     GREATEST(
@@ -444,10 +576,11 @@ impl DbConn<'_> {
                 version, 
                 description, 
                 created_at, 
-                updated_at
+                updated_at,
+                downloads
             FROM ranked_versions
             WHERE rank = 1 AND relevance_score > 0.1
-            ORDER BY relevance_score DESC, created_at DESC
+            ORDER BY relevance_score DESC, downloads DESC, created_at DESC
             OFFSET $2
             LIMIT $3;
             "#,
@@ -593,6 +726,7 @@ impl DbConn<'_> {
                     pv.package_description AS description, 
                     p.created_at AS created_at, 
                     pv.created_at AS updated_at,
+                    p.downloads AS downloads,
                     ROW_NUMBER() OVER (PARTITION BY p.id ORDER BY pv.created_at DESC) AS rank
                 FROM package_versions pv
                 JOIN packages p ON pv.package_id = p.id
@@ -606,7 +740,8 @@ impl DbConn<'_> {
                 version, 
                 description, 
                 created_at, 
-                updated_at
+                updated_at,
+                downloads
             FROM ranked_versions
             WHERE rank = 1
             ORDER BY created_at DESC
@@ -733,6 +868,7 @@ impl DbConn<'_> {
                     pv.package_description AS description, 
                     p.created_at AS created_at, 
                     pv.created_at AS updated_at,
+                    p.downloads AS downloads,
                     ROW_NUMBER() OVER (PARTITION BY p.id ORDER BY pv.created_at DESC) AS rank
                 FROM package_versions pv
                 JOIN packages p ON pv.package_id = p.id
@@ -746,7 +882,8 @@ impl DbConn<'_> {
                 version, 
                 description, 
                 created_at, 
-                updated_at
+                updated_at,
+                downloads
             FROM ranked_versions
             WHERE rank = 1
             ORDER BY created_at DESC
@@ -945,6 +1082,7 @@ impl DbConn<'_> {
                     pv.package_description AS description, 
                     p.created_at AS created_at, 
                     pv.created_at AS updated_at,
+                    p.downloads AS downloads,
                     ROW_NUMBER() OVER (PARTITION BY p.id ORDER BY pv.created_at DESC) AS rank,
                     -- Combined relevance scoring
                     GREATEST(
@@ -972,10 +1110,11 @@ impl DbConn<'_> {
                 version, 
                 description, 
                 created_at, 
-                updated_at
+                updated_at,
+                downloads
             FROM ranked_versions
             WHERE rank = 1 AND relevance_score > 0.1
-            ORDER BY relevance_score DESC, created_at DESC
+            ORDER BY relevance_score DESC, downloads DESC, created_at DESC
             OFFSET $3
             LIMIT $4;
             "#,
@@ -1041,6 +1180,7 @@ impl DbConn<'_> {
                     pv.package_description AS description, 
                     p.created_at AS created_at, 
                     pv.created_at AS updated_at,
+                    p.downloads AS downloads,
                     ROW_NUMBER() OVER (PARTITION BY p.id ORDER BY pv.created_at DESC) AS rank,
                     -- Combined relevance scoring
                     GREATEST(
@@ -1068,10 +1208,11 @@ impl DbConn<'_> {
                 version, 
                 description, 
                 created_at, 
-                updated_at
+                updated_at,
+                downloads
             FROM ranked_versions
             WHERE rank = 1 AND relevance_score > 0.1
-            ORDER BY relevance_score DESC, created_at DESC
+            ORDER BY relevance_score DESC, downloads DESC, created_at DESC
             OFFSET $3
             LIMIT $4;
             "#,
@@ -1137,6 +1278,7 @@ impl DbConn<'_> {
                     pv.package_description AS description, 
                     p.created_at AS created_at, 
                     pv.created_at AS updated_at,
+                    p.downloads AS downloads,
                     ROW_NUMBER() OVER (PARTITION BY p.id ORDER BY pv.created_at DESC) AS rank
                 FROM package_versions pv
                 JOIN packages p ON pv.package_id = p.id
@@ -1151,7 +1293,8 @@ impl DbConn<'_> {
                 version, 
                 description, 
                 created_at, 
-                updated_at
+                updated_at,
+                downloads
             FROM ranked_versions
             WHERE rank = 1
             ORDER BY created_at DESC
@@ -1223,6 +1366,7 @@ impl DbConn<'_> {
                     pv.package_description AS description, 
                     p.created_at AS created_at, 
                     pv.created_at AS updated_at,
+                    p.downloads AS downloads,
                     ROW_NUMBER() OVER (PARTITION BY p.id ORDER BY pv.created_at DESC) AS rank,
                     -- Combined relevance scoring
                     GREATEST(
@@ -1252,10 +1396,11 @@ impl DbConn<'_> {
                 version, 
                 description, 
                 created_at, 
-                updated_at
+                updated_at,
+                downloads
             FROM ranked_versions
             WHERE rank = 1 AND relevance_score > 0.1
-            ORDER BY relevance_score DESC, created_at DESC
+            ORDER BY relevance_score DESC, downloads DESC, created_at DESC
             OFFSET $4
             LIMIT $5;
             "#,
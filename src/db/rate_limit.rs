@@ -0,0 +1,46 @@
+use super::error::DatabaseError;
+use super::{schema, DbConn};
+use chrono::{DateTime, Utc};
+use diesel::dsl::case_when;
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+
+impl DbConn<'_> {
+    /// Count a request in the rate limit window of the bucket with the given key, and return
+    /// the number of requests counted in the window so far. A request in a new window
+    /// restarts the count. Windows that have ended are removed.
+    pub fn hit_rate_limit_window(
+        &mut self,
+        bucket_key: &str,
+        window_start: DateTime<Utc>,
+        window_end: DateTime<Utc>,
+    ) -> Result<i64, DatabaseError> {
+        use schema::rate_limit_windows as windows;
+
+        diesel::delete(windows::table.filter(windows::window_end.lt(window_start)))
+            .execute(self.inner())
+            .map_err(|err| DatabaseError::QueryFailed("rate limit windows".to_string(), err))?;
+
+        diesel::insert_into(windows::table)
+            .values((
+                windows::key.eq(bucket_key),
+                windows::window_start.eq(window_start),
+                windows::window_end.eq(window_end),
+                windows::count.eq(1),
+            ))
+            .on_conflict(windows::key)
+            .do_update()
+            .set((
+                windows::count.eq(case_when(
+                    windows::window_start.eq(excluded(windows::window_start)),
+                    windows::count + 1,
+                )
+                .otherwise(1)),
+                windows::window_start.eq(excluded(windows::window_start)),
+                windows::window_end.eq(excluded(windows::window_end)),
+            ))
+            .returning(windows::count)
+            .get_result(self.inner())
+            .map_err(|err| DatabaseError::QueryFailed(bucket_key.to_string(), err))
+    }
+}
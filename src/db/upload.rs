@@ -27,4 +27,30 @@ impl DbConn<'_> {
             .first::<models::Upload>(self.inner())
             .map_err(|err| DatabaseError::NotFound(upload_id.to_string(), err))
     }
+
+    /// Fetch the upload records to publish given an upload ID. For a workspace upload,
+    /// these are the uploads of all the workspace members.
+    pub fn get_uploads_to_publish(
+        &mut self,
+        upload_id: Uuid,
+    ) -> Result<Vec<models::Upload>, DatabaseError> {
+        let uploads = schema::uploads::table
+            .filter(
+                schema::uploads::id
+                    .eq(upload_id)
+                    .or(schema::uploads::workspace_upload_id.eq(upload_id)),
+            )
+            .order_by(schema::uploads::created_at.asc())
+            .select(models::Upload::as_returning())
+            .load::<models::Upload>(self.inner())
+            .map_err(|err| DatabaseError::NotFound(upload_id.to_string(), err))?;
+
+        if uploads.is_empty() {
+            return Err(DatabaseError::NotFound(
+                upload_id.to_string(),
+                diesel::result::Error::NotFound,
+            ));
+        }
+        Ok(uploads)
+    }
 }
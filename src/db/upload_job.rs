@@ -0,0 +1,319 @@
+use super::error::DatabaseError;
+use super::{models, schema, string_to_uuid, DbConn};
+use crate::models::NewUpload;
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// The state of an upload job.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum UploadJobStatus {
+    /// Waiting for a worker, either for the first time or to be retried.
+    Pending,
+    /// Claimed by a worker.
+    Running,
+    /// The upload was created and can be published.
+    Succeeded,
+    /// The job failed and won't be retried.
+    Failed,
+}
+
+impl UploadJobStatus {
+    pub const ALL: [UploadJobStatus; 4] = [
+        UploadJobStatus::Pending,
+        UploadJobStatus::Running,
+        UploadJobStatus::Succeeded,
+        UploadJobStatus::Failed,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UploadJobStatus::Pending => "pending",
+            UploadJobStatus::Running => "running",
+            UploadJobStatus::Succeeded => "succeeded",
+            UploadJobStatus::Failed => "failed",
+        }
+    }
+}
+
+impl fmt::Display for UploadJobStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for UploadJobStatus {
+    type Err = DatabaseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        UploadJobStatus::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .ok_or_else(|| DatabaseError::InvalidUploadJobStatus(s.to_string()))
+    }
+}
+
+impl models::UploadJob {
+    pub fn status(&self) -> Result<UploadJobStatus, DatabaseError> {
+        self.status.parse()
+    }
+}
+
+impl DbConn<'_> {
+    /// Insert a pending upload job for the tarball and return it. A dry-run job builds the
+    /// project without storing anything.
+    pub fn new_upload_job(
+        &mut self,
+        id: Uuid,
+        forc_version: String,
+        tarball: Vec<u8>,
+        message: String,
+        dry_run: bool,
+    ) -> Result<models::UploadJob, DatabaseError> {
+        let new_job = models::NewUploadJob {
+            id,
+            forc_version,
+            tarball: Some(tarball),
+            message: Some(message),
+            dry_run,
+        };
+
+        diesel::insert_into(schema::upload_jobs::table)
+            .values(&new_job)
+            .returning(models::UploadJob::as_returning())
+            .get_result(self.inner())
+            .map_err(|err| DatabaseError::InsertUploadFailed(id.to_string(), err))
+    }
+
+    /// Fetch an upload job given its ID.
+    pub fn get_upload_job(&mut self, job_id: String) -> Result<models::UploadJob, DatabaseError> {
+        let job_uuid = string_to_uuid(job_id.clone())?;
+        schema::upload_jobs::table
+            .filter(schema::upload_jobs::id.eq(job_uuid))
+            .select(models::UploadJob::as_returning())
+            .first::<models::UploadJob>(self.inner())
+            .map_err(|err| DatabaseError::NotFound(job_id, err))
+    }
+
+    /// Fetch the tarball of an upload job, which is cleared once the job has finished.
+    pub fn get_upload_job_tarball(&mut self, job_id: Uuid) -> Result<Vec<u8>, DatabaseError> {
+        schema::upload_jobs::table
+            .filter(schema::upload_jobs::id.eq(job_id))
+            .select(schema::upload_jobs::tarball)
+            .first::<Option<Vec<u8>>>(self.inner())
+            .map_err(|err| DatabaseError::NotFound(job_id.to_string(), err))?
+            .ok_or_else(|| DatabaseError::NotFound(job_id.to_string(), diesel::NotFound))
+    }
+
+    /// Claim the next upload job that is ready to run and return it. Running jobs whose
+    /// worker hasn't reported progress within the lease are claimed again, so jobs
+    /// survive worker restarts.
+    ///
+    /// Jobs are locked with `SKIP LOCKED`, so concurrent workers never claim the same job.
+    pub fn claim_upload_job(
+        &mut self,
+        lease: Duration,
+    ) -> Result<Option<models::UploadJob>, DatabaseError> {
+        use schema::upload_jobs;
+
+        let now = Utc::now();
+        let job_id = upload_jobs::table
+            .filter(
+                upload_jobs::status
+                    .eq(UploadJobStatus::Pending.as_str())
+                    .and(upload_jobs::run_at.le(now))
+                    .or(upload_jobs::status
+                        .eq(UploadJobStatus::Running.as_str())
+                        .and(upload_jobs::locked_at.lt(now - lease))),
+            )
+            .order_by(upload_jobs::run_at.asc())
+            .select(upload_jobs::id)
+            .for_update()
+            .skip_locked()
+            .first::<Uuid>(self.inner())
+            .optional()
+            .map_err(|err| DatabaseError::QueryFailed("claim upload job".to_string(), err))?;
+
+        let Some(job_id) = job_id else {
+            return Ok(None);
+        };
+
+        diesel::update(upload_jobs::table.filter(upload_jobs::id.eq(job_id)))
+            .set((
+                upload_jobs::status.eq(UploadJobStatus::Running.as_str()),
+                upload_jobs::attempts.eq(upload_jobs::attempts + 1),
+                upload_jobs::locked_at.eq(now),
+                upload_jobs::updated_at.eq(now),
+            ))
+            .returning(models::UploadJob::as_returning())
+            .get_result(self.inner())
+            .map(Some)
+            .map_err(|err| DatabaseError::QueryFailed(job_id.to_string(), err))
+    }
+
+    /// Report the progress of a running upload job, which also renews its lease.
+    pub fn set_upload_job_message(
+        &mut self,
+        job_id: Uuid,
+        message: String,
+    ) -> Result<(), DatabaseError> {
+        use schema::upload_jobs;
+
+        let now = Utc::now();
+        diesel::update(
+            upload_jobs::table
+                .filter(upload_jobs::id.eq(job_id))
+                .filter(upload_jobs::status.eq(UploadJobStatus::Running.as_str())),
+        )
+        .set((
+            upload_jobs::message.eq(message),
+            upload_jobs::locked_at.eq(now),
+            upload_jobs::updated_at.eq(now),
+        ))
+        .execute(self.inner())
+        .map_err(|err| DatabaseError::QueryFailed(job_id.to_string(), err))?;
+        Ok(())
+    }
+
+    /// Renew the lease of a running upload job without changing its progress message.
+    pub fn renew_upload_job_lease(&mut self, job_id: Uuid) -> Result<(), DatabaseError> {
+        use schema::upload_jobs;
+
+        let now = Utc::now();
+        diesel::update(
+            upload_jobs::table
+                .filter(upload_jobs::id.eq(job_id))
+                .filter(upload_jobs::status.eq(UploadJobStatus::Running.as_str())),
+        )
+        .set((
+            upload_jobs::locked_at.eq(now),
+            upload_jobs::updated_at.eq(now),
+        ))
+        .execute(self.inner())
+        .map_err(|err| DatabaseError::QueryFailed(job_id.to_string(), err))?;
+        Ok(())
+    }
+
+    /// Store the uploads created by an upload job and mark the job as succeeded.
+    pub fn complete_upload_job(
+        &mut self,
+        job_id: Uuid,
+        uploads: &[NewUpload],
+    ) -> Result<models::UploadJob, DatabaseError> {
+        use schema::upload_jobs;
+
+        for upload in uploads {
+            self.new_upload(upload)?;
+        }
+
+        diesel::update(upload_jobs::table.filter(upload_jobs::id.eq(job_id)))
+            .set((
+                upload_jobs::status.eq(UploadJobStatus::Succeeded.as_str()),
+                upload_jobs::tarball.eq(None::<Vec<u8>>),
+                upload_jobs::error.eq(None::<String>),
+                upload_jobs::diagnostics.eq(None::<String>),
+                upload_jobs::locked_at.eq(None::<DateTime<Utc>>),
+                upload_jobs::updated_at.eq(Utc::now()),
+            ))
+            .returning(models::UploadJob::as_returning())
+            .get_result(self.inner())
+            .map_err(|err| DatabaseError::QueryFailed(job_id.to_string(), err))
+    }
+
+    /// Store the uploads that a dry-run upload job would have created in its report, and
+    /// mark the job as succeeded. Nothing is inserted into `uploads`.
+    pub fn complete_dry_run_upload_job(
+        &mut self,
+        job_id: Uuid,
+        uploads: &[NewUpload],
+    ) -> Result<models::UploadJob, DatabaseError> {
+        use schema::upload_jobs;
+
+        let report = serde_json::to_string(uploads)
+            .map_err(|err| DatabaseError::InvalidUploadReport(job_id.to_string(), err))?;
+        diesel::update(upload_jobs::table.filter(upload_jobs::id.eq(job_id)))
+            .set((
+                upload_jobs::status.eq(UploadJobStatus::Succeeded.as_str()),
+                upload_jobs::report.eq(report),
+                upload_jobs::tarball.eq(None::<Vec<u8>>),
+                upload_jobs::error.eq(None::<String>),
+                upload_jobs::diagnostics.eq(None::<String>),
+                upload_jobs::locked_at.eq(None::<DateTime<Utc>>),
+                upload_jobs::updated_at.eq(Utc::now()),
+            ))
+            .returning(models::UploadJob::as_returning())
+            .get_result(self.inner())
+            .map_err(|err| DatabaseError::QueryFailed(job_id.to_string(), err))
+    }
+
+    /// Fetch the uploads that a succeeded dry-run upload job would have created.
+    pub fn get_dry_run_uploads(
+        &mut self,
+        upload_id: Uuid,
+    ) -> Result<Vec<NewUpload>, DatabaseError> {
+        let job = self.get_upload_job(upload_id.to_string())?;
+        let report = job
+            .report
+            .filter(|_| job.dry_run && job.status == UploadJobStatus::Succeeded.as_str())
+            .ok_or_else(|| DatabaseError::NotFound(upload_id.to_string(), diesel::NotFound))?;
+        serde_json::from_str(&report)
+            .map_err(|err| DatabaseError::InvalidUploadReport(upload_id.to_string(), err))
+    }
+
+    /// Record the error and compiler diagnostics of a failed upload job attempt. The job
+    /// is retried at `retry_at` if given, and otherwise fails permanently.
+    pub fn fail_upload_job(
+        &mut self,
+        job_id: Uuid,
+        error: String,
+        diagnostics: Option<String>,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<models::UploadJob, DatabaseError> {
+        use schema::upload_jobs;
+
+        let now = Utc::now();
+        let update = diesel::update(upload_jobs::table.filter(upload_jobs::id.eq(job_id)));
+        let result = match retry_at {
+            Some(retry_at) => update
+                .set((
+                    upload_jobs::status.eq(UploadJobStatus::Pending.as_str()),
+                    upload_jobs::error.eq(error),
+                    upload_jobs::diagnostics.eq(diagnostics),
+                    upload_jobs::run_at.eq(retry_at),
+                    upload_jobs::locked_at.eq(None::<DateTime<Utc>>),
+                    upload_jobs::updated_at.eq(now),
+                ))
+                .returning(models::UploadJob::as_returning())
+                .get_result(self.inner()),
+            None => update
+                .set((
+                    upload_jobs::status.eq(UploadJobStatus::Failed.as_str()),
+                    upload_jobs::error.eq(error),
+                    upload_jobs::diagnostics.eq(diagnostics),
+                    upload_jobs::tarball.eq(None::<Vec<u8>>),
+                    upload_jobs::locked_at.eq(None::<DateTime<Utc>>),
+                    upload_jobs::updated_at.eq(now),
+                ))
+                .returning(models::UploadJob::as_returning())
+                .get_result(self.inner()),
+        };
+        result.map_err(|err| DatabaseError::QueryFailed(job_id.to_string(), err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upload_job_status_round_trip() {
+        for status in UploadJobStatus::ALL {
+            assert_eq!(status.as_str().parse::<UploadJobStatus>().unwrap(), status);
+        }
+        assert!("done".parse::<UploadJobStatus>().is_err());
+    }
+}
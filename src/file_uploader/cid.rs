@@ -0,0 +1,209 @@
+//! Computes IPFS content identifiers (CIDs) locally, without an IPFS node.
+//!
+//! The CIDs match those produced by `ipfs add --cid-version=1` with the default
+//! settings: files are split into 256KiB chunks stored as raw leaves, and the
+//! chunks are linked by a balanced tree of UnixFS file nodes.
+
+use sha2::{Digest, Sha256};
+
+const CHUNK_SIZE: usize = 256 * 1024;
+const MAX_LINKS: usize = 174;
+const CID_VERSION: u64 = 1;
+const RAW_CODEC: u64 = 0x55;
+const DAG_PB_CODEC: u64 = 0x70;
+const SHA2_256_CODE: u64 = 0x12;
+const UNIXFS_FILE_TYPE: u64 = 2;
+const BASE32_ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+/// A node in the file's DAG.
+struct DagNode {
+    /// The binary CID of the node.
+    cid: Vec<u8>,
+    /// The number of bytes of file content below the node.
+    file_size: u64,
+    /// The total size of the encoded blocks below and including the node.
+    tree_size: u64,
+}
+
+/// Returns the CIDv1 of a file with the given contents, encoded as a base32 string.
+pub fn file_cid(contents: &[u8]) -> String {
+    let mut nodes: Vec<DagNode> = if contents.is_empty() {
+        vec![raw_leaf(contents)]
+    } else {
+        contents.chunks(CHUNK_SIZE).map(raw_leaf).collect()
+    };
+    while nodes.len() > 1 {
+        nodes = nodes.chunks(MAX_LINKS).map(file_node).collect();
+    }
+    format!("b{}", base32_encode(&nodes[0].cid))
+}
+
+/// Returns true if the string looks like a CID, so it is safe to use as a file name or URL path.
+pub fn is_valid_cid(cid: &str) -> bool {
+    !cid.is_empty() && cid.len() <= 128 && cid.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn raw_leaf(chunk: &[u8]) -> DagNode {
+    DagNode {
+        cid: cid_bytes(RAW_CODEC, chunk),
+        file_size: chunk.len() as u64,
+        tree_size: chunk.len() as u64,
+    }
+}
+
+fn file_node(children: &[DagNode]) -> DagNode {
+    let file_size = children.iter().map(|child| child.file_size).sum();
+
+    // The UnixFS data of a file node: its type, total size, and the size of each child.
+    let mut data = vec![];
+    write_varint_field(&mut data, 1, UNIXFS_FILE_TYPE);
+    write_varint_field(&mut data, 3, file_size);
+    for child in children {
+        write_varint_field(&mut data, 4, child.file_size);
+    }
+
+    // The DAG-PB node, with the links encoded before the data.
+    let mut node = vec![];
+    for child in children {
+        let mut link = vec![];
+        write_bytes_field(&mut link, 1, &child.cid);
+        write_bytes_field(&mut link, 2, b"");
+        write_varint_field(&mut link, 3, child.tree_size);
+        write_bytes_field(&mut node, 2, &link);
+    }
+    write_bytes_field(&mut node, 1, &data);
+
+    let tree_size = node.len() as u64 + children.iter().map(|c| c.tree_size).sum::<u64>();
+    DagNode {
+        cid: cid_bytes(DAG_PB_CODEC, &node),
+        file_size,
+        tree_size,
+    }
+}
+
+fn cid_bytes(codec: u64, block: &[u8]) -> Vec<u8> {
+    let digest = Sha256::digest(block);
+    let mut cid = vec![];
+    write_varint(&mut cid, CID_VERSION);
+    write_varint(&mut cid, codec);
+    write_varint(&mut cid, SHA2_256_CODE);
+    write_varint(&mut cid, digest.len() as u64);
+    cid.extend_from_slice(&digest);
+    cid
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn write_varint_field(buf: &mut Vec<u8>, field: u64, value: u64) {
+    write_varint(buf, field << 3);
+    write_varint(buf, value);
+}
+
+fn write_bytes_field(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    write_varint(buf, (field << 3) | 2);
+    write_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+/// Encodes bytes as lowercase RFC 4648 base32 without padding.
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let mut buffer: u16 = 0;
+    let mut bits = 0;
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u16;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base32_encode() {
+        assert_eq!(base32_encode(b""), "");
+        assert_eq!(base32_encode(b"f"), "my");
+        assert_eq!(base32_encode(b"foobar"), "mzxw6ytboi");
+    }
+
+    #[test]
+    fn test_write_varint() {
+        let mut buf = vec![];
+        write_varint(&mut buf, 300);
+        assert_eq!(buf, vec![0xac, 0x02]);
+    }
+
+    #[test]
+    fn test_file_cid_single_chunk() {
+        assert_eq!(
+            file_cid(b""),
+            "bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku"
+        );
+        assert_eq!(
+            file_cid(b"hello world"),
+            "bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e"
+        );
+    }
+
+    #[test]
+    fn test_file_cid_multiple_chunks() {
+        let contents = vec![7u8; CHUNK_SIZE * 2 + 1];
+        let cid = file_cid(&contents);
+
+        // Files with more than one chunk have a DAG-PB root node.
+        assert!(cid.starts_with("bafybei"));
+        assert_eq!(cid, file_cid(&contents));
+        assert_ne!(cid, file_cid(&contents[1..]));
+    }
+
+    /// Checks the CIDs of files with one and two levels of file nodes against the CIDs
+    /// produced by Kubo. Run it with `cargo test -- --ignored` where the Kubo `ipfs` binary
+    /// is installed and its repo is initialized with `ipfs init`.
+    #[test]
+    #[ignore = "requires the Kubo `ipfs` binary"]
+    fn test_file_cid_matches_kubo() {
+        use std::io::Write;
+        use std::process::Command;
+
+        for len in [CHUNK_SIZE * 2 + 1, CHUNK_SIZE * (MAX_LINKS + 1) + 1] {
+            let contents: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+            let mut file = tempfile::NamedTempFile::new().unwrap();
+            file.write_all(&contents).unwrap();
+
+            let output = Command::new("ipfs")
+                .args(["add", "--only-hash", "--quieter", "--cid-version=1"])
+                .arg(file.path())
+                .output()
+                .expect("ipfs is installed");
+            assert!(output.status.success(), "{output:?}");
+            assert_eq!(
+                String::from_utf8(output.stdout).unwrap().trim(),
+                file_cid(&contents)
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_valid_cid() {
+        assert!(is_valid_cid(
+            "bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e"
+        ));
+        assert!(!is_valid_cid(""));
+        assert!(!is_valid_cid("../etc/passwd"));
+    }
+}
@@ -0,0 +1,65 @@
+use crate::{
+    file_uploader::{cid::file_cid, s3::S3Client, storage::FileStorage},
+    handlers::upload::UploadError,
+};
+use std::{fs, path::Path};
+
+/// Computes the IPFS CIDs that files would be stored under without storing them, for
+/// dry-run uploads.
+pub struct DryRunClient;
+
+impl FileStorage for DryRunClient {
+    async fn new() -> Result<Self, UploadError> {
+        Ok(DryRunClient)
+    }
+
+    fn stores_files(&self) -> bool {
+        false
+    }
+
+    /// Returns the CID of the file at the given path.
+    async fn upload_file_to_ipfs(&self, path: &Path) -> Result<String, UploadError> {
+        let contents = fs::read(path).map_err(|_| UploadError::OpenFile)?;
+        Ok(file_cid(&contents))
+    }
+
+    /// Nothing is stored, so nothing can be fetched.
+    async fn fetch_ipfs_content(&self, ipfs_hash: &str) -> Result<Vec<u8>, UploadError> {
+        Err(UploadError::IpfsFetchFailed(format!(
+            "{ipfs_hash} was not stored by a dry run"
+        )))
+    }
+}
+
+impl S3Client for DryRunClient {
+    async fn new() -> Result<Self, UploadError> {
+        Ok(DryRunClient)
+    }
+
+    fn is_enabled(&self) -> bool {
+        false
+    }
+
+    async fn upload_file_to_s3(&self, _path: &Path, _file_name: String) -> Result<(), UploadError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_uploader::FileUploader;
+
+    #[tokio::test]
+    async fn test_dry_run_upload_returns_cid() {
+        let dir = tempfile::tempdir().expect("tempdir ok");
+        let path = dir.path().join("project.tgz");
+        fs::write(&path, b"Test file contents").unwrap();
+
+        let file_uploader = FileUploader::new(&DryRunClient, &DryRunClient);
+        let ipfs_hash = file_uploader.upload_file(&path).await.unwrap();
+        assert_eq!(ipfs_hash, file_cid(b"Test file contents"));
+        assert!(DryRunClient.fetch_ipfs_content(&ipfs_hash).await.is_err());
+        assert!(!DryRunClient.stores_files());
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{
+    file_uploader::{
+        cid::{file_cid, is_valid_cid},
+        storage::{FileStorage, FileStream},
+    },
+    handlers::upload::UploadError,
+    util::load_env,
+};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+const DEFAULT_STORAGE_PATH: &str = "storage";
+
+/// Stores files in a local directory, named by their IPFS CID.
+pub struct FilesystemClient {
+    storage_path: PathBuf,
+}
+
+impl FilesystemClient {
+    pub fn with_storage_path(storage_path: PathBuf) -> Result<Self, UploadError> {
+        fs::create_dir_all(&storage_path).map_err(|_| UploadError::CreateTempDir)?;
+        Ok(Self { storage_path })
+    }
+
+    /// Returns the path of the file with the given CID.
+    pub fn file_path(&self, ipfs_hash: &str) -> Result<PathBuf, UploadError> {
+        if !is_valid_cid(ipfs_hash) {
+            return Err(UploadError::IpfsFetchFailed(format!(
+                "Invalid IPFS hash: {ipfs_hash}"
+            )));
+        }
+        Ok(self.storage_path.join(ipfs_hash))
+    }
+
+    /// Checks that the storage directory exists and is writable.
+    pub fn check_reachable(&self) -> Result<(), UploadError> {
+        let metadata = fs::metadata(&self.storage_path)
+            .map_err(|err| UploadError::IpfsFetchFailed(err.to_string()))?;
+        if !metadata.is_dir() || metadata.permissions().readonly() {
+            return Err(UploadError::IpfsFetchFailed(format!(
+                "Storage path {} is not a writable directory",
+                self.storage_path.display()
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl FileStorage for FilesystemClient {
+    async fn new() -> Result<Self, UploadError> {
+        load_env();
+        let storage_path =
+            env::var("STORAGE_PATH").unwrap_or_else(|_| DEFAULT_STORAGE_PATH.to_string());
+        Self::with_storage_path(PathBuf::from(storage_path))
+    }
+
+    /// Copies the file at the given path into the storage directory. The file is written
+    /// to a temporary file first and then renamed, so that a file stored under a CID is
+    /// always complete.
+    async fn upload_file_to_ipfs(&self, path: &Path) -> Result<String, UploadError> {
+        let contents = tokio::fs::read(path)
+            .await
+            .map_err(|_| UploadError::OpenFile)?;
+        let ipfs_hash = file_cid(&contents);
+        let file_path = self.file_path(&ipfs_hash)?;
+
+        // Temporary files aren't valid CIDs, so they can't be fetched.
+        let tmp_path = self
+            .storage_path
+            .join(format!(".{ipfs_hash}.{}.tmp", Uuid::new_v4()));
+        let result = async {
+            let mut file = tokio::fs::File::create(&tmp_path).await?;
+            file.write_all(&contents).await?;
+            file.sync_all().await?;
+            tokio::fs::rename(&tmp_path, &file_path).await
+        }
+        .await;
+        if let Err(err) = result {
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+            return Err(UploadError::IpfsUploadFailed(err.to_string()));
+        }
+        Ok(ipfs_hash)
+    }
+
+    /// Reads the file with the given CID from the storage directory.
+    async fn fetch_ipfs_content(&self, ipfs_hash: &str) -> Result<Vec<u8>, UploadError> {
+        tokio::fs::read(self.file_path(ipfs_hash)?)
+            .await
+            .map_err(|err| UploadError::IpfsFetchFailed(err.to_string()))
+    }
+
+    /// Opens the file with the given CID in the storage directory.
+    async fn fetch_ipfs_stream(&self, ipfs_hash: &str) -> Result<FileStream, UploadError> {
+        let file = tokio::fs::File::open(self.file_path(ipfs_hash)?)
+            .await
+            .map_err(|err| UploadError::IpfsFetchFailed(err.to_string()))?;
+        let metadata = file
+            .metadata()
+            .await
+            .map_err(|err| UploadError::IpfsFetchFailed(err.to_string()))?;
+        Ok(FileStream {
+            len: Some(metadata.len()),
+            reader: Box::pin(file),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_upload_and_fetch() {
+        let dir = tempdir().expect("tempdir ok");
+        let client = FilesystemClient::with_storage_path(dir.path().join("storage")).unwrap();
+        let file_path = dir.path().join("file.txt");
+        fs::write(&file_path, b"hello world").unwrap();
+
+        let ipfs_hash = client.upload_file_to_ipfs(&file_path).await.unwrap();
+        assert_eq!(
+            ipfs_hash,
+            "bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e"
+        );
+        assert_eq!(
+            client.fetch_ipfs_content(&ipfs_hash).await.unwrap(),
+            b"hello world"
+        );
+
+        let mut stream = client.fetch_ipfs_stream(&ipfs_hash).await.unwrap();
+        assert_eq!(stream.len, Some(11));
+        let mut contents = Vec::new();
+        tokio::io::AsyncReadExt::read_to_end(&mut stream.reader, &mut contents)
+            .await
+            .unwrap();
+        assert_eq!(contents, b"hello world");
+
+        // Only the file itself is left in the storage directory.
+        let stored: Vec<_> = fs::read_dir(dir.path().join("storage"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(stored, vec![ipfs_hash.as_str()]);
+    }
+
+    #[test]
+    fn test_check_reachable() {
+        let dir = tempdir().expect("tempdir ok");
+        let storage_path = dir.path().join("storage");
+        let client = FilesystemClient::with_storage_path(storage_path.clone()).unwrap();
+        assert!(client.check_reachable().is_ok());
+
+        fs::remove_dir(&storage_path).unwrap();
+        assert!(client.check_reachable().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_invalid_hash() {
+        let dir = tempdir().expect("tempdir ok");
+        let client = FilesystemClient::with_storage_path(dir.path().to_path_buf()).unwrap();
+
+        assert!(client.fetch_ipfs_content("../secret").await.is_err());
+        assert!(client.fetch_ipfs_content("bafkmissing").await.is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{
+    file_uploader::{
+        cid::file_cid,
+        storage::{FileStorage, FileStream},
+    },
+    handlers::upload::UploadError,
+    util::load_env,
+};
+use reqwest::multipart::{Form, Part};
+use serde::Deserialize;
+use std::{env, fs, path::Path};
+
+const DEFAULT_KUBO_API_URL: &str = "http://127.0.0.1:5001";
+
+/// Stores files in a self-hosted IPFS node through the Kubo HTTP RPC API.
+pub struct KuboClient {
+    client: reqwest::Client,
+    api_url: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AddResponse {
+    hash: String,
+}
+
+impl KuboClient {
+    pub fn with_api_url(api_url: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            api_url: api_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Requests content with the given CID from the IPFS node.
+    async fn cat(&self, ipfs_hash: &str) -> Result<reqwest::Response, UploadError> {
+        let response = self
+            .client
+            .post(format!("{}/api/v0/cat", self.api_url))
+            .query(&[("arg", ipfs_hash)])
+            .send()
+            .await
+            .map_err(|e| UploadError::IpfsFetchFailed(format!("Failed to fetch from IPFS: {e}")))?;
+
+        if !response.status().is_success() {
+            return Err(UploadError::IpfsFetchFailed(format!(
+                "IPFS fetch failed with status: {}",
+                response.status()
+            )));
+        }
+        Ok(response)
+    }
+
+    /// Checks that the IPFS node responds to API requests.
+    pub async fn check_reachable(&self) -> Result<(), UploadError> {
+        let response = self
+            .client
+            .post(format!("{}/api/v0/version", self.api_url))
+            .send()
+            .await
+            .map_err(|e| UploadError::IpfsFetchFailed(format!("IPFS node unreachable: {e}")))?;
+        if !response.status().is_success() {
+            return Err(UploadError::IpfsFetchFailed(format!(
+                "IPFS version failed with status: {}",
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl FileStorage for KuboClient {
+    async fn new() -> Result<Self, UploadError> {
+        load_env();
+        let api_url = env::var("KUBO_API_URL").unwrap_or_else(|_| DEFAULT_KUBO_API_URL.to_string());
+        Ok(Self::with_api_url(api_url))
+    }
+
+    /// Adds and pins the file at the given path in the IPFS node.
+    async fn upload_file_to_ipfs(&self, path: &Path) -> Result<String, UploadError> {
+        let contents = fs::read(path).map_err(|_| UploadError::OpenFile)?;
+        let ipfs_hash = file_cid(&contents);
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let form = Form::new().part("file", Part::bytes(contents).file_name(file_name));
+
+        let response = self
+            .client
+            .post(format!("{}/api/v0/add", self.api_url))
+            .query(&[("cid-version", "1"), ("pin", "true")])
+            .multipart(form)
+            .send()
+            .await
+            .map_err(|e| UploadError::IpfsUploadFailed(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(UploadError::IpfsUploadFailed(format!(
+                "IPFS add failed with status: {}",
+                response.status()
+            )));
+        }
+        let added: AddResponse = response
+            .json()
+            .await
+            .map_err(|e| UploadError::IpfsUploadFailed(e.to_string()))?;
+
+        // The node can be configured with a different chunker or hash function.
+        if added.hash != ipfs_hash {
+            tracing::warn!(
+                "IPFS node returned CID {} for {}, expected {}",
+                added.hash,
+                path.display(),
+                ipfs_hash
+            );
+        }
+        Ok(added.hash)
+    }
+
+    /// Fetches content with the given CID from the IPFS node.
+    async fn fetch_ipfs_content(&self, ipfs_hash: &str) -> Result<Vec<u8>, UploadError> {
+        self.cat(ipfs_hash)
+            .await?
+            .bytes()
+            .await
+            .map(|bytes| bytes.to_vec())
+            .map_err(|e| UploadError::IpfsFetchFailed(format!("Failed to read IPFS content: {e}")))
+    }
+
+    /// Streams content with the given CID from the IPFS node.
+    async fn fetch_ipfs_stream(&self, ipfs_hash: &str) -> Result<FileStream, UploadError> {
+        Ok(FileStream::from(self.cat(ipfs_hash).await?))
+    }
+}
@@ -1,27 +1,36 @@
+pub mod cid;
+pub mod dry_run;
+pub mod filesystem;
+pub mod kubo;
 pub mod pinata;
 pub mod s3;
+pub mod storage;
 
-use crate::file_uploader::{pinata::PinataClient, s3::S3Client};
+use crate::file_uploader::{s3::S3Client, storage::FileStorage};
 use crate::handlers::upload::UploadError;
+use crate::metrics::{metrics, UploadStage};
 use std::path::Path;
 use std::{fs::File, io::Read};
 
-pub struct FileUploader<'a, T: PinataClient, E: S3Client> {
-    pinata_client: &'a T,
+/// Stores files in the storage backend, and copies them to S3 if it is enabled.
+pub struct FileUploader<'a, T: FileStorage, E: S3Client> {
+    storage: &'a T,
     s3_client: &'a E,
 }
 
-impl<'a, T: PinataClient, E: S3Client> FileUploader<'a, T, E> {
-    pub fn new(pinata_client: &'a T, s3_client: &'a E) -> Self {
-        Self {
-            pinata_client,
-            s3_client,
-        }
+impl<'a, T: FileStorage, E: S3Client> FileUploader<'a, T, E> {
+    pub fn new(storage: &'a T, s3_client: &'a E) -> Self {
+        Self { storage, s3_client }
     }
 
     pub async fn upload_file(&self, path: &Path) -> Result<String, UploadError> {
         tracing::info!("Uploading file to IPFS: {:?}", path);
-        let ipfs_hash = self.pinata_client.upload_file_to_ipfs(path).await?;
+        let ipfs_timer = self
+            .storage
+            .stores_files()
+            .then(|| metrics().start_upload_stage(UploadStage::Ipfs));
+        let ipfs_hash = self.storage.upload_file_to_ipfs(path).await?;
+        drop(ipfs_timer);
 
         // Read file contents
         let mut file = File::open(path).map_err(|_| UploadError::OpenFile)?;
@@ -29,8 +38,13 @@ impl<'a, T: PinataClient, E: S3Client> FileUploader<'a, T, E> {
         file.read_to_end(&mut buffer)
             .map_err(|_| UploadError::ReadFile)?;
 
+        if !self.s3_client.is_enabled() {
+            return Ok(ipfs_hash);
+        }
+
         // Upload to S3
         tracing::info!("Uploading file to S3: {:?}", path);
+        let _timer = metrics().start_upload_stage(UploadStage::S3);
         self.s3_client
             .upload_file_to_s3(path, ipfs_hash.clone())
             .await?;
@@ -73,7 +87,7 @@ pub(crate) mod tests {
     #[tokio::test]
     async fn test_upload_file_ipfs_failure() {
         struct FailingPinataClient;
-        impl PinataClient for FailingPinataClient {
+        impl FileStorage for FailingPinataClient {
             async fn new() -> Result<Self, UploadError> {
                 Ok(FailingPinataClient)
             }
@@ -136,6 +150,38 @@ pub(crate) mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_upload_file_skips_disabled_s3() {
+        struct DisabledS3Client;
+        impl S3Client for DisabledS3Client {
+            async fn new() -> Result<Self, UploadError> {
+                Ok(DisabledS3Client)
+            }
+            fn is_enabled(&self) -> bool {
+                false
+            }
+            async fn upload_file_to_s3(
+                &self,
+                _path: &Path,
+                _file_name: String,
+            ) -> Result<(), UploadError> {
+                Err(UploadError::S3UploadFailed("S3 is disabled".to_string()))
+            }
+        }
+
+        let pinata_client = MockPinataClient;
+        let s3_client = DisabledS3Client;
+        let file_uploader = FileUploader::new(&pinata_client, &s3_client);
+
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        temp_file
+            .write_all(b"Test file contents")
+            .expect("Failed to write to temp file");
+
+        let result = file_uploader.upload_file(temp_file.path()).await;
+        assert_eq!(result, Ok("ABC123".to_string()));
+    }
+
     #[tokio::test]
     async fn test_upload_file_open_failure() {
         let pinata_client = MockPinataClient;
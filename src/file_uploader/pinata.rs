@@ -1,29 +1,38 @@
 use crate::{
-    handlers::upload::{UploadError, TARBALL_NAME},
+    file_uploader::storage::{FileStorage, FileStream},
+    handlers::upload::UploadError,
     util::load_env,
 };
 use pinata_sdk::{PinByFile, PinataApi};
 use std::{env, path::Path};
 
-const DOCS_TARBALL_NAME: &str = "docs.tgz";
-
-pub trait PinataClient: Sized {
-    fn new() -> impl std::future::Future<Output = Result<Self, UploadError>> + Send;
-    fn upload_file_to_ipfs(
-        &self,
-        path: &Path,
-    ) -> impl std::future::Future<Output = Result<String, UploadError>> + Send;
-    fn fetch_ipfs_content(
-        &self,
-        ipfs_hash: &str,
-    ) -> impl std::future::Future<Output = Result<Vec<u8>, UploadError>> + Send;
+const DEFAULT_PINATA_URL: &str = "https://gateway.pinata.cloud";
+
+/// Returns the URL of the Pinata gateway that serves pinned files.
+pub fn pinata_gateway_url() -> String {
+    env::var("PINATA_URL")
+        .ok()
+        .filter(|url| !url.is_empty())
+        .map_or(DEFAULT_PINATA_URL.to_string(), |url| {
+            url.trim_end_matches('/').to_string()
+        })
 }
 
 pub struct PinataClientImpl {
     pinata_api: PinataApi,
 }
 
-impl PinataClient for PinataClientImpl {
+impl PinataClientImpl {
+    /// Checks that Pinata is reachable and accepts the API key.
+    pub async fn check_reachable(&self) -> Result<(), UploadError> {
+        self.pinata_api
+            .test_authentication()
+            .await
+            .map_err(|_| UploadError::Authentication)
+    }
+}
+
+impl FileStorage for PinataClientImpl {
     async fn new() -> Result<Self, UploadError> {
         load_env();
 
@@ -56,49 +65,43 @@ impl PinataClient for PinataClientImpl {
 
     /// Fetches content from IPFS using the provided hash.
     async fn fetch_ipfs_content(&self, ipfs_hash: &str) -> Result<Vec<u8>, UploadError> {
-        let pinata_domain = env::var("PINATA_URL").expect("PINATA_URL must be set");
-        let url = format!("{pinata_domain}/ipfs/{ipfs_hash}");
-
-        let response = reqwest::get(&url)
-            .await
-            .map_err(|e| UploadError::IpfsFetchFailed(format!("Failed to fetch from IPFS: {e}")))?;
-
-        if !response.status().is_success() {
-            return Err(UploadError::IpfsFetchFailed(format!(
-                "IPFS fetch failed with status: {}",
-                response.status()
-            )));
-        }
-
-        response
+        fetch_from_gateway(ipfs_hash)
+            .await?
             .bytes()
             .await
             .map(|bytes| bytes.to_vec())
             .map_err(|e| UploadError::IpfsFetchFailed(format!("Failed to read IPFS content: {e}")))
     }
-}
 
-pub fn ipfs_hash_to_abi_url(hash: &str) -> String {
-    let pinata_domain = env::var("PINATA_URL").expect("PINATA_URL must be set");
-    format!("{pinata_domain}/ipfs/{hash}")
+    /// Streams content from IPFS using the provided hash.
+    async fn fetch_ipfs_stream(&self, ipfs_hash: &str) -> Result<FileStream, UploadError> {
+        Ok(FileStream::from(fetch_from_gateway(ipfs_hash).await?))
+    }
 }
 
-pub fn ipfs_hash_to_tgz_url(hash: &str) -> String {
-    let pinata_domain = env::var("PINATA_URL").expect("PINATA_URL must be set");
-    format!("{pinata_domain}/ipfs/{hash}?filename={TARBALL_NAME}")
-}
+/// Requests content from the Pinata IPFS gateway.
+async fn fetch_from_gateway(ipfs_hash: &str) -> Result<reqwest::Response, UploadError> {
+    let url = format!("{}/ipfs/{ipfs_hash}", pinata_gateway_url());
+
+    let response = reqwest::get(&url)
+        .await
+        .map_err(|e| UploadError::IpfsFetchFailed(format!("Failed to fetch from IPFS: {e}")))?;
 
-pub fn ipfs_hash_to_docs_url(hash: &str) -> String {
-    let pinata_domain = env::var("PINATA_URL").expect("PINATA_URL must be set");
-    format!("{pinata_domain}/ipfs/{hash}?filename={DOCS_TARBALL_NAME}")
+    if !response.status().is_success() {
+        return Err(UploadError::IpfsFetchFailed(format!(
+            "IPFS fetch failed with status: {}",
+            response.status()
+        )));
+    }
+    Ok(response)
 }
 
-/// A mock implementation of the PinataClient trait for testing.
+/// A mock implementation of the Pinata client for testing.
 #[cfg(test)]
 pub struct MockPinataClient;
 
 #[cfg(test)]
-impl PinataClient for MockPinataClient {
+impl FileStorage for MockPinataClient {
     async fn new() -> Result<Self, UploadError> {
         Ok(MockPinataClient)
     }
@@ -8,6 +8,10 @@ use std::{env, fs::File, io::Read, path::Path};
 
 pub trait S3Client: Sized {
     fn new() -> impl std::future::Future<Output = Result<Self, UploadError>> + Send;
+    /// Returns false if files aren't copied to S3, in which case uploads skip S3.
+    fn is_enabled(&self) -> bool {
+        true
+    }
     fn upload_file_to_s3(
         &self,
         path: &Path,
@@ -20,6 +24,16 @@ pub struct S3ClientImpl {
     bucket_name: String,
 }
 
+impl S3ClientImpl {
+    /// Returns a client that doesn't upload anything.
+    fn disabled() -> Self {
+        S3ClientImpl {
+            s3_client: None,
+            bucket_name: "".to_string(),
+        }
+    }
+}
+
 impl S3Client for S3ClientImpl {
     async fn new() -> Result<Self, UploadError> {
         load_env();
@@ -27,14 +41,18 @@ impl S3Client for S3ClientImpl {
         let run_env = env::var("RUN_ENV").unwrap_or_default();
 
         if run_env == "local" {
-            return Ok(S3ClientImpl {
-                s3_client: None,
-                bucket_name: "".to_string(),
-            });
+            tracing::info!("Skipping S3 uploads for local environment");
+            return Ok(S3ClientImpl::disabled());
         }
 
-        let bucket_name = env::var("S3_BUCKET_NAME")
-            .map_err(|_| UploadError::S3UploadFailed("Missing S3_BUCKET_NAME".to_string()))?;
+        // S3 is an optional copy of the stored files, for the download links.
+        let Some(bucket_name) = env::var("S3_BUCKET_NAME")
+            .ok()
+            .filter(|bucket_name| !bucket_name.is_empty())
+        else {
+            tracing::info!("Skipping S3 uploads because S3_BUCKET_NAME is not set");
+            return Ok(S3ClientImpl::disabled());
+        };
         let bucket_region = env::var("S3_BUCKET_REGION")
             .map_err(|_| UploadError::S3UploadFailed("Missing S3_BUCKET_REGION".to_string()))?;
 
@@ -50,6 +68,10 @@ impl S3Client for S3ClientImpl {
         })
     }
 
+    fn is_enabled(&self) -> bool {
+        self.s3_client.is_some()
+    }
+
     /// Uploads a file at the given path to an S3 bucket.
     async fn upload_file_to_s3(&self, path: &Path, file_name: String) -> Result<(), UploadError> {
         if let Some(s3_client) = &self.s3_client {
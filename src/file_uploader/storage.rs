@@ -0,0 +1,307 @@
+use crate::{
+    api::download::PackageFile,
+    file_uploader::{
+        filesystem::FilesystemClient,
+        kubo::KuboClient,
+        pinata::{pinata_gateway_url, PinataClientImpl},
+    },
+    handlers::upload::{UploadError, TARBALL_NAME},
+    util::load_env,
+};
+use futures_util::TryStreamExt;
+use std::{env, io, io::Cursor, path::Path, pin::Pin, str::FromStr};
+use tokio::io::AsyncRead;
+use tokio_util::io::StreamReader;
+
+const DOCS_TARBALL_NAME: &str = "docs.tgz";
+
+const DEFAULT_SERVER_URL: &str = "http://localhost:8080";
+
+/// A backend that stores files and fetches them by their IPFS hash. Every backend
+/// addresses files by their IPFS CID, whether or not it stores them on IPFS.
+pub trait FileStorage: Sized {
+    fn new() -> impl std::future::Future<Output = Result<Self, UploadError>> + Send;
+    /// Returns false if files are only addressed by their CID and not stored, in which
+    /// case uploads aren't timed as an IPFS stage.
+    fn stores_files(&self) -> bool {
+        true
+    }
+    fn upload_file_to_ipfs(
+        &self,
+        path: &Path,
+    ) -> impl std::future::Future<Output = Result<String, UploadError>> + Send;
+    fn fetch_ipfs_content(
+        &self,
+        ipfs_hash: &str,
+    ) -> impl std::future::Future<Output = Result<Vec<u8>, UploadError>> + Send;
+    /// Fetches a file so that it can be read as it is sent. By default, the whole file is
+    /// fetched first.
+    fn fetch_ipfs_stream(
+        &self,
+        ipfs_hash: &str,
+    ) -> impl std::future::Future<Output = Result<FileStream, UploadError>> + Send
+    where
+        Self: Sync,
+    {
+        async move { Ok(FileStream::from(self.fetch_ipfs_content(ipfs_hash).await?)) }
+    }
+}
+
+/// The contents of a stored file, which are read as they are sent.
+pub struct FileStream {
+    /// The size of the file in bytes, if the backend reports it.
+    pub len: Option<u64>,
+    pub reader: Pin<Box<dyn AsyncRead + Send>>,
+}
+
+impl From<Vec<u8>> for FileStream {
+    fn from(contents: Vec<u8>) -> Self {
+        FileStream {
+            len: Some(contents.len() as u64),
+            reader: Box::pin(Cursor::new(contents)),
+        }
+    }
+}
+
+impl From<reqwest::Response> for FileStream {
+    fn from(response: reqwest::Response) -> Self {
+        FileStream {
+            len: response.content_length(),
+            reader: Box::pin(StreamReader::new(
+                response.bytes_stream().map_err(io::Error::other),
+            )),
+        }
+    }
+}
+
+/// The backend that uploaded files are stored in, selected with `STORAGE_BACKEND`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StorageBackend {
+    /// The Pinata IPFS pinning service.
+    #[default]
+    Pinata,
+    /// A local directory, for private registries and tests.
+    Filesystem,
+    /// A self-hosted IPFS node using the Kubo HTTP RPC API.
+    Kubo,
+}
+
+impl FromStr for StorageBackend {
+    type Err = UploadError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pinata" => Ok(StorageBackend::Pinata),
+            "filesystem" => Ok(StorageBackend::Filesystem),
+            "kubo" => Ok(StorageBackend::Kubo),
+            _ => Err(UploadError::UnsupportedStorageBackend(s.to_string())),
+        }
+    }
+}
+
+impl StorageBackend {
+    /// Returns the configured storage backend, defaulting to Pinata.
+    pub fn from_env() -> Result<Self, UploadError> {
+        load_env();
+        env::var("STORAGE_BACKEND")
+            .ok()
+            .filter(|backend| !backend.is_empty())
+            .map_or(Ok(StorageBackend::default()), |backend| backend.parse())
+    }
+}
+
+/// Builds the links to stored files that are returned by the API, which depend on where
+/// the files can be downloaded from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileUrls {
+    /// An IPFS gateway at the given URL, which serves files by their CID.
+    Gateway(String),
+    /// The download endpoints of this server at the given URL.
+    Registry(String),
+}
+
+impl FileUrls {
+    /// Returns the links for files stored in the given backend. Files stored with Pinata
+    /// are linked on the Pinata gateway at `PINATA_URL`, and files stored in other
+    /// backends on the download endpoints of the server at `SERVER_URL`.
+    /// `STORAGE_GATEWAY_URL` links files on another IPFS gateway instead, such as the
+    /// gateway of the Kubo node.
+    pub fn from_env(backend: StorageBackend) -> Self {
+        load_env();
+        let url = |name: &str| {
+            env::var(name)
+                .ok()
+                .filter(|url| !url.is_empty())
+                .map(|url| url.trim_end_matches('/').to_string())
+        };
+        if let Some(gateway) = url("STORAGE_GATEWAY_URL") {
+            return FileUrls::Gateway(gateway);
+        }
+        match backend {
+            StorageBackend::Pinata => FileUrls::Gateway(pinata_gateway_url()),
+            StorageBackend::Filesystem | StorageBackend::Kubo => FileUrls::Registry(
+                url("SERVER_URL").unwrap_or_else(|| DEFAULT_SERVER_URL.to_string()),
+            ),
+        }
+    }
+
+    /// Returns the link to a file of a package version, which is stored under the given
+    /// CID.
+    pub fn file_url(
+        &self,
+        file: PackageFile,
+        name: &str,
+        version: &str,
+        ipfs_hash: &str,
+    ) -> String {
+        match self {
+            FileUrls::Gateway(gateway) => match file {
+                PackageFile::Tarball => {
+                    format!("{gateway}/ipfs/{ipfs_hash}?filename={TARBALL_NAME}")
+                }
+                PackageFile::Abi => format!("{gateway}/ipfs/{ipfs_hash}"),
+                PackageFile::Docs => {
+                    format!("{gateway}/ipfs/{ipfs_hash}?filename={DOCS_TARBALL_NAME}")
+                }
+            },
+            FileUrls::Registry(server) => {
+                let endpoint = match file {
+                    PackageFile::Tarball => "download",
+                    PackageFile::Abi => "abi",
+                    PackageFile::Docs => "docs",
+                };
+                format!("{server}/api/v1/crates/{name}/{version}/{endpoint}")
+            }
+        }
+    }
+
+    /// Returns true if files are linked on the download endpoints of this server, which
+    /// record the downloads themselves.
+    pub fn is_registry(&self) -> bool {
+        matches!(self, FileUrls::Registry(_))
+    }
+}
+
+/// A client for the configured storage backend.
+pub enum StorageClient {
+    Pinata(PinataClientImpl),
+    Filesystem(FilesystemClient),
+    Kubo(KuboClient),
+}
+
+impl StorageClient {
+    /// Returns the backend that files are stored in.
+    pub fn backend(&self) -> StorageBackend {
+        match self {
+            StorageClient::Pinata(_) => StorageBackend::Pinata,
+            StorageClient::Filesystem(_) => StorageBackend::Filesystem,
+            StorageClient::Kubo(_) => StorageBackend::Kubo,
+        }
+    }
+
+    /// Returns true if files are stored on the local filesystem rather than a remote
+    /// service.
+    pub fn is_local(&self) -> bool {
+        self.backend() == StorageBackend::Filesystem
+    }
+
+    /// Returns the links to the files in the storage backend.
+    pub fn file_urls(&self) -> FileUrls {
+        FileUrls::from_env(self.backend())
+    }
+
+    /// Checks that the storage backend can be reached, for readiness checks.
+    pub async fn check_reachable(&self) -> Result<(), UploadError> {
+        match self {
+            StorageClient::Pinata(client) => client.check_reachable().await,
+            StorageClient::Filesystem(client) => client.check_reachable(),
+            StorageClient::Kubo(client) => client.check_reachable().await,
+        }
+    }
+}
+
+impl FileStorage for StorageClient {
+    async fn new() -> Result<Self, UploadError> {
+        let backend = StorageBackend::from_env()?;
+        tracing::info!("Using {:?} storage backend", backend);
+        match backend {
+            StorageBackend::Pinata => Ok(StorageClient::Pinata(PinataClientImpl::new().await?)),
+            StorageBackend::Filesystem => {
+                Ok(StorageClient::Filesystem(FilesystemClient::new().await?))
+            }
+            StorageBackend::Kubo => Ok(StorageClient::Kubo(KuboClient::new().await?)),
+        }
+    }
+
+    async fn upload_file_to_ipfs(&self, path: &Path) -> Result<String, UploadError> {
+        match self {
+            StorageClient::Pinata(client) => client.upload_file_to_ipfs(path).await,
+            StorageClient::Filesystem(client) => client.upload_file_to_ipfs(path).await,
+            StorageClient::Kubo(client) => client.upload_file_to_ipfs(path).await,
+        }
+    }
+
+    async fn fetch_ipfs_content(&self, ipfs_hash: &str) -> Result<Vec<u8>, UploadError> {
+        match self {
+            StorageClient::Pinata(client) => client.fetch_ipfs_content(ipfs_hash).await,
+            StorageClient::Filesystem(client) => client.fetch_ipfs_content(ipfs_hash).await,
+            StorageClient::Kubo(client) => client.fetch_ipfs_content(ipfs_hash).await,
+        }
+    }
+
+    async fn fetch_ipfs_stream(&self, ipfs_hash: &str) -> Result<FileStream, UploadError> {
+        match self {
+            StorageClient::Pinata(client) => client.fetch_ipfs_stream(ipfs_hash).await,
+            StorageClient::Filesystem(client) => client.fetch_ipfs_stream(ipfs_hash).await,
+            StorageClient::Kubo(client) => client.fetch_ipfs_stream(ipfs_hash).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_backend_from_str() {
+        assert_eq!("pinata".parse(), Ok(StorageBackend::Pinata));
+        assert_eq!("Filesystem".parse(), Ok(StorageBackend::Filesystem));
+        assert_eq!("kubo".parse(), Ok(StorageBackend::Kubo));
+        assert_eq!(
+            "s3".parse::<StorageBackend>(),
+            Err(UploadError::UnsupportedStorageBackend("s3".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_file_urls() {
+        let gateway = FileUrls::Gateway("https://gateway.pinata.cloud".to_string());
+        assert_eq!(
+            gateway.file_url(PackageFile::Tarball, "std", "0.1.0", "bafy"),
+            "https://gateway.pinata.cloud/ipfs/bafy?filename=project.tgz"
+        );
+        assert_eq!(
+            gateway.file_url(PackageFile::Abi, "std", "0.1.0", "bafy"),
+            "https://gateway.pinata.cloud/ipfs/bafy"
+        );
+        assert_eq!(
+            gateway.file_url(PackageFile::Docs, "std", "0.1.0", "bafy"),
+            "https://gateway.pinata.cloud/ipfs/bafy?filename=docs.tgz"
+        );
+
+        let registry = FileUrls::Registry("https://api.forc.pub".to_string());
+        assert_eq!(
+            registry.file_url(PackageFile::Tarball, "std", "0.1.0", "bafy"),
+            "https://api.forc.pub/api/v1/crates/std/0.1.0/download"
+        );
+        assert_eq!(
+            registry.file_url(PackageFile::Abi, "std", "0.1.0", "bafy"),
+            "https://api.forc.pub/api/v1/crates/std/0.1.0/abi"
+        );
+        assert_eq!(
+            registry.file_url(PackageFile::Docs, "std", "0.1.0", "bafy"),
+            "https://api.forc.pub/api/v1/crates/std/0.1.0/docs"
+        );
+        assert!(registry.is_registry() && !gateway.is_registry());
+    }
+}
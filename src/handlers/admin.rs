@@ -0,0 +1,269 @@
+use std::env;
+
+use crate::db::audit_event::AuditEventKind;
+use crate::db::error::DatabaseError;
+use crate::db::Database;
+use crate::handlers::publish::{github_index_publisher, index_tmpdir};
+use crate::handlers::toolchain::{InstalledToolchain, ToolchainManager};
+use crate::handlers::upload::UploadError;
+use crate::handlers::yank::yank_index_entry;
+use crate::index::handler::{IndexPublishError, IndexPublisher};
+use crate::metrics::metrics;
+use crate::middleware::client_info::ClientInfo;
+use crate::models::{PackageNameFlag, PackageVersion, User};
+use crate::util::load_env;
+use semver::Version;
+use serde::Serialize;
+use serde_json::json;
+use thiserror::Error;
+use tracing::info;
+
+#[derive(Error, Debug, Serialize)]
+pub enum AdminError {
+    #[error("Invalid version: {0}")]
+    InvalidVersion(String),
+
+    #[error(transparent)]
+    #[serde(skip)]
+    Database(#[from] DatabaseError),
+
+    #[error(transparent)]
+    #[serde(skip)]
+    Diesel(#[from] diesel::result::Error),
+
+    #[error(transparent)]
+    Index(#[from] IndexPublishError),
+
+    #[error(transparent)]
+    Upload(#[from] UploadError),
+}
+
+/// Returns true if changes to the remote index repo should be skipped.
+fn skip_index() -> bool {
+    load_env();
+    env::var("RUN_ENV").unwrap_or_default() == "local"
+}
+
+/// Removes an index entry from the remote index repo.
+async fn remove_index_entry(
+    package_name: String,
+    version: Version,
+) -> Result<(), IndexPublishError> {
+    let tmpdir = index_tmpdir()?;
+    let github_index_publisher = github_index_publisher(tmpdir.path())?;
+    github_index_publisher
+        .remove_entry(package_name, version)
+        .await
+        .inspect_err(|err| metrics().record_index_publish_failure(err))?;
+    Ok(())
+}
+
+/// Handles an admin yanking or un-yanking any package version. Unlike [handle_yank],
+/// package ownership and token scopes aren't checked. The action is recorded in the
+/// audit log along with the change.
+///
+/// [handle_yank]: crate::handlers::yank::handle_yank
+pub async fn handle_admin_yank(
+    db: &Database,
+    admin: &User,
+    client: &ClientInfo,
+    package_name: String,
+    version: String,
+    yanked: bool,
+) -> Result<PackageVersion, AdminError> {
+    let version =
+        Version::parse(&version).map_err(|_| AdminError::InvalidVersion(version.clone()))?;
+    info!(
+        "Admin {} setting yanked={} for package {} version {}",
+        admin.github_login, yanked, package_name, version
+    );
+
+    let package_version =
+        db.transaction(|conn| conn.get_package_version(package_name.clone(), version.to_string()))?;
+    if package_version.yanked == yanked {
+        return Ok(package_version);
+    }
+
+    if !skip_index() {
+        // Wait for the index to be updated first, so forc and the database
+        // never disagree about whether the version can be resolved.
+        yank_index_entry(package_name.clone(), version.clone(), yanked).await?;
+    }
+
+    let package_version = db.transaction(|conn| {
+        let package = conn.get_package_by_name(package_name.clone())?;
+        let package_version =
+            conn.update_package_version_yanked(&package, version.to_string(), yanked)?;
+        let event = if yanked {
+            AuditEventKind::AdminYank
+        } else {
+            AuditEventKind::AdminUnyank
+        };
+        conn.record_audit_event(
+            admin.id,
+            event,
+            client,
+            json!({ "name": package_name, "version": version }),
+        )?;
+        Ok::<_, DatabaseError>(package_version)
+    })?;
+
+    Ok(package_version)
+}
+
+/// Handles an admin removing a package version, for content that must be taken down.
+/// The version's entry is removed from the index first, so forc stops resolving it,
+/// then the version is deleted from the database. The uploaded files are kept, and the
+/// version number can't be published again.
+pub async fn handle_remove_version(
+    db: &Database,
+    admin: &User,
+    client: &ClientInfo,
+    package_name: String,
+    version: String,
+) -> Result<PackageVersion, AdminError> {
+    let version =
+        Version::parse(&version).map_err(|_| AdminError::InvalidVersion(version.clone()))?;
+    info!(
+        "Admin {} removing package {} version {}",
+        admin.github_login, package_name, version
+    );
+
+    // Ensure the version exists before changing the index.
+    db.transaction(|conn| conn.get_package_version(package_name.clone(), version.to_string()))?;
+
+    if !skip_index() {
+        remove_index_entry(package_name.clone(), version.clone()).await?;
+    }
+
+    let package_version = db.transaction(|conn| {
+        let package_version =
+            conn.remove_package_version(admin.id, package_name.clone(), version.to_string())?;
+        conn.record_audit_event(
+            admin.id,
+            AuditEventKind::AdminRemoveVersion,
+            client,
+            json!({ "name": package_name, "version": version }),
+        )?;
+        Ok::<_, DatabaseError>(package_version)
+    })?;
+
+    Ok(package_version)
+}
+
+/// Handles an admin blocking or unblocking a user from publishing.
+pub fn handle_block_user(
+    db: &Database,
+    admin: &User,
+    client: &ClientInfo,
+    github_login: String,
+    blocked: bool,
+) -> Result<User, AdminError> {
+    info!(
+        "Admin {} setting publish_blocked={} for user {}",
+        admin.github_login, blocked, github_login
+    );
+    let user = db.transaction(|conn| {
+        let user = conn.set_user_publish_blocked(github_login.clone(), blocked)?;
+        let event = if blocked {
+            AuditEventKind::AdminBlockUser
+        } else {
+            AuditEventKind::AdminUnblockUser
+        };
+        conn.record_audit_event(
+            admin.id,
+            event,
+            client,
+            json!({ "githubLogin": github_login }),
+        )?;
+        Ok::<_, DatabaseError>(user)
+    })?;
+    Ok(user)
+}
+
+/// Handles an admin transferring a package to a new owner, who replaces all of the
+/// package's owners. Returns the GitHub logins of the previous owners.
+pub fn handle_transfer_ownership(
+    db: &Database,
+    admin: &User,
+    client: &ClientInfo,
+    package_name: String,
+    github_login: String,
+) -> Result<Vec<String>, AdminError> {
+    info!(
+        "Admin {} transferring package {} to {}",
+        admin.github_login, package_name, github_login
+    );
+    let previous_owners = db.transaction(|conn| {
+        let previous_owners =
+            conn.transfer_package_ownership(package_name.clone(), github_login.clone())?;
+        conn.record_audit_event(
+            admin.id,
+            AuditEventKind::AdminTransferOwnership,
+            client,
+            json!({
+                "name": package_name,
+                "githubLogin": github_login,
+                "previousOwners": previous_owners,
+            }),
+        )?;
+        Ok::<_, DatabaseError>(previous_owners)
+    })?;
+    Ok(previous_owners)
+}
+
+/// Handles an admin marking a package name flag as reviewed.
+pub fn handle_review_package_name_flag(
+    db: &Database,
+    admin: &User,
+    client: &ClientInfo,
+    flag_id: String,
+) -> Result<PackageNameFlag, AdminError> {
+    let flag = db.transaction(|conn| {
+        let flag = conn.review_package_name_flag(flag_id.clone())?;
+        conn.record_audit_event(
+            admin.id,
+            AuditEventKind::AdminReviewPackageName,
+            client,
+            json!({
+                "flagId": flag_id,
+                "packageId": flag.package_id,
+                "similarPackageId": flag.similar_package_id,
+            }),
+        )?;
+        Ok::<_, DatabaseError>(flag)
+    })?;
+    Ok(flag)
+}
+
+/// Handles an admin installing a version of forc, so that uploads for it don't wait for
+/// the install. Installing a version that is already installed marks it as recently used.
+pub async fn handle_install_toolchain(
+    db: &Database,
+    admin: &User,
+    client: &ClientInfo,
+    toolchains: &ToolchainManager,
+    forc_version: String,
+) -> Result<InstalledToolchain, AdminError> {
+    info!(
+        "Admin {} installing forc {}",
+        admin.github_login, forc_version
+    );
+    let toolchain = toolchains.install(&forc_version).await?;
+    let version = toolchain.version().to_string();
+    drop(toolchain);
+
+    db.transaction(|conn| {
+        conn.record_audit_event(
+            admin.id,
+            AuditEventKind::AdminInstallToolchain,
+            client,
+            json!({ "forcVersion": version }),
+        )
+    })?;
+    toolchains
+        .installed()?
+        .into_iter()
+        .find(|toolchain| toolchain.version == version)
+        .ok_or(AdminError::Upload(UploadError::InvalidForcVersion(version)))
+}
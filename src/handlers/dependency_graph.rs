@@ -0,0 +1,221 @@
+use crate::db::error::DatabaseError;
+use crate::db::Database;
+use semver::{Version, VersionReq};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use tracing::info;
+use uuid::Uuid;
+
+/// Whether a dependency could be resolved to a published version.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DependencyStatus {
+    /// A published version that isn't yanked matches the requirement.
+    Resolved,
+    /// Only yanked versions match the requirement.
+    Yanked,
+    /// No published version matches the requirement.
+    Missing,
+}
+
+/// A package version in the dependency graph.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DependencyNode {
+    pub id: usize,
+    pub name: String,
+    /// The resolved version, or `None` if the dependency is missing.
+    pub version: Option<String>,
+    pub status: DependencyStatus,
+}
+
+/// A dependency of one node in the graph on another.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DependencyEdge {
+    /// The ID of the dependent node.
+    pub from: usize,
+    /// The ID of the dependency node.
+    pub to: usize,
+    /// The version requirement the dependent uses for the dependency.
+    pub version_req: String,
+}
+
+/// The transitive dependencies of a package version.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DependencyGraph {
+    /// The ID of the node of the requested package version.
+    pub root: usize,
+    pub nodes: Vec<DependencyNode>,
+    pub edges: Vec<DependencyEdge>,
+}
+
+impl DependencyGraph {
+    /// Adds a node to the graph and returns its ID.
+    fn add_node(
+        &mut self,
+        name: String,
+        version: Option<String>,
+        status: DependencyStatus,
+    ) -> usize {
+        let id = self.nodes.len();
+        self.nodes.push(DependencyNode {
+            id,
+            name,
+            version,
+            status,
+        });
+        id
+    }
+}
+
+/// Returns the highest published version that matches the requirement, and whether it
+/// is yanked. Versions that aren't yanked are preferred over yanked ones.
+pub fn resolve_version(version_req: &str, published: &[(String, bool)]) -> Option<(Version, bool)> {
+    let version_req = VersionReq::parse(version_req).ok()?;
+    published
+        .iter()
+        .filter_map(|(num, yanked)| {
+            Version::parse(num)
+                .ok()
+                .filter(|version| version_req.matches(version))
+                .map(|version| (version, *yanked))
+        })
+        .max_by_key(|(version, yanked)| (!yanked, version.clone()))
+}
+
+/// Resolves the full transitive dependency graph of a package version by matching the
+/// version requirement of each dependency against the published versions of the
+/// dependency.
+///
+/// Every resolved package version appears once in the graph, so shared dependencies
+/// and dependency cycles are represented by multiple edges to the same node. Missing
+/// dependencies have one node per package name.
+pub fn handle_dependency_graph(
+    db: &Database,
+    package_name: String,
+    version: String,
+) -> Result<DependencyGraph, DatabaseError> {
+    info!(
+        "Resolving dependency graph for package {} version {}",
+        package_name, version
+    );
+
+    db.transaction(|conn| {
+        let root = conn.get_package_version(package_name.clone(), version.clone())?;
+
+        let mut graph = DependencyGraph {
+            root: 0,
+            nodes: vec![],
+            edges: vec![],
+        };
+        let root_status = if root.yanked {
+            DependencyStatus::Yanked
+        } else {
+            DependencyStatus::Resolved
+        };
+        graph.root = graph.add_node(package_name.clone(), Some(version.clone()), root_status);
+
+        let mut node_ids: HashMap<(String, Option<String>), usize> = HashMap::new();
+        node_ids.insert((package_name, Some(version)), graph.root);
+        let mut published_versions: HashMap<String, Vec<(String, bool)>> = HashMap::new();
+        let mut queue: VecDeque<(usize, Uuid)> = VecDeque::from([(graph.root, root.id)]);
+
+        while let Some((from, package_version_id)) = queue.pop_front() {
+            for dep in conn.get_dependencies_for_package_version(package_version_id)? {
+                let name = dep.dependency_package_name;
+                if !published_versions.contains_key(&name) {
+                    let versions = conn.get_published_versions(name.clone())?;
+                    published_versions.insert(name.clone(), versions);
+                }
+                let resolved =
+                    resolve_version(&dep.dependency_version_req, &published_versions[&name]);
+
+                let key = (name.clone(), resolved.as_ref().map(|(v, _)| v.to_string()));
+                let to = match node_ids.get(&key) {
+                    Some(id) => *id,
+                    None => {
+                        let id = match resolved {
+                            Some((version, yanked)) => {
+                                let status = if yanked {
+                                    DependencyStatus::Yanked
+                                } else {
+                                    DependencyStatus::Resolved
+                                };
+                                let package_version =
+                                    conn.get_package_version(name.clone(), version.to_string())?;
+                                let id =
+                                    graph.add_node(name.clone(), Some(version.to_string()), status);
+                                queue.push_back((id, package_version.id));
+                                id
+                            }
+                            None => graph.add_node(name.clone(), None, DependencyStatus::Missing),
+                        };
+                        node_ids.insert(key, id);
+                        id
+                    }
+                };
+
+                graph.edges.push(DependencyEdge {
+                    from,
+                    to,
+                    version_req: dep.dependency_version_req,
+                });
+            }
+        }
+
+        Ok(graph)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn published(versions: &[(&str, bool)]) -> Vec<(String, bool)> {
+        versions
+            .iter()
+            .map(|(num, yanked)| (num.to_string(), *yanked))
+            .collect()
+    }
+
+    #[test]
+    fn test_resolve_version() {
+        let versions = published(&[("0.1.0", false), ("0.1.2", false), ("0.2.0", false)]);
+
+        assert_eq!(
+            resolve_version("^0.1.0", &versions),
+            Some((Version::new(0, 1, 2), false))
+        );
+        assert_eq!(
+            resolve_version("0.1.0", &versions),
+            Some((Version::new(0, 1, 2), false))
+        );
+        assert_eq!(
+            resolve_version("=0.1.0", &versions),
+            Some((Version::new(0, 1, 0), false))
+        );
+        assert_eq!(
+            resolve_version(">=0.1.0", &versions),
+            Some((Version::new(0, 2, 0), false))
+        );
+        assert_eq!(resolve_version("^1.0.0", &versions), None);
+        assert_eq!(resolve_version("not a requirement", &versions), None);
+    }
+
+    #[test]
+    fn test_resolve_version_prefers_versions_not_yanked() {
+        let versions = published(&[("0.1.0", false), ("0.1.1", true)]);
+        assert_eq!(
+            resolve_version("^0.1.0", &versions),
+            Some((Version::new(0, 1, 0), false))
+        );
+
+        let versions = published(&[("0.1.0", true), ("0.1.1", true)]);
+        assert_eq!(
+            resolve_version("^0.1.0", &versions),
+            Some((Version::new(0, 1, 1), true))
+        );
+    }
+}
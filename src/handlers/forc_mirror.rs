@@ -0,0 +1,294 @@
+use crate::handlers::upload::UploadError;
+use crate::util::load_env;
+use flate2::read::GzDecoder;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use std::{env, fmt};
+
+/// How long a download from an HTTP mirror may take.
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// A mirror of the forc release archives, which forc is installed from instead of GitHub
+/// when `FORC_MIRROR` is set.
+///
+/// The mirror has the same layout as the release downloads of the Sway repo: the archive
+/// of a version is at `v<version>/forc-binaries-<os>_<arch>.tar.gz`. Each archive must
+/// have its SHA-256 checksum next to it, in a file with a `.sha256` extension, in the
+/// format of `sha256sum`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ForcMirror {
+    /// A local directory, which may be a mounted volume.
+    Directory(PathBuf),
+    /// An HTTP server.
+    Http(String),
+}
+
+impl FromStr for ForcMirror {
+    type Err = UploadError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("http://") || s.starts_with("https://") {
+            return Ok(ForcMirror::Http(s.trim_end_matches('/').to_string()));
+        }
+        let path = s.strip_prefix("file://").unwrap_or(s);
+        if path.is_empty() {
+            return Err(UploadError::ForcMirror(format!("Invalid mirror: {s}")));
+        }
+        Ok(ForcMirror::Directory(PathBuf::from(path)))
+    }
+}
+
+impl fmt::Display for ForcMirror {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ForcMirror::Directory(path) => write!(f, "{}", path.display()),
+            ForcMirror::Http(url) => f.write_str(url),
+        }
+    }
+}
+
+impl ForcMirror {
+    /// Returns the configured mirror, or `None` if forc is installed from GitHub.
+    pub fn from_env() -> Result<Option<Self>, UploadError> {
+        load_env();
+        env::var("FORC_MIRROR")
+            .ok()
+            .filter(|mirror| !mirror.is_empty())
+            .map(|mirror| mirror.parse())
+            .transpose()
+    }
+
+    /// Returns the path of the archive of a version in the mirror.
+    fn archive_path(forc_version: &str, os: &str, arch: &str) -> String {
+        format!("v{forc_version}/forc-binaries-{os}_{arch}.tar.gz")
+    }
+
+    /// Reads a file from the mirror.
+    fn fetch(&self, path: &str) -> Result<Vec<u8>, UploadError> {
+        match self {
+            ForcMirror::Directory(dir) => fs::read(dir.join(path))
+                .map_err(|err| UploadError::ForcMirror(format!("Failed to read {path}: {err}"))),
+            ForcMirror::Http(url) => {
+                let url = format!("{url}/{path}");
+                let response = reqwest::blocking::Client::builder()
+                    .timeout(DOWNLOAD_TIMEOUT)
+                    .build()
+                    .and_then(|client| client.get(&url).send())
+                    .and_then(|response| response.error_for_status())
+                    .map_err(|err| {
+                        UploadError::ForcMirror(format!("Failed to download {url}: {err}"))
+                    })?;
+                response.bytes().map(|bytes| bytes.to_vec()).map_err(|err| {
+                    UploadError::ForcMirror(format!("Failed to download {url}: {err}"))
+                })
+            }
+        }
+    }
+
+    /// Installs the given binaries of a version of forc from the mirror into the `bin`
+    /// directory of `forc_path`. The archive is verified against its checksum before
+    /// anything is extracted.
+    pub fn install(
+        &self,
+        forc_version: &str,
+        forc_path: &Path,
+        os: &str,
+        arch: &str,
+        binaries: &[&str],
+    ) -> Result<(), UploadError> {
+        let archive_path = Self::archive_path(forc_version, os, arch);
+        tracing::info!("Installing forc {} from mirror {}", forc_version, self);
+
+        let archive = self.fetch(&archive_path)?;
+        let checksum = self.fetch(&format!("{archive_path}.sha256"))?;
+        verify_sha256(&archive, &String::from_utf8_lossy(&checksum), &archive_path)?;
+
+        let missing =
+            extract_binaries(&archive, &forc_path.join("bin"), binaries).map_err(|err| {
+                UploadError::ForcMirror(format!("Failed to extract {archive_path}: {err}"))
+            })?;
+        if !missing.is_empty() {
+            return Err(UploadError::ForcMirror(format!(
+                "{archive_path} doesn't contain {}",
+                missing.join(", ")
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Checks the contents against a checksum file in the format of `sha256sum`, where the
+/// first word is the hex digest.
+fn verify_sha256(contents: &[u8], checksum_file: &str, name: &str) -> Result<(), UploadError> {
+    let expected = checksum_file
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| UploadError::ChecksumMismatch(name.to_string()))?;
+    let actual = hex::encode(Sha256::digest(contents));
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(UploadError::ChecksumMismatch(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Extracts the regular files with the given names from a gzipped tarball into
+/// `bin_dir`, wherever they are in the tarball, and makes them executable. Returns the
+/// names that weren't found.
+fn extract_binaries<'a>(
+    archive: &[u8],
+    bin_dir: &Path,
+    binaries: &[&'a str],
+) -> io::Result<Vec<&'a str>> {
+    fs::create_dir_all(bin_dir)?;
+    let mut missing = binaries.to_vec();
+    let mut tar = tar::Archive::new(GzDecoder::new(archive));
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?;
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let Some(index) = missing.iter().position(|binary| *binary == name) else {
+            continue;
+        };
+        let binary = missing.remove(index);
+
+        let binary_path = bin_dir.join(binary);
+        let mut file = File::create(&binary_path)?;
+        io::copy(&mut entry, &mut file)?;
+        fs::set_permissions(&binary_path, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(missing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use tempfile::tempdir;
+
+    /// Creates an archive with the given files, like the forc release archives.
+    fn release_archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut tar = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::fast()));
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, path, *contents).unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn write_mirror(mirror: &Path, archive: &[u8], checksum: Option<String>) {
+        let version_dir = mirror.join("v0.70.1");
+        fs::create_dir_all(&version_dir).unwrap();
+        let archive_path = version_dir.join("forc-binaries-linux_amd64.tar.gz");
+        fs::write(&archive_path, archive).unwrap();
+        if let Some(checksum) = checksum {
+            fs::write(archive_path.with_extension("gz.sha256"), checksum).unwrap();
+        }
+    }
+
+    #[test]
+    fn parses_mirrors() {
+        assert_eq!(
+            "https://mirror.example.com/sway/".parse(),
+            Ok(ForcMirror::Http(
+                "https://mirror.example.com/sway".to_string()
+            ))
+        );
+        assert_eq!(
+            "file:///srv/forc".parse(),
+            Ok(ForcMirror::Directory(PathBuf::from("/srv/forc")))
+        );
+        assert_eq!(
+            "/srv/forc".parse(),
+            Ok(ForcMirror::Directory(PathBuf::from("/srv/forc")))
+        );
+    }
+
+    #[test]
+    fn installs_verified_binaries_from_directory() {
+        let mirror_dir = tempdir().unwrap();
+        let forc_dir = tempdir().unwrap();
+        let archive = release_archive(&[
+            ("forc-binaries/forc", b"forc binary"),
+            ("forc-binaries/forc-doc", b"forc-doc binary"),
+            ("forc-binaries/forc-fmt", b"forc-fmt binary"),
+        ]);
+        let checksum = format!(
+            "{}  forc-binaries-linux_amd64.tar.gz\n",
+            hex::encode(Sha256::digest(&archive))
+        );
+        write_mirror(mirror_dir.path(), &archive, Some(checksum));
+
+        let mirror = ForcMirror::Directory(mirror_dir.path().to_path_buf());
+        mirror
+            .install(
+                "0.70.1",
+                forc_dir.path(),
+                "linux",
+                "amd64",
+                &["forc", "forc-doc"],
+            )
+            .unwrap();
+
+        let forc = forc_dir.path().join("bin/forc");
+        assert_eq!(fs::read(&forc).unwrap(), b"forc binary");
+        assert_eq!(
+            fs::metadata(&forc).unwrap().permissions().mode() & 0o777,
+            0o755
+        );
+        assert!(forc_dir.path().join("bin/forc-doc").exists());
+        assert!(!forc_dir.path().join("bin/forc-fmt").exists());
+
+        // Binaries that aren't in the archive fail the install.
+        assert!(matches!(
+            mirror.install("0.70.1", forc_dir.path(), "linux", "amd64", &["forc-lsp"]),
+            Err(UploadError::ForcMirror(_))
+        ));
+        // Versions that aren't mirrored fail the install.
+        assert!(matches!(
+            mirror.install("0.70.2", forc_dir.path(), "linux", "amd64", &["forc"]),
+            Err(UploadError::ForcMirror(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_archives_that_fail_verification() {
+        let archive = release_archive(&[("forc-binaries/forc", b"forc binary")]);
+        let install = |checksum: Option<String>| {
+            let mirror_dir = tempdir().unwrap();
+            let forc_dir = tempdir().unwrap();
+            write_mirror(mirror_dir.path(), &archive, checksum);
+            let result = ForcMirror::Directory(mirror_dir.path().to_path_buf()).install(
+                "0.70.1",
+                forc_dir.path(),
+                "linux",
+                "amd64",
+                &["forc"],
+            );
+            assert!(!forc_dir.path().join("bin/forc").exists());
+            result
+        };
+
+        assert!(matches!(
+            install(Some(hex::encode(Sha256::digest(b"other archive")))),
+            Err(UploadError::ChecksumMismatch(_))
+        ));
+        assert!(matches!(
+            install(Some(String::new())),
+            Err(UploadError::ChecksumMismatch(_))
+        ));
+        assert!(matches!(install(None), Err(UploadError::ForcMirror(_))));
+    }
+}
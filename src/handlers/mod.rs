@@ -1,2 +1,13 @@
+pub mod admin;
+pub mod dependency_graph;
+pub mod forc_mirror;
+pub mod package_files;
+pub mod package_name;
 pub mod publish;
+pub mod readiness;
+pub mod sandbox;
+pub mod tarball;
+pub mod toolchain;
 pub mod upload;
+pub mod upload_job;
+pub mod yank;
@@ -0,0 +1,368 @@
+use crate::handlers::upload::UploadError;
+use flate2::{Compression, GzBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::WalkBuilder;
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use tar::{Header, HeaderMode};
+use toml_edit::DocumentMut;
+
+const FORC_MANIFEST_FILE: &str = "Forc.toml";
+
+/// The files that are published by default, as `.gitignore`-style patterns.
+const DEFAULT_INCLUDE: [&str; 4] = ["Forc.toml", "Forc.lock", "README.md", "*.sw"];
+
+/// Returns the files of a package that are published, relative to the package directory
+/// and sorted.
+///
+/// The Sway sources, manifest, lock file and README are published by default. The
+/// `[project]` table of the manifest can publish more files with `include`, and leave
+/// files out with `exclude`. Both are lists of `.gitignore`-style patterns, and `exclude`
+/// takes precedence. Files ignored by `.gitignore` or `.ignore` files in the package are
+/// left out too. The manifest itself is always published.
+pub fn package_files(package_dir: &Path) -> Result<Vec<PathBuf>, UploadError> {
+    let manifest = fs::read_to_string(package_dir.join(FORC_MANIFEST_FILE))
+        .map_err(|_| UploadError::MissingForcManifest)?;
+    let manifest: toml::Table = toml::from_str(&manifest)
+        .map_err(|err| UploadError::InvalidForcManifest(err.to_string()))?;
+    let project = manifest
+        .get("project")
+        .and_then(|project| project.as_table());
+    let patterns = |key: &str| -> Result<Vec<String>, UploadError> {
+        let Some(patterns) = project.and_then(|project| project.get(key)) else {
+            return Ok(vec![]);
+        };
+        patterns
+            .as_array()
+            .and_then(|patterns| {
+                patterns
+                    .iter()
+                    .map(|pattern| pattern.as_str().map(str::to_string))
+                    .collect::<Option<Vec<_>>>()
+            })
+            .ok_or_else(|| {
+                UploadError::InvalidFilePattern(format!("`{key}` must be a list of strings"))
+            })
+    };
+
+    let mut include = DEFAULT_INCLUDE.map(str::to_string).to_vec();
+    include.extend(patterns("include")?);
+    let include = build_matcher(package_dir, &include)?;
+    let exclude = build_matcher(package_dir, &patterns("exclude")?)?;
+
+    let mut files = vec![PathBuf::from(FORC_MANIFEST_FILE)];
+    let walker = WalkBuilder::new(package_dir)
+        .standard_filters(false)
+        .git_ignore(true)
+        .ignore(true)
+        .require_git(false)
+        .follow_links(true)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build();
+    for entry in walker {
+        let entry = entry.map_err(|err| {
+            tracing::error!("Failed to list package files: {}", err);
+            UploadError::CopyFiles
+        })?;
+        if !entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_file())
+        {
+            continue;
+        }
+        let Ok(path) = entry.path().strip_prefix(package_dir) else {
+            continue;
+        };
+
+        let is_included = include.matched_path_or_any_parents(path, false).is_ignore()
+            && !exclude.matched_path_or_any_parents(path, false).is_ignore();
+        if is_included && path != Path::new(FORC_MANIFEST_FILE) {
+            files.push(path.to_path_buf());
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Returns the manifest of a package as it is published.
+///
+/// Path dependencies on other members of the workspace, as in
+/// `lib_a = { path = "../lib_a" }`, are replaced with a requirement on the version of the
+/// member, since the other members aren't part of the published package. `member_versions`
+/// maps the name of each workspace member to its version, and is empty for packages that
+/// aren't in a workspace.
+pub fn published_manifest(
+    manifest: &str,
+    member_versions: &HashMap<String, String>,
+) -> Result<String, UploadError> {
+    let mut document: DocumentMut = manifest
+        .parse()
+        .map_err(|err: toml_edit::TomlError| UploadError::InvalidForcManifest(err.to_string()))?;
+    let Some(dependencies) = document
+        .get_mut("dependencies")
+        .and_then(|dependencies| dependencies.as_table_like_mut())
+    else {
+        return Ok(manifest.to_string());
+    };
+
+    for (name, dependency) in dependencies.iter_mut() {
+        let (Some(version), Some(dependency)) = (
+            member_versions.get(name.get()),
+            dependency.as_table_like_mut(),
+        ) else {
+            continue;
+        };
+        if dependency.remove("path").is_some() {
+            dependency.insert("version", toml_edit::value(version.as_str()));
+        }
+    }
+    Ok(document.to_string())
+}
+
+fn build_matcher(package_dir: &Path, patterns: &[String]) -> Result<Gitignore, UploadError> {
+    let mut builder = GitignoreBuilder::new(package_dir);
+    for pattern in patterns {
+        builder
+            .add_line(None, pattern)
+            .map_err(|err| UploadError::InvalidFilePattern(err.to_string()))?;
+    }
+    builder
+        .build()
+        .map_err(|err| UploadError::InvalidFilePattern(err.to_string()))
+}
+
+/// Packs the files of a package into a gzipped tarball, with `manifest` as the contents
+/// of `Forc.toml`.
+///
+/// The tarball only depends on the paths and contents of the files: entries are sorted,
+/// and their timestamps, owners and permissions as well as the gzip header are fixed.
+/// This way the same sources always have the same IPFS CID.
+pub fn create_package_tarball(
+    package_dir: &Path,
+    files: &[PathBuf],
+    manifest: &str,
+    output_path: &Path,
+) -> Result<(), UploadError> {
+    let dirs: BTreeSet<&Path> = files
+        .iter()
+        .flat_map(|file| file.ancestors().skip(1))
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .collect();
+    let mut entries: Vec<(&Path, bool)> = dirs
+        .into_iter()
+        .map(|dir| (dir, true))
+        .chain(files.iter().map(|file| (file.as_path(), false)))
+        .collect();
+    entries.sort();
+
+    let tar_gz = File::create(output_path).map_err(|_| UploadError::OpenFile)?;
+    let enc = GzBuilder::new()
+        .mtime(0)
+        .write(tar_gz, Compression::default());
+    let mut tar = tar::Builder::new(enc);
+    tar.mode(HeaderMode::Deterministic);
+
+    for (path, is_dir) in entries {
+        let result = if is_dir {
+            tar.append_dir(path, package_dir.join(path))
+        } else if path == Path::new(FORC_MANIFEST_FILE) {
+            fs::metadata(package_dir.join(path)).and_then(|metadata| {
+                let mut header = Header::new_gnu();
+                header.set_metadata_in_mode(&metadata, HeaderMode::Deterministic);
+                header.set_size(manifest.len() as u64);
+                tar.append_data(&mut header, path, manifest.as_bytes())
+            })
+        } else {
+            tar.append_path_with_name(package_dir.join(path), path)
+        };
+        result.map_err(|err| {
+            tracing::error!("Failed to add {} to tarball: {}", path.display(), err);
+            UploadError::CopyFiles
+        })?;
+    }
+
+    let enc = tar.into_inner().map_err(|_| UploadError::CopyFiles)?;
+    enc.finish().map_err(|_| UploadError::CopyFiles)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    fn write_file(dir: &Path, path: &str, contents: &str) {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn write_manifest(dir: &Path, extra: &str) {
+        write_file(
+            dir,
+            FORC_MANIFEST_FILE,
+            &format!("[project]\nname = \"test\"\nlicense = \"Apache-2.0\"\n{extra}"),
+        );
+    }
+
+    fn paths(files: &[&str]) -> Vec<PathBuf> {
+        files.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn package_files_selects_default_files() {
+        let dir = tempfile::tempdir().unwrap();
+        write_manifest(dir.path(), "");
+        write_file(dir.path(), "Forc.lock", "");
+        write_file(dir.path(), "README.md", "");
+        write_file(dir.path(), "src/main.sw", "");
+        write_file(dir.path(), "src/lib/util.sw", "");
+        write_file(dir.path(), "notes.txt", "");
+        write_file(dir.path(), "out/release/test.bin", "");
+        write_file(dir.path(), ".git/HEAD.sw", "");
+
+        assert_eq!(
+            package_files(dir.path()).unwrap(),
+            paths(&[
+                "Forc.lock",
+                "Forc.toml",
+                "README.md",
+                "src/lib/util.sw",
+                "src/main.sw"
+            ])
+        );
+    }
+
+    #[test]
+    fn package_files_honors_manifest_and_ignore_files() {
+        let dir = tempfile::tempdir().unwrap();
+        write_manifest(
+            dir.path(),
+            "include = [\"LICENSE\", \"abis/*.json\"]\nexclude = [\"tests/\"]\n",
+        );
+        write_file(dir.path(), "LICENSE", "");
+        write_file(dir.path(), "abis/token.json", "");
+        write_file(dir.path(), "src/main.sw", "");
+        write_file(dir.path(), "src/generated.sw", "");
+        write_file(dir.path(), "tests/harness.sw", "");
+        write_file(dir.path(), ".gitignore", "generated.sw\n");
+        write_file(dir.path(), ".ignore", "Forc.toml\n");
+
+        assert_eq!(
+            package_files(dir.path()).unwrap(),
+            paths(&["Forc.toml", "LICENSE", "abis/token.json", "src/main.sw"])
+        );
+    }
+
+    #[test]
+    fn package_files_rejects_invalid_patterns() {
+        let dir = tempfile::tempdir().unwrap();
+        write_manifest(dir.path(), "include = \"*.json\"\n");
+        assert!(matches!(
+            package_files(dir.path()),
+            Err(UploadError::InvalidFilePattern(_))
+        ));
+
+        write_manifest(dir.path(), "exclude = [\"tests/\", 1]\n");
+        assert!(matches!(
+            package_files(dir.path()),
+            Err(UploadError::InvalidFilePattern(_))
+        ));
+    }
+
+    #[test]
+    fn package_files_rejects_invalid_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "Forc.toml", "[project\n");
+        assert!(matches!(
+            package_files(dir.path()),
+            Err(UploadError::InvalidForcManifest(_))
+        ));
+    }
+
+    #[test]
+    fn create_package_tarball_is_deterministic() {
+        let pack = |files: &[&str], mtime: SystemTime| {
+            let dir = tempfile::tempdir().unwrap();
+            write_manifest(dir.path(), "");
+            for file in files {
+                write_file(dir.path(), file, file);
+                File::options()
+                    .write(true)
+                    .open(dir.path().join(file))
+                    .unwrap()
+                    .set_modified(mtime)
+                    .unwrap();
+            }
+            let files = package_files(dir.path()).unwrap();
+            let manifest = fs::read_to_string(dir.path().join(FORC_MANIFEST_FILE)).unwrap();
+            let tarball_path = dir.path().join("project.tgz");
+            create_package_tarball(dir.path(), &files, &manifest, &tarball_path).unwrap();
+            fs::read(tarball_path).unwrap()
+        };
+
+        let tarball = pack(&["src/main.sw", "src/lib.sw"], SystemTime::UNIX_EPOCH);
+        assert_eq!(
+            tarball,
+            pack(
+                &["src/lib.sw", "src/main.sw"],
+                SystemTime::now() - Duration::from_secs(3600)
+            )
+        );
+
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(tarball.as_slice()));
+        let entries = archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().into_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            paths(&["Forc.toml", "src", "src/lib.sw", "src/main.sw"])
+        );
+    }
+
+    #[test]
+    fn published_manifest_replaces_path_deps_on_workspace_members() {
+        let manifest = r#"[project]
+name = "lib_b"
+version = "0.2.0"
+
+[dependencies]
+# Another member of the workspace.
+lib_a = { path = "../lib_a" }
+std = { git = "https://github.com/FuelLabs/sway", tag = "v0.70.1" }
+other = { path = "../../other" }
+"#;
+        let member_versions = HashMap::from([("lib_a".to_string(), "0.1.0".to_string())]);
+        assert_eq!(
+            published_manifest(manifest, &member_versions).unwrap(),
+            manifest.replace(
+                r#"lib_a = { path = "../lib_a" }"#,
+                r#"lib_a = { version = "0.1.0" }"#
+            )
+        );
+        assert_eq!(
+            published_manifest(manifest, &HashMap::new()).unwrap(),
+            manifest
+        );
+    }
+
+    #[test]
+    fn create_package_tarball_packs_the_given_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        write_manifest(dir.path(), "");
+        let files = package_files(dir.path()).unwrap();
+        let tarball_path = dir.path().join("project.tgz");
+        create_package_tarball(dir.path(), &files, "[project]\n", &tarball_path).unwrap();
+
+        let tarball = fs::read(tarball_path).unwrap();
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(tarball.as_slice()));
+        let mut entry = archive.entries().unwrap().next().unwrap().unwrap();
+        let mut contents = String::new();
+        std::io::Read::read_to_string(&mut entry, &mut contents).unwrap();
+        assert_eq!(contents, "[project]\n");
+    }
+}
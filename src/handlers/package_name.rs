@@ -0,0 +1,224 @@
+use crate::db::DbConn;
+use crate::handlers::publish::PublishError;
+use serde::Serialize;
+use thiserror::Error;
+
+/// The maximum length of a package name.
+pub const MAX_PACKAGE_NAME_LEN: usize = 64;
+
+/// Names that can't be used by new packages, because they belong to the Sway toolchain or
+/// would be mistaken for it. Names are compared by their lookalike form.
+pub const RESERVED_PACKAGE_NAMES: [&str; 13] = [
+    "core",
+    "contract",
+    "forc",
+    "fuel",
+    "fuel-core",
+    "fuels",
+    "library",
+    "predicate",
+    "script",
+    "std",
+    "sway",
+    "sway-libs",
+    "test",
+];
+
+/// Characters that are indistinguishable from other characters, mapped to the character
+/// they are normalized to. Names must be ASCII to be published, so the letters from other
+/// scripts only guard names that get past that check.
+const CONFUSABLES: [(char, char); 15] = [
+    ('_', '-'),
+    ('а', 'a'),
+    ('е', 'e'),
+    ('о', 'o'),
+    ('р', 'p'),
+    ('с', 'c'),
+    ('у', 'y'),
+    ('х', 'x'),
+    ('і', 'l'),
+    ('ј', 'j'),
+    ('ѕ', 's'),
+    ('ο', 'o'),
+    ('α', 'a'),
+    ('ν', 'v'),
+    ('ι', 'l'),
+];
+
+/// ASCII characters that look alike in some fonts, mapped to the character they are folded
+/// to in the lookalike form of a name.
+const ASCII_LOOKALIKES: [(char, char); 3] = [('0', 'o'), ('1', 'l'), ('i', 'l')];
+
+/// Sequences of ASCII characters that look like a single character in some fonts.
+const ASCII_LOOKALIKE_SEQUENCES: [(&str, &str); 2] = [("rn", "m"), ("vv", "w")];
+
+#[derive(Error, Debug, Serialize, PartialEq, Eq)]
+pub enum PackageNameError {
+    #[error("Package name must be between 1 and {MAX_PACKAGE_NAME_LEN} characters: {0}")]
+    InvalidLength(String),
+
+    #[error(
+        "Package name must start with a letter and only contain letters, numbers, `-` and `_`: {0}"
+    )]
+    InvalidCharacters(String),
+
+    #[error("Package name is reserved: {0}")]
+    Reserved(String),
+
+    #[error("Package name {name} is too similar to existing package {existing}")]
+    Taken { name: String, existing: String },
+}
+
+/// Returns the form that package names are compared by. Names that only differ by case,
+/// `-` and `_`, or characters from other scripts that are indistinguishable from ASCII
+/// letters have the same normalized form, and can't both be used.
+///
+/// The migration that made `packages.normalized_name` unique normalizes existing ASCII
+/// names the same way.
+pub fn normalize_package_name(name: &str) -> String {
+    name.to_lowercase()
+        .chars()
+        .map(|c| fold_char(&CONFUSABLES, c))
+        .collect()
+}
+
+/// Returns the normalized form of the name with ASCII lookalikes, like `1` and `l` or `rn`
+/// and `m`, folded together. Different names can have the same lookalike form, like `fail`
+/// and `fall`, so new packages that share it with an existing package are flagged for
+/// review instead of refused.
+///
+/// This must match the `package_name_lookalike` SQL function.
+pub fn lookalike_package_name(name: &str) -> String {
+    let mut lookalike: String = normalize_package_name(name)
+        .chars()
+        .map(|c| fold_char(&ASCII_LOOKALIKES, c))
+        .collect();
+    for (sequence, replacement) in ASCII_LOOKALIKE_SEQUENCES {
+        lookalike = lookalike.replace(sequence, replacement);
+    }
+    lookalike
+}
+
+fn fold_char(lookalikes: &[(char, char)], c: char) -> char {
+    lookalikes
+        .iter()
+        .find(|(lookalike, _)| *lookalike == c)
+        .map_or(c, |(_, folded)| *folded)
+}
+
+/// Checks that a name can be used by a new package: it must be a valid ASCII identifier
+/// of a limited length, and not be reserved.
+pub fn validate_package_name(name: &str) -> Result<(), PackageNameError> {
+    if name.is_empty() || name.len() > MAX_PACKAGE_NAME_LEN {
+        return Err(PackageNameError::InvalidLength(name.to_string()));
+    }
+    let mut chars = name.chars();
+    let valid_chars = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid_chars {
+        return Err(PackageNameError::InvalidCharacters(name.to_string()));
+    }
+
+    // Reserved names have no package to be flagged against, so lookalikes are refused.
+    let lookalike = lookalike_package_name(name);
+    if RESERVED_PACKAGE_NAMES
+        .iter()
+        .any(|reserved| lookalike_package_name(reserved) == lookalike)
+    {
+        return Err(PackageNameError::Reserved(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Checks that a name can be used by a new package, and doesn't collide with the name of
+/// an existing package.
+pub fn check_new_package_name(conn: &mut DbConn<'_>, name: &str) -> Result<(), PublishError> {
+    validate_package_name(name)?;
+    if let Some(existing) = conn.get_package_by_normalized_name(&normalize_package_name(name))? {
+        return Err(PackageNameError::Taken {
+            name: name.to_string(),
+            existing: existing.package_name,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_package_name_folds_case_separators_and_homoglyphs() {
+        assert_eq!(normalize_package_name("My_Token"), "my-token");
+        assert_eq!(
+            normalize_package_name("tоken"),
+            normalize_package_name("token")
+        );
+        assert_ne!(
+            normalize_package_name("token"),
+            normalize_package_name("tokens")
+        );
+        for (name, other) in [("fall", "fail"), ("modern", "modem"), ("burn", "bum")] {
+            assert_ne!(
+                normalize_package_name(name),
+                normalize_package_name(other),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn lookalike_package_name_folds_ascii_lookalikes() {
+        for (name, other) in [
+            ("swap-lib", "SWAP_L1B"),
+            ("fall", "fail"),
+            ("modern", "modem"),
+            ("burn", "bum"),
+            ("vvallet", "wallet"),
+            ("t0ken", "token"),
+        ] {
+            assert_eq!(
+                lookalike_package_name(name),
+                lookalike_package_name(other),
+                "{name}"
+            );
+        }
+        assert_ne!(
+            lookalike_package_name("token"),
+            lookalike_package_name("tokens")
+        );
+    }
+
+    #[test]
+    fn validate_package_name_enforces_format() {
+        assert!(validate_package_name("my-token_2").is_ok());
+        assert_eq!(
+            validate_package_name(""),
+            Err(PackageNameError::InvalidLength(String::new()))
+        );
+        assert!(matches!(
+            validate_package_name(&"a".repeat(MAX_PACKAGE_NAME_LEN + 1)),
+            Err(PackageNameError::InvalidLength(_))
+        ));
+        for name in ["2fast", "-token", "my token", "tоken", "lib.sw"] {
+            assert_eq!(
+                validate_package_name(name),
+                Err(PackageNameError::InvalidCharacters(name.to_string())),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn validate_package_name_rejects_reserved_names() {
+        for name in ["std", "STD", "Forc", "sway_libs", "c0re", "fue1"] {
+            assert_eq!(
+                validate_package_name(name),
+                Err(PackageNameError::Reserved(name.to_string())),
+                "{name}"
+            );
+        }
+        assert!(validate_package_name("std-extras").is_ok());
+    }
+}
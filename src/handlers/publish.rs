@@ -1,33 +1,79 @@
+use std::collections::HashMap;
 use std::env;
+use std::fmt;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 
-use crate::api::publish::PublishRequest;
+use crate::api::publish::{DryRunDependency, DryRunPackage, PublishRequest};
+use crate::db::audit_event::AuditEventKind;
 use crate::db::error::DatabaseError;
-use crate::db::Database;
-use crate::index::handler::git::GithubRepoBuilder;
+use crate::db::{Database, DbConn};
+use crate::handlers::dependency_graph::resolve_version;
+use crate::handlers::package_name::{check_new_package_name, PackageNameError};
+use crate::index::handler::git::{GithubIndexPublisher, GithubRepoBuilder};
 use crate::index::handler::{IndexPublishError, IndexPublisher};
-use crate::models::{ApiToken, NewPackageDep};
+use crate::metrics::metrics;
+use crate::middleware::client_info::ClientInfo;
+use crate::models::{ApiToken, NewPackageDep, Upload};
 use crate::util::load_env;
+use chrono::Utc;
+use forc_pkg::manifest::Dependency;
 use forc_pkg::source::reg::{
     self,
     file_location::Namespace,
     index_file::{PackageDependencyIdentifier, PackageEntry},
 };
 use forc_pkg::PackageManifest;
-use semver::Version;
+use semver::{Version, VersionReq};
 use serde::Serialize;
+use serde_json::json;
 use tempfile::TempDir;
 use thiserror::Error;
 use tracing::error;
 use tracing::info;
+use tracing::warn;
 use url::Url;
 use uuid::Uuid;
 
+/// A dependency of a package that can't be satisfied by the registry.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct UnsatisfiedDependency {
+    pub name: String,
+    pub version_req: String,
+    pub reason: String,
+}
+
+impl fmt::Display for UnsatisfiedDependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}: {}", self.name, self.version_req, self.reason)
+    }
+}
+
 #[derive(Error, Debug, Serialize)]
 pub enum PublishError {
     #[error("Invalid Forc manifest: {0}")]
     InvalidForcManifest(String),
 
+    #[error("Version {version} of package {name} is already published")]
+    VersionExists { name: String, version: Version },
+
+    #[error("Version {version} of package {name} was removed and can't be published again")]
+    VersionRemoved { name: String, version: Version },
+
+    #[error("Version {version} of package {name} is lower than the latest version {latest} with the same major version")]
+    VersionNotIncreasing {
+        name: String,
+        version: Version,
+        latest: Version,
+    },
+
+    #[error(transparent)]
+    PackageName(#[from] PackageNameError),
+
+    #[error("Unsatisfiable dependencies: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+    UnsatisfiableDependencies(Vec<UnsatisfiedDependency>),
+
     #[error(transparent)]
     #[serde(skip)]
     Database(#[from] DatabaseError),
@@ -61,158 +107,762 @@ pub struct PartialPackageDep {
     pub dependency_version_req: String,
 }
 
-/// Publish index file for the given `PackageEntry`.
-/// The `PackageEntry` is inserted into the `IndexFile` that is parsed from the
-/// remote repo, which is used by forc.pub and forc to communicate. Index repo
-/// can be found on GitHub.
+/// Clones the index repo into `tmp_path` and returns a publisher for it.
+/// The index repo is used by forc.pub and forc to communicate, and can be
+/// found on GitHub.
 ///
 /// The org name is `reg::GithubRegistryResolver::DEFAULT_REPO_ORG`.
 /// The repo name is `reg::GithubRegistryResolver::DEFAULT_REPO_NAME`.
 /// The file locations for the package entries are calculated using:
 /// `reg::GithubRegistryResolver::DEFAULT_CHUNKING_SIZE`.
-async fn publish_index_file(package_entry: PackageEntry) -> Result<(), IndexPublishError> {
+pub(crate) fn github_index_publisher(
+    tmp_path: &Path,
+) -> Result<GithubIndexPublisher<GithubRepoBuilder>, IndexPublishError> {
     let repo_name = reg::GithubRegistryResolver::DEFAULT_REPO_NAME;
     let repo_org = reg::GithubRegistryResolver::DEFAULT_GITHUB_ORG;
     let chunk_size = reg::GithubRegistryResolver::DEFAULT_CHUNKING_SIZE;
 
-    let tmpdir = TempDir::new().map_err(|_| {
-        IndexPublishError::RepoError(
-            "cannot create temporary dir for index repo fetch operation".to_string(),
-        )
-    })?;
-    let tmp_path = tmpdir.path();
     let github_repo_builder = Arc::new(Mutex::new(GithubRepoBuilder::with_repo_details(
         repo_name, repo_org, tmp_path,
     )?));
-    let github_index_publisher = crate::index::handler::git::GithubIndexPublisher::new(
+    Ok(GithubIndexPublisher::new(
         chunk_size,
         Namespace::Flat,
         github_repo_builder,
-    );
+    ))
+}
+
+/// Creates the temporary directory that the index repo is cloned into.
+pub(crate) fn index_tmpdir() -> Result<TempDir, IndexPublishError> {
+    TempDir::new().map_err(|_| {
+        IndexPublishError::RepoError(
+            "cannot create temporary dir for index repo fetch operation".to_string(),
+        )
+    })
+}
+
+/// Publish index files for the given `PackageEntry`s in a single commit.
+/// Each `PackageEntry` is inserted into the `IndexFile` that is parsed from the
+/// remote repo.
+async fn publish_index_files(package_entries: Vec<PackageEntry>) -> Result<(), IndexPublishError> {
+    let tmpdir = index_tmpdir()?;
+    let github_index_publisher = github_index_publisher(tmpdir.path())?;
+    github_index_publisher
+        .publish_entries(package_entries)
+        .await
+        .inspect_err(|err| metrics().record_index_publish_failure(err))?;
+    Ok(())
+}
+
+/// A package manifest to publish, along with the upload that contains it.
+struct PackageToPublish {
+    upload: Upload,
+    manifest: PackageManifest,
+    version: Version,
+    /// Whether publishing creates the package.
+    new_package: bool,
+}
+
+/// Returns the order in which workspace members must be published so that every
+/// member is published after the members it depends on.
+fn publish_order(packages: &[PackageToPublish]) -> Result<Vec<usize>, PublishError> {
+    let member_index: HashMap<&str, usize> = packages
+        .iter()
+        .enumerate()
+        .map(|(i, pkg)| (pkg.manifest.project.name.as_str(), i))
+        .collect();
+    let member_deps: Vec<Vec<usize>> = packages
+        .iter()
+        .map(|pkg| {
+            pkg.manifest
+                .dependencies
+                .iter()
+                .flatten()
+                .filter_map(|(name, _)| member_index.get(name.as_str()).copied())
+                .collect()
+        })
+        .collect();
+
+    // Repeatedly publish the first member whose dependencies have all been published,
+    // which keeps the upload order for independent members.
+    let mut order = Vec::with_capacity(packages.len());
+    let mut published = vec![false; packages.len()];
+    while order.len() < packages.len() {
+        let next = (0..packages.len())
+            .find(|&i| !published[i] && member_deps[i].iter().all(|&dep| published[dep]))
+            .ok_or_else(|| {
+                PublishError::InvalidForcManifest(
+                    "Workspace members have a dependency cycle".to_string(),
+                )
+            })?;
+        published[next] = true;
+        order.push(next);
+    }
+    Ok(order)
+}
+
+/// Returns why no published version that isn't yanked satisfies the requirement, or
+/// `None` if one does.
+fn unsatisfied_reason(version_req: &VersionReq, published: &[(String, bool)]) -> Option<String> {
+    if published.is_empty() {
+        return Some("Package not found".to_string());
+    }
+    match resolve_version(&version_req.to_string(), published) {
+        Some((_, false)) => None,
+        Some((_, true)) => Some("Only yanked versions match the requirement".to_string()),
+        None => Some("No published version matches the requirement".to_string()),
+    }
+}
+
+/// Returns the version requirement of a dependency. Path dependencies on workspace
+/// members, as in `lib_a = { path = "../lib_a" }`, require the version of the member.
+fn dependency_version(
+    name: &str,
+    dependency: &Dependency,
+    member_versions: &HashMap<String, Version>,
+) -> Result<String, PublishError> {
+    match (dependency, member_versions.get(name)) {
+        (Dependency::Detailed(details), Some(member_version)) if details.path.is_some() => {
+            Ok(member_version.to_string())
+        }
+        _ => dependency
+            .version()
+            .map(str::to_string)
+            .ok_or(PublishError::InvalidForcManifest(
+                "Dependency must have a version".to_string(),
+            )),
+    }
+}
+
+/// Validates the dependencies of a package and returns them with their normalized
+/// version requirements. Dependencies on other workspace members must match the version
+/// of the member being published, while other dependencies must be satisfied by a
+/// published version that isn't yanked.
+fn validate_package_deps(
+    conn: &mut DbConn<'_>,
+    manifest: &PackageManifest,
+    member_versions: &HashMap<String, Version>,
+) -> Result<Vec<PartialPackageDep>, PublishError> {
+    let mut package_deps = vec![];
+    let mut unsatisfied = vec![];
+    for (name, dependency) in manifest.dependencies.iter().flatten() {
+        let version = dependency_version(name, dependency, member_versions)?;
+        let version = version.as_str();
+        let version_req = match VersionReq::parse(version) {
+            Ok(version_req) => version_req,
+            Err(err) => {
+                unsatisfied.push(UnsatisfiedDependency {
+                    name: name.clone(),
+                    version_req: version.to_string(),
+                    reason: format!("Invalid version requirement: {err}"),
+                });
+                continue;
+            }
+        };
+
+        let reason = match member_versions.get(name) {
+            Some(member_version) if !version_req.matches(member_version) => Some(format!(
+                "Does not match workspace member version {member_version}"
+            )),
+            Some(_) => None,
+            None => {
+                let published = conn.get_published_versions(name.clone())?;
+                unsatisfied_reason(&version_req, &published)
+            }
+        };
+        if let Some(reason) = reason {
+            unsatisfied.push(UnsatisfiedDependency {
+                name: name.clone(),
+                version_req: version.to_string(),
+                reason,
+            });
+            continue;
+        }
+
+        package_deps.push(PartialPackageDep {
+            dependency_package_name: name.clone(),
+            dependency_version_req: version_req.to_string(),
+        });
+    }
+
+    if !unsatisfied.is_empty() {
+        return Err(PublishError::UnsatisfiableDependencies(unsatisfied));
+    }
+    Ok(package_deps)
+}
+
+/// The rules that the version of a published package must follow, besides not being
+/// published already.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PublishPolicy {
+    /// Refuse versions lower than the latest version that isn't yanked with the same major
+    /// version, so that a release line only moves forward.
+    pub increasing_versions: bool,
+}
+
+impl PublishPolicy {
+    /// Returns the configured policy, which only refuses versions that are already
+    /// published by default.
+    pub fn from_env() -> Self {
+        load_env();
+        Self {
+            increasing_versions: env::var("PUBLISH_INCREASING_VERSIONS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Checks that a package version can be published given the published versions of the
+/// package and whether they are yanked, and the versions removed by an admin. Yanked and
+/// removed versions can't be published again, but don't count as the latest version.
+fn check_version(
+    name: &str,
+    version: &Version,
+    published: &[(String, bool)],
+    removed: &[String],
+    policy: &PublishPolicy,
+) -> Result<(), PublishError> {
+    let published: Vec<(Version, bool)> = published
+        .iter()
+        .filter_map(|(num, yanked)| Some((Version::parse(num).ok()?, *yanked)))
+        .collect();
+    if published.iter().any(|(num, _)| num == version) {
+        return Err(PublishError::VersionExists {
+            name: name.to_string(),
+            version: version.clone(),
+        });
+    }
+    if removed
+        .iter()
+        .any(|num| Version::parse(num).is_ok_and(|num| num == *version))
+    {
+        return Err(PublishError::VersionRemoved {
+            name: name.to_string(),
+            version: version.clone(),
+        });
+    }
 
-    github_index_publisher.publish_entry(package_entry).await?;
+    if policy.increasing_versions {
+        let latest = published
+            .into_iter()
+            .filter(|(num, yanked)| !yanked && num.major == version.major)
+            .map(|(num, _)| num)
+            .max();
+        if let Some(latest) = latest.filter(|latest| latest > version) {
+            return Err(PublishError::VersionNotIncreasing {
+                name: name.to_string(),
+                version: version.clone(),
+                latest,
+            });
+        }
+    }
     Ok(())
 }
-/// Handles the publishing process by:
-/// 1. Parsing the forc manifest and extracting the dependencies and metadata
-/// 2. Store the package version in the database
-/// 3. Store the package dependencies in the database
+
+/// Returns the uploads to publish for the request. A dry-run request can also validate
+/// the report of a dry-run upload, which isn't stored in `uploads`.
+fn uploads_to_publish(
+    conn: &mut DbConn<'_>,
+    request: &PublishRequest,
+) -> Result<Vec<Upload>, DatabaseError> {
+    match conn.get_uploads_to_publish(request.upload_id) {
+        Err(DatabaseError::NotFound(..)) if request.dry_run => Ok(conn
+            .get_dry_run_uploads(request.upload_id)?
+            .into_iter()
+            .map(|upload| Upload {
+                id: upload.id,
+                source_code_ipfs_hash: upload.source_code_ipfs_hash,
+                forc_version: upload.forc_version,
+                abi_ipfs_hash: upload.abi_ipfs_hash,
+                bytecode_identifier: upload.bytecode_identifier,
+                readme: upload.readme,
+                forc_manifest: upload.forc_manifest,
+                docs_ipfs_hash: upload.docs_ipfs_hash,
+                created_at: Utc::now(),
+                workspace_upload_id: upload.workspace_upload_id,
+            })
+            .collect()),
+        result => result,
+    }
+}
+
+/// Validates a publish request without changing anything by:
+/// 1. Parsing the forc manifest of each upload and extracting the dependencies and metadata
+/// 2. Checking that the token is allowed to publish every package, and that new packages
+///    follow the package name policy
+/// 3. Checking that the package versions can be published under the [PublishPolicy]
+/// 4. Checking that the dependencies can be satisfied
 ///
-/// Returns the published [PackageVersion] on success.
-pub async fn handle_publish(
+/// Returns the packages with their normalized dependencies, in publish order.
+fn prepare_publish(
     db: &Database,
     request: &PublishRequest,
     token: &ApiToken,
-) -> Result<PublishInfo, PublishError> {
-    info!("Starting to publish upload {}", request.upload_id);
-
-    let upload = db.transaction(|conn| conn.get_upload(request.upload_id))?;
+) -> Result<Vec<(PackageToPublish, Vec<PartialPackageDep>)>, PublishError> {
+    let policy = PublishPolicy::from_env();
+    let uploads = db.transaction(|conn| uploads_to_publish(conn, request))?;
 
-    // For now, only package manifests are supported. Workspace manifests will be supported in the future.
-    let pkg_manifest = PackageManifest::from_string(upload.forc_manifest)
-        .map_err(|e| PublishError::InvalidForcManifest(e.to_string()))?;
-    let pkg_version = pkg_manifest
-        .project
-        .version
-        .ok_or(PublishError::InvalidForcManifest(
-            "Project manifest must have a version".to_string(),
-        ))?;
+    let mut packages = uploads
+        .into_iter()
+        .map(|upload| {
+            let manifest = PackageManifest::from_string(upload.forc_manifest.clone())
+                .map_err(|e| PublishError::InvalidForcManifest(e.to_string()))?;
+            let version =
+                manifest
+                    .project
+                    .version
+                    .clone()
+                    .ok_or(PublishError::InvalidForcManifest(
+                        "Project manifest must have a version".to_string(),
+                    ))?;
+            Ok(PackageToPublish {
+                upload,
+                manifest,
+                version,
+                new_package: false,
+            })
+        })
+        .collect::<Result<Vec<_>, PublishError>>()?;
+    let member_versions: HashMap<String, Version> = packages
+        .iter()
+        .map(|pkg| (pkg.manifest.project.name.clone(), pkg.version.clone()))
+        .collect();
+    let order = publish_order(&packages)?;
 
-    // Validate the package dependencies.
+    // Validate the package dependencies, and check that the token is allowed to publish
+    // every package before touching the index.
     let package_deps = db.transaction(|conn| {
-        match pkg_manifest.dependencies {
-            Some(deps) => {
-                let mut package_deps = vec![];
-                for (name, dependency) in deps {
-                    // Check if the package version exists in the database.
-                    let version = dependency
-                        .version()
-                        .ok_or(PublishError::InvalidForcManifest(
-                            "Dependency must have a version".to_string(),
-                        ))?;
-                    let _ = conn.get_package_version(name.clone(), version.to_string())?;
-
-                    package_deps.push(PartialPackageDep {
-                        dependency_package_name: name.clone(),
-                        dependency_version_req: version.to_string(),
-                    });
+        packages
+            .iter_mut()
+            .map(|pkg| {
+                let name = &pkg.manifest.project.name;
+                pkg.new_package = conn.authorize_publish(token, name)?.is_none();
+                if pkg.new_package {
+                    check_new_package_name(conn, name)?;
                 }
-                Ok::<_, PublishError>(package_deps)
-            }
-            None => Ok(vec![]),
-        }
+                let published = conn.get_published_versions(name.clone())?;
+                let removed = conn.get_removed_versions(name.clone())?;
+                check_version(name, &pkg.version, &published, &removed, &policy)?;
+                validate_package_deps(conn, &pkg.manifest, &member_versions)
+            })
+            .collect::<Result<Vec<_>, PublishError>>()
     })?;
 
-    let publish_info = PublishInfo {
-        package_name: pkg_manifest.project.name,
-        upload_id: request.upload_id,
-        num: pkg_version,
-        package_description: pkg_manifest.project.description.clone(),
-        repository: pkg_manifest.project.repository.clone(),
-        documentation: pkg_manifest.project.documentation.clone(),
-        homepage: pkg_manifest.project.homepage.clone(),
-        urls: request.urls.clone().unwrap_or_default(),
-        readme: upload.readme.clone(),
-        license: Some(pkg_manifest.project.license.clone()),
-    };
+    let mut packages = packages
+        .into_iter()
+        .zip(package_deps)
+        .map(Some)
+        .collect::<Vec<_>>();
+    Ok(order
+        .into_iter()
+        .filter_map(|i| packages[i].take())
+        .collect())
+}
+
+/// Handles the publishing process by validating the request, and then publishing the
+/// packages in dependency order.
+///
+/// A workspace upload contains one upload per workspace member, and all members are
+/// published by a single request. Every member is validated before any is published,
+/// and the members are then published together: their index entries are pushed in a
+/// single commit, and they are stored in the database in a single transaction.
+/// Each published package is recorded in the audit log of the token's user.
+///
+/// Returns the published [PublishInfo] for each package, in publish order.
+pub async fn handle_publish(
+    db: &Database,
+    request: &PublishRequest,
+    token: &ApiToken,
+    client: &ClientInfo,
+) -> Result<Vec<PublishInfo>, PublishError> {
+    info!("Starting to publish upload {}", request.upload_id);
+
+    let packages = prepare_publish(db, request, token)?;
 
     load_env();
     let run_env = env::var("RUN_ENV").unwrap_or_default();
 
     if run_env != "local" {
-        let package_name = publish_info.package_name.clone();
-        let package_version = publish_info.num.clone();
-        let source_cid = upload.source_code_ipfs_hash;
-        let abi_cid = upload.abi_ipfs_hash;
-        let dependencies = package_deps
+        let package_entries = packages
             .iter()
-            .cloned()
-            .map(PackageDependencyIdentifier::from)
+            .map(|(package, package_deps)| package_entry(package, package_deps))
             .collect();
-        let yanked = false;
-
-        let package_entry = PackageEntry::new(
-            package_name,
-            package_version,
-            source_cid,
-            abi_cid,
-            dependencies,
-            yanked,
-        );
 
         // Wait for index file insertion to finalize, if it fails we should not
         // insert the publish information into db.
-        publish_index_file(package_entry).await?;
+        publish_index_files(package_entries).await?;
     }
 
-    db.transaction(|conn| {
-        // Insert package version into the database along with metadata from the package manifest.
-        let package_version = conn.new_package_version(token, &publish_info)?;
-
-        // Insert package dependencies into the database.
-        let new_package_deps = package_deps
+    let published = db.transaction(|conn| {
+        packages
             .iter()
-            .map(|dep| NewPackageDep {
-                dependent_package_version_id: package_version.id,
-                dependency_package_name: dep.dependency_package_name.clone(),
-                dependency_version_req: dep.dependency_version_req.clone(),
+            .map(|(package, package_deps)| {
+                publish_package(conn, request, token, client, package, package_deps)
             })
-            .collect();
-        let _ = conn.insert_dependencies(new_package_deps)?;
-
-        // Insert package categories and keywords into the database.
-        if let Some(categories) = pkg_manifest.project.categories {
-            let _ = conn.insert_categories(package_version.package_id, &categories)?;
-        }
-        if let Some(keywords) = pkg_manifest.project.keywords {
-            let _ = conn.insert_keywords(package_version.package_id, &keywords)?;
-        }
-
+            .collect::<Result<Vec<_>, PublishError>>()
+    })?;
+    for publish_info in &published {
         info!(
             "Successfully published package {} version {}",
             publish_info.package_name, publish_info.num
         );
+    }
+    Ok(published)
+}
 
-        Ok(publish_info)
-    })
+/// Runs the same validation as [handle_publish] without publishing anything: nothing is
+/// inserted into the database or pushed to the index.
+///
+/// Returns a [DryRunPackage] for each package that would be published, in publish order.
+pub fn handle_publish_dry_run(
+    db: &Database,
+    request: &PublishRequest,
+    token: &ApiToken,
+) -> Result<Vec<DryRunPackage>, PublishError> {
+    info!(
+        "Validating publish of upload {} (dry run)",
+        request.upload_id
+    );
+
+    let packages = prepare_publish(db, request, token)?;
+    Ok(packages
+        .into_iter()
+        .map(|(package, package_deps)| {
+            let info = publish_info(request, &package);
+            let project = package.manifest.project;
+            DryRunPackage {
+                name: info.package_name,
+                version: info.num,
+                upload_id: info.upload_id,
+                forc_version: package.upload.forc_version,
+                source_code_ipfs_hash: package.upload.source_code_ipfs_hash,
+                abi_ipfs_hash: package.upload.abi_ipfs_hash,
+                docs_ipfs_hash: package.upload.docs_ipfs_hash,
+                bytecode_identifier: package.upload.bytecode_identifier,
+                description: info.package_description,
+                license: info.license,
+                repository: info.repository,
+                documentation: info.documentation,
+                homepage: info.homepage,
+                urls: info.urls,
+                readme: info.readme,
+                categories: project.categories.unwrap_or_default(),
+                keywords: project.keywords.unwrap_or_default(),
+                dependencies: package_deps
+                    .into_iter()
+                    .map(|dep| DryRunDependency {
+                        name: dep.dependency_package_name,
+                        version_req: dep.dependency_version_req,
+                    })
+                    .collect(),
+            }
+        })
+        .collect())
+}
+
+/// Returns the information to publish for a package.
+fn publish_info(request: &PublishRequest, package: &PackageToPublish) -> PublishInfo {
+    let PackageToPublish {
+        upload,
+        manifest: pkg_manifest,
+        version: pkg_version,
+        ..
+    } = package;
+
+    PublishInfo {
+        package_name: pkg_manifest.project.name.clone(),
+        upload_id: upload.id,
+        num: pkg_version.clone(),
+        package_description: pkg_manifest.project.description.clone(),
+        repository: pkg_manifest.project.repository.clone(),
+        documentation: pkg_manifest.project.documentation.clone(),
+        homepage: pkg_manifest.project.homepage.clone(),
+        urls: request.urls.clone().unwrap_or_default(),
+        readme: upload.readme.clone(),
+        license: Some(pkg_manifest.project.license.clone()),
+    }
+}
+
+/// Returns the index entry of a package.
+fn package_entry(package: &PackageToPublish, package_deps: &[PartialPackageDep]) -> PackageEntry {
+    let dependencies = package_deps
+        .iter()
+        .cloned()
+        .map(PackageDependencyIdentifier::from)
+        .collect();
+    let yanked = false;
+
+    PackageEntry::new(
+        package.manifest.project.name.clone(),
+        package.version.clone(),
+        package.upload.source_code_ipfs_hash.clone(),
+        package.upload.abi_ipfs_hash.clone(),
+        dependencies,
+        yanked,
+    )
+}
+
+/// Publishes a single package, once its index entry is pushed, by:
+/// 1. Storing the package version in the database
+/// 2. Storing the package dependencies in the database
+fn publish_package(
+    conn: &mut DbConn<'_>,
+    request: &PublishRequest,
+    token: &ApiToken,
+    client: &ClientInfo,
+    package: &PackageToPublish,
+    package_deps: &[PartialPackageDep],
+) -> Result<PublishInfo, PublishError> {
+    let pkg_manifest = &package.manifest;
+    let publish_info = publish_info(request, package);
+
+    // Insert package version into the database along with metadata from the package manifest.
+    let package_version = conn.new_package_version(token, &publish_info)?;
+
+    // Insert package dependencies into the database.
+    let new_package_deps = package_deps
+        .iter()
+        .map(|dep| NewPackageDep {
+            dependent_package_version_id: package_version.id,
+            dependency_package_name: dep.dependency_package_name.clone(),
+            dependency_version_req: dep.dependency_version_req.clone(),
+        })
+        .collect();
+    let _ = conn.insert_dependencies(new_package_deps)?;
+
+    // Insert package categories and keywords into the database.
+    if let Some(categories) = &pkg_manifest.project.categories {
+        let _ = conn.insert_categories(package_version.package_id, categories)?;
+    }
+    if let Some(keywords) = &pkg_manifest.project.keywords {
+        let _ = conn.insert_keywords(package_version.package_id, keywords)?;
+    }
+
+    // Flag new packages whose names look like the names of other packages, or are
+    // near-duplicates of popular packages, for review.
+    if package.new_package {
+        let flags = conn.flag_similar_package_names(package_version.package_id)?;
+        if !flags.is_empty() {
+            warn!(
+                "Package {} was flagged for review: its name is similar to {} package(s)",
+                publish_info.package_name,
+                flags.len()
+            );
+        }
+    }
+
+    conn.record_audit_event(
+        token.user_id,
+        AuditEventKind::Publish,
+        client,
+        json!({
+            "name": publish_info.package_name,
+            "version": publish_info.num,
+            "uploadId": publish_info.upload_id,
+            "tokenId": token.id,
+        }),
+    )?;
+
+    Ok(publish_info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package_to_publish(name: &str, deps: &[&str]) -> PackageToPublish {
+        let deps = deps
+            .iter()
+            .map(|dep| format!("{dep} = \"0.1.0\"\n"))
+            .collect::<String>();
+        let manifest = PackageManifest::from_string(format!(
+            "[project]\nauthors = [\"Fuel Labs\"]\nentry = \"lib.sw\"\nlicense = \"Apache-2.0\"\nname = \"{name}\"\nversion = \"0.1.0\"\n\n[dependencies]\n{deps}"
+        ))
+        .expect("manifest ok");
+        PackageToPublish {
+            upload: Upload {
+                id: Uuid::new_v4(),
+                source_code_ipfs_hash: "ABC123".to_string(),
+                forc_version: "0.66.6".to_string(),
+                abi_ipfs_hash: None,
+                bytecode_identifier: None,
+                readme: None,
+                forc_manifest: String::new(),
+                docs_ipfs_hash: None,
+                created_at: Utc::now(),
+                workspace_upload_id: None,
+            },
+            version: manifest.project.version.clone().unwrap(),
+            manifest,
+            new_package: false,
+        }
+    }
+
+    #[test]
+    fn publish_order_publishes_dependencies_first() {
+        let packages = vec![
+            package_to_publish("app", &["lib_b", "std"]),
+            package_to_publish("lib_b", &["lib_a"]),
+            package_to_publish("lib_a", &[]),
+            package_to_publish("other", &[]),
+        ];
+
+        assert_eq!(publish_order(&packages).unwrap(), vec![2, 1, 0, 3]);
+    }
+
+    #[test]
+    fn publish_order_rejects_cycles() {
+        let packages = vec![
+            package_to_publish("lib_a", &["lib_b"]),
+            package_to_publish("lib_b", &["lib_a"]),
+        ];
+
+        assert!(matches!(
+            publish_order(&packages),
+            Err(PublishError::InvalidForcManifest(_))
+        ));
+    }
+
+    #[test]
+    fn dependency_version_uses_workspace_member_versions_for_path_deps() {
+        let manifest = PackageManifest::from_string(
+            r#"
+[project]
+authors = ["Fuel Labs"]
+entry = "lib.sw"
+license = "Apache-2.0"
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+lib_a = { path = "../lib_a" }
+lib_b = "0.1"
+other = { path = "../other" }
+"#
+            .to_string(),
+        )
+        .expect("manifest ok");
+        let member_versions = HashMap::from([
+            ("lib_a".to_string(), Version::new(0, 2, 0)),
+            ("lib_b".to_string(), Version::new(0, 1, 3)),
+        ]);
+        let version = |name: &str| {
+            let dependency = &manifest.dependencies.as_ref().unwrap()[name];
+            dependency_version(name, dependency, &member_versions)
+        };
+
+        assert_eq!(version("lib_a").unwrap(), "0.2.0");
+        assert_eq!(version("lib_b").unwrap(), "0.1");
+        assert!(matches!(
+            version("other"),
+            Err(PublishError::InvalidForcManifest(_))
+        ));
+    }
+
+    #[test]
+    fn check_version_rejects_published_versions() {
+        let published = vec![("0.1.0".to_string(), false), ("0.2.0".to_string(), true)];
+        let check = |version: &str| {
+            check_version(
+                "pkg",
+                &Version::parse(version).unwrap(),
+                &published,
+                &[],
+                &PublishPolicy::default(),
+            )
+        };
+
+        assert!(check("0.1.1").is_ok());
+        assert!(check("0.0.9").is_ok());
+        assert!(matches!(
+            check("0.1.0"),
+            Err(PublishError::VersionExists { ref name, ref version })
+                if name == "pkg" && version.to_string() == "0.1.0"
+        ));
+        assert!(matches!(
+            check("0.2.0"),
+            Err(PublishError::VersionExists { .. })
+        ));
+    }
+
+    #[test]
+    fn check_version_rejects_removed_versions() {
+        let published = vec![("0.1.0".to_string(), false)];
+        let removed = vec!["0.2.0".to_string()];
+        let check = |version: &str| {
+            check_version(
+                "pkg",
+                &Version::parse(version).unwrap(),
+                &published,
+                &removed,
+                &PublishPolicy::default(),
+            )
+        };
+
+        assert!(check("0.2.1").is_ok());
+        assert!(matches!(
+            check("0.2.0"),
+            Err(PublishError::VersionRemoved { ref name, ref version })
+                if name == "pkg" && version.to_string() == "0.2.0"
+        ));
+    }
+
+    #[test]
+    fn check_version_rejects_lower_versions_with_policy() {
+        let published = vec![
+            ("1.2.0".to_string(), false),
+            ("1.3.0".to_string(), true),
+            ("2.0.0".to_string(), false),
+        ];
+        let policy = PublishPolicy {
+            increasing_versions: true,
+        };
+        let check = |version: &str| {
+            check_version(
+                "pkg",
+                &Version::parse(version).unwrap(),
+                &published,
+                &[],
+                &policy,
+            )
+        };
+
+        // Yanked versions and other major versions don't count.
+        assert!(check("1.2.1").is_ok());
+        assert!(check("0.9.0").is_ok());
+        assert!(check("3.0.0").is_ok());
+        assert!(matches!(
+            check("1.1.9"),
+            Err(PublishError::VersionNotIncreasing { ref latest, .. })
+                if latest.to_string() == "1.2.0"
+        ));
+        assert!(matches!(
+            check("1.2.0-rc.1"),
+            Err(PublishError::VersionNotIncreasing { .. })
+        ));
+    }
+
+    #[test]
+    fn unsatisfied_reason_requires_a_version_not_yanked() {
+        let published = vec![("0.1.0".to_string(), false), ("0.2.0".to_string(), true)];
+        let req = |req: &str| VersionReq::parse(req).unwrap();
+
+        assert_eq!(unsatisfied_reason(&req("^0.1"), &published), None);
+        assert_eq!(unsatisfied_reason(&req("~0.1.0"), &published), None);
+        assert_eq!(unsatisfied_reason(&req(">=0.1.0"), &published), None);
+        assert_eq!(
+            unsatisfied_reason(&req("^0.2"), &published),
+            Some("Only yanked versions match the requirement".to_string())
+        );
+        assert_eq!(
+            unsatisfied_reason(&req("^1.0"), &published),
+            Some("No published version matches the requirement".to_string())
+        );
+        assert_eq!(
+            unsatisfied_reason(&req("^0.1"), &[]),
+            Some("Package not found".to_string())
+        );
+    }
 }
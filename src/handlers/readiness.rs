@@ -0,0 +1,148 @@
+use crate::api::readiness::{ComponentReadiness, ReadinessResponse};
+use crate::db::{Database, DbConn};
+use crate::file_uploader::storage::StorageClient;
+use crate::index::handler::git::check_repo_fetchable;
+use crate::util::load_env;
+use forc_pkg::source::reg;
+use std::env;
+use std::future::Future;
+use std::time::Duration;
+use tokio::{task, time};
+
+/// How long each component has to respond before it is reported as unavailable.
+const READINESS_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for a database connection, which is shorter than the check's timeout
+/// so that the connection error is reported.
+const DATABASE_CONNECT_TIMEOUT: Duration = Duration::from_secs(4);
+
+/// Runs a readiness check, which fails if it doesn't finish in time.
+async fn run_check<F>(check: F) -> ComponentReadiness
+where
+    F: Future<Output = Result<(), String>>,
+{
+    match time::timeout(READINESS_CHECK_TIMEOUT, check).await {
+        Ok(Ok(())) => ComponentReadiness::ok(),
+        Ok(Err(err)) => ComponentReadiness::unavailable(err),
+        Err(_) => ComponentReadiness::unavailable(format!(
+            "No response within {} seconds",
+            READINESS_CHECK_TIMEOUT.as_secs()
+        )),
+    }
+}
+
+/// Checks a round-trip to the database.
+pub async fn check_database(db: &Database) -> ComponentReadiness {
+    let db = db.clone();
+    run_check(async move {
+        task::spawn_blocking(move || {
+            let mut conn = db
+                .pool
+                .get_timeout(DATABASE_CONNECT_TIMEOUT)
+                .map_err(|err| format!("Failed to connect to the database: {err}"))?;
+            DbConn::new(&mut conn).ping().map_err(|err| err.to_string())
+        })
+        .await
+        .map_err(|err| err.to_string())?
+    })
+    .await
+}
+
+/// Checks that the storage backend can be reached.
+pub async fn check_storage(storage: &StorageClient) -> ComponentReadiness {
+    run_check(async {
+        storage
+            .check_reachable()
+            .await
+            .map_err(|err| err.to_string())
+    })
+    .await
+}
+
+/// Checks that the index repo can be fetched. The index isn't used in local environments.
+pub async fn check_index() -> ComponentReadiness {
+    load_env();
+    if env::var("RUN_ENV").unwrap_or_default() == "local" {
+        return ComponentReadiness::skipped();
+    }
+    run_check(async {
+        task::spawn_blocking(|| {
+            check_repo_fetchable(
+                reg::GithubRegistryResolver::DEFAULT_REPO_NAME,
+                reg::GithubRegistryResolver::DEFAULT_GITHUB_ORG,
+            )
+        })
+        .await
+        .map_err(|err| err.to_string())?
+        .map_err(|err| err.to_string())
+    })
+    .await
+}
+
+/// Handles the readiness probe by checking the database, the storage backend and the
+/// index repo concurrently. Only the database and local storage gate readiness.
+pub async fn handle_readiness(db: &Database, storage: &StorageClient) -> ReadinessResponse {
+    let local_storage = storage.is_local();
+    let (database, storage, index) =
+        tokio::join!(check_database(db), check_storage(storage), check_index());
+    ReadinessResponse::new(database, storage, index, local_storage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn run_check_reports_failures() {
+        assert_eq!(run_check(async { Ok(()) }).await, ComponentReadiness::ok());
+        assert_eq!(
+            run_check(async { Err("refused".to_string()) }).await,
+            ComponentReadiness::unavailable("refused".to_string())
+        );
+    }
+
+    #[test]
+    fn readiness_response_is_gated_by_the_database_and_local_storage() {
+        let response = ReadinessResponse::new(
+            ComponentReadiness::ok(),
+            ComponentReadiness::ok(),
+            ComponentReadiness::skipped(),
+            true,
+        );
+        assert!(response.ready);
+
+        let response = ReadinessResponse::new(
+            ComponentReadiness::unavailable("refused".to_string()),
+            ComponentReadiness::ok(),
+            ComponentReadiness::ok(),
+            false,
+        );
+        assert!(!response.ready);
+
+        let response = ReadinessResponse::new(
+            ComponentReadiness::ok(),
+            ComponentReadiness::unavailable("not writable".to_string()),
+            ComponentReadiness::ok(),
+            true,
+        );
+        assert!(!response.ready);
+
+        // Remote storage and the index are reported, but don't gate readiness.
+        let response = ReadinessResponse::new(
+            ComponentReadiness::ok(),
+            ComponentReadiness::unavailable("unreachable".to_string()),
+            ComponentReadiness::unavailable("fetch failed".to_string()),
+            false,
+        );
+        assert!(response.ready);
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            serde_json::json!({
+                "ready": true,
+                "database": { "status": "ok" },
+                "storage": { "status": "unavailable", "error": "unreachable" },
+                "index": { "status": "unavailable", "error": "fetch failed" },
+            })
+        );
+    }
+}
@@ -0,0 +1,519 @@
+use crate::handlers::upload::UploadError;
+use crate::util::load_env;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Read};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+use std::{env, fs};
+use thiserror::Error;
+
+/// How often a running command is checked for completion.
+const WAIT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The directory in the working root that is used as the home directory of commands.
+const HOME_DIR: &str = ".home";
+
+/// The directory in the working root that is used for temporary files of commands.
+const TMP_DIR: &str = ".tmp";
+
+/// The `PATH` of commands, in addition to the directory of the command itself.
+const SYSTEM_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+
+/// The host paths that are visible to commands run with bubblewrap, if they exist. Other
+/// host files, like the server's configuration and keys and the directories of other
+/// uploads, are hidden. The files in `/etc` are needed for TLS and name resolution.
+const SYSTEM_PATHS: &[&str] = &[
+    "/usr",
+    "/bin",
+    "/sbin",
+    "/lib",
+    "/lib32",
+    "/lib64",
+    "/etc/ssl",
+    "/etc/ca-certificates",
+    "/etc/resolv.conf",
+    "/etc/hosts",
+    "/etc/nsswitch.conf",
+];
+
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type RlimitResource = libc::__rlimit_resource_t;
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+type RlimitResource = libc::c_int;
+
+#[derive(Error, Debug)]
+pub enum SandboxError {
+    #[error("Failed to spawn {0}: {1}")]
+    Spawn(String, io::Error),
+
+    #[error("Failed to wait for {0}: {1}")]
+    Wait(String, io::Error),
+
+    #[error("{0}")]
+    LimitExceeded(String),
+}
+
+/// How commands are isolated from the host, selected with `BUILD_SANDBOX`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SandboxMode {
+    /// Use bubblewrap if it is installed and works, and resource limits only otherwise.
+    #[default]
+    Auto,
+    /// Always use bubblewrap.
+    Bubblewrap,
+    /// Resource limits only.
+    None,
+}
+
+impl FromStr for SandboxMode {
+    type Err = UploadError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(SandboxMode::Auto),
+            "bwrap" | "bubblewrap" => Ok(SandboxMode::Bubblewrap),
+            "none" => Ok(SandboxMode::None),
+            _ => Err(UploadError::UnsupportedSandbox(s.to_string())),
+        }
+    }
+}
+
+impl SandboxMode {
+    /// Returns the configured sandbox mode, defaulting to [SandboxMode::Auto].
+    pub fn from_env() -> Result<Self, UploadError> {
+        load_env();
+        env::var("BUILD_SANDBOX")
+            .ok()
+            .filter(|mode| !mode.is_empty())
+            .map_or(Ok(SandboxMode::default()), |mode| mode.parse())
+    }
+}
+
+/// The resources a command may use before it is killed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SandboxLimits {
+    /// The wall-clock time the command may run for.
+    pub timeout: Duration,
+    /// The CPU time each process may use, in seconds.
+    pub cpu_secs: u64,
+    /// The virtual memory each process may use, in bytes.
+    pub memory_bytes: u64,
+    /// The size of the largest file a process may write, in bytes.
+    pub file_size_bytes: u64,
+}
+
+impl Default for SandboxLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(600),
+            cpu_secs: 600,
+            memory_bytes: 8 * 1024 * BYTES_PER_MB,
+            file_size_bytes: 1024 * BYTES_PER_MB,
+        }
+    }
+}
+
+impl SandboxLimits {
+    /// Returns the limits set with `BUILD_TIMEOUT_SECS`, `BUILD_CPU_LIMIT_SECS`,
+    /// `BUILD_MEMORY_LIMIT_MB` and `BUILD_FILE_SIZE_LIMIT_MB`, using [SandboxLimits::default]
+    /// for the variables that are unset or empty. A limit that isn't a whole number is
+    /// rejected rather than silently replaced by its default.
+    pub fn from_env() -> Result<Self, UploadError> {
+        load_env();
+        let var = |name: &str| match env::var(name) {
+            Ok(value) if !value.is_empty() => value
+                .parse::<u64>()
+                .map(Some)
+                .map_err(|_| UploadError::InvalidSandboxLimit(format!("{name}={value}"))),
+            _ => Ok(None),
+        };
+        let default = Self::default();
+        Ok(Self {
+            timeout: var("BUILD_TIMEOUT_SECS")?
+                .map(Duration::from_secs)
+                .unwrap_or(default.timeout),
+            cpu_secs: var("BUILD_CPU_LIMIT_SECS")?.unwrap_or(default.cpu_secs),
+            memory_bytes: var("BUILD_MEMORY_LIMIT_MB")?
+                .map(|mb| mb * BYTES_PER_MB)
+                .unwrap_or(default.memory_bytes),
+            file_size_bytes: var("BUILD_FILE_SIZE_LIMIT_MB")?
+                .map(|mb| mb * BYTES_PER_MB)
+                .unwrap_or(default.file_size_bytes),
+        })
+    }
+}
+
+/// Runs the build tools on untrusted projects.
+///
+/// Commands run with an empty environment, and with their home and temporary directories
+/// inside a working root. Every process is subject to the [SandboxLimits], and the whole
+/// process group is killed when the command exits or runs out of time. With bubblewrap,
+/// commands also run in their own namespaces, where they can only read the system
+/// directories, the directory of the program and the read-only directories of the
+/// sandbox, and only the working root is writable.
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    bwrap_path: Option<PathBuf>,
+    limits: SandboxLimits,
+    work_root: PathBuf,
+    read_only_dirs: Vec<PathBuf>,
+}
+
+impl Sandbox {
+    /// Creates a sandbox whose commands can only write to `work_root`.
+    pub fn new(
+        mode: SandboxMode,
+        limits: SandboxLimits,
+        work_root: &Path,
+    ) -> Result<Self, UploadError> {
+        let bwrap_path = match mode {
+            SandboxMode::None => None,
+            SandboxMode::Auto => {
+                let bwrap_path = find_usable_bwrap();
+                if bwrap_path.is_none() {
+                    tracing::warn!("bubblewrap is not usable; builds only have resource limits");
+                }
+                bwrap_path
+            }
+            SandboxMode::Bubblewrap => Some(
+                find_usable_bwrap()
+                    .ok_or_else(|| UploadError::UnsupportedSandbox("bwrap".to_string()))?,
+            ),
+        };
+        Ok(Self {
+            bwrap_path,
+            limits,
+            work_root: work_root.to_path_buf(),
+            read_only_dirs: vec![],
+        })
+    }
+
+    /// Makes the directory readable by commands, like the toolchain that they belong to.
+    pub fn with_read_only_dir(mut self, dir: &Path) -> Self {
+        self.read_only_dirs.push(dir.to_path_buf());
+        self
+    }
+
+    /// Returns the sandbox configured with `BUILD_SANDBOX` and the `BUILD_*` limits.
+    pub fn from_env(work_root: &Path) -> Result<Self, UploadError> {
+        Self::new(
+            SandboxMode::from_env()?,
+            SandboxLimits::from_env()?,
+            work_root,
+        )
+    }
+
+    /// Runs the program in `current_dir`, which must be inside the working root, to
+    /// completion and returns its output.
+    ///
+    /// A command that exits unsuccessfully still returns its output, unless it was killed
+    /// for exceeding a limit.
+    pub fn run<I, S>(
+        &self,
+        program: &Path,
+        args: I,
+        current_dir: &Path,
+    ) -> Result<Output, SandboxError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let program_name = program.display().to_string();
+        let work_root = self.work_root.as_path();
+        let home_dir = work_root.join(HOME_DIR);
+        let tmp_dir = work_root.join(TMP_DIR);
+        for dir in [&home_dir, &tmp_dir] {
+            fs::create_dir_all(dir)
+                .map_err(|err| SandboxError::Spawn(program_name.clone(), err))?;
+        }
+
+        let program_dir = program.parent().unwrap_or(Path::new("/"));
+        let mut command = match &self.bwrap_path {
+            Some(bwrap_path) => {
+                let mut command = Command::new(bwrap_path);
+                command
+                    .args(["--die-with-parent", "--unshare-all", "--share-net"])
+                    .args(system_mount_args())
+                    .args(["--dev", "/dev", "--proc", "/proc", "--tmpfs", "/tmp"])
+                    .arg("--ro-bind")
+                    .args([program_dir, program_dir]);
+                for dir in &self.read_only_dirs {
+                    command.arg("--ro-bind").args([dir, dir]);
+                }
+                command
+                    .arg("--bind")
+                    .args([work_root, work_root])
+                    .arg("--chdir")
+                    .arg(current_dir)
+                    .arg("--")
+                    .arg(program);
+                command
+            }
+            None => Command::new(program),
+        };
+        command
+            .args(args)
+            .current_dir(current_dir)
+            .env_clear()
+            .env("PATH", format!("{}:{SYSTEM_PATH}", program_dir.display()))
+            .env("HOME", &home_dir)
+            .env("TMPDIR", &tmp_dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let limits = self.limits.clone();
+        // SAFETY: `setsid` and `setrlimit` are async-signal-safe.
+        unsafe {
+            command.pre_exec(move || {
+                if libc::setsid() == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                // The hard CPU limit is a little higher so that SIGXCPU is sent first.
+                set_rlimit(libc::RLIMIT_CPU, limits.cpu_secs, limits.cpu_secs + 5)?;
+                set_rlimit(libc::RLIMIT_AS, limits.memory_bytes, limits.memory_bytes)?;
+                set_rlimit(
+                    libc::RLIMIT_FSIZE,
+                    limits.file_size_bytes,
+                    limits.file_size_bytes,
+                )?;
+                set_rlimit(libc::RLIMIT_CORE, 0, 0)
+            });
+        }
+
+        let mut child = command
+            .spawn()
+            .map_err(|err| SandboxError::Spawn(program_name.clone(), err))?;
+        let stdout = read_in_background(child.stdout.take());
+        let stderr = read_in_background(child.stderr.take());
+
+        let status = wait_with_timeout(&mut child, self.limits.timeout);
+        // Kill any processes that the command left behind, so they can't outlive it.
+        kill_process_group(&child);
+        let status = status.map_err(|err| SandboxError::Wait(program_name.clone(), err))?;
+
+        let output = Output {
+            status: status.ok_or_else(|| {
+                SandboxError::LimitExceeded(format!(
+                    "wall-clock time limit of {}s",
+                    self.limits.timeout.as_secs()
+                ))
+            })?,
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+        };
+        if let Some(limit) = self.exceeded_limit(&output) {
+            return Err(SandboxError::LimitExceeded(limit));
+        }
+        Ok(output)
+    }
+
+    /// Returns the limit the command was killed for exceeding, if any.
+    fn exceeded_limit(&self, output: &Output) -> Option<String> {
+        match termination_signal(output.status) {
+            Some(libc::SIGXCPU) => {
+                return Some(format!("CPU time limit of {}s", self.limits.cpu_secs))
+            }
+            Some(libc::SIGXFSZ) => {
+                return Some(format!(
+                    "file size limit of {} MB",
+                    self.limits.file_size_bytes / BYTES_PER_MB
+                ))
+            }
+            _ => {}
+        }
+
+        // Rust programs abort with this message when an allocation fails.
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() && stderr.contains("memory allocation of") {
+            return Some(format!(
+                "memory limit of {} MB",
+                self.limits.memory_bytes / BYTES_PER_MB
+            ));
+        }
+        None
+    }
+}
+
+/// Returns the signal that terminated the process. Shells and bubblewrap report the
+/// signals of their children as an exit code of 128 plus the signal.
+fn termination_signal(status: ExitStatus) -> Option<i32> {
+    status.signal().or_else(|| {
+        status
+            .code()
+            .filter(|code| *code > 128)
+            .map(|code| code - 128)
+    })
+}
+
+fn set_rlimit(resource: RlimitResource, soft: u64, hard: u64) -> io::Result<()> {
+    let rlimit = libc::rlimit {
+        rlim_cur: soft,
+        rlim_max: hard,
+    };
+    // SAFETY: `rlimit` is a valid pointer for the duration of the call.
+    if unsafe { libc::setrlimit(resource, &rlimit) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Waits for the child to exit, and returns `None` if it was killed for running out of time.
+fn wait_with_timeout(child: &mut Child, timeout: Duration) -> io::Result<Option<ExitStatus>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            kill_process_group(child);
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(WAIT_INTERVAL);
+    }
+}
+
+/// Kills every process in the process group of the child, which is led by the child.
+fn kill_process_group(child: &Child) {
+    // SAFETY: Signalling a process group has no memory safety requirements.
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+fn read_in_background(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = vec![];
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+/// Returns the bubblewrap arguments that make the [SYSTEM_PATHS] of the host visible.
+/// Symlinks, like `/lib` on systems with a merged `/usr`, are recreated instead of bound.
+fn system_mount_args() -> Vec<OsString> {
+    let mut args: Vec<OsString> = vec![];
+    for path in SYSTEM_PATHS {
+        match fs::read_link(path) {
+            Ok(target) => args.extend(["--symlink".into(), target.into(), path.into()]),
+            Err(_) => args.extend(["--ro-bind-try".into(), path.into(), path.into()]),
+        }
+    }
+    args
+}
+
+/// Returns the path of bubblewrap if it is installed and can create namespaces, which
+/// isn't allowed in some containers.
+fn find_usable_bwrap() -> Option<PathBuf> {
+    let bwrap_path = env::var_os("PATH").and_then(|paths| {
+        env::split_paths(&paths)
+            .map(|dir| dir.join("bwrap"))
+            .find(|path| path.is_file())
+    })?;
+
+    Command::new(&bwrap_path)
+        .arg("--unshare-all")
+        .args(system_mount_args())
+        .args(["--", "true"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .ok()
+        .filter(|status| status.success())
+        .map(|_| bwrap_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_sh(limits: SandboxLimits, script: &str) -> Result<Output, SandboxError> {
+        let work_root = tempfile::tempdir().expect("tempdir");
+        let sandbox = Sandbox::new(SandboxMode::None, limits, work_root.path()).expect("sandbox");
+        sandbox.run(Path::new("/bin/sh"), ["-c", script], work_root.path())
+    }
+
+    #[test]
+    fn sandbox_mode_from_str() {
+        assert_eq!("auto".parse::<SandboxMode>().unwrap(), SandboxMode::Auto);
+        assert_eq!(
+            "bwrap".parse::<SandboxMode>().unwrap(),
+            SandboxMode::Bubblewrap
+        );
+        assert_eq!("None".parse::<SandboxMode>().unwrap(), SandboxMode::None);
+        assert_eq!(
+            "docker".parse::<SandboxMode>(),
+            Err(UploadError::UnsupportedSandbox("docker".to_string()))
+        );
+    }
+
+    #[test]
+    fn system_mount_args_only_expose_system_paths() {
+        let args = system_mount_args();
+        let sources: Vec<_> = args.chunks(3).map(|mount| mount[1].clone()).collect();
+
+        assert!(sources.contains(&OsString::from("/usr")));
+        assert!(!sources.contains(&OsString::from("/")));
+        assert!(!sources
+            .iter()
+            .any(|source| source == "/root" || source == "/etc"));
+    }
+
+    #[test]
+    fn run_captures_output_with_a_clean_environment() {
+        env::set_var("SANDBOX_TEST_SECRET", "secret");
+        let output = run_sh(
+            SandboxLimits::default(),
+            "echo \"out:$SANDBOX_TEST_SECRET\"; echo err >&2; exit 3",
+        )
+        .expect("output");
+
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "out:\n");
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "err\n");
+    }
+
+    #[test]
+    fn run_kills_commands_that_time_out() {
+        let limits = SandboxLimits {
+            timeout: Duration::from_millis(200),
+            ..Default::default()
+        };
+        let started = Instant::now();
+        let err = run_sh(limits, "sleep 10 & sleep 10").expect_err("timeout");
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(matches!(err, SandboxError::LimitExceeded(limit) if limit.contains("wall-clock")));
+    }
+
+    #[test]
+    fn run_enforces_cpu_limit() {
+        let limits = SandboxLimits {
+            cpu_secs: 1,
+            ..Default::default()
+        };
+        let err = run_sh(limits, "while :; do :; done").expect_err("cpu limit");
+
+        assert!(matches!(err, SandboxError::LimitExceeded(limit) if limit.contains("CPU")));
+    }
+
+    #[test]
+    fn run_enforces_file_size_limit() {
+        let limits = SandboxLimits {
+            file_size_bytes: BYTES_PER_MB,
+            ..Default::default()
+        };
+        let err = run_sh(limits, "head -c 2000000 /dev/zero > big").expect_err("file size limit");
+
+        assert!(matches!(err, SandboxError::LimitExceeded(limit) if limit.contains("file size")));
+    }
+}
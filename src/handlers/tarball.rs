@@ -0,0 +1,332 @@
+use crate::handlers::upload::UploadError;
+use flate2::read::GzDecoder;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use tar::{Archive, EntryType};
+
+/// Below this unpacked size, the compression ratio of a tarball isn't checked.
+const MIN_RATIO_CHECK_SIZE: u64 = 1024 * 1024;
+
+/// The limits a tarball must stay within to be unpacked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnpackLimits {
+    /// The maximum number of entries in the tarball.
+    pub max_entries: usize,
+    /// The maximum total size of the unpacked files, in bytes.
+    pub max_unpacked_size: u64,
+    /// The maximum size of a single unpacked file, in bytes.
+    pub max_file_size: u64,
+    /// The maximum ratio of the unpacked size to the size of the tarball.
+    pub max_compression_ratio: u64,
+}
+
+impl Default for UnpackLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: 10_000,
+            max_unpacked_size: 100 * 1024 * 1024,
+            max_file_size: 25 * 1024 * 1024,
+            max_compression_ratio: 100,
+        }
+    }
+}
+
+/// Unpacks a gzipped tarball of untrusted content into `dest`.
+///
+/// Only regular files, directories and links are unpacked. Entries must have relative
+/// paths that stay inside `dest`, can't be written through links, and links must resolve
+/// to files inside `dest`. The sizes in the entry headers are checked against the limits
+/// before anything is written, so the unpacked data can't exceed them.
+pub fn unpack_tarball(
+    tarball_path: &Path,
+    dest: &Path,
+    limits: &UnpackLimits,
+) -> Result<(), UploadError> {
+    let tarball = File::open(tarball_path).map_err(|_| UploadError::OpenFile)?;
+    let tarball_size = tarball.metadata().map_err(|_| UploadError::OpenFile)?.len();
+    let mut archive = Archive::new(GzDecoder::new(tarball));
+    fs::create_dir_all(dest).map_err(|_| UploadError::SaveFile)?;
+
+    let mut unpacked_size: u64 = 0;
+    let entries = archive.entries().map_err(|_| UploadError::OpenFile)?;
+    for (i, entry) in entries.enumerate() {
+        if i >= limits.max_entries {
+            return Err(UploadError::TooManyEntries(limits.max_entries));
+        }
+        let mut entry = entry.map_err(|_| UploadError::OpenFile)?;
+        let entry_type = entry.header().entry_type();
+        let entry_path = entry
+            .path()
+            .map_err(|_| UploadError::OpenFile)?
+            .into_owned();
+        let display_path = entry_path.display().to_string();
+
+        // Extension headers are applied to the entries that follow them.
+        if matches!(entry_type, EntryType::XGlobalHeader | EntryType::XHeader) {
+            continue;
+        }
+
+        let relative_path = normalize_path(&entry_path)
+            .ok_or_else(|| UploadError::InvalidTarballPath(display_path.clone()))?;
+        let Some(relative_path) = relative_path else {
+            // The root directory already exists.
+            continue;
+        };
+        ensure_no_symlinks(dest, &relative_path, &display_path)?;
+        let path = dest.join(&relative_path);
+
+        match entry_type {
+            EntryType::Directory => {
+                fs::create_dir_all(&path).map_err(|_| UploadError::SaveFile)?;
+            }
+            EntryType::Regular | EntryType::Continuous => {
+                let size = entry.size();
+                if size > limits.max_file_size {
+                    return Err(UploadError::FileTooLarge(
+                        display_path,
+                        limits.max_file_size,
+                    ));
+                }
+                unpacked_size += size;
+                if unpacked_size > limits.max_unpacked_size {
+                    return Err(UploadError::UnpackedTooLarge(limits.max_unpacked_size));
+                }
+                if unpacked_size > MIN_RATIO_CHECK_SIZE
+                    && unpacked_size > tarball_size.saturating_mul(limits.max_compression_ratio)
+                {
+                    return Err(UploadError::CompressionRatioTooHigh(
+                        limits.max_compression_ratio,
+                    ));
+                }
+
+                create_parent_dir(&path)?;
+                let mut file = File::create(&path).map_err(|_| UploadError::SaveFile)?;
+                io::copy(&mut entry, &mut file).map_err(|_| UploadError::SaveFile)?;
+            }
+            EntryType::Symlink => {
+                let target = entry
+                    .link_name()
+                    .map_err(|_| UploadError::OpenFile)?
+                    .ok_or_else(|| UploadError::InvalidTarballPath(display_path.clone()))?
+                    .into_owned();
+                // Symlink targets are relative to the directory of the link.
+                let link_dir = relative_path.parent().unwrap_or(Path::new(""));
+                if target.is_absolute() || normalize_path(&link_dir.join(&target)).is_none() {
+                    return Err(UploadError::LinkOutsideProject(display_path));
+                }
+
+                create_parent_dir(&path)?;
+                std::os::unix::fs::symlink(&target, &path).map_err(|_| UploadError::SaveFile)?;
+            }
+            EntryType::Link => {
+                let target = entry
+                    .link_name()
+                    .map_err(|_| UploadError::OpenFile)?
+                    .ok_or_else(|| UploadError::InvalidTarballPath(display_path.clone()))?;
+                // Hard link targets are relative to the root of the tarball.
+                let Some(Some(target)) = normalize_path(&target) else {
+                    return Err(UploadError::LinkOutsideProject(display_path));
+                };
+                ensure_no_symlinks(dest, &target, &display_path)?;
+
+                create_parent_dir(&path)?;
+                fs::hard_link(dest.join(target), &path).map_err(|_| UploadError::SaveFile)?;
+            }
+            _ => return Err(UploadError::UnsupportedTarballEntry(display_path)),
+        }
+    }
+
+    // Links can still point outside through other links, which is only known once every
+    // link exists.
+    let root = fs::canonicalize(dest).map_err(|_| UploadError::OpenFile)?;
+    verify_links(&root, &root)
+}
+
+/// Returns an error if the path or any of its parents inside `dest` is a symlink, so
+/// nothing is written through a link.
+fn ensure_no_symlinks(
+    dest: &Path,
+    relative_path: &Path,
+    display_path: &str,
+) -> Result<(), UploadError> {
+    let mut path = dest.to_path_buf();
+    for component in relative_path.components() {
+        path.push(component);
+        if path.is_symlink() {
+            return Err(UploadError::InvalidTarballPath(display_path.to_string()));
+        }
+    }
+    Ok(())
+}
+
+/// Returns an error if any link in `dir` doesn't resolve to a file inside `root`.
+fn verify_links(root: &Path, dir: &Path) -> Result<(), UploadError> {
+    for entry in fs::read_dir(dir).map_err(|_| UploadError::OpenFile)? {
+        let path = entry.map_err(|_| UploadError::OpenFile)?.path();
+        let file_type = fs::symlink_metadata(&path)
+            .map_err(|_| UploadError::OpenFile)?
+            .file_type();
+        if file_type.is_symlink() {
+            let inside_root = fs::canonicalize(&path).is_ok_and(|target| target.starts_with(root));
+            if !inside_root {
+                let display_path = path.strip_prefix(root).unwrap_or(&path).display();
+                return Err(UploadError::LinkOutsideProject(display_path.to_string()));
+            }
+        } else if file_type.is_dir() {
+            verify_links(root, &path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the path without `.` components, or `None` if the path isn't a relative path
+/// that stays inside its root. The inner `None` is the root itself.
+fn normalize_path(path: &Path) -> Option<Option<PathBuf>> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(Some(normalized).filter(|path| !path.as_os_str().is_empty()))
+}
+
+fn create_parent_dir(path: &Path) -> Result<(), UploadError> {
+    match path.parent() {
+        Some(parent) => fs::create_dir_all(parent).map_err(|_| UploadError::SaveFile),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unpack_fixture(name: &str, limits: &UnpackLimits) -> Result<tempfile::TempDir, UploadError> {
+        let dest = tempfile::tempdir().expect("tempdir ok");
+        let tarball_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name);
+        unpack_tarball(&tarball_path, &dest.path().join("unpacked"), limits)?;
+        Ok(dest)
+    }
+
+    #[test]
+    fn unpack_tarball_unpacks_project() {
+        let dest = unpack_fixture("sway-project.tgz", &UnpackLimits::default()).expect("ok");
+        assert!(dest.path().join("unpacked/Forc.toml").is_file());
+    }
+
+    #[test]
+    fn unpack_tarball_unpacks_links_inside_project() {
+        let dest = unpack_fixture("safe-links.tgz", &UnpackLimits::default()).expect("ok");
+        let src_dir = dest.path().join("unpacked/src");
+        assert_eq!(
+            fs::read_link(src_dir.join("lib.sw")).unwrap(),
+            Path::new("main.sw")
+        );
+        assert_eq!(
+            fs::read_to_string(src_dir.join("copy.sw")).unwrap(),
+            "contract;\n"
+        );
+    }
+
+    #[test]
+    fn unpack_tarball_rejects_unsafe_paths() {
+        assert_eq!(
+            unpack_fixture("unsafe-absolute-path.tgz", &UnpackLimits::default()).err(),
+            Some(UploadError::InvalidTarballPath(
+                "/tmp/forc-pub-evil.sw".to_string()
+            ))
+        );
+        assert_eq!(
+            unpack_fixture("unsafe-parent-dir.tgz", &UnpackLimits::default()).err(),
+            Some(UploadError::InvalidTarballPath(
+                "src/../../forc-pub-evil.sw".to_string()
+            ))
+        );
+        assert!(!Path::new("/tmp/forc-pub-evil.sw").exists());
+    }
+
+    #[test]
+    fn unpack_tarball_rejects_links_outside_project() {
+        assert_eq!(
+            unpack_fixture("unsafe-symlink.tgz", &UnpackLimits::default()).err(),
+            Some(UploadError::LinkOutsideProject("src/lib.sw".to_string()))
+        );
+        assert_eq!(
+            unpack_fixture("unsafe-hardlink.tgz", &UnpackLimits::default()).err(),
+            Some(UploadError::LinkOutsideProject("src/lib.sw".to_string()))
+        );
+    }
+
+    #[test]
+    fn unpack_tarball_rejects_links_through_links() {
+        assert_eq!(
+            unpack_fixture("unsafe-symlink-chain.tgz", &UnpackLimits::default()).err(),
+            Some(UploadError::LinkOutsideProject("escape".to_string()))
+        );
+        assert_eq!(
+            unpack_fixture("unsafe-write-through-symlink.tgz", &UnpackLimits::default()).err(),
+            Some(UploadError::InvalidTarballPath("dir/evil.sw".to_string()))
+        );
+    }
+
+    #[test]
+    fn unpack_tarball_rejects_device_files() {
+        assert_eq!(
+            unpack_fixture("unsafe-device-file.tgz", &UnpackLimits::default()).err(),
+            Some(UploadError::UnsupportedTarballEntry("src/null".to_string()))
+        );
+    }
+
+    #[test]
+    fn unpack_tarball_rejects_zip_bombs() {
+        assert_eq!(
+            unpack_fixture("unsafe-zip-bomb.tgz", &UnpackLimits::default()).err(),
+            Some(UploadError::CompressionRatioTooHigh(100))
+        );
+    }
+
+    #[test]
+    fn unpack_tarball_enforces_limits() {
+        let limits = UnpackLimits {
+            max_file_size: 1024 * 1024,
+            ..Default::default()
+        };
+        assert_eq!(
+            unpack_fixture("unsafe-zip-bomb.tgz", &limits).err(),
+            Some(UploadError::FileTooLarge(
+                "src/bomb-0.sw".to_string(),
+                1024 * 1024
+            ))
+        );
+
+        let limits = UnpackLimits {
+            max_unpacked_size: 6 * 1024 * 1024,
+            max_compression_ratio: u64::MAX,
+            ..Default::default()
+        };
+        assert_eq!(
+            unpack_fixture("unsafe-zip-bomb.tgz", &limits).err(),
+            Some(UploadError::UnpackedTooLarge(6 * 1024 * 1024))
+        );
+
+        let limits = UnpackLimits {
+            max_entries: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            unpack_fixture("sway-project.tgz", &limits).err(),
+            Some(UploadError::TooManyEntries(2))
+        );
+    }
+}
@@ -0,0 +1,712 @@
+use crate::handlers::upload::{install_binaries_at_path, installed_at_path, UploadError};
+use crate::metrics::{metrics, UploadStage};
+use crate::util::load_env;
+use chrono::{DateTime, Utc};
+use semver::Version;
+use serde::Deserialize;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::{env, fmt};
+use tokio::task;
+use tracing::{error, info, warn};
+
+/// The prefix of the directories that versions of forc are installed in.
+const TOOLCHAIN_DIR_PREFIX: &str = "forc-";
+
+/// The directory in the root that installs are staged in, before they are moved into place.
+const STAGING_DIR: &str = ".staging";
+
+/// The directory in the root that holds the lock file of each version.
+const LOCKS_DIR: &str = ".locks";
+
+/// The file in a toolchain directory that records when it was last used.
+const LAST_USED_FILE: &str = ".last-used";
+
+/// The prefix of a prewarm list that installs the latest releases of forc.
+const LATEST_PREFIX: &str = "latest:";
+
+/// The GitHub API endpoint that lists the releases of forc.
+const SWAY_RELEASES_URL: &str = "https://api.github.com/repos/FuelLabs/sway/releases";
+
+/// Installs the given version of forc at the given path.
+pub type Installer = fn(&str, &Path) -> Result<(), UploadError>;
+
+/// An advisory lock on a file, which is released when it is dropped.
+struct FileLock {
+    _file: File,
+}
+
+impl FileLock {
+    fn open(path: &Path) -> io::Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)
+    }
+
+    /// Waits for a lock on the file, shared with other readers unless `exclusive` is set.
+    fn acquire(path: &Path, exclusive: bool) -> io::Result<Self> {
+        let file = Self::open(path)?;
+        let operation = if exclusive {
+            libc::LOCK_EX
+        } else {
+            libc::LOCK_SH
+        };
+        flock(&file, operation)?;
+        Ok(FileLock { _file: file })
+    }
+
+    /// Takes an exclusive lock on the file, or returns `None` if it is locked.
+    fn try_exclusive(path: &Path) -> io::Result<Option<Self>> {
+        let file = Self::open(path)?;
+        match flock(&file, libc::LOCK_EX | libc::LOCK_NB) {
+            Ok(()) => Ok(Some(FileLock { _file: file })),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+fn flock(file: &File, operation: libc::c_int) -> io::Result<()> {
+    // SAFETY: The file descriptor is owned by `file` and stays open for the call.
+    if unsafe { libc::flock(file.as_raw_fd(), operation) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Where versions of forc are installed, and how many of them are kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolchainConfig {
+    /// The directory that versions are installed in.
+    pub root: PathBuf,
+    /// The number of versions that are kept before the least recently used are evicted.
+    pub max_versions: usize,
+    /// The total size of the installed versions, in bytes, before the least recently
+    /// used are evicted.
+    pub max_bytes: Option<u64>,
+    /// The versions that are installed when the server starts.
+    pub prewarm: Prewarm,
+}
+
+/// The versions of forc that are installed when the server starts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Prewarm {
+    /// The given versions.
+    Versions(Vec<String>),
+    /// The given number of the latest releases of forc.
+    Latest(usize),
+    /// No versions.
+    #[default]
+    None,
+}
+
+impl Prewarm {
+    /// Parses `latest:<count>`, or a comma-separated list of versions, skipping invalid
+    /// versions.
+    pub fn parse(prewarm: &str) -> Self {
+        let prewarm = prewarm.trim();
+        match prewarm.strip_prefix(LATEST_PREFIX) {
+            Some(count) => match count.trim().parse() {
+                Ok(0) => Self::None,
+                Ok(count) => Self::Latest(count),
+                Err(_) => {
+                    warn!("Skipping invalid forc prewarm list {}", prewarm);
+                    Self::None
+                }
+            },
+            None => match parse_versions(prewarm) {
+                versions if versions.is_empty() => Self::None,
+                versions => Self::Versions(versions),
+            },
+        }
+    }
+
+    /// Returns the versions to install, looking up the latest releases if needed.
+    async fn versions(&self) -> Result<Vec<String>, reqwest::Error> {
+        match self {
+            Self::Versions(versions) => Ok(versions.clone()),
+            Self::Latest(count) => latest_releases(*count).await,
+            Self::None => Ok(Vec::new()),
+        }
+    }
+}
+
+/// A release of forc, as listed by the GitHub API.
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    prerelease: bool,
+}
+
+/// Returns the given number of the latest releases of forc, newest first.
+async fn latest_releases(count: usize) -> Result<Vec<String>, reqwest::Error> {
+    let releases = reqwest::Client::new()
+        .get(SWAY_RELEASES_URL)
+        .header("Accept", "application/json")
+        .header("User-Agent", "Rust")
+        .query(&[("per_page", count.min(100))])
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<Vec<Release>>()
+        .await?;
+    Ok(newest_versions(releases, count))
+}
+
+/// Returns the given number of the newest published releases, newest first.
+fn newest_versions(releases: Vec<Release>, count: usize) -> Vec<String> {
+    let mut versions: Vec<Version> = releases
+        .into_iter()
+        .filter(|release| !release.draft && !release.prerelease)
+        .filter_map(|release| Version::parse(release.tag_name.trim_start_matches('v')).ok())
+        .collect();
+    versions.sort_unstable_by(|a, b| b.cmp(a));
+    versions.dedup();
+    versions
+        .into_iter()
+        .take(count)
+        .map(|version| version.to_string())
+        .collect()
+}
+
+impl Default for ToolchainConfig {
+    fn default() -> Self {
+        Self {
+            root: PathBuf::from("forc-toolchains"),
+            max_versions: 20,
+            max_bytes: None,
+            prewarm: Prewarm::None,
+        }
+    }
+}
+
+impl ToolchainConfig {
+    /// Returns the toolchain settings from the `FORC_TOOLCHAIN_*` variables. The cache
+    /// limits fall back to their defaults with a warning if they aren't numbers, so that a
+    /// typo only loosens the cache limits instead of keeping the server from starting.
+    pub fn from_env() -> Self {
+        load_env();
+        let default = Self::default();
+        Self {
+            root: env::var("FORC_TOOLCHAIN_DIR")
+                .ok()
+                .filter(|root| !root.is_empty())
+                .map_or(default.root, PathBuf::from),
+            max_versions: parse_var("FORC_TOOLCHAIN_MAX_VERSIONS").unwrap_or(default.max_versions),
+            max_bytes: parse_var::<u64>("FORC_TOOLCHAIN_MAX_MB").map(|mb| mb * 1024 * 1024),
+            prewarm: env::var("FORC_TOOLCHAIN_PREWARM")
+                .map(|prewarm| Prewarm::parse(&prewarm))
+                .unwrap_or(default.prewarm),
+        }
+    }
+
+    /// Returns true if the installed versions must be evicted until they are within limits.
+    fn exceeded_by(&self, versions: usize, bytes: u64) -> bool {
+        versions > self.max_versions || self.max_bytes.is_some_and(|max| bytes > max)
+    }
+}
+
+/// Parses the number in the environment variable `name`, warning if it is set to something
+/// else.
+fn parse_var<T: FromStr>(name: &str) -> Option<T> {
+    let value = env::var(name).ok().filter(|value| !value.is_empty())?;
+    let parsed = value.parse().ok();
+    if parsed.is_none() {
+        warn!("Ignoring {} = '{}', which isn't a number", name, value);
+    }
+    parsed
+}
+
+/// Parses a comma-separated list of versions, skipping invalid versions.
+fn parse_versions(versions: &str) -> Vec<String> {
+    versions
+        .split(',')
+        .map(str::trim)
+        .filter(|version| !version.is_empty())
+        .filter_map(|version| match parse_version(version) {
+            Ok(version) => Some(version),
+            Err(_) => {
+                warn!("Skipping invalid forc version {}", version);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Returns the canonical form of a forc version, which is safe to use in paths.
+fn parse_version(version: &str) -> Result<String, UploadError> {
+    Version::parse(version.trim_start_matches('v'))
+        .map(|version| version.to_string())
+        .map_err(|_| UploadError::InvalidForcVersion(version.to_string()))
+}
+
+/// A version of forc that is installed, and when it was last used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstalledToolchain {
+    pub version: String,
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    pub last_used: DateTime<Utc>,
+}
+
+/// An installed version of forc. It can't be evicted until this is dropped.
+pub struct Toolchain {
+    version: String,
+    path: PathBuf,
+    _lock: FileLock,
+}
+
+impl Toolchain {
+    /// Returns the canonical form of the version.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Returns the directory that the version is installed in, with the binaries in `bin`.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl fmt::Debug for Toolchain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Toolchain")
+            .field("version", &self.version)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+/// Installs versions of forc in a shared root, and evicts the least recently used.
+///
+/// Every version has a lock file. Installs take the lock exclusively, so concurrent
+/// uploads for the same version wait for a single install. A version is installed in a
+/// staging directory and then renamed into place, so a version directory is always
+/// complete. Users of a version hold a shared lock, and versions are only evicted when
+/// their lock can be taken exclusively.
+#[derive(Debug, Clone)]
+pub struct ToolchainManager {
+    config: ToolchainConfig,
+    installer: Installer,
+}
+
+impl ToolchainManager {
+    pub fn new(config: ToolchainConfig) -> Self {
+        Self::with_installer(config, install_binaries_at_path)
+    }
+
+    /// Creates a manager that installs versions with the given installer.
+    pub fn with_installer(config: ToolchainConfig, installer: Installer) -> Self {
+        Self { config, installer }
+    }
+
+    /// Returns the toolchain manager configured with the `FORC_TOOLCHAIN_*` variables.
+    pub fn from_env() -> Self {
+        Self::new(ToolchainConfig::from_env())
+    }
+
+    pub fn config(&self) -> &ToolchainConfig {
+        &self.config
+    }
+
+    fn toolchain_path(&self, version: &str) -> PathBuf {
+        self.config
+            .root
+            .join(format!("{TOOLCHAIN_DIR_PREFIX}{version}"))
+    }
+
+    fn staging_path(&self, version: &str) -> PathBuf {
+        self.config
+            .root
+            .join(STAGING_DIR)
+            .join(format!("{TOOLCHAIN_DIR_PREFIX}{version}"))
+    }
+
+    fn lock_path(&self, version: &str) -> PathBuf {
+        self.config
+            .root
+            .join(LOCKS_DIR)
+            .join(format!("{version}.lock"))
+    }
+
+    fn lock(&self, version: &str, exclusive: bool) -> Result<FileLock, UploadError> {
+        FileLock::acquire(&self.lock_path(version), exclusive).map_err(|err| {
+            error!("Failed to lock forc {}: {}", version, err);
+            UploadError::ToolchainLock(version.to_string())
+        })
+    }
+
+    fn is_installed(&self, version: &str) -> bool {
+        installed_at_path(version, &self.toolchain_path(version))
+    }
+
+    /// Installs the given version of forc if it isn't installed yet, and evicts the least
+    /// recently used versions if the installed versions exceed the limits.
+    pub fn install_blocking(&self, version: &str) -> Result<Toolchain, UploadError> {
+        let version = parse_version(version)?;
+        for dir in [STAGING_DIR, LOCKS_DIR] {
+            fs::create_dir_all(self.config.root.join(dir)).map_err(|_| UploadError::SaveFile)?;
+        }
+
+        let mut cached = true;
+        let lock = loop {
+            let lock = self.lock(&version, false)?;
+            if self.is_installed(&version) {
+                break lock;
+            }
+            drop(lock);
+
+            // The version may have been installed while waiting for the lock. It may also
+            // be evicted after the lock is released, in which case it is installed again.
+            let lock = self.lock(&version, true)?;
+            if !self.is_installed(&version) {
+                cached = false;
+                self.install_locked(&version)?;
+            }
+            drop(lock);
+        };
+        metrics().record_forc_install(cached);
+
+        let path =
+            fs::canonicalize(self.toolchain_path(&version)).map_err(|_| UploadError::SaveFile)?;
+        if let Err(err) = fs::write(path.join(LAST_USED_FILE), Utc::now().to_rfc3339()) {
+            warn!("Failed to record use of forc {}: {}", version, err);
+        }
+        if let Err(err) = self.evict(&version) {
+            error!("Failed to evict forc toolchains: {}", err);
+        }
+        Ok(Toolchain {
+            version,
+            path,
+            _lock: lock,
+        })
+    }
+
+    /// Installs the given version of forc, see [ToolchainManager::install_blocking].
+    pub async fn install(&self, version: &str) -> Result<Toolchain, UploadError> {
+        let manager = self.clone();
+        let version = version.to_string();
+        let label = version.clone();
+        task::spawn_blocking(move || manager.install_blocking(&version))
+            .await
+            .map_err(|_| UploadError::ToolchainLock(label))?
+    }
+
+    /// Installs a version in the staging directory and moves it into place. The caller
+    /// must hold the exclusive lock of the version.
+    fn install_locked(&self, version: &str) -> Result<(), UploadError> {
+        info!("Installing forc {}", version);
+        let staging_path = self.staging_path(version);
+        // Staged files are left behind by installs that were interrupted.
+        let _ = fs::remove_dir_all(&staging_path);
+        fs::create_dir_all(&staging_path).map_err(|_| UploadError::SaveFile)?;
+
+        let result = {
+            let _timer = metrics().start_upload_stage(UploadStage::Install);
+            (self.installer)(version, &staging_path)
+        };
+        if let Err(err) = result {
+            let _ = fs::remove_dir_all(&staging_path);
+            return Err(err);
+        }
+        if !installed_at_path(version, &staging_path) {
+            let _ = fs::remove_dir_all(&staging_path);
+            return Err(UploadError::InvalidForcVersion(version.to_string()));
+        }
+
+        // A partial install from before installs were staged is replaced.
+        let toolchain_path = self.toolchain_path(version);
+        let _ = fs::remove_dir_all(&toolchain_path);
+        fs::rename(&staging_path, &toolchain_path).map_err(|_| UploadError::SaveFile)
+    }
+
+    /// Returns the installed versions of forc, most recently used first.
+    pub fn installed(&self) -> Result<Vec<InstalledToolchain>, UploadError> {
+        let entries = match fs::read_dir(&self.config.root) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(_) => return Err(UploadError::ReadFile),
+        };
+
+        let mut toolchains = Vec::new();
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            let Some(version) = file_name
+                .to_str()
+                .and_then(|name| name.strip_prefix(TOOLCHAIN_DIR_PREFIX))
+                .and_then(|version| parse_version(version).ok())
+            else {
+                continue;
+            };
+            let path = entry.path();
+            if !path.is_dir() {
+                continue;
+            }
+            let last_used = fs::read_to_string(path.join(LAST_USED_FILE))
+                .ok()
+                .and_then(|last_used| DateTime::parse_from_rfc3339(last_used.trim()).ok())
+                .map(|last_used| last_used.with_timezone(&Utc))
+                .unwrap_or_default();
+            toolchains.push(InstalledToolchain {
+                version,
+                size_bytes: dir_size(&path),
+                path,
+                last_used,
+            });
+        }
+        toolchains.sort_by(|a, b| b.last_used.cmp(&a.last_used));
+        Ok(toolchains)
+    }
+
+    /// Evicts the least recently used versions, other than `keep`, until the installed
+    /// versions are within the limits. Versions that are in use are skipped. Returns the
+    /// evicted versions.
+    pub fn evict(&self, keep: &str) -> Result<Vec<String>, UploadError> {
+        let toolchains = self.installed()?;
+        let mut versions = toolchains.len();
+        let mut bytes: u64 = toolchains
+            .iter()
+            .map(|toolchain| toolchain.size_bytes)
+            .sum();
+
+        let mut evicted = Vec::new();
+        for toolchain in toolchains.iter().rev() {
+            if !self.config.exceeded_by(versions, bytes) {
+                break;
+            }
+            if toolchain.version == keep {
+                continue;
+            }
+            let lock = FileLock::try_exclusive(&self.lock_path(&toolchain.version))
+                .map_err(|_| UploadError::ToolchainLock(toolchain.version.clone()))?;
+            if lock.is_none() {
+                continue;
+            }
+
+            info!("Evicting forc {}", toolchain.version);
+            // Moving the directory first means that it never appears partially removed.
+            let evicted_path = self.config.root.join(STAGING_DIR).join(format!(
+                "{TOOLCHAIN_DIR_PREFIX}{}.evicted",
+                toolchain.version
+            ));
+            let _ = fs::remove_dir_all(&evicted_path);
+            fs::rename(&toolchain.path, &evicted_path).map_err(|_| UploadError::SaveFile)?;
+            fs::remove_dir_all(&evicted_path).map_err(|_| UploadError::RemoveTempDir)?;
+
+            versions -= 1;
+            bytes = bytes.saturating_sub(toolchain.size_bytes);
+            evicted.push(toolchain.version.clone());
+        }
+        Ok(evicted)
+    }
+
+    /// Installs the versions in the prewarm list, so that the first uploads for them
+    /// don't wait for an install. Every version is attempted, and the error of the last one
+    /// that failed is returned.
+    pub async fn prewarm(self) -> Result<(), UploadError> {
+        let versions = self.config.prewarm.versions().await.map_err(|err| {
+            error!("Failed to look up the forc versions to prewarm: {}", err);
+            UploadError::ToolchainPrewarm(err.to_string())
+        })?;
+        let mut result = Ok(());
+        for version in &versions {
+            match self.install(version).await {
+                Ok(_) => info!("Prewarmed forc {}", version),
+                Err(err) => {
+                    error!("Failed to prewarm forc {}: {}", version, err);
+                    result = Err(err);
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Returns the total size of the files in a directory, in bytes.
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => dir_size(&entry.path()),
+            Ok(_) => entry.metadata().map(|metadata| metadata.len()).unwrap_or(0),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    /// The number of installs of the version that is installed concurrently.
+    static CONCURRENT_VERSION_INSTALLS: AtomicUsize = AtomicUsize::new(0);
+
+    /// Installs fake binaries of 1000 bytes each.
+    fn fake_installer(version: &str, path: &Path) -> Result<(), UploadError> {
+        if version == "0.0.1" {
+            return Err(UploadError::InvalidForcVersion(version.to_string()));
+        }
+        if version == "0.71.0" {
+            CONCURRENT_VERSION_INSTALLS.fetch_add(1, Ordering::SeqCst);
+        }
+        // Give concurrent installs a chance to race.
+        thread::sleep(std::time::Duration::from_millis(50));
+        let bin_dir = path.join("bin");
+        fs::create_dir_all(&bin_dir).unwrap();
+        fs::write(bin_dir.join("forc"), vec![0; 1000]).unwrap();
+        fs::write(bin_dir.join("forc-doc"), vec![0; 1000]).unwrap();
+        Ok(())
+    }
+
+    fn manager(root: &Path, max_versions: usize, max_bytes: Option<u64>) -> ToolchainManager {
+        ToolchainManager::with_installer(
+            ToolchainConfig {
+                root: root.to_path_buf(),
+                max_versions,
+                max_bytes,
+                prewarm: Prewarm::None,
+            },
+            fake_installer,
+        )
+    }
+
+    fn installed_versions(manager: &ToolchainManager) -> Vec<String> {
+        let mut versions: Vec<String> = manager
+            .installed()
+            .unwrap()
+            .into_iter()
+            .map(|toolchain| toolchain.version)
+            .collect();
+        versions.sort();
+        versions
+    }
+
+    #[test]
+    fn install_is_atomic_and_cached() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = manager(root.path(), 5, None);
+
+        let toolchain = manager.install_blocking("v0.70.1").unwrap();
+        assert!(toolchain.path().ends_with("forc-0.70.1"));
+        assert!(toolchain.path().join("bin/forc").exists());
+        assert!(!root.path().join(STAGING_DIR).join("forc-0.70.1").exists());
+        drop(toolchain);
+
+        // Failed installs leave nothing behind.
+        assert!(matches!(
+            manager.install_blocking("0.0.1"),
+            Err(UploadError::InvalidForcVersion(_))
+        ));
+        assert!(matches!(
+            manager.install_blocking("../0.70.1"),
+            Err(UploadError::InvalidForcVersion(_))
+        ));
+        assert_eq!(installed_versions(&manager), vec!["0.70.1"]);
+    }
+
+    #[test]
+    fn concurrent_installs_of_a_version_install_once() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = Arc::new(manager(root.path(), 5, None));
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let manager = manager.clone();
+                thread::spawn(move || manager.install_blocking("0.71.0").map(|_| ()))
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap().unwrap();
+        }
+        assert_eq!(CONCURRENT_VERSION_INSTALLS.load(Ordering::SeqCst), 1);
+        assert_eq!(installed_versions(&manager), vec!["0.71.0"]);
+    }
+
+    #[test]
+    fn evicts_least_recently_used_versions() {
+        let root = tempfile::tempdir().unwrap();
+        let manager = manager(root.path(), 2, None);
+
+        manager.install_blocking("0.70.0").unwrap();
+        thread::sleep(std::time::Duration::from_millis(10));
+        manager.install_blocking("0.70.1").unwrap();
+        thread::sleep(std::time::Duration::from_millis(10));
+        // Using a version makes it the most recently used.
+        let in_use = manager.install_blocking("0.70.0").unwrap();
+        thread::sleep(std::time::Duration::from_millis(10));
+
+        manager.install_blocking("0.70.2").unwrap();
+        assert_eq!(installed_versions(&manager), vec!["0.70.0", "0.70.2"]);
+        thread::sleep(std::time::Duration::from_millis(10));
+
+        // Versions that are in use aren't evicted.
+        manager.install_blocking("0.70.3").unwrap();
+        assert_eq!(installed_versions(&manager), vec!["0.70.0", "0.70.3"]);
+        drop(in_use);
+    }
+
+    #[test]
+    fn evicts_versions_over_size_limit() {
+        let root = tempfile::tempdir().unwrap();
+        // Each fake version is about 2000 bytes.
+        let manager = manager(root.path(), 10, Some(5000));
+
+        for version in ["0.70.0", "0.70.1", "0.70.2"] {
+            manager.install_blocking(version).unwrap();
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(installed_versions(&manager), vec!["0.70.1", "0.70.2"]);
+    }
+
+    #[test]
+    fn parse_versions_skips_invalid_versions() {
+        assert_eq!(
+            parse_versions("0.70.1, v0.69.0,,latest"),
+            vec!["0.70.1".to_string(), "0.69.0".to_string()]
+        );
+    }
+
+    #[test]
+    fn parse_prewarm_accepts_versions_and_latest_releases() {
+        assert_eq!(
+            Prewarm::parse("0.70.1, v0.69.0"),
+            Prewarm::Versions(vec!["0.70.1".to_string(), "0.69.0".to_string()])
+        );
+        assert_eq!(Prewarm::parse(" latest:20 "), Prewarm::Latest(20));
+        assert_eq!(Prewarm::parse("latest:0"), Prewarm::None);
+        assert_eq!(Prewarm::parse("latest:all"), Prewarm::None);
+        assert_eq!(Prewarm::parse(""), Prewarm::None);
+    }
+
+    #[test]
+    fn newest_versions_skips_drafts_prereleases_and_invalid_tags() {
+        let release = |tag_name: &str, draft, prerelease| Release {
+            tag_name: tag_name.to_string(),
+            draft,
+            prerelease,
+        };
+        let releases = vec![
+            release("v0.69.0", false, false),
+            release("v0.70.1", false, false),
+            release("v0.71.0", true, false),
+            release("v0.71.0-rc.1", false, true),
+            release("nightly", false, false),
+            release("v0.70.0", false, false),
+        ];
+        assert_eq!(newest_versions(releases, 2), vec!["0.70.1", "0.70.0"]);
+    }
+}
@@ -1,30 +1,36 @@
 use crate::file_uploader::FileUploader;
-use crate::file_uploader::{pinata::PinataClient, s3::S3Client};
+use crate::file_uploader::{s3::S3Client, storage::FileStorage};
+use crate::handlers::forc_mirror::ForcMirror;
+use crate::handlers::package_files::{create_package_tarball, package_files, published_manifest};
+use crate::handlers::sandbox::{Sandbox, SandboxError};
+use crate::handlers::tarball::{unpack_tarball, UnpackLimits};
+use crate::metrics::{metrics, UploadStage};
 use crate::models::NewUpload;
-use flate2::{
-    Compression,
-    {read::GzDecoder, write::GzEncoder},
-};
+use flate2::{write::GzEncoder, Compression};
+use forc_pkg::manifest::{GenericManifestFile, WorkspaceManifestFile};
 use forc_util::bytecode::get_bytecode_id;
+use regex::Regex;
 use semver::Version;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt;
 use std::fs::{self, File};
 use std::path::{Path, PathBuf};
-use std::process::Command;
-use tar::Archive;
+use std::process::{Command, Output};
 use thiserror::Error;
+use tokio::task;
 use tracing::error;
 use uuid::Uuid;
 
 const UNPACKED_DIR: &str = "unpacked";
 const RELEASE_DIR: &str = "out/release";
-const PROJECT_DIR: &str = "project";
+const WORKSPACE_MEMBERS_DIR: &str = "members";
 const README_FILE: &str = "README.md";
 const FORC_MANIFEST_FILE: &str = "Forc.toml";
 const MAX_UPLOAD_SIZE_STR: &str = "10MB";
 pub const TARBALL_NAME: &str = "project.tgz";
-#[derive(Error, Debug, PartialEq, Eq, Serialize)]
+const MAX_DIAGNOSTICS_LEN: usize = 64 * 1024;
+#[derive(Error, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum UploadError {
     #[error("Failed to create temporary directory.")]
     CreateTempDir,
@@ -54,7 +60,13 @@ pub enum UploadError {
     InvalidForcVersion(String),
 
     #[error("Failed to compile project.")]
-    FailedToCompile,
+    FailedToCompile(String),
+
+    #[error("Failed to run the build sandbox: {0}")]
+    Sandbox(String),
+
+    #[error("The upload failed for an unknown reason.")]
+    Unknown,
 
     #[error("Failed to generate documentation.")]
     FailedToGenerateDocumentation,
@@ -80,8 +92,115 @@ pub enum UploadError {
     #[error("Upload does not contain a Forc manifest.")]
     MissingForcManifest,
 
+    #[error("Invalid Forc manifest: {0}")]
+    InvalidForcManifest(String),
+
+    #[error("Invalid workspace manifest: {0}")]
+    InvalidWorkspaceManifest(String),
+
     #[error("Failed to fetch from IPFS. Err: {0}")]
     IpfsFetchFailed(String),
+
+    #[error("Storage backend '{0}' not supported.")]
+    UnsupportedStorageBackend(String),
+
+    #[error("The upload was interrupted too many times.")]
+    Interrupted,
+
+    #[error("The build exceeded the {0}.")]
+    ResourceLimitExceeded(String),
+
+    #[error("Sandbox '{0}' not supported.")]
+    UnsupportedSandbox(String),
+
+    #[error("Invalid build sandbox limit: {0}")]
+    InvalidSandboxLimit(String),
+
+    #[error("The project contains more than {0} files.")]
+    TooManyEntries(usize),
+
+    #[error("The unpacked project exceeds the maximum size of {0} bytes.")]
+    UnpackedTooLarge(u64),
+
+    #[error("The project exceeds the maximum compression ratio of {0}.")]
+    CompressionRatioTooHigh(u64),
+
+    #[error("File '{0}' exceeds the maximum file size of {1} bytes.")]
+    FileTooLarge(String, u64),
+
+    #[error("Invalid path in tarball: '{0}'.")]
+    InvalidTarballPath(String),
+
+    #[error("Link '{0}' points outside the project.")]
+    LinkOutsideProject(String),
+
+    #[error("Unsupported file type in tarball: '{0}'.")]
+    UnsupportedTarballEntry(String),
+
+    #[error("Invalid file pattern in Forc.toml: {0}")]
+    InvalidFilePattern(String),
+
+    #[error("Failed to lock forc {0}.")]
+    ToolchainLock(String),
+
+    #[error("Failed to look up the forc versions to prewarm: {0}")]
+    ToolchainPrewarm(String),
+
+    #[error("Failed to install forc from the mirror: {0}")]
+    ForcMirror(String),
+
+    #[error("The checksum of '{0}' doesn't match.")]
+    ChecksumMismatch(String),
+}
+
+impl UploadError {
+    /// Returns true if the error may be transient, so the upload is worth retrying.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            UploadError::CreateTempDir
+                | UploadError::RemoveTempDir
+                | UploadError::SaveFile
+                | UploadError::CopyFiles
+                | UploadError::Sandbox(_)
+                | UploadError::IpfsUploadFailed(_)
+                | UploadError::S3UploadFailed(_)
+                | UploadError::IpfsFetchFailed(_)
+                | UploadError::ToolchainLock(_)
+                | UploadError::ForcMirror(_)
+        )
+    }
+
+    /// Returns the compiler diagnostics of a failed build.
+    pub fn diagnostics(&self) -> Option<&str> {
+        match self {
+            UploadError::FailedToCompile(diagnostics) => Some(diagnostics),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the output of a failed build with the terminal colors removed, truncated to
+/// [MAX_DIAGNOSTICS_LEN] bytes.
+fn build_diagnostics(output: &Output) -> String {
+    let ansi_escape = Regex::new(r"\x1b\[[0-9;]*[A-Za-z]").expect("valid regex");
+    let output = [&output.stdout, &output.stderr]
+        .into_iter()
+        .map(|out| String::from_utf8_lossy(out).trim_end().to_string())
+        .filter(|out| !out.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut diagnostics = ansi_escape.replace_all(&output, "").into_owned();
+
+    if diagnostics.len() > MAX_DIAGNOSTICS_LEN {
+        let mut end = MAX_DIAGNOSTICS_LEN;
+        while !diagnostics.is_char_boundary(end) {
+            end -= 1;
+        }
+        diagnostics.truncate(end);
+        diagnostics.push_str("\n... (truncated)");
+    }
+    diagnostics
 }
 
 /// Generates documentation for a Sway project and uploads it to IPFS.
@@ -89,7 +208,8 @@ pub enum UploadError {
 async fn generate_and_upload_documentation(
     unpacked_dir: &Path,
     forc_path: &Path,
-    file_uploader: &FileUploader<'_, impl PinataClient, impl S3Client>,
+    sandbox: &Sandbox,
+    file_uploader: &FileUploader<'_, impl FileStorage, impl S3Client>,
 ) -> Result<String, UploadError> {
     let forc_doc_bin_path = forc_path.join("bin/forc-doc");
     if !forc_doc_bin_path.exists() {
@@ -100,23 +220,52 @@ async fn generate_and_upload_documentation(
         return Err(UploadError::FailedToGenerateDocumentation);
     }
 
+    let docs_tarball_path = blocking({
+        let unpacked_dir = unpacked_dir.to_path_buf();
+        let sandbox = sandbox.clone();
+        move || generate_documentation(&unpacked_dir, &forc_doc_bin_path, &sandbox)
+    })
+    .await?;
+
+    tracing::info!(
+        "Uploading documentation: {}",
+        docs_tarball_path.to_string_lossy()
+    );
+    let docs_ipfs_hash = file_uploader.upload_file(&docs_tarball_path).await?;
+
+    // Clean up temporary docs tarball after successful upload
+    if let Err(e) = tokio::fs::remove_file(&docs_tarball_path).await {
+        tracing::warn!(
+            "Failed to cleanup docs tarball at {}: {}",
+            docs_tarball_path.display(),
+            e
+        );
+    }
+
+    Ok(docs_ipfs_hash)
+}
+
+/// Generates the documentation of the package in `unpacked_dir` with forc-doc, and packs
+/// it into a tarball. Returns the path of the tarball.
+fn generate_documentation(
+    unpacked_dir: &Path,
+    forc_doc_bin_path: &Path,
+    sandbox: &Sandbox,
+) -> Result<PathBuf, UploadError> {
     // Generate documentation
     tracing::info!(
         "Generating documentation using forc-doc binary at {}",
         forc_doc_bin_path.display()
     );
-    let output = Command::new(&forc_doc_bin_path)
-        .args(["--path", unpacked_dir.to_str().unwrap()])
-        .current_dir(unpacked_dir)
-        .output()
-        .map_err(|err| {
-            tracing::error!(
-                "Failed to spawn forc-doc command at {}: {:?}",
-                forc_doc_bin_path.display(),
-                err
-            );
-            UploadError::FailedToGenerateDocumentation
-        })?;
+    let doc_timer = metrics().start_upload_stage(UploadStage::Doc);
+    let output = sandbox
+        .run(
+            forc_doc_bin_path,
+            ["--path", unpacked_dir.to_str().unwrap()],
+            unpacked_dir,
+        )
+        .map_err(sandbox_error)?;
+    drop(doc_timer);
 
     tracing::info!("forc-doc completed with status: {}", output.status);
 
@@ -134,7 +283,7 @@ async fn generate_and_upload_documentation(
         return Err(UploadError::FailedToGenerateDocumentation);
     }
 
-    // Upload documentation
+    // Pack documentation
     let doc_dir = unpacked_dir.join("out/doc");
     if !doc_dir.exists() {
         tracing::warn!(
@@ -150,23 +299,7 @@ async fn generate_and_upload_documentation(
         docs_tarball_path.display()
     );
     create_docs_tarball(&doc_dir, &docs_tarball_path)?;
-
-    tracing::info!(
-        "Uploading documentation: {}",
-        docs_tarball_path.to_string_lossy()
-    );
-    let docs_ipfs_hash = file_uploader.upload_file(&docs_tarball_path).await?;
-
-    // Clean up temporary docs tarball after successful upload
-    if let Err(e) = fs::remove_file(&docs_tarball_path) {
-        tracing::warn!(
-            "Failed to cleanup docs tarball at {}: {}",
-            docs_tarball_path.display(),
-            e
-        );
-    }
-
-    Ok(docs_ipfs_hash)
+    Ok(docs_tarball_path)
 }
 
 /// Creates a compressed tarball of the documentation directory.
@@ -215,102 +348,244 @@ fn create_docs_tarball(docs_dir: &Path, output_path: &Path) -> Result<(), Upload
     Ok(())
 }
 
+/// Converts the error of a sandboxed command.
+/// Runs a step of the upload that blocks on the file system or a sandboxed command on the
+/// blocking thread pool, so that a build doesn't stall the async runtime.
+async fn blocking<T, F>(f: F) -> Result<T, UploadError>
+where
+    F: FnOnce() -> Result<T, UploadError> + Send + 'static,
+    T: Send + 'static,
+{
+    task::spawn_blocking(f).await.map_err(|err| {
+        error!("Blocking upload step failed: {}", err);
+        UploadError::Unknown
+    })?
+}
+
+fn sandbox_error(err: SandboxError) -> UploadError {
+    error!("Failed to run sandboxed command: {}", err);
+    match err {
+        SandboxError::LimitExceeded(limit) => UploadError::ResourceLimitExceeded(limit),
+        SandboxError::Spawn(..) | SandboxError::Wait(..) => UploadError::Sandbox(err.to_string()),
+    }
+}
+
+/// Returns the workspace member directories if the root of the unpacked project has a
+/// workspace manifest, or `None` if it has a package manifest.
+fn workspace_member_dirs(unpacked_dir: &Path) -> Result<Option<Vec<PathBuf>>, UploadError> {
+    let manifest_path = unpacked_dir.join(FORC_MANIFEST_FILE);
+    if !manifest_path.exists() {
+        return Err(UploadError::MissingForcManifest);
+    }
+
+    let manifest: toml::Table = fs::read_to_string(&manifest_path)
+        .map_err(|_| UploadError::ReadFile)?
+        .parse()
+        .map_err(|err: toml::de::Error| UploadError::InvalidForcManifest(err.to_string()))?;
+    let has_table = |name: &str| manifest.get(name).is_some_and(toml::Value::is_table);
+    match (has_table("project"), has_table("workspace")) {
+        (true, false) => return Ok(None),
+        (false, true) => {}
+        _ => {
+            return Err(UploadError::InvalidForcManifest(
+                "expected either a [project] or a [workspace] table".to_string(),
+            ))
+        }
+    }
+
+    let workspace = WorkspaceManifestFile::from_file(&manifest_path)
+        .map_err(|err| UploadError::InvalidWorkspaceManifest(err.to_string()))?;
+
+    // Workspace members must be inside the uploaded project.
+    let root_dir = workspace.dir().to_path_buf();
+    let member_dirs = workspace
+        .member_paths()
+        .map_err(|err| UploadError::InvalidWorkspaceManifest(err.to_string()))?
+        .map(|member_dir| {
+            member_dir
+                .canonicalize()
+                .ok()
+                .filter(|member_dir| member_dir.starts_with(&root_dir) && *member_dir != root_dir)
+                .ok_or_else(|| {
+                    UploadError::InvalidWorkspaceManifest(format!(
+                        "invalid workspace member {}",
+                        member_dir.display()
+                    ))
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if member_dirs.is_empty() {
+        return Err(UploadError::InvalidWorkspaceManifest(
+            "workspace has no members".to_string(),
+        ));
+    }
+    Ok(Some(member_dirs))
+}
+
+/// Returns the version of each workspace member that has one, by name.
+fn workspace_member_versions(
+    member_dirs: &[PathBuf],
+) -> Result<HashMap<String, String>, UploadError> {
+    let mut member_versions = HashMap::new();
+    for member_dir in member_dirs {
+        let manifest: toml::Table = fs::read_to_string(member_dir.join(FORC_MANIFEST_FILE))
+            .map_err(|_| UploadError::MissingForcManifest)?
+            .parse()
+            .map_err(|err: toml::de::Error| UploadError::InvalidForcManifest(err.to_string()))?;
+        let project = manifest.get("project").and_then(toml::Value::as_table);
+        let field = |key: &str| project?.get(key)?.as_str().map(str::to_string);
+        if let (Some(name), Some(version)) = (field("name"), field("version")) {
+            member_versions.insert(name, version);
+        }
+    }
+    Ok(member_versions)
+}
+
 /// Handles the project upload process by:
 /// 1. Unpacking the tarball, compiling the project
-/// 2. Copying the necessary files to a new directory
-/// 3. Storing the source code tarball and ABI file in IPFS
-/// 4. Generating and uploading documentation
+/// 2. Copying the necessary files of each package to a new directory
+/// 3. Storing the source code tarball and ABI file of each package in IPFS
+/// 4. Generating and uploading documentation for each package
 ///
-/// Returns a [NewUpload] with the necessary information to store in the database.
+/// If the project root is a workspace, every workspace member is uploaded as a separate
+/// package with its own upload ID, and `upload_id` identifies the workspace upload.
+///
+/// Returns a [NewUpload] for each package with the necessary information to store in the database.
 pub async fn handle_project_upload<'a>(
     upload_dir: &'a Path,
     upload_id: &Uuid,
-    orig_tarball_path: &PathBuf,
+    orig_tarball_path: &Path,
     forc_path: &Path,
     forc_version: String,
-    file_uploader: &FileUploader<'a, impl PinataClient, impl S3Client>,
-) -> Result<NewUpload, UploadError> {
+    file_uploader: &FileUploader<'a, impl FileStorage, impl S3Client>,
+) -> Result<Vec<NewUpload>, UploadError> {
     let unpacked_dir = upload_dir.join(UNPACKED_DIR);
-    let release_dir = unpacked_dir.join(RELEASE_DIR);
-    let project_dir = upload_dir.join(PROJECT_DIR);
 
+    // Unpacking and building the project block until forc exits, so they run on the
+    // blocking thread pool.
+    let (member_dirs, sandbox) = blocking({
+        let upload_dir = upload_dir.to_path_buf();
+        let unpacked_dir = unpacked_dir.clone();
+        let orig_tarball_path = orig_tarball_path.to_path_buf();
+        let forc_path = forc_path.to_path_buf();
+        move || build_project(&upload_dir, &unpacked_dir, &orig_tarball_path, &forc_path)
+    })
+    .await?;
+
+    // A package upload is stored under the upload ID. Workspace members are stored under
+    // new upload IDs that reference the workspace upload ID.
+    let Some(member_dirs) = member_dirs else {
+        let upload = upload_package(
+            &unpacked_dir,
+            upload_dir,
+            *upload_id,
+            None,
+            &HashMap::new(),
+            forc_path,
+            &sandbox,
+            forc_version,
+            file_uploader,
+        )
+        .await?;
+        return Ok(vec![upload]);
+    };
+
+    let member_versions = workspace_member_versions(&member_dirs)?;
+    let mut uploads = Vec::with_capacity(member_dirs.len());
+    for (i, member_dir) in member_dirs.iter().enumerate() {
+        let work_dir = upload_dir.join(WORKSPACE_MEMBERS_DIR).join(i.to_string());
+        tokio::fs::create_dir_all(&work_dir)
+            .await
+            .map_err(|_| UploadError::SaveFile)?;
+        let upload = upload_package(
+            member_dir,
+            &work_dir,
+            Uuid::new_v4(),
+            Some(*upload_id),
+            &member_versions,
+            forc_path,
+            &sandbox,
+            forc_version.clone(),
+            file_uploader,
+        )
+        .await?;
+        uploads.push(upload);
+    }
+    Ok(uploads)
+}
+
+/// Unpacks the project tarball into `unpacked_dir` and builds it with the forc binary at
+/// `forc_path`. Returns the workspace member directories, if any, and the sandbox that the
+/// documentation of each package is generated in.
+fn build_project(
+    upload_dir: &Path,
+    unpacked_dir: &Path,
+    orig_tarball_path: &Path,
+    forc_path: &Path,
+) -> Result<(Option<Vec<PathBuf>>, Sandbox), UploadError> {
     // Unpack the tarball.
     tracing::info!("Unpacking tarball: {}", orig_tarball_path.to_string_lossy());
-    let tarball = File::open(orig_tarball_path).map_err(|_| UploadError::OpenFile)?;
-    let decompressed = GzDecoder::new(tarball);
-    let mut archive = Archive::new(decompressed);
-    archive
-        .unpack(&unpacked_dir)
-        .map_err(|_| UploadError::OpenFile)?;
-
-    // Remove `out` directory if it exists.
+    unpack_tarball(orig_tarball_path, unpacked_dir, &UnpackLimits::default())?;
+
+    let member_dirs = workspace_member_dirs(unpacked_dir)?;
+
+    // Remove `out` directories if they exist.
     let _ = fs::remove_dir_all(unpacked_dir.join("out"));
+    for member_dir in member_dirs.iter().flatten() {
+        let _ = fs::remove_dir_all(member_dir.join("out"));
+    }
 
+    // The build and documentation of every package share the upload directory.
+    let sandbox = Sandbox::from_env(upload_dir)?.with_read_only_dir(forc_path);
     let forc_bin_path = forc_path.join("bin/forc");
     tracing::info!(
         "Executing forc build with binary: {}",
         forc_bin_path.display()
     );
-    let output = Command::new(&forc_bin_path)
-        .arg("build")
-        .arg("--release")
-        .current_dir(&unpacked_dir)
-        .output()
-        .map_err(|err| {
-            error!("Failed to execute forc build: {:?}", err);
-            UploadError::FailedToCompile
-        })?;
-
-    if !output.status.success() {
-        return Err(UploadError::FailedToCompile);
-    }
-
-    // Copy files that are part of the Sway project to a new directory.
-    tracing::info!(
-        "Copying files to project directory: {}",
-        project_dir.to_string_lossy()
-    );
-    let output = Command::new("rsync")
-        .args([
-            "-av",
-            "--prune-empty-dirs",
-            "--include=*/",
-            "--include=Forc.toml",
-            "--include=Forc.lock",
-            "--include=README.md",
-            "--include=*.sw",
-            "--exclude=*",
-            "unpacked/",
-            "project",
-        ])
-        .current_dir(upload_dir)
-        .output()
-        .map_err(|err| {
-            error!("Failed to copy project files: {:?}", err);
-            UploadError::CopyFiles
-        })?;
+    let build_timer = metrics().start_upload_stage(UploadStage::Build);
+    let output = sandbox
+        .run(&forc_bin_path, ["build", "--release"], unpacked_dir)
+        .map_err(sandbox_error)?;
+    drop(build_timer);
 
     if !output.status.success() {
-        return Err(UploadError::CopyFiles);
+        return Err(UploadError::FailedToCompile(build_diagnostics(&output)));
     }
+    Ok((member_dirs, sandbox))
+}
 
-    // Pack the new tarball.
-    tracing::info!("Packing tarball: {}", upload_dir.to_string_lossy());
-    let final_tarball_path = upload_dir.join(TARBALL_NAME);
-    let tar_gz = File::create(&final_tarball_path).map_err(|_| UploadError::OpenFile)?;
-    let enc = GzEncoder::new(tar_gz, Compression::default());
-    let mut tar = tar::Builder::new(enc);
-
-    // Add files to the tar archive
-    tar.append_dir_all(".", &project_dir)
-        .map_err(|_| UploadError::CopyFiles)?;
-
-    // Finish writing the tar archive
-    tar.finish().map_err(|_| UploadError::CopyFiles)?;
-
-    // Make sure the GzEncoder finishes and flushes all data
-    let enc = tar.into_inner().map_err(|_| UploadError::CopyFiles)?;
-    enc.finish().map_err(|_| UploadError::CopyFiles)?;
+/// Uploads a single compiled package by:
+/// 1. Packing the files that are part of the package into a tarball in `work_dir`, with
+///    path dependencies on the other workspace members in `member_versions` replaced by
+///    version dependencies
+/// 2. Storing the source code tarball and ABI file in IPFS
+/// 3. Generating and uploading documentation
+///
+/// Returns a [NewUpload] with the necessary information to store in the database.
+#[allow(clippy::too_many_arguments)]
+async fn upload_package<'a>(
+    package_dir: &Path,
+    work_dir: &Path,
+    upload_id: Uuid,
+    workspace_upload_id: Option<Uuid>,
+    member_versions: &HashMap<String, String>,
+    forc_path: &Path,
+    sandbox: &Sandbox,
+    forc_version: String,
+    file_uploader: &FileUploader<'a, impl FileStorage, impl S3Client>,
+) -> Result<NewUpload, UploadError> {
+    let release_dir = package_dir.join(RELEASE_DIR);
+
+    // Pack the files that are part of the Sway project into a new tarball.
+    let final_tarball_path = work_dir.join(TARBALL_NAME);
+    let (files, forc_manifest) = blocking({
+        let package_dir = package_dir.to_path_buf();
+        let member_versions = member_versions.clone();
+        let final_tarball_path = final_tarball_path.clone();
+        move || pack_package(&package_dir, &member_versions, &final_tarball_path)
+    })
+    .await?;
 
     // Store the tarball.
     tracing::info!(
@@ -351,26 +626,34 @@ pub async fn handle_project_upload<'a>(
     // Generate the bytecode identifier and store in the database along with the ABI hash.
     let bytecode_identifier = match find_file_in_dir_by_suffix(&release_dir, ".bin") {
         Some(bin_path) => Some(
-            get_bytecode_id(&bin_path).map_err(|err| UploadError::BytecodeId(err.to_string()))?,
+            blocking(move || {
+                get_bytecode_id(&bin_path).map_err(|err| UploadError::BytecodeId(err.to_string()))
+            })
+            .await?,
         ),
         None => None,
     };
 
     // Generate and upload documentation
-    let docs_ipfs_hash = generate_and_upload_documentation(&unpacked_dir, forc_path, file_uploader)
-        .await
-        .map_err(|e| {
-            tracing::warn!("Documentation generation failed: {}", e);
-        })
-        .ok();
-
-    // Load the contents of readme and Forc.toml into memory for storage in the database.
-    let readme = fs::read_to_string(project_dir.join(README_FILE)).ok();
-    let forc_manifest = fs::read_to_string(project_dir.join(FORC_MANIFEST_FILE))
-        .map_err(|_| UploadError::MissingForcManifest)?;
+    let docs_ipfs_hash =
+        generate_and_upload_documentation(package_dir, forc_path, sandbox, file_uploader)
+            .await
+            .map_err(|e| {
+                tracing::warn!("Documentation generation failed: {}", e);
+            })
+            .ok();
+
+    // Load the contents of readme into memory for storage in the database, along with the
+    // published Forc.toml.
+    let readme = match files.contains(&PathBuf::from(README_FILE)) {
+        true => tokio::fs::read_to_string(package_dir.join(README_FILE))
+            .await
+            .ok(),
+        false => None,
+    };
 
     let upload = NewUpload {
-        id: *upload_id,
+        id: upload_id,
         source_code_ipfs_hash: tarball_ipfs_hash,
         forc_version,
         abi_ipfs_hash,
@@ -378,12 +661,35 @@ pub async fn handle_project_upload<'a>(
         readme,
         forc_manifest,
         docs_ipfs_hash,
+        workspace_upload_id,
     };
 
     Ok(upload)
 }
 
-/// Installs the given version of forc and forc-doc at the specific root path using cargo-binstall.
+/// Packs the files of the package in `package_dir` into a tarball at `tarball_path`.
+/// Returns the packed files and the published Forc.toml, in which path dependencies on
+/// the workspace members in `member_versions` are replaced by version dependencies.
+fn pack_package(
+    package_dir: &Path,
+    member_versions: &HashMap<String, String>,
+    tarball_path: &Path,
+) -> Result<(Vec<PathBuf>, String), UploadError> {
+    let files = package_files(package_dir)?;
+    let forc_manifest = fs::read_to_string(package_dir.join(FORC_MANIFEST_FILE))
+        .map_err(|_| UploadError::MissingForcManifest)?;
+    let forc_manifest = published_manifest(&forc_manifest, member_versions)?;
+    tracing::info!(
+        "Packing {} files into tarball: {}",
+        files.len(),
+        tarball_path.to_string_lossy()
+    );
+    create_package_tarball(package_dir, &files, &forc_manifest, tarball_path)?;
+    Ok((files, forc_manifest))
+}
+
+/// Installs the given version of forc and forc-doc at the specific root path, from the
+/// [ForcMirror] if one is configured, or from GitHub using cargo-binstall.
 pub fn install_binaries_at_path(forc_version: &str, forc_path: &Path) -> Result<(), UploadError> {
     let os = match std::env::consts::OS {
         "linux" => "linux",
@@ -401,15 +707,26 @@ pub fn install_binaries_at_path(forc_version: &str, forc_path: &Path) -> Result<
     };
 
     let components = components_for_version(forc_version);
+    let missing = missing_components(forc_path, &components);
+    if missing.is_empty() {
+        return Ok(());
+    }
 
-    for component in missing_components(forc_path, &components) {
-        install_component(forc_version, forc_path, os, arch, component)?;
-
-        if !forc_path.join("bin").join(component.binary_name()).exists() {
-            return Err(UploadError::InvalidForcVersion(forc_version.to_string()));
+    match ForcMirror::from_env()? {
+        Some(mirror) => {
+            let binaries: Vec<&str> = missing.iter().map(|c| c.binary_name()).collect();
+            mirror.install(forc_version, forc_path, os, arch, &binaries)?;
+        }
+        None => {
+            for component in missing.iter().copied() {
+                install_component(forc_version, forc_path, os, arch, component)?;
+            }
         }
     }
 
+    if !missing_components(forc_path, &components).is_empty() {
+        return Err(UploadError::InvalidForcVersion(forc_version.to_string()));
+    }
     Ok(())
 }
 
@@ -496,6 +813,11 @@ fn install_component(
     }
 }
 
+/// Returns true if all binaries of the given version of forc are installed at the path.
+pub(crate) fn installed_at_path(forc_version: &str, forc_path: &Path) -> bool {
+    missing_components(forc_path, &components_for_version(forc_version)).is_empty()
+}
+
 fn missing_components(forc_path: &Path, components: &[Component]) -> Vec<Component> {
     components
         .iter()
@@ -581,7 +903,10 @@ mod tests {
         .await
         .expect("result ok");
 
+        assert_eq!(result.len(), 1);
+        let result = result.into_iter().next().unwrap();
         assert_eq!(result.id, upload_id);
+        assert_eq!(result.workspace_upload_id, None);
         assert_eq!(result.source_code_ipfs_hash, "ABC123".to_string());
         assert_eq!(result.abi_ipfs_hash, Some("ABC123".to_string()));
         assert_eq!(result.forc_version, forc_version);
@@ -590,4 +915,149 @@ mod tests {
             "009683afb9a422c3d23aeafce43e3a8e29099d8d64d55c63cf8179af3f8112de"
         );
     }
+
+    fn write_package_manifest(dir: &Path, name: &str) {
+        fs::create_dir_all(dir.join("src")).expect("create dir ok");
+        fs::write(dir.join("src/lib.sw"), "library;\n").expect("write source ok");
+        fs::write(
+            dir.join(FORC_MANIFEST_FILE),
+            format!(
+                "[project]\nauthors = [\"Fuel Labs\"]\nentry = \"lib.sw\"\nlicense = \"Apache-2.0\"\nname = \"{name}\"\n"
+            ),
+        )
+        .expect("write manifest ok");
+    }
+
+    #[test]
+    fn sandbox_error_distinguishes_sandbox_failures_from_limits() {
+        let spawn_err = SandboxError::Spawn("forc".into(), std::io::Error::other("no bwrap"));
+        assert_eq!(
+            sandbox_error(spawn_err),
+            UploadError::Sandbox("Failed to spawn forc: no bwrap".into())
+        );
+        assert_eq!(
+            sandbox_error(SandboxError::LimitExceeded("CPU time limit of 1s".into())),
+            UploadError::ResourceLimitExceeded("CPU time limit of 1s".into())
+        );
+        assert_eq!(
+            UploadError::FailedToCompile("error: expected `;`".into()).diagnostics(),
+            Some("error: expected `;`")
+        );
+    }
+
+    #[test]
+    fn workspace_member_dirs_returns_none_for_package() {
+        let dir = tempfile::tempdir().expect("tempdir ok");
+        write_package_manifest(dir.path(), "pkg");
+
+        assert_eq!(workspace_member_dirs(dir.path()), Ok(None));
+    }
+
+    #[test]
+    fn workspace_member_dirs_returns_members() {
+        let dir = tempfile::tempdir().expect("tempdir ok");
+        fs::write(
+            dir.path().join(FORC_MANIFEST_FILE),
+            "[workspace]\nmembers = [\"lib_a\", \"lib_b\"]\n",
+        )
+        .expect("write manifest ok");
+        write_package_manifest(&dir.path().join("lib_a"), "lib_a");
+        write_package_manifest(&dir.path().join("lib_b"), "lib_b");
+
+        let root = dir.path().canonicalize().unwrap();
+        assert_eq!(
+            workspace_member_dirs(dir.path()),
+            Ok(Some(vec![root.join("lib_a"), root.join("lib_b")]))
+        );
+    }
+
+    #[test]
+    fn workspace_member_versions_returns_versioned_members() {
+        let dir = tempfile::tempdir().expect("tempdir ok");
+        let versioned = dir.path().join("lib_a");
+        write_package_manifest(&versioned, "lib_a");
+        let manifest = fs::read_to_string(versioned.join(FORC_MANIFEST_FILE)).unwrap();
+        fs::write(
+            versioned.join(FORC_MANIFEST_FILE),
+            format!("{manifest}version = \"0.1.0\"\n"),
+        )
+        .expect("write manifest ok");
+        let unversioned = dir.path().join("lib_b");
+        write_package_manifest(&unversioned, "lib_b");
+
+        assert_eq!(
+            workspace_member_versions(&[versioned, unversioned]),
+            Ok(HashMap::from([("lib_a".to_string(), "0.1.0".to_string())]))
+        );
+    }
+
+    #[test]
+    fn workspace_member_dirs_rejects_members_outside_project() {
+        let dir = tempfile::tempdir().expect("tempdir ok");
+        let project_dir = dir.path().join("project");
+        fs::create_dir_all(&project_dir).expect("create dir ok");
+        fs::write(
+            project_dir.join(FORC_MANIFEST_FILE),
+            "[workspace]\nmembers = [\"../outside\"]\n",
+        )
+        .expect("write manifest ok");
+        write_package_manifest(&dir.path().join("outside"), "outside");
+
+        assert!(matches!(
+            workspace_member_dirs(&project_dir),
+            Err(UploadError::InvalidWorkspaceManifest(_))
+        ));
+    }
+
+    #[test]
+    fn workspace_member_dirs_rejects_manifests_without_project_or_workspace() {
+        let dir = tempfile::tempdir().expect("tempdir ok");
+        for manifest in ["[dependencies]\n", "[project\n", "project = \"pkg\"\n"] {
+            fs::write(dir.path().join(FORC_MANIFEST_FILE), manifest).expect("write manifest ok");
+
+            assert!(matches!(
+                workspace_member_dirs(dir.path()),
+                Err(UploadError::InvalidForcManifest(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn workspace_member_dirs_requires_manifest() {
+        let dir = tempfile::tempdir().expect("tempdir ok");
+
+        assert_eq!(
+            workspace_member_dirs(dir.path()),
+            Err(UploadError::MissingForcManifest)
+        );
+    }
+
+    #[test]
+    fn build_diagnostics_removes_colors() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let output = Output {
+            status: std::process::ExitStatus::from_raw(1 << 8),
+            stdout: b"\x1b[1;32m   Compiling\x1b[0m library std\n".to_vec(),
+            stderr: b"\x1b[1;31merror\x1b[0m: expected `;`\n".to_vec(),
+        };
+
+        assert_eq!(
+            build_diagnostics(&output),
+            "   Compiling library std\nerror: expected `;`"
+        );
+    }
+
+    #[test]
+    fn build_diagnostics_truncates_long_output() {
+        let output = Output {
+            status: std::os::unix::process::ExitStatusExt::from_raw(1 << 8),
+            stdout: "é".repeat(MAX_DIAGNOSTICS_LEN).into_bytes(),
+            stderr: vec![],
+        };
+
+        let diagnostics = build_diagnostics(&output);
+        assert!(diagnostics.len() <= MAX_DIAGNOSTICS_LEN + "\n... (truncated)".len());
+        assert!(diagnostics.ends_with("\n... (truncated)"));
+    }
 }
@@ -0,0 +1,232 @@
+use crate::db::Database;
+use crate::file_uploader::dry_run::DryRunClient;
+use crate::file_uploader::s3::{S3Client, S3ClientImpl};
+use crate::file_uploader::{
+    storage::{FileStorage, StorageClient},
+    FileUploader,
+};
+use crate::handlers::toolchain::ToolchainManager;
+use crate::handlers::upload::{handle_project_upload, UploadError};
+use crate::models::{NewUpload, UploadJob};
+use chrono::{Duration, Utc};
+use tempfile::tempdir;
+use tokio::fs;
+use tokio::task::JoinHandle;
+use tokio::time;
+use tracing::{error, info};
+
+/// How long a running job can go without renewing its lease before it is assumed that
+/// its worker died, and the job is claimed by another worker.
+pub const UPLOAD_JOB_LEASE_MINUTES: i64 = 30;
+
+/// How often a worker renews the lease of the job it is running.
+const LEASE_RENEWAL_INTERVAL_SECS: u64 = 60;
+
+/// How long an idle worker waits before checking for new jobs.
+const POLL_INTERVAL_SECS: u64 = 2;
+
+/// How long to wait before retrying a failed job, multiplied by the number of attempts.
+const RETRY_DELAY_SECS: i64 = 30;
+
+const ORIGINAL_TARBALL_NAME: &str = "original.tgz";
+
+impl UploadJob {
+    /// Returns the error of the last failed attempt of the job.
+    pub fn upload_error(&self) -> Option<UploadError> {
+        self.error
+            .as_deref()
+            .and_then(|error| serde_json::from_str(error).ok())
+    }
+
+    /// Returns the uploads that a succeeded dry-run job would have created.
+    pub fn dry_run_report(&self) -> Option<Vec<NewUpload>> {
+        self.report
+            .as_deref()
+            .and_then(|report| serde_json::from_str(report).ok())
+    }
+}
+
+/// Reports the progress of a job. Failing to report progress doesn't fail the job.
+async fn set_message(db: &Database, job: &UploadJob, message: &str) {
+    let (job_id, message) = (job.id, message.to_string());
+    let result = db
+        .transaction_blocking(move |conn| conn.set_upload_job_message(job_id, message))
+        .await;
+    if let Err(err) = result {
+        error!("Failed to update upload job {}: {}", job.id, err);
+    }
+}
+
+/// Renews the lease of a job until the returned task is aborted, so that a step that runs
+/// longer than the lease, like a slow build, doesn't get the job claimed by another worker.
+fn spawn_lease_renewal(db: &Database, job: &UploadJob) -> JoinHandle<()> {
+    let db = db.clone();
+    let job_id = job.id;
+    tokio::spawn(async move {
+        let mut interval = time::interval(time::Duration::from_secs(LEASE_RENEWAL_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            let result = db
+                .transaction_blocking(move |conn| conn.renew_upload_job_lease(job_id))
+                .await;
+            if let Err(err) = result {
+                error!("Failed to renew lease of upload job {}: {}", job_id, err);
+            }
+        }
+    })
+}
+
+/// Builds the project in the job's tarball and uploads it to the storage backends. Dry-run
+/// jobs don't upload anything.
+async fn run_upload_job(
+    db: &Database,
+    toolchains: &ToolchainManager,
+    job: &UploadJob,
+    file_uploader: &FileUploader<'_, impl FileStorage, impl S3Client>,
+) -> Result<Vec<NewUpload>, UploadError> {
+    let job_id = job.id;
+    let tarball = db
+        .transaction_blocking(move |conn| conn.get_upload_job_tarball(job_id))
+        .await
+        .map_err(|_| UploadError::OpenFile)?;
+
+    set_message(db, job, &format!("Installing forc {}", job.forc_version)).await;
+    // The toolchain can't be evicted until the job is done with it.
+    let toolchain = toolchains.install(&job.forc_version).await?;
+    let forc_path = toolchain.path();
+
+    set_message(db, job, "Preparing project for publishing").await;
+    let tmp_dir = tempdir().map_err(|_| UploadError::CreateTempDir)?;
+    let upload_dir = tmp_dir.path().join(job.id.to_string());
+    fs::create_dir(&upload_dir)
+        .await
+        .map_err(|_| UploadError::SaveFile)?;
+    let orig_tarball_path = upload_dir.join(ORIGINAL_TARBALL_NAME);
+    fs::write(&orig_tarball_path, tarball)
+        .await
+        .map_err(|_| UploadError::SaveFile)?;
+
+    // A dry run builds the project the same way, but only computes the IPFS CIDs of the
+    // files instead of storing them.
+    let upload_entries = if job.dry_run {
+        set_message(db, job, "Building project (dry run)").await;
+        let dry_run_uploader = FileUploader::new(&DryRunClient, &DryRunClient);
+        handle_project_upload(
+            &upload_dir,
+            &job.id,
+            &orig_tarball_path,
+            forc_path,
+            job.forc_version.clone(),
+            &dry_run_uploader,
+        )
+        .await?
+    } else {
+        set_message(db, job, "Uploading project to IPFS and S3").await;
+        handle_project_upload(
+            &upload_dir,
+            &job.id,
+            &orig_tarball_path,
+            forc_path,
+            job.forc_version.clone(),
+            file_uploader,
+        )
+        .await?
+    };
+
+    tmp_dir.close().map_err(|_| UploadError::RemoveTempDir)?;
+    Ok(upload_entries)
+}
+
+/// Runs a claimed upload job to completion, and records whether it succeeded. A
+/// succeeded dry-run job records the uploads it would have created in its report. Jobs that
+/// fail with an error that may be transient are retried with a backoff until they run
+/// out of attempts.
+pub async fn process_upload_job(
+    db: &Database,
+    toolchains: &ToolchainManager,
+    job: &UploadJob,
+    file_uploader: &FileUploader<'_, impl FileStorage, impl S3Client>,
+) {
+    info!(
+        "Processing upload job {} (attempt {})",
+        job.id, job.attempts
+    );
+
+    // A job is claimed again when its worker dies, so a job that keeps killing its
+    // worker eventually runs out of attempts.
+    let result = if job.attempts > job.max_attempts {
+        Err(UploadError::Interrupted)
+    } else {
+        let lease_renewal = spawn_lease_renewal(db, job);
+        let result = run_upload_job(db, toolchains, job, file_uploader).await;
+        lease_renewal.abort();
+        result
+    };
+
+    let (job_id, dry_run) = (job.id, job.dry_run);
+    let result = match result {
+        Ok(upload_entries) => {
+            info!("Upload job {} succeeded", job.id);
+            db.transaction_blocking(move |conn| {
+                if dry_run {
+                    conn.complete_dry_run_upload_job(job_id, &upload_entries)
+                } else {
+                    conn.complete_upload_job(job_id, &upload_entries)
+                }
+            })
+            .await
+        }
+        Err(err) => {
+            let retry_at = (err.is_retryable() && job.attempts < job.max_attempts).then(|| {
+                Utc::now() + Duration::seconds(RETRY_DELAY_SECS * i64::from(job.attempts))
+            });
+            error!(
+                "Upload job {} failed (retry at {:?}): {}",
+                job.id, retry_at, err
+            );
+            let error = serde_json::to_string(&err).unwrap_or_default();
+            let diagnostics = err.diagnostics().map(str::to_string);
+            db.transaction_blocking(move |conn| {
+                conn.fail_upload_job(job_id, error, diagnostics, retry_at)
+            })
+            .await
+        }
+    };
+    if let Err(err) = result {
+        error!("Failed to update upload job {}: {}", job.id, err);
+    }
+}
+
+/// Claims and processes upload jobs forever.
+pub async fn run_upload_worker(db: Database, toolchains: ToolchainManager) {
+    let storage_client = match StorageClient::new().await {
+        Ok(client) => client,
+        Err(err) => {
+            error!("Failed to start upload worker: {}", err);
+            return;
+        }
+    };
+    let s3_client = match S3ClientImpl::new().await {
+        Ok(client) => client,
+        Err(err) => {
+            error!("Failed to start upload worker: {}", err);
+            return;
+        }
+    };
+    let file_uploader = FileUploader::new(&storage_client, &s3_client);
+    let lease = Duration::minutes(UPLOAD_JOB_LEASE_MINUTES);
+
+    loop {
+        match db
+            .transaction_blocking(move |conn| conn.claim_upload_job(lease))
+            .await
+        {
+            Ok(Some(job)) => process_upload_job(&db, &toolchains, &job, &file_uploader).await,
+            Ok(None) => time::sleep(time::Duration::from_secs(POLL_INTERVAL_SECS)).await,
+            Err(err) => {
+                error!("Failed to claim upload job: {}", err);
+                time::sleep(time::Duration::from_secs(POLL_INTERVAL_SECS)).await;
+            }
+        }
+    }
+}
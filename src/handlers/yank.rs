@@ -0,0 +1,124 @@
+use std::env;
+
+use crate::db::api_token::TokenScope;
+use crate::db::audit_event::AuditEventKind;
+use crate::db::error::DatabaseError;
+use crate::db::Database;
+use crate::handlers::publish::{github_index_publisher, index_tmpdir};
+use crate::index::handler::{IndexPublishError, IndexPublisher};
+use crate::metrics::metrics;
+use crate::middleware::client_info::ClientInfo;
+use crate::models::{ApiToken, PackageVersion};
+use crate::util::load_env;
+use semver::Version;
+use serde::Serialize;
+use serde_json::json;
+use thiserror::Error;
+use tracing::info;
+
+#[derive(Error, Debug, Serialize)]
+pub enum YankError {
+    #[error("Invalid version: {0}")]
+    InvalidVersion(String),
+
+    #[error(transparent)]
+    #[serde(skip)]
+    Database(#[from] DatabaseError),
+
+    #[error(transparent)]
+    #[serde(skip)]
+    Diesel(#[from] diesel::result::Error),
+
+    #[error(transparent)]
+    Index(#[from] IndexPublishError),
+}
+
+/// Sets the `yanked` flag of an index entry in the remote index repo.
+pub(crate) async fn yank_index_entry(
+    package_name: String,
+    version: Version,
+    yanked: bool,
+) -> Result<(), IndexPublishError> {
+    let tmpdir = index_tmpdir()?;
+    let github_index_publisher = github_index_publisher(tmpdir.path())?;
+    github_index_publisher
+        .set_entry_yanked(package_name, version, yanked)
+        .await
+        .inspect_err(|err| metrics().record_index_publish_failure(err))?;
+    Ok(())
+}
+
+/// Handles yanking or un-yanking a package version by:
+/// 1. Checking that the token owner is an owner of the package, and the token has the
+///    `yank` scope for it and the version exists
+/// 2. Updating the `yanked` flag of the version's entry in the index
+/// 3. Storing the `yanked` state in the database, and recording it in the audit log
+///
+/// Yanking a version that is already yanked (or vice versa) is a no-op.
+///
+/// Returns the updated [PackageVersion] on success.
+pub async fn handle_yank(
+    db: &Database,
+    package_name: String,
+    version: String,
+    yanked: bool,
+    token: &ApiToken,
+    client: &ClientInfo,
+) -> Result<PackageVersion, YankError> {
+    let version =
+        Version::parse(&version).map_err(|_| YankError::InvalidVersion(version.clone()))?;
+    info!(
+        "Setting yanked={} for package {} version {}",
+        yanked, package_name, version
+    );
+
+    let package_version = db.transaction(|conn| {
+        let package = conn.get_package_by_name(package_name.clone())?;
+        if !conn.is_package_owner(package.id, token.user_id)? {
+            return Err(DatabaseError::InvalidPublishToken);
+        }
+        token.authorize(TokenScope::Yank, &package_name)?;
+        conn.get_package_version(package_name.clone(), version.to_string())
+    })?;
+
+    if package_version.yanked == yanked {
+        return Ok(package_version);
+    }
+
+    load_env();
+    let run_env = env::var("RUN_ENV").unwrap_or_default();
+
+    if run_env != "local" {
+        // Wait for the index to be updated first, so forc and the database
+        // never disagree about whether the version can be resolved.
+        yank_index_entry(package_name.clone(), version.clone(), yanked).await?;
+    }
+
+    let package_version = db.transaction(|conn| {
+        let package_version = conn.set_package_version_yanked(
+            token,
+            package_name.clone(),
+            version.to_string(),
+            yanked,
+        )?;
+        let event = if yanked {
+            AuditEventKind::Yank
+        } else {
+            AuditEventKind::Unyank
+        };
+        conn.record_audit_event(
+            token.user_id,
+            event,
+            client,
+            json!({ "name": package_name, "version": version, "tokenId": token.id }),
+        )?;
+        Ok::<_, DatabaseError>(package_version)
+    })?;
+
+    info!(
+        "Successfully set yanked={} for package {} version {}",
+        yanked, package_name, version
+    );
+
+    Ok(package_version)
+}
@@ -5,6 +5,7 @@ use forc_pkg::source::reg::{
     index_file::{IndexFile, PackageEntry},
 };
 use git2::{FetchOptions, PushOptions, RemoteCallbacks, Signature};
+use semver::Version;
 use std::{
     env, fs,
     path::Path,
@@ -49,7 +50,7 @@ impl GithubRepoBuilder {
         repo_owner: &str,
         repo_path: &Path,
     ) -> Result<Self, IndexPublishError> {
-        let repo_url = format!("git@github.com:{repo_owner}/{repo_name}.git");
+        let repo_url = repo_url(repo_name, repo_owner);
 
         // Configure callbacks for SSH authentication
         let callbacks = remote_callbacks();
@@ -68,6 +69,28 @@ impl GithubRepoBuilder {
     }
 }
 
+/// Returns the SSH URL of the GitHub repo.
+fn repo_url(repo_name: &str, repo_owner: &str) -> String {
+    format!("git@github.com:{repo_owner}/{repo_name}.git")
+}
+
+/// Checks that the GitHub repo can be fetched with the configured credentials, by listing
+/// its references without cloning it.
+pub fn check_repo_fetchable(repo_name: &str, repo_owner: &str) -> Result<(), IndexPublishError> {
+    let mut remote = git2::Remote::create_detached(repo_url(repo_name, repo_owner))?;
+    remote
+        .connect_auth(git2::Direction::Fetch, Some(remote_callbacks()), None)
+        .map_err(|e| IndexPublishError::FetchError(format!("Failed to connect to remote: {e}")))?;
+    let has_refs = !remote.list()?.is_empty();
+    remote.disconnect()?;
+    if !has_refs {
+        return Err(IndexPublishError::FetchError(
+            "Remote has no references".to_string(),
+        ));
+    }
+    Ok(())
+}
+
 impl GitRepoBuilder for GithubRepoBuilder {
     fn repo(&self) -> &git2::Repository {
         &self.repo
@@ -317,7 +340,12 @@ impl<T: GitRepoBuilder> GithubIndexPublisher<T> {
         }
     }
 
-    fn process_repo(&self, package_entry: &PackageEntry) -> Result<(), IndexPublishError> {
+    /// Syncs the index repo with the remote, applies `update` to the local
+    /// checkout and pushes the resulting commit.
+    fn process_repo<F>(&self, commit_message: &str, update: F) -> Result<(), IndexPublishError>
+    where
+        F: FnOnce(&Path) -> Result<(), IndexPublishError>,
+    {
         let repo_builder_guard = self
             .repo_builder
             .lock()
@@ -328,27 +356,22 @@ impl<T: GitRepoBuilder> GithubIndexPublisher<T> {
         let tmp_path = repo_builder.path()?;
         let branch_name = repo_builder.resolve_default_branch_name()?;
         repo_builder.update_and_checkout_default_branch(&branch_name)?;
-        self.write_package_entry(tmp_path, package_entry)?;
-
-        let commit_message = match &self.namespace {
-            Namespace::Flat => format!(
-                "Add package {} version {}",
-                package_entry.name(),
-                package_entry.version()
-            ),
-            Namespace::Domain(domain) => format!(
-                "Add package {}/{} version {}",
-                domain,
-                package_entry.name(),
-                package_entry.version()
-            ),
-        };
-
-        repo_builder.stage_and_commit_changes(&commit_message)?;
+        update(tmp_path)?;
+
+        repo_builder.stage_and_commit_changes(commit_message)?;
         repo_builder.push_changes(&branch_name)?;
 
         Ok(())
     }
+
+    /// Returns the package name qualified with the namespace, for use in commit messages.
+    fn qualified_name(&self, package_name: &str) -> String {
+        match &self.namespace {
+            Namespace::Flat => package_name.to_string(),
+            Namespace::Domain(domain) => format!("{domain}/{package_name}"),
+        }
+    }
+
     /// Write the package entry to the appropriate location in the repository
     fn write_package_entry(
         &self,
@@ -393,6 +416,86 @@ impl<T: GitRepoBuilder> GithubIndexPublisher<T> {
 
         Ok(())
     }
+
+    /// Update the `yanked` flag of an existing package entry in the repository
+    fn write_yanked_entry(
+        &self,
+        repo_path: &Path,
+        package_name: &str,
+        version: &Version,
+        yanked: bool,
+    ) -> Result<(), IndexPublishError> {
+        let relative_path = location_from_root(self.chunk_size, &self.namespace, package_name);
+        let package_path = repo_path.join(&relative_path);
+        let not_found =
+            || IndexPublishError::VersionNotFound(package_name.to_string(), version.to_string());
+
+        if !package_path.exists() {
+            return Err(not_found());
+        }
+
+        let existing_content = fs::read_to_string(&package_path)?;
+        let mut index_file: IndexFile = serde_json::from_str(&existing_content)?;
+        let entry = index_file.get(version).ok_or_else(not_found)?;
+
+        // `PackageEntry` has no setters, so rebuild the entry with the new flag.
+        let updated_entry = PackageEntry::new(
+            entry.name().to_string(),
+            entry.version().clone(),
+            entry.source_cid().to_string(),
+            entry.abi_cid().map(str::to_string),
+            entry.dependencies().cloned().collect(),
+            yanked,
+        );
+        index_file.insert(updated_entry);
+        let new_content = serde_json::to_string(&index_file)?;
+        fs::write(package_path, new_content)?;
+
+        Ok(())
+    }
+
+    /// Remove a package entry from the repository. The package's index file is removed
+    /// along with its last entry.
+    fn write_removed_entry(
+        &self,
+        repo_path: &Path,
+        package_name: &str,
+        version: &Version,
+    ) -> Result<(), IndexPublishError> {
+        let relative_path = location_from_root(self.chunk_size, &self.namespace, package_name);
+        let package_path = repo_path.join(&relative_path);
+        let not_found =
+            || IndexPublishError::VersionNotFound(package_name.to_string(), version.to_string());
+
+        if !package_path.exists() {
+            return Err(not_found());
+        }
+
+        let existing_content = fs::read_to_string(&package_path)?;
+        let index_file: IndexFile = serde_json::from_str(&existing_content)?;
+        if index_file.get(version).is_none() {
+            return Err(not_found());
+        }
+
+        // `IndexFile` has no way to remove entries, so rebuild it without the version.
+        let mut updated_index_file = IndexFile::default();
+        for entry in index_file
+            .versions()
+            .filter(|v| *v != version)
+            .filter_map(|v| index_file.get(v))
+        {
+            updated_index_file.insert(entry.clone());
+        }
+
+        if updated_index_file.versions().next().is_none() {
+            fs::remove_file(package_path)?;
+        } else {
+            let new_content = serde_json::to_string(&updated_index_file)?;
+            fs::write(package_path, new_content)?;
+        }
+
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -401,9 +504,71 @@ where
     T: GitRepoBuilder + Send + 'static,
 {
     async fn publish_entry(self, package_entry: PackageEntry) -> Result<(), IndexPublishError> {
-        task::spawn_blocking(move || self.process_repo(&package_entry))
-            .await
-            .map_err(|e| IndexPublishError::RepoError(format!("Blocking task JoinError: {e}")))?
+        self.publish_entries(vec![package_entry]).await
+    }
+
+    async fn publish_entries(
+        self,
+        package_entries: Vec<PackageEntry>,
+    ) -> Result<(), IndexPublishError> {
+        task::spawn_blocking(move || {
+            let added = package_entries
+                .iter()
+                .map(|entry| {
+                    format!(
+                        "package {} version {}",
+                        self.qualified_name(entry.name()),
+                        entry.version()
+                    )
+                })
+                .collect::<Vec<_>>();
+            let commit_message = format!("Add {}", added.join(", "));
+            self.process_repo(&commit_message, |repo_path| {
+                package_entries
+                    .iter()
+                    .try_for_each(|entry| self.write_package_entry(repo_path, entry))
+            })
+        })
+        .await
+        .map_err(|e| IndexPublishError::RepoError(format!("Blocking task JoinError: {e}")))?
+    }
+
+    async fn set_entry_yanked(
+        self,
+        package_name: String,
+        version: Version,
+        yanked: bool,
+    ) -> Result<(), IndexPublishError> {
+        task::spawn_blocking(move || {
+            let action = if yanked { "Yank" } else { "Unyank" };
+            let commit_message = format!(
+                "{action} package {} version {version}",
+                self.qualified_name(&package_name)
+            );
+            self.process_repo(&commit_message, |repo_path| {
+                self.write_yanked_entry(repo_path, &package_name, &version, yanked)
+            })
+        })
+        .await
+        .map_err(|e| IndexPublishError::RepoError(format!("Blocking task JoinError: {e}")))?
+    }
+
+    async fn remove_entry(
+        self,
+        package_name: String,
+        version: Version,
+    ) -> Result<(), IndexPublishError> {
+        task::spawn_blocking(move || {
+            let commit_message = format!(
+                "Remove package {} version {version}",
+                self.qualified_name(&package_name)
+            );
+            self.process_repo(&commit_message, |repo_path| {
+                self.write_removed_entry(repo_path, &package_name, &version)
+            })
+        })
+        .await
+        .map_err(|e| IndexPublishError::RepoError(format!("Blocking task JoinError: {e}")))?
     }
 }
 
@@ -644,4 +809,156 @@ mod tests {
         let content_after = fs::read_to_string(&file_path).unwrap();
         assert_eq!(content_after.trim(), initial_content.trim());
     }
+
+    #[tokio::test]
+    async fn publish_entries_writes_every_entry() {
+        let tmp_dir = tempdir().unwrap();
+        let repo_path = tmp_dir.path();
+        let chunk_size = 2;
+        let namespace = Namespace::Flat;
+        let publisher = mock_github_index_publisher(repo_path, chunk_size, namespace.clone());
+
+        let version = semver::Version::from_str("0.1.0").unwrap();
+        let entries: Vec<PackageEntry> = ["lib-a", "lib-b"]
+            .into_iter()
+            .map(|name| {
+                PackageEntry::new(
+                    name.to_string(),
+                    version.clone(),
+                    "QmHash".to_string(),
+                    None,
+                    vec![],
+                    false,
+                )
+            })
+            .collect();
+
+        publisher.publish_entries(entries.clone()).await.unwrap();
+
+        for entry in &entries {
+            let file_path =
+                repo_path.join(location_from_root(chunk_size, &namespace, entry.name()));
+            let content = fs::read_to_string(&file_path).unwrap();
+            let index_file: IndexFile = serde_json::from_str(&content).unwrap();
+            assert!(index_file.get(entry.version()).is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn set_entry_yanked_updates_existing_entry() {
+        let tmp_dir = tempdir().unwrap();
+        let repo_path = tmp_dir.path();
+        let chunk_size = 2;
+        let namespace = Namespace::Flat;
+
+        // Setup initial state: Pre-write a file with version 0.1.0
+        let name = "my-package".to_string();
+        let version = semver::Version::from_str("0.1.0").unwrap();
+        let entry = PackageEntry::new(
+            name.clone(),
+            version.clone(),
+            "QmHash".to_string(),
+            None,
+            vec![],
+            false,
+        );
+
+        let relative_path = location_from_root(chunk_size, &namespace, entry.name());
+        let file_path = repo_path.join(&relative_path);
+        fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+        let mut initial_index = IndexFile::default();
+        initial_index.insert(entry.clone());
+        fs::write(&file_path, serde_json::to_string(&initial_index).unwrap()).unwrap();
+
+        // Act: yank the version
+        let publisher = mock_github_index_publisher(repo_path, chunk_size, namespace.clone());
+        publisher
+            .set_entry_yanked(name.clone(), version.clone(), true)
+            .await
+            .unwrap();
+
+        // Assert the entry is yanked and otherwise unchanged
+        let content = fs::read_to_string(&file_path).unwrap();
+        let index_file: IndexFile = serde_json::from_str(&content).unwrap();
+        let yanked_entry = index_file.get(&version).expect("entry exists");
+        assert!(yanked_entry.yanked());
+        assert_eq!(yanked_entry.source_cid(), "QmHash");
+
+        // Act: un-yank the version
+        let publisher = mock_github_index_publisher(repo_path, chunk_size, namespace.clone());
+        publisher
+            .set_entry_yanked(name.clone(), version.clone(), false)
+            .await
+            .unwrap();
+
+        let content = fs::read_to_string(&file_path).unwrap();
+        let index_file: IndexFile = serde_json::from_str(&content).unwrap();
+        assert!(!index_file.get(&version).expect("entry exists").yanked());
+
+        // Yanking a version that was never published returns an error
+        let publisher = mock_github_index_publisher(repo_path, chunk_size, namespace);
+        let missing_version = semver::Version::from_str("0.2.0").unwrap();
+        let result = publisher
+            .set_entry_yanked(name, missing_version, true)
+            .await;
+        match result {
+            Err(IndexPublishError::VersionNotFound(_, version)) => assert_eq!(version, "0.2.0"),
+            other => panic!("Expected VersionNotFound error, but got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn remove_entry_removes_version() {
+        let tmp_dir = tempdir().unwrap();
+        let repo_path = tmp_dir.path();
+        let chunk_size = 2;
+        let namespace = Namespace::Flat;
+
+        // Setup initial state: Pre-write a file with versions 0.1.0 and 0.2.0
+        let name = "my-package".to_string();
+        let version_1 = semver::Version::from_str("0.1.0").unwrap();
+        let version_2 = semver::Version::from_str("0.2.0").unwrap();
+        let mut initial_index = IndexFile::default();
+        for version in [&version_1, &version_2] {
+            initial_index.insert(PackageEntry::new(
+                name.clone(),
+                version.clone(),
+                "QmHash".to_string(),
+                None,
+                vec![],
+                false,
+            ));
+        }
+
+        let relative_path = location_from_root(chunk_size, &namespace, &name);
+        let file_path = repo_path.join(&relative_path);
+        fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+        fs::write(&file_path, serde_json::to_string(&initial_index).unwrap()).unwrap();
+
+        // Act: remove the first version
+        let publisher = mock_github_index_publisher(repo_path, chunk_size, namespace.clone());
+        publisher
+            .remove_entry(name.clone(), version_1.clone())
+            .await
+            .unwrap();
+
+        // Assert only the second version is left
+        let content = fs::read_to_string(&file_path).unwrap();
+        let index_file: IndexFile = serde_json::from_str(&content).unwrap();
+        assert!(index_file.get(&version_1).is_none());
+        assert!(index_file.get(&version_2).is_some());
+
+        // Removing a version that isn't in the index returns an error
+        let publisher = mock_github_index_publisher(repo_path, chunk_size, namespace.clone());
+        let result = publisher.remove_entry(name.clone(), version_1).await;
+        match result {
+            Err(IndexPublishError::VersionNotFound(_, version)) => assert_eq!(version, "0.1.0"),
+            other => panic!("Expected VersionNotFound error, but got {other:?}"),
+        }
+
+        // Removing the last version removes the index file
+        let publisher = mock_github_index_publisher(repo_path, chunk_size, namespace);
+        publisher.remove_entry(name, version_2).await.unwrap();
+        assert!(!file_path.exists());
+    }
 }
@@ -2,6 +2,7 @@ pub mod git;
 
 use async_trait::async_trait;
 use forc_pkg::source::reg::index_file::PackageEntry;
+use semver::Version;
 use serde::Serialize;
 use thiserror::Error;
 
@@ -13,6 +14,9 @@ pub enum IndexPublishError {
     #[error("Version {1} of package {0} already exists in the index")]
     VersionCollision(String, String),
 
+    #[error("Version {1} of package {0} does not exist in the index")]
+    VersionNotFound(String, String),
+
     #[error("Failed to authenticate with remote repository: {0}")]
     AuthenticationError(String),
 
@@ -47,7 +51,50 @@ pub enum IndexPublishError {
     RepoError(String),
 }
 
+impl IndexPublishError {
+    /// Returns the name of the variant, which labels index failures in the metrics.
+    pub fn variant_name(&self) -> &'static str {
+        match self {
+            IndexPublishError::ConnectionLost(_) => "ConnectionLost",
+            IndexPublishError::VersionCollision(..) => "VersionCollision",
+            IndexPublishError::VersionNotFound(..) => "VersionNotFound",
+            IndexPublishError::AuthenticationError(_) => "AuthenticationError",
+            IndexPublishError::CloneError(_) => "CloneError",
+            IndexPublishError::FetchError(_) => "FetchError",
+            IndexPublishError::PushError(_) => "PushError",
+            IndexPublishError::ParseError(_) => "ParseError",
+            IndexPublishError::FileSystemError(_) => "FileSystemError",
+            IndexPublishError::NoChanges => "NoChanges",
+            IndexPublishError::PackageDataError(_) => "PackageDataError",
+            IndexPublishError::Git2Error(_) => "Git2Error",
+            IndexPublishError::RepoError(_) => "RepoError",
+        }
+    }
+}
+
 #[async_trait]
 pub trait IndexPublisher {
     async fn publish_entry(self, package_entry: PackageEntry) -> Result<(), IndexPublishError>;
+
+    /// Publishes several package entries in a single commit, so that either all of them
+    /// are published or none are.
+    async fn publish_entries(
+        self,
+        package_entries: Vec<PackageEntry>,
+    ) -> Result<(), IndexPublishError>;
+
+    /// Sets the `yanked` flag of an already published package entry.
+    async fn set_entry_yanked(
+        self,
+        package_name: String,
+        version: Version,
+        yanked: bool,
+    ) -> Result<(), IndexPublishError>;
+
+    /// Removes a published package entry, for versions that are taken down by an admin.
+    async fn remove_entry(
+        self,
+        package_name: String,
+        version: Version,
+    ) -> Result<(), IndexPublishError>;
 }
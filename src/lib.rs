@@ -4,6 +4,7 @@ pub mod file_uploader;
 pub mod github;
 pub mod handlers;
 pub mod index;
+pub mod metrics;
 pub mod middleware;
 pub mod models;
 pub mod schema;
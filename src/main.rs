@@ -3,10 +3,23 @@
 #[macro_use]
 extern crate rocket;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Days, Utc};
+use forc_pub::api::admin::{
+    AdminUser, AdminUserResponse, PackageNameFlagsResponse, RemoveVersionResponse,
+    ToolchainResponse, ToolchainsResponse, TransferOwnershipRequest, TransferOwnershipResponse,
+};
 use forc_pub::api::api_token::{CreateTokenRequest, CreateTokenResponse, Token, TokensResponse};
+use forc_pub::api::download::{
+    FileRequest, FileResponse, PackageFile, PackageStatsResponse, DEFAULT_STATS_DAYS,
+    MAX_STATS_DAYS,
+};
+use forc_pub::api::owners::{InviteOwnerRequest, OwnerInvitesResponse, OwnersResponse};
 use forc_pub::api::pagination::{PaginatedResponse, Pagination};
-use forc_pub::api::publish::{PublishRequest, PublishResponse, UploadResponse};
+use forc_pub::api::publish::{
+    DryRunPublishResponse, PublishRequest, PublishResponse, PublishResult, UploadResponse,
+    UploadStatusResponse, WorkspacePublishResponse, YankResponse,
+};
+use forc_pub::api::readiness::ReadinessResponse;
 use forc_pub::api::search::{
     DownloadLinksResponse, FullPackage, RecentPackage, RecentPackagesResponse,
 };
@@ -15,25 +28,41 @@ use forc_pub::api::{
     auth::{LoginRequest, LoginResponse, UserResponse},
     ApiResult, EmptyResponse,
 };
+use forc_pub::db::api_token::TokenScope;
+use forc_pub::db::audit_event::AuditEventKind;
 use forc_pub::db::error::DatabaseError;
+use forc_pub::db::upload_job::UploadJobStatus;
 use forc_pub::db::Database;
 use forc_pub::file_uploader::s3::{ipfs_hash_to_s3_url, S3Client, S3ClientImpl};
-use forc_pub::file_uploader::{
-    pinata::{ipfs_hash_to_docs_url, PinataClient, PinataClientImpl},
-    FileUploader,
-};
+use forc_pub::file_uploader::storage::{FileStorage, FileUrls, StorageClient};
 use forc_pub::github::handle_login;
-use forc_pub::handlers::publish::handle_publish;
-use forc_pub::handlers::upload::{handle_project_upload, install_binaries_at_path, UploadError};
+use forc_pub::handlers::admin::{
+    handle_admin_yank, handle_block_user, handle_install_toolchain, handle_remove_version,
+    handle_review_package_name_flag, handle_transfer_ownership,
+};
+use forc_pub::handlers::dependency_graph::{handle_dependency_graph, DependencyGraph};
+use forc_pub::handlers::publish::{handle_publish, handle_publish_dry_run};
+use forc_pub::handlers::readiness::handle_readiness;
+use forc_pub::handlers::toolchain::ToolchainManager;
+use forc_pub::handlers::upload::UploadError;
+use forc_pub::handlers::upload_job::run_upload_worker;
+use forc_pub::handlers::yank::handle_yank;
+use forc_pub::metrics::metrics;
+use forc_pub::middleware::admin_auth::AdminAuth;
+use forc_pub::middleware::client_info::ClientInfo;
 use forc_pub::middleware::cors::Cors;
+use forc_pub::middleware::metrics::RequestMetrics;
+use forc_pub::middleware::rate_limit::{retry_after, RateLimit, RateLimiter};
 use forc_pub::middleware::session_auth::{SessionAuth, SESSION_COOKIE_NAME};
 use forc_pub::middleware::token_auth::TokenAuth;
 use forc_pub::models::{
-    FullPackageWithCategories, PackagePreviewWithCategories, PackagePreviewWithDocsHash,
-    PackageVersionInfo,
+    AuditEventInfo, FullPackageWithCategories, PackageDependent, PackagePreviewWithCategories,
+    PackagePreviewWithDocsHash, PackageVersionInfo,
 };
 use forc_pub::util::{load_env, validate_or_format_semver};
-use rocket::http::Status;
+use rocket::futures::Stream;
+use rocket::http::{ContentType, Status};
+use rocket::tokio::io::AsyncReadExt;
 use rocket::tokio::task;
 use rocket::tokio::time::{self, Duration};
 use rocket::{
@@ -43,22 +72,21 @@ use rocket::{
     request::Request,
     response::{
         self,
-        stream::{Event, EventStream},
+        stream::{stream, Event, EventStream},
         Redirect,
     },
     serde::json::Json,
     State,
 };
+use serde_json::json;
 use std::env;
-use std::fs::{self};
-use std::path::PathBuf;
 use std::str::FromStr;
-use tempfile::tempdir;
 use tracing::{error, info};
 use tracing_subscriber::EnvFilter;
 use uuid::Uuid;
 
-const ORIGINAL_TARBALL_NAME: &str = "original.tgz";
+/// The number of upload workers to run if `UPLOAD_WORKERS` isn't set.
+const DEFAULT_UPLOAD_WORKERS: usize = 2;
 
 #[derive(Default)]
 pub struct ServerState {
@@ -70,10 +98,15 @@ pub struct ServerState {
 async fn login(
     db: &State<Database>,
     cookies: &CookieJar<'_>,
+    client: ClientInfo,
     request: Json<LoginRequest>,
 ) -> ApiResult<LoginResponse> {
     let (user, expires_in) = handle_login(request.code.clone()).await?;
-    let session = db.transaction(|conn| conn.new_user_session(&user, expires_in))?;
+    let session = db.transaction(|conn| {
+        let session = conn.new_user_session(&user, expires_in)?;
+        conn.record_audit_event(session.user_id, AuditEventKind::Login, &client, json!({}))?;
+        Ok::<_, DatabaseError>(session)
+    })?;
     let session_id = session.id.to_string();
     cookies.add(Cookie::build((SESSION_COOKIE_NAME, session_id.clone())));
     Ok(Json(LoginResponse { user, session_id }))
@@ -81,9 +114,16 @@ async fn login(
 
 /// The endpoint to log out.
 #[post("/logout")]
-async fn logout(db: &State<Database>, auth: SessionAuth) -> ApiResult<EmptyResponse> {
+async fn logout(
+    db: &State<Database>,
+    auth: SessionAuth,
+    client: ClientInfo,
+) -> ApiResult<EmptyResponse> {
     let session_id = auth.session_id;
-    let _ = db.transaction(|conn| conn.delete_session(session_id))?;
+    db.transaction(|conn| {
+        conn.delete_session(session_id)?;
+        conn.record_audit_event(auth.user.id, AuditEventKind::Logout, &client, json!({}))
+    })?;
     Ok(Json(EmptyResponse))
 }
 
@@ -95,15 +135,67 @@ fn user(auth: SessionAuth) -> Json<UserResponse> {
     })
 }
 
+/// Get the audit events of the user.
+#[get("/user/audit?<pagination..>")]
+fn user_audit(
+    db: &State<Database>,
+    auth: SessionAuth,
+    pagination: Pagination,
+) -> ApiResult<PaginatedResponse<AuditEventInfo>> {
+    let user_id = auth.user.id;
+    let events = db.transaction(|conn| conn.get_audit_events(Some(user_id), pagination))?;
+    Ok(Json(events))
+}
+
 #[post("/new_token", data = "<request>")]
 fn new_token(
     db: &State<Database>,
     auth: SessionAuth,
+    client: ClientInfo,
     request: Json<CreateTokenRequest>,
 ) -> ApiResult<CreateTokenResponse> {
     let user = auth.user;
-    let (token, plain_token) =
-        db.transaction(|conn| conn.new_token(user.id, request.name.clone()))?;
+    if request
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= Utc::now())
+    {
+        return Err(ApiError::Generic(
+            "Token expiry must be in the future".to_string(),
+            Status::BadRequest,
+        ));
+    }
+    let scopes = request
+        .scopes
+        .clone()
+        .unwrap_or_else(|| TokenScope::ALL.to_vec());
+    if scopes.is_empty() {
+        return Err(ApiError::Generic(
+            "Token must have at least one scope".to_string(),
+            Status::BadRequest,
+        ));
+    }
+    let (token, plain_token) = db.transaction(|conn| {
+        let (token, plain_token) = conn.new_scoped_token(
+            user.id,
+            request.name.clone(),
+            request.expires_at,
+            &scopes,
+            request.package_glob.clone(),
+        )?;
+        conn.record_audit_event(
+            user.id,
+            AuditEventKind::NewToken,
+            &client,
+            json!({
+                "tokenId": token.id,
+                "name": token.friendly_name,
+                "scopes": token.scopes,
+                "packageGlob": token.package_glob,
+                "expiresAt": token.expires_at,
+            }),
+        )?;
+        Ok::<_, DatabaseError>((token, plain_token))
+    })?;
     Ok(Json(CreateTokenResponse {
         token: Token {
             // The only time we return the plain token is when it's created.
@@ -114,9 +206,22 @@ fn new_token(
 }
 
 #[delete("/token/<id>")]
-fn delete_token(db: &State<Database>, auth: SessionAuth, id: String) -> ApiResult<EmptyResponse> {
+fn delete_token(
+    db: &State<Database>,
+    auth: SessionAuth,
+    client: ClientInfo,
+    id: String,
+) -> ApiResult<EmptyResponse> {
     let user_id = auth.user.id;
-    db.transaction(|conn| conn.delete_token(user_id, id.clone()))?;
+    db.transaction(|conn| {
+        conn.delete_token(user_id, id.clone())?;
+        conn.record_audit_event(
+            user_id,
+            AuditEventKind::DeleteToken,
+            &client,
+            json!({ "tokenId": id }),
+        )
+    })?;
     Ok(Json(EmptyResponse))
 }
 
@@ -129,36 +234,404 @@ fn tokens(db: &State<Database>, auth: SessionAuth) -> ApiResult<TokensResponse>
     }))
 }
 
+/// Publish an upload, or validate publishing it without storing anything if the request
+/// is a dry run.
 #[post("/publish", data = "<request>")]
 async fn publish(
     db: &State<Database>,
+    _limit: RateLimit,
     request: Json<PublishRequest>,
     auth: TokenAuth,
-) -> ApiResult<PublishResponse> {
-    match handle_publish(db, &request, &auth.token).await {
-        Ok(info) => Ok(Json(PublishResponse {
-            name: info.package_name,
-            version: info.num,
-        })),
+    client: ClientInfo,
+) -> ApiResult<PublishResult> {
+    if request.dry_run {
+        return match handle_publish_dry_run(db, &request, &auth.token) {
+            Ok(packages) => Ok(Json(PublishResult::DryRun(DryRunPublishResponse {
+                packages,
+            }))),
+            Err(e) => Err(ApiError::Publish(e)),
+        };
+    }
+
+    match handle_publish(db, &request, &auth.token, &client).await {
+        // A package upload publishes a single package under the same upload ID.
+        Ok(published) if published.len() == 1 && published[0].upload_id == request.upload_id => {
+            let info = published.into_iter().next().expect("one package");
+            Ok(Json(PublishResult::Package(PublishResponse {
+                name: info.package_name,
+                version: info.num,
+            })))
+        }
+        Ok(published) => Ok(Json(PublishResult::Workspace(WorkspacePublishResponse {
+            members: published
+                .into_iter()
+                .map(|info| PublishResponse {
+                    name: info.package_name,
+                    version: info.num,
+                })
+                .collect(),
+        }))),
         Err(e) => Err(ApiError::Publish(e)),
     }
 }
 
+/// Yank a published package version so that forc no longer resolves it.
+#[put("/package/<name>/<version>/yank")]
+async fn yank(
+    db: &State<Database>,
+    auth: TokenAuth,
+    client: ClientInfo,
+    name: String,
+    version: String,
+) -> ApiResult<YankResponse> {
+    let package_version =
+        handle_yank(db, name.clone(), version, true, &auth.token, &client).await?;
+    Ok(Json(YankResponse {
+        name,
+        version: package_version.num,
+        yanked: package_version.yanked,
+    }))
+}
+
+/// Un-yank a previously yanked package version.
+#[put("/package/<name>/<version>/unyank")]
+async fn unyank(
+    db: &State<Database>,
+    auth: TokenAuth,
+    client: ClientInfo,
+    name: String,
+    version: String,
+) -> ApiResult<YankResponse> {
+    let package_version =
+        handle_yank(db, name.clone(), version, false, &auth.token, &client).await?;
+    Ok(Json(YankResponse {
+        name,
+        version: package_version.num,
+        yanked: package_version.yanked,
+    }))
+}
+
+/// Get the owners of a package.
+#[get("/package/<name>/owners")]
+fn package_owners(db: &State<Database>, name: String) -> ApiResult<OwnersResponse> {
+    let owners = db.transaction(|conn| conn.get_package_owners(name))?;
+    Ok(Json(OwnersResponse { owners }))
+}
+
+/// Invite a user to become an owner of a package.
+#[post("/package/<name>/owners", data = "<request>")]
+fn invite_package_owner(
+    db: &State<Database>,
+    auth: SessionAuth,
+    client: ClientInfo,
+    name: String,
+    request: Json<InviteOwnerRequest>,
+) -> ApiResult<EmptyResponse> {
+    let user_id = auth.user.id;
+    db.transaction(|conn| {
+        conn.new_package_owner_invite(user_id, name.clone(), request.github_login.clone())?;
+        conn.record_audit_event(
+            user_id,
+            AuditEventKind::InviteOwner,
+            &client,
+            json!({ "name": name, "githubLogin": request.github_login }),
+        )
+    })?;
+    Ok(Json(EmptyResponse))
+}
+
+/// Remove an owner from a package.
+#[delete("/package/<name>/owners/<github_login>")]
+fn remove_package_owner(
+    db: &State<Database>,
+    auth: SessionAuth,
+    client: ClientInfo,
+    name: String,
+    github_login: String,
+) -> ApiResult<EmptyResponse> {
+    let user_id = auth.user.id;
+    db.transaction(|conn| {
+        conn.remove_package_owner(user_id, name.clone(), github_login.clone())?;
+        conn.record_audit_event(
+            user_id,
+            AuditEventKind::RemoveOwner,
+            &client,
+            json!({ "name": name, "githubLogin": github_login }),
+        )
+    })?;
+    Ok(Json(EmptyResponse))
+}
+
+/// Get the pending package owner invites for the user.
+#[get("/owner_invites")]
+fn owner_invites(db: &State<Database>, auth: SessionAuth) -> ApiResult<OwnerInvitesResponse> {
+    let user_id = auth.user.id;
+    let invites = db.transaction(|conn| conn.get_package_owner_invites_for_user(user_id))?;
+    Ok(Json(OwnerInvitesResponse { invites }))
+}
+
+#[post("/owner_invites/<id>/accept")]
+fn accept_owner_invite(
+    db: &State<Database>,
+    auth: SessionAuth,
+    client: ClientInfo,
+    id: String,
+) -> ApiResult<EmptyResponse> {
+    let user_id = auth.user.id;
+    db.transaction(|conn| {
+        let owner = conn.accept_package_owner_invite(user_id, id.clone())?;
+        let package = conn.get_package_by_id(owner.package_id)?;
+        conn.record_audit_event(
+            user_id,
+            AuditEventKind::AcceptOwnerInvite,
+            &client,
+            json!({ "name": package.package_name, "inviteId": id }),
+        )
+    })?;
+    Ok(Json(EmptyResponse))
+}
+
+#[delete("/owner_invites/<id>")]
+fn decline_owner_invite(
+    db: &State<Database>,
+    auth: SessionAuth,
+    client: ClientInfo,
+    id: String,
+) -> ApiResult<EmptyResponse> {
+    let user_id = auth.user.id;
+    db.transaction(|conn| {
+        conn.decline_package_owner_invite(user_id, id.clone())?;
+        conn.record_audit_event(
+            user_id,
+            AuditEventKind::DeclineOwnerInvite,
+            &client,
+            json!({ "inviteId": id }),
+        )
+    })?;
+    Ok(Json(EmptyResponse))
+}
+
+/// Search users by GitHub login, name or email.
+#[get("/admin/users?<query>&<pagination..>")]
+fn admin_users(
+    db: &State<Database>,
+    _auth: AdminAuth,
+    query: Option<String>,
+    pagination: Pagination,
+) -> ApiResult<PaginatedResponse<AdminUser>> {
+    let users = db.transaction(|conn| conn.search_users(query, pagination))?;
+    Ok(Json(users))
+}
+
+/// Block a user from publishing.
+#[put("/admin/users/<github_login>/block")]
+fn admin_block_user(
+    db: &State<Database>,
+    auth: AdminAuth,
+    client: ClientInfo,
+    github_login: String,
+) -> ApiResult<AdminUserResponse> {
+    let user = handle_block_user(db, &auth.user, &client, github_login, true)?;
+    Ok(Json(AdminUserResponse { user: user.into() }))
+}
+
+/// Allow a blocked user to publish again.
+#[delete("/admin/users/<github_login>/block")]
+fn admin_unblock_user(
+    db: &State<Database>,
+    auth: AdminAuth,
+    client: ClientInfo,
+    github_login: String,
+) -> ApiResult<AdminUserResponse> {
+    let user = handle_block_user(db, &auth.user, &client, github_login, false)?;
+    Ok(Json(AdminUserResponse { user: user.into() }))
+}
+
+/// Yank any package version.
+#[put("/admin/package/<name>/<version>/yank")]
+async fn admin_yank(
+    db: &State<Database>,
+    auth: AdminAuth,
+    client: ClientInfo,
+    name: String,
+    version: String,
+) -> ApiResult<YankResponse> {
+    let package_version =
+        handle_admin_yank(db, &auth.user, &client, name.clone(), version, true).await?;
+    Ok(Json(YankResponse {
+        name,
+        version: package_version.num,
+        yanked: package_version.yanked,
+    }))
+}
+
+/// Un-yank any package version.
+#[delete("/admin/package/<name>/<version>/yank")]
+async fn admin_unyank(
+    db: &State<Database>,
+    auth: AdminAuth,
+    client: ClientInfo,
+    name: String,
+    version: String,
+) -> ApiResult<YankResponse> {
+    let package_version =
+        handle_admin_yank(db, &auth.user, &client, name.clone(), version, false).await?;
+    Ok(Json(YankResponse {
+        name,
+        version: package_version.num,
+        yanked: package_version.yanked,
+    }))
+}
+
+/// Remove a package version from the index and the registry.
+#[delete("/admin/package/<name>/<version>")]
+async fn admin_remove_version(
+    db: &State<Database>,
+    auth: AdminAuth,
+    client: ClientInfo,
+    name: String,
+    version: String,
+) -> ApiResult<RemoveVersionResponse> {
+    let package_version =
+        handle_remove_version(db, &auth.user, &client, name.clone(), version).await?;
+    Ok(Json(RemoveVersionResponse {
+        name,
+        version: package_version.num,
+    }))
+}
+
+/// Transfer a package to a new owner, who replaces all of its owners.
+#[put("/admin/package/<name>/owner", data = "<request>")]
+fn admin_transfer_ownership(
+    db: &State<Database>,
+    auth: AdminAuth,
+    client: ClientInfo,
+    name: String,
+    request: Json<TransferOwnershipRequest>,
+) -> ApiResult<TransferOwnershipResponse> {
+    let owner = request.github_login.clone();
+    let previous_owners =
+        handle_transfer_ownership(db, &auth.user, &client, name.clone(), owner.clone())?;
+    Ok(Json(TransferOwnershipResponse {
+        name,
+        owner,
+        previous_owners,
+    }))
+}
+
+/// Get the most recent upload jobs.
+#[get("/admin/uploads?<pagination..>")]
+fn admin_uploads(
+    db: &State<Database>,
+    _auth: AdminAuth,
+    pagination: Pagination,
+) -> ApiResult<PaginatedResponse<UploadStatusResponse>> {
+    let jobs = db.transaction(|conn| conn.get_recent_upload_jobs(pagination))?;
+    let data = jobs
+        .data
+        .into_iter()
+        .map(UploadStatusResponse::try_from)
+        .collect::<Result<_, _>>()?;
+    Ok(Json(PaginatedResponse {
+        data,
+        total_count: jobs.total_count,
+        total_pages: jobs.total_pages,
+        current_page: jobs.current_page,
+        per_page: jobs.per_page,
+    }))
+}
+
+/// Get the installed versions of forc, most recently used first.
+#[get("/admin/toolchains")]
+fn admin_toolchains(
+    toolchains: &State<ToolchainManager>,
+    _auth: AdminAuth,
+) -> ApiResult<ToolchainsResponse> {
+    let toolchains = toolchains
+        .installed()
+        .map_err(ApiError::Upload)?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    Ok(Json(ToolchainsResponse { toolchains }))
+}
+
+/// Install a version of forc.
+#[put("/admin/toolchains/<version>")]
+async fn admin_install_toolchain(
+    db: &State<Database>,
+    toolchains: &State<ToolchainManager>,
+    auth: AdminAuth,
+    client: ClientInfo,
+    version: String,
+) -> ApiResult<ToolchainResponse> {
+    let toolchain = handle_install_toolchain(db, &auth.user, &client, toolchains, version).await?;
+    Ok(Json(ToolchainResponse {
+        toolchain: toolchain.into(),
+    }))
+}
+
+/// Get the package names that were flagged as similar to popular packages and haven't
+/// been reviewed.
+#[get("/admin/package_name_flags")]
+fn admin_package_name_flags(
+    db: &State<Database>,
+    _auth: AdminAuth,
+) -> ApiResult<PackageNameFlagsResponse> {
+    let flags = db.transaction(|conn| conn.get_package_name_flags_for_review())?;
+    Ok(Json(PackageNameFlagsResponse { flags }))
+}
+
+/// Mark a package name flag as reviewed.
+#[post("/admin/package_name_flags/<id>/review")]
+fn admin_review_package_name_flag(
+    db: &State<Database>,
+    auth: AdminAuth,
+    client: ClientInfo,
+    id: String,
+) -> ApiResult<EmptyResponse> {
+    handle_review_package_name_flag(db, &auth.user, &client, id)?;
+    Ok(Json(EmptyResponse))
+}
+
+/// Get the audit events of all users.
+#[get("/admin/audit_events?<pagination..>")]
+fn admin_audit_events(
+    db: &State<Database>,
+    _auth: AdminAuth,
+    pagination: Pagination,
+) -> ApiResult<PaginatedResponse<AuditEventInfo>> {
+    let events = db.transaction(|conn| conn.get_audit_events(None, pagination))?;
+    Ok(Json(events))
+}
+
+/// Get the audit events of the actions taken by admins.
+#[get("/admin/audit?<pagination..>")]
+fn admin_audit(
+    db: &State<Database>,
+    _auth: AdminAuth,
+    pagination: Pagination,
+) -> ApiResult<PaginatedResponse<AuditEventInfo>> {
+    let events = db.transaction(|conn| conn.get_admin_audit_events(pagination))?;
+    Ok(Json(events))
+}
+
+/// Upload a project to be built by an upload worker. An `upload_id` event with the ID of the
+/// job is sent as soon as it is queued. A dry run builds the project without storing it, and
+/// reports the uploads that would have been created.
 #[post(
-    "/upload_project?<forc_version>",
+    "/upload_project?<forc_version>&<dry_run>",
     format = "application/gzip",
     data = "<tarball>"
 )]
 async fn upload_project<'a>(
     db: &'a State<Database>,
-    pinata_client: &'a State<PinataClientImpl>,
-    s3_client: &'a State<S3ClientImpl>,
+    _limit: RateLimit,
     forc_version: &'a str,
-    mut tarball: Capped<TempFile<'a>>,
+    dry_run: Option<bool>,
+    tarball: Capped<TempFile<'a>>,
 ) -> EventStream![Event + 'a] {
     EventStream! {
-
-        let mut interval = time::interval(Duration::from_secs(1));
         // Ensure that the tarball was fully uploaded.
         yield Event::data("Uploading sway project");
         if !tarball.is_complete() {
@@ -176,107 +649,114 @@ async fn upload_project<'a>(
             }
         };
 
-        // Install the forc version if it's not already installed.
-        let forc_path_str = format!("forc-{forc_version}");
-        let forc_path = PathBuf::from(&forc_path_str);
-        if fs::create_dir_all(forc_path.clone()).is_err() {
-            yield Event::json(&ApiError::Upload(UploadError::SaveFile));
-            return;
-        }
-        let forc_path = match fs::canonicalize(forc_path.clone()) {
-            Ok(p) => p,
-            Err(_) => {
-                yield Event::json(&ApiError::Upload(UploadError::SaveFile));
-                return;
-            }
+        // Store the tarball in a job, which is processed by an upload worker.
+        let mut contents = vec![];
+        let read_result = match tarball.open().await {
+            Ok(mut file) => file.read_to_end(&mut contents).await.map(|_| ()),
+            Err(err) => Err(err),
         };
-
-        // Spawn a task to install forc.
-        let forc_path_clone = forc_path.clone();
-        let forc_version_clone = forc_version.clone();
-        let handle = task::spawn_blocking(move || {
-            install_binaries_at_path(&forc_version_clone, &forc_path_clone)
-        });
-
-        while !handle.is_finished() {
-            interval.tick().await;
-            yield Event::comment("keep-alive");
-        }
-
-        match handle.await {
-            Ok(Ok(_)) => {},
-            Ok(Err(err)) => {
-                yield Event::json(&ApiError::Upload(err));
-                return;
-            }
-            Err(_) => {
-                yield Event::json(&ApiError::Upload(UploadError::FailedToCompile));
-                return;
-            }
+        if read_result.is_err() {
+            yield Event::json(&ApiError::Upload(UploadError::ReadFile));
+            return;
         }
-
-        // Create an upload ID and temporary directory.
-        yield Event::data("Preparing project for publishing");
         let upload_id = Uuid::new_v4();
-        let tmp_dir = match tempdir() {
-            Ok(d) => d,
-            Err(_) => {
-                yield Event::json(&ApiError::Upload(UploadError::CreateTempDir));
-                return;
-            }
-        };
-        let upload_dir = tmp_dir.path().join(upload_id.to_string());
-
-        if fs::create_dir(upload_dir.clone()).is_err() {
+        let message = format!("Installing forc {forc_version}");
+        let dry_run = dry_run.unwrap_or_default();
+        if db
+            .transaction(|conn| {
+                conn.new_upload_job(upload_id, forc_version, contents, message, dry_run)
+            })
+            .is_err()
+        {
             yield Event::json(&ApiError::Upload(UploadError::SaveFile));
             return;
         }
+        // The client can resume following the job by its ID if the stream is dropped.
+        yield Event::data(upload_id.to_string()).event("upload_id");
 
-        // Persist the file to disk.
-        let orig_tarball_path = upload_dir.join(ORIGINAL_TARBALL_NAME);
-        if (tarball.persist_to(&orig_tarball_path).await).is_err() {
-            yield Event::json(&ApiError::Upload(UploadError::SaveFile));
-            return;
+        for await event in follow_upload_job(db, upload_id) {
+            yield event;
         }
+    }
+}
 
-        // Handle the project upload and store the metadata in the database.
-        yield Event::data("Uploading project to IPFS and S3");
-        let file_uploader = FileUploader::new(pinata_client.inner(), s3_client.inner());
-        // TODO: Add keep-alives for this future.
-        let upload_entry = match handle_project_upload(
-            &upload_dir,
-            &upload_id,
-            &orig_tarball_path,
-            &forc_path,
-            forc_version.to_string(),
-            &file_uploader,
-        ).await {
-            Ok(entry) => entry,
-            Err(e) => {
-                yield Event::json(&ApiError::Upload(e));
-                return;
+/// Streams the progress messages of an upload job until it finishes. The final event is
+/// the [UploadResponse] if the job succeeded, which includes the report of a dry run, or the
+/// error if it failed, which follows a `diagnostics` event with the compiler output if the
+/// build failed.
+fn follow_upload_job(db: &Database, upload_id: Uuid) -> impl Stream<Item = Event> + '_ {
+    stream! {
+        let mut interval = time::interval(Duration::from_secs(1));
+        let mut last_message = None;
+        loop {
+            interval.tick().await;
+            let job = db
+                .transaction_blocking(move |conn| conn.get_upload_job(upload_id.to_string()))
+                .await;
+            let job = match job {
+                Ok(job) => job,
+                Err(err) => {
+                    yield Event::json(&ApiError::Database(err));
+                    return;
+                }
+            };
+
+            if job.message != last_message {
+                if let Some(message) = &job.message {
+                    yield Event::data(message.clone());
+                }
+                last_message = job.message.clone();
+            } else {
+                yield Event::comment("keep-alive");
             }
-        };
 
-        if db.transaction(|conn| conn.new_upload(&upload_entry)).is_err() {
-            yield Event::json(&ApiError::Upload(UploadError::SaveFile));
-            return;
+            match job.status() {
+                Ok(UploadJobStatus::Succeeded) => {
+                    yield Event::json(&UploadResponse {
+                        upload_id,
+                        report: job.dry_run_report(),
+                    });
+                    return;
+                }
+                Ok(UploadJobStatus::Failed) => {
+                    let err = job.upload_error().unwrap_or(UploadError::Unknown);
+                    if let Some(diagnostics) = err.diagnostics() {
+                        yield Event::data(diagnostics.to_string()).event("diagnostics");
+                    }
+                    yield Event::json(&ApiError::Upload(err));
+                    return;
+                }
+                Ok(UploadJobStatus::Pending | UploadJobStatus::Running) => {}
+                Err(err) => {
+                    yield Event::json(&ApiError::Database(err));
+                    return;
+                }
+            }
         }
+    }
+}
 
-        // Clean up the temporary directory.
-        if tmp_dir.close().is_err() {
-            yield Event::json(&ApiError::Upload(UploadError::RemoveTempDir));
-            return;
-        }
+/// Get the status of an upload job.
+#[get("/upload/<id>/status", rank = 2)]
+fn upload_status(db: &State<Database>, id: String) -> ApiResult<UploadStatusResponse> {
+    let job = db.transaction(|conn| conn.get_upload_job(id))?;
+    Ok(Json(UploadStatusResponse::try_from(job)?))
+}
 
-        // Final event: success with upload_id
-        yield Event::json(&UploadResponse { upload_id });
-    }
+/// Subscribe to the progress of an upload job until it finishes.
+#[get("/upload/<id>/status", format = "text/event-stream", rank = 1)]
+fn upload_status_events(
+    db: &State<Database>,
+    id: String,
+) -> Result<EventStream![Event + '_], ApiError> {
+    let job = db.transaction(|conn| conn.get_upload_job(id))?;
+    Ok(EventStream::from(follow_upload_job(db, job.id)))
 }
 
 #[get("/packages?<updated_after>&<pagination..>")]
 fn packages(
     db: &State<Database>,
+    file_urls: &State<FileUrls>,
     updated_after: Option<&str>,
     pagination: Pagination,
 ) -> ApiResult<PaginatedResponse<FullPackage>> {
@@ -295,7 +775,7 @@ fn packages(
                 categories: vec![], // Empty for now
                 keywords: vec![],   // Empty for now
             };
-            FullPackage::from(full_package_with_categories)
+            FullPackage::new(full_package_with_categories, file_urls)
         })
         .collect();
 
@@ -311,7 +791,8 @@ fn packages(
 #[get("/package?<name>&<version>&<inline_abi>")]
 async fn package(
     db: &State<Database>,
-    pinata_client: &State<PinataClientImpl>,
+    storage_client: &State<StorageClient>,
+    file_urls: &State<FileUrls>,
     name: String,
     version: Option<String>,
     inline_abi: Option<bool>,
@@ -333,12 +814,12 @@ async fn package(
             e
         })?;
 
-    let mut full_package = FullPackage::from(db_data.clone());
+    let mut full_package = FullPackage::new(db_data.clone(), file_urls);
 
     // If inline_abi is true and we have an ABI hash, fetch the ABI content
     if inline_abi.unwrap_or(false) {
         if let Some(abi_hash) = &db_data.package.abi_ipfs_hash {
-            match pinata_client.fetch_ipfs_content(abi_hash).await {
+            match storage_client.fetch_ipfs_content(abi_hash).await {
                 Ok(abi_content) => {
                     if let Ok(abi_json) = serde_json::from_slice::<serde_json::Value>(&abi_content)
                     {
@@ -363,6 +844,28 @@ fn package_versions(db: &State<Database>, name: String) -> ApiResult<Vec<Package
     Ok(Json(versions))
 }
 
+/// Get the packages whose latest version depends on a package.
+#[get("/package/dependents?<name>&<pagination..>")]
+fn package_dependents(
+    db: &State<Database>,
+    name: String,
+    pagination: Pagination,
+) -> ApiResult<PaginatedResponse<PackageDependent>> {
+    let dependents = db.transaction(|conn| conn.get_package_dependents(name, pagination))?;
+    Ok(Json(dependents))
+}
+
+/// Get the transitive dependency graph of a package version.
+#[get("/package/dependency_graph?<name>&<version>")]
+fn package_dependency_graph(
+    db: &State<Database>,
+    name: String,
+    version: String,
+) -> ApiResult<DependencyGraph> {
+    let graph = handle_dependency_graph(db, name, version)?;
+    Ok(Json(graph))
+}
+
 /// Get S3 download links for a package.
 #[get("/package/download?<name>&<version>")]
 fn package_download_links(
@@ -387,6 +890,8 @@ fn package_download_links(
         .abi_ipfs_hash
         .and_then(|hash| ipfs_hash_to_s3_url(&hash));
 
+    record_download(db, name.clone(), db_data.package.version.clone());
+
     Ok(Json(DownloadLinksResponse {
         package_name: name,
         version: db_data.package.version,
@@ -395,27 +900,178 @@ fn package_download_links(
     }))
 }
 
+/// Record a download of a package version. Failing to record a download is logged
+/// rather than failing the download itself.
+fn record_download(db: &Database, name: String, version: String) {
+    if let Err(err) = db.transaction(|conn| conn.record_download(name.clone(), version.clone())) {
+        error!(
+            "Failed to record download of package '{}' version '{}': {}",
+            name, version, err
+        );
+    }
+}
+
+/// Get the download statistics of a package, with daily downloads per version for the
+/// last `days` days.
+#[get("/package/stats?<name>&<days>")]
+fn package_stats(
+    db: &State<Database>,
+    name: String,
+    days: Option<u32>,
+) -> ApiResult<PackageStatsResponse> {
+    let days = days.unwrap_or(DEFAULT_STATS_DAYS);
+    if days == 0 || days > MAX_STATS_DAYS {
+        return Err(ApiError::Generic(
+            format!("Days must be between 1 and {MAX_STATS_DAYS}"),
+            Status::BadRequest,
+        ));
+    }
+    let since = Utc::now().date_naive() - Days::new(u64::from(days - 1));
+
+    let (package, versions, daily_downloads) = db.transaction(|conn| {
+        let package = conn.get_package_by_name(name.clone())?;
+        let versions = conn.get_version_downloads(name.clone())?;
+        let daily_downloads = conn.get_daily_downloads(name.clone(), since)?;
+        Ok::<_, DatabaseError>((package, versions, daily_downloads))
+    })?;
+
+    Ok(Json(PackageStatsResponse {
+        name: package.package_name,
+        downloads: package.downloads,
+        versions,
+        daily_downloads,
+    }))
+}
+
+/// Fetch a file of a package version from the storage backend.
+async fn package_file(
+    db: &Database,
+    storage_client: &StorageClient,
+    request: FileRequest,
+    name: String,
+    version: String,
+    file: PackageFile,
+) -> Result<FileResponse, ApiError> {
+    let package =
+        db.transaction(|conn| conn.get_full_package_version(name.clone(), version.clone()))?;
+    let ipfs_hash = match file {
+        PackageFile::Tarball => Some(package.source_code_ipfs_hash),
+        PackageFile::Abi => package.abi_ipfs_hash,
+        PackageFile::Docs => package.docs_ipfs_hash,
+    }
+    .filter(|hash| !hash.is_empty())
+    .ok_or_else(|| {
+        ApiError::Generic(
+            format!("File not found for package {name} version {version}"),
+            Status::NotFound,
+        )
+    })?;
+
+    let body = request
+        .body(&ipfs_hash, storage_client.fetch_ipfs_stream(&ipfs_hash))
+        .await
+        .map_err(|err| ApiError::Generic(err.to_string(), Status::BadGateway))?;
+
+    // The ABI is fetched by tooling to interact with contracts, so it isn't a download.
+    // Revalidations and range requests for part of the file aren't downloads either.
+    if file != PackageFile::Abi && body.is_full() {
+        record_download(db, name.clone(), version.clone());
+    }
+
+    Ok(FileResponse {
+        body,
+        content_type: file.content_type(),
+        file_name: file.file_name(&name, &version),
+        ipfs_hash,
+    })
+}
+
+/// Download the source code tarball of a package version.
+#[get("/api/v1/crates/<name>/<version>/download")]
+async fn download_tarball(
+    db: &State<Database>,
+    storage_client: &State<StorageClient>,
+    request: FileRequest,
+    name: String,
+    version: String,
+) -> Result<FileResponse, ApiError> {
+    package_file(
+        db,
+        storage_client,
+        request,
+        name,
+        version,
+        PackageFile::Tarball,
+    )
+    .await
+}
+
+/// Download the ABI of a package version.
+#[get("/api/v1/crates/<name>/<version>/abi")]
+async fn download_abi(
+    db: &State<Database>,
+    storage_client: &State<StorageClient>,
+    request: FileRequest,
+    name: String,
+    version: String,
+) -> Result<FileResponse, ApiError> {
+    package_file(db, storage_client, request, name, version, PackageFile::Abi).await
+}
+
+/// Download the documentation tarball of a package version.
+#[get("/api/v1/crates/<name>/<version>/docs")]
+async fn download_docs(
+    db: &State<Database>,
+    storage_client: &State<StorageClient>,
+    request: FileRequest,
+    name: String,
+    version: String,
+) -> Result<FileResponse, ApiError> {
+    package_file(
+        db,
+        storage_client,
+        request,
+        name,
+        version,
+        PackageFile::Docs,
+    )
+    .await
+}
+
 #[get("/recent_packages")]
-fn recent_packages(db: &State<Database>) -> ApiResult<RecentPackagesResponse> {
+fn recent_packages(
+    db: &State<Database>,
+    file_urls: &State<FileUrls>,
+) -> ApiResult<RecentPackagesResponse> {
     let (recently_created, recently_updated) = db.transaction(|conn| {
         let recently_created = conn.get_recently_created()?;
         let recently_updated = conn.get_recently_updated()?;
         Ok::<_, DatabaseError>((recently_created, recently_updated))
     })?;
     Ok(Json(RecentPackagesResponse {
-        recently_created: map_recent_packages(recently_created),
-        recently_updated: map_recent_packages(recently_updated),
+        recently_created: map_recent_packages(recently_created, file_urls),
+        recently_updated: map_recent_packages(recently_updated, file_urls),
     }))
 }
 
-fn map_recent_packages(packages: Vec<PackagePreviewWithDocsHash>) -> Vec<RecentPackage> {
+fn map_recent_packages(
+    packages: Vec<PackagePreviewWithDocsHash>,
+    file_urls: &FileUrls,
+) -> Vec<RecentPackage> {
     packages
         .into_iter()
         .map(|pkg| {
             let docs_ipfs_url = pkg
                 .docs_ipfs_hash
                 .filter(|hash| !hash.is_empty())
-                .map(|hash| ipfs_hash_to_docs_url(&hash));
+                .map(|hash| {
+                    file_urls.file_url(
+                        PackageFile::Docs,
+                        &pkg.package.name,
+                        &pkg.package.version,
+                        &hash,
+                    )
+                });
 
             RecentPackage {
                 package: pkg.package,
@@ -433,7 +1089,10 @@ fn all_options() {
 
 /// Catch all errors and log them before returning a custom error message.
 #[catch(default)]
-fn default_catcher(status: Status, _req: &Request<'_>) -> response::status::Custom<ApiError> {
+fn default_catcher(status: Status, req: &Request<'_>) -> response::status::Custom<ApiError> {
+    if let Some(retry_after) = retry_after(req) {
+        return response::status::Custom(status, ApiError::RateLimited(retry_after));
+    }
     tracing::error!(
         "Error occurred: {} - {:?}",
         status.code,
@@ -452,6 +1111,7 @@ fn default_catcher(status: Status, _req: &Request<'_>) -> response::status::Cust
 #[get("/search?<q>&<category>&<keyword>&<pagination..>")]
 fn search(
     db: &State<Database>,
+    _limit: RateLimit,
     q: Option<String>,        // General search query
     category: Option<String>, // Category filter
     keyword: Option<String>,  // Keyword filter
@@ -501,6 +1161,7 @@ fn search(
 #[get("/docs/<name>/<version>")]
 async fn get_package_docs(
     db: &State<Database>,
+    file_urls: &State<FileUrls>,
     name: String,
     version: String,
 ) -> Result<Redirect, Status> {
@@ -510,7 +1171,16 @@ async fn get_package_docs(
     match package_result {
         Ok(package_data) => {
             if let Some(docs_hash) = package_data.package.docs_ipfs_hash {
-                let docs_url = ipfs_hash_to_docs_url(&docs_hash);
+                let docs_url = file_urls.file_url(
+                    PackageFile::Docs,
+                    &name,
+                    &package_data.package.version,
+                    &docs_hash,
+                );
+                // The download endpoints of the server record the download themselves.
+                if !file_urls.is_registry() {
+                    record_download(db, name, package_data.package.version);
+                }
                 Ok(Redirect::to(docs_url))
             } else {
                 error!(
@@ -530,11 +1200,40 @@ async fn get_package_docs(
     }
 }
 
+/// The liveness probe, which doesn't check the components the server depends on.
 #[get("/health")]
 fn health() -> String {
     "true".to_string()
 }
 
+/// The readiness probe, which checks the database, the storage backend and the index repo.
+/// Responds with `503 Service Unavailable` if the database, or local storage, is unavailable.
+#[get("/ready")]
+async fn ready(
+    db: &State<Database>,
+    storage_client: &State<StorageClient>,
+) -> (Status, Json<ReadinessResponse>) {
+    let response = handle_readiness(db, storage_client).await;
+    let status = if response.ready {
+        Status::Ok
+    } else {
+        Status::ServiceUnavailable
+    };
+    (status, Json(response))
+}
+
+/// Serves the metrics of the server in the Prometheus text exposition format.
+#[get("/metrics")]
+fn metrics_endpoint(db: &State<Database>) -> Result<(ContentType, String), ApiError> {
+    metrics().record_db_pool(db);
+    let rendered = metrics()
+        .render()
+        .map_err(|err| ApiError::Generic(err.to_string(), Status::InternalServerError))?;
+    // The content type of version 0.0.4 of the Prometheus text exposition format.
+    let content_type = ContentType::Plain.with_params(("version", "0.0.4"));
+    Ok((content_type, rendered))
+}
+
 // Launch the rocket server.
 #[launch]
 async fn rocket() -> _ {
@@ -542,35 +1241,86 @@ async fn rocket() -> _ {
 
     let s3_client = S3ClientImpl::new().await.expect("s3 client");
 
-    let pinata_client = PinataClientImpl::new().await.expect("pinata client");
+    let storage_client = StorageClient::new().await.expect("storage client");
+    let file_urls = storage_client.file_urls();
+
+    let db = Database::default();
+    let rate_limiter = RateLimiter::from_env(&db).expect("rate limiter");
+    let upload_workers = env::var("UPLOAD_WORKERS")
+        .ok()
+        .and_then(|workers| workers.parse().ok())
+        .unwrap_or(DEFAULT_UPLOAD_WORKERS);
+    info!("Starting {} upload workers", upload_workers);
+    let toolchains = ToolchainManager::from_env();
+    task::spawn(toolchains.clone().prewarm());
+    for _ in 0..upload_workers {
+        task::spawn(run_upload_worker(db.clone(), toolchains.clone()));
+    }
 
     info!("Starting forc.pub server");
 
     rocket::build()
-        .manage(Database::default())
-        .manage(pinata_client)
+        .manage(db)
+        .manage(storage_client)
+        .manage(file_urls)
         .manage(s3_client)
+        .manage(toolchains)
         .attach(Cors)
+        .attach(RequestMetrics)
+        .attach(rate_limiter)
         .mount(
             "/",
             routes![
                 login,
                 logout,
                 user,
+                user_audit,
                 new_token,
                 delete_token,
                 tokens,
                 publish,
+                yank,
+                unyank,
+                package_owners,
+                invite_package_owner,
+                remove_package_owner,
+                owner_invites,
+                accept_owner_invite,
+                decline_owner_invite,
+                admin_users,
+                admin_block_user,
+                admin_unblock_user,
+                admin_yank,
+                admin_unyank,
+                admin_remove_version,
+                admin_transfer_ownership,
+                admin_uploads,
+                admin_toolchains,
+                admin_install_toolchain,
+                admin_package_name_flags,
+                admin_review_package_name_flag,
+                admin_audit,
+                admin_audit_events,
                 upload_project,
+                upload_status,
+                upload_status_events,
                 packages,
                 package,
                 package_versions,
+                package_dependents,
+                package_dependency_graph,
                 package_download_links,
+                package_stats,
+                download_tarball,
+                download_abi,
+                download_docs,
                 recent_packages,
                 search,
                 get_package_docs,
                 all_options,
-                health
+                health,
+                ready,
+                metrics_endpoint
             ],
         )
         .register("/", catchers![default_catcher])
@@ -0,0 +1,263 @@
+use crate::db::Database;
+use crate::index::handler::IndexPublishError;
+use prometheus::{
+    exponential_buckets, Gauge, HistogramOpts, HistogramTimer, HistogramVec, IntCounterVec,
+    IntGauge, Opts, Registry, TextEncoder,
+};
+use std::fmt;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+/// The prefix of the names of all metrics.
+const METRICS_NAMESPACE: &str = "forc_pub";
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Returns the metrics of the server, which are served at `/metrics`.
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+/// A stage of the upload pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UploadStage {
+    /// Installing forc, when it isn't cached.
+    Install,
+    /// Building the project.
+    Build,
+    /// Generating the documentation of a package.
+    Doc,
+    /// Storing a file in IPFS.
+    Ipfs,
+    /// Storing a file in S3.
+    S3,
+}
+
+impl UploadStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UploadStage::Install => "install",
+            UploadStage::Build => "build",
+            UploadStage::Doc => "doc",
+            UploadStage::Ipfs => "ipfs",
+            UploadStage::S3 => "s3",
+        }
+    }
+}
+
+impl fmt::Display for UploadStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The Prometheus metrics of the server and the upload pipeline.
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    upload_stage_duration: HistogramVec,
+    forc_install_cache: IntCounterVec,
+    index_publish_failures: IntCounterVec,
+    db_pool_connections: IntGauge,
+    db_pool_idle_connections: IntGauge,
+    db_pool_max_connections: IntGauge,
+    db_pool_saturation: Gauge,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+impl Metrics {
+    /// Creates the metrics in a new registry.
+    pub fn new() -> Self {
+        let registry =
+            Registry::new_custom(Some(METRICS_NAMESPACE.to_string()), None).expect("registry");
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "Number of HTTP requests by route."),
+            &["method", "route", "status"],
+        )
+        .expect("metric");
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Latency of HTTP requests by route.",
+            ),
+            &["method", "route"],
+        )
+        .expect("metric");
+        // Stages range from milliseconds for small files to minutes for forc installs.
+        let upload_stage_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "upload_stage_duration_seconds",
+                "Duration of the stages of the upload pipeline.",
+            )
+            .buckets(exponential_buckets(0.05, 2.5, 10).expect("buckets")),
+            &["stage"],
+        )
+        .expect("metric");
+        let forc_install_cache = IntCounterVec::new(
+            Opts::new(
+                "forc_install_cache_total",
+                "Number of forc installs that were cached (hit) or installed (miss).",
+            ),
+            &["result"],
+        )
+        .expect("metric");
+        let index_publish_failures = IntCounterVec::new(
+            Opts::new(
+                "index_publish_failures_total",
+                "Number of failed index updates by error.",
+            ),
+            &["error"],
+        )
+        .expect("metric");
+        let db_pool_connections = IntGauge::new(
+            "db_pool_connections",
+            "Number of connections in the database pool.",
+        )
+        .expect("metric");
+        let db_pool_idle_connections = IntGauge::new(
+            "db_pool_idle_connections",
+            "Number of idle connections in the database pool.",
+        )
+        .expect("metric");
+        let db_pool_max_connections = IntGauge::new(
+            "db_pool_max_connections",
+            "Maximum number of connections in the database pool.",
+        )
+        .expect("metric");
+        let db_pool_saturation = Gauge::new(
+            "db_pool_saturation",
+            "Ratio of the maximum number of connections in the database pool that are in use.",
+        )
+        .expect("metric");
+
+        for collector in [
+            Box::new(http_requests.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(http_request_duration.clone()),
+            Box::new(upload_stage_duration.clone()),
+            Box::new(forc_install_cache.clone()),
+            Box::new(index_publish_failures.clone()),
+            Box::new(db_pool_connections.clone()),
+            Box::new(db_pool_idle_connections.clone()),
+            Box::new(db_pool_max_connections.clone()),
+            Box::new(db_pool_saturation.clone()),
+        ] {
+            registry.register(collector).expect("unique metric");
+        }
+
+        Metrics {
+            registry,
+            http_requests,
+            http_request_duration,
+            upload_stage_duration,
+            forc_install_cache,
+            index_publish_failures,
+            db_pool_connections,
+            db_pool_idle_connections,
+            db_pool_max_connections,
+            db_pool_saturation,
+        }
+    }
+
+    /// Records a handled HTTP request. `route` is the URI of the matched route, so that
+    /// requests for different packages are counted together.
+    pub fn record_request(&self, method: &str, route: &str, status: u16, duration: Duration) {
+        self.http_requests
+            .with_label_values(&[method, route, &status.to_string()])
+            .inc();
+        self.http_request_duration
+            .with_label_values(&[method, route])
+            .observe(duration.as_secs_f64());
+    }
+
+    /// Starts timing a stage of the upload pipeline. The duration is recorded when the
+    /// returned timer is dropped, so failed stages are recorded too.
+    pub fn start_upload_stage(&self, stage: UploadStage) -> HistogramTimer {
+        self.upload_stage_duration
+            .with_label_values(&[stage.as_str()])
+            .start_timer()
+    }
+
+    /// Records whether a forc version was already installed.
+    pub fn record_forc_install(&self, cached: bool) {
+        let result = if cached { "hit" } else { "miss" };
+        self.forc_install_cache.with_label_values(&[result]).inc();
+    }
+
+    /// Records a failed update of the index.
+    pub fn record_index_publish_failure(&self, err: &IndexPublishError) {
+        self.index_publish_failures
+            .with_label_values(&[err.variant_name()])
+            .inc();
+    }
+
+    /// Records the state of the database connection pool.
+    pub fn record_db_pool(&self, db: &Database) {
+        let state = db.pool.state();
+        let max_size = db.pool.max_size();
+        let in_use = state.connections.saturating_sub(state.idle_connections);
+        self.db_pool_connections.set(state.connections.into());
+        self.db_pool_idle_connections
+            .set(state.idle_connections.into());
+        self.db_pool_max_connections.set(max_size.into());
+        self.db_pool_saturation
+            .set(f64::from(in_use) / f64::from(max_size.max(1)));
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> Result<String, prometheus::Error> {
+        TextEncoder::new().encode_to_string(&self.registry.gather())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_reports_requests_by_route() {
+        let metrics = Metrics::new();
+        metrics.record_request("GET", "/package/<name>", 200, Duration::from_millis(20));
+        metrics.record_request("GET", "/package/<name>", 200, Duration::from_millis(40));
+        metrics.record_request("GET", "/package/<name>", 404, Duration::from_millis(5));
+
+        let rendered = metrics.render().unwrap();
+        assert!(rendered.contains(
+            r#"forc_pub_http_requests_total{method="GET",route="/package/<name>",status="200"} 2"#
+        ));
+        assert!(rendered.contains(
+            r#"forc_pub_http_requests_total{method="GET",route="/package/<name>",status="404"} 1"#
+        ));
+        assert!(rendered.contains(
+            r#"forc_pub_http_request_duration_seconds_count{method="GET",route="/package/<name>"} 3"#
+        ));
+    }
+
+    #[test]
+    fn render_reports_upload_pipeline() {
+        let metrics = Metrics::new();
+        drop(metrics.start_upload_stage(UploadStage::Build));
+        drop(metrics.start_upload_stage(UploadStage::S3));
+        drop(metrics.start_upload_stage(UploadStage::S3));
+        metrics.record_forc_install(true);
+        metrics.record_forc_install(true);
+        metrics.record_forc_install(false);
+        metrics.record_index_publish_failure(&IndexPublishError::PushError("rejected".into()));
+
+        let rendered = metrics.render().unwrap();
+        assert!(
+            rendered.contains(r#"forc_pub_upload_stage_duration_seconds_count{stage="build"} 1"#)
+        );
+        assert!(rendered.contains(r#"forc_pub_upload_stage_duration_seconds_count{stage="s3"} 2"#));
+        assert!(!rendered.contains(r#"stage="install""#));
+        assert!(rendered.contains(r#"forc_pub_forc_install_cache_total{result="hit"} 2"#));
+        assert!(rendered.contains(r#"forc_pub_forc_install_cache_total{result="miss"} 1"#));
+        assert!(rendered.contains(r#"forc_pub_index_publish_failures_total{error="PushError"} 1"#));
+    }
+}
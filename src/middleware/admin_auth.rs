@@ -0,0 +1,37 @@
+use crate::middleware::session_auth::{SessionAuth, SessionAuthError};
+use crate::models;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+use uuid::Uuid;
+
+/// A session of a user with admin rights.
+pub struct AdminAuth {
+    pub user: models::User,
+    pub session_id: Uuid,
+}
+
+#[derive(Debug)]
+pub enum AdminAuthError {
+    Session(SessionAuthError),
+    NotAdmin,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminAuth {
+    type Error = AdminAuthError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.guard::<SessionAuth>().await {
+            Outcome::Success(SessionAuth { user, session_id }) => {
+                if user.is_admin {
+                    Outcome::Success(AdminAuth { user, session_id })
+                } else {
+                    Outcome::Error((Status::Forbidden, AdminAuthError::NotAdmin))
+                }
+            }
+            Outcome::Error((status, e)) => Outcome::Error((status, AdminAuthError::Session(e))),
+            Outcome::Forward(status) => Outcome::Forward(status),
+        }
+    }
+}
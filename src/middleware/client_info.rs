@@ -0,0 +1,26 @@
+use rocket::http::hyper::header;
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+
+/// The client that made a request, as recorded in audit events.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientInfo {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(ClientInfo {
+            // Respects the `X-Real-IP` header set by the proxy in front of the server.
+            ip_address: request.client_ip().map(|ip| ip.to_string()),
+            user_agent: request
+                .headers()
+                .get_one(header::USER_AGENT.as_str())
+                .map(str::to_string),
+        })
+    }
+}
@@ -0,0 +1,73 @@
+use crate::metrics::metrics;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Data, Request, Response};
+use std::time::Instant;
+
+/// The route label of requests that didn't match a route, so that the paths of unknown
+/// URLs don't each get their own metric.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// When the handling of a request started, cached for the request.
+struct RequestStart(Instant);
+
+/// Records the number and latency of requests per route in the metrics.
+pub struct RequestMetrics;
+
+#[rocket::async_trait]
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request Metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        request.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let RequestStart(start) = request.local_cache(|| RequestStart(Instant::now()));
+        let route = request
+            .route()
+            .map(|route| route.uri.as_str().to_string())
+            .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+        metrics().record_request(
+            request.method().as_str(),
+            &route,
+            response.status().code,
+            start.elapsed(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+
+    #[rocket::get("/widgets/<id>")]
+    fn widget(id: u32) -> String {
+        id.to_string()
+    }
+
+    #[test]
+    fn request_metrics_counts_requests_by_route() {
+        let rocket = rocket::build()
+            .attach(RequestMetrics)
+            .mount("/", rocket::routes![widget]);
+        let client = Client::tracked(rocket).expect("valid rocket");
+        for id in [1, 2, 3] {
+            client.get(format!("/widgets/{id}")).dispatch();
+        }
+        client.get("/gadgets/1").dispatch();
+
+        let rendered = metrics().render().unwrap();
+        assert!(rendered.contains(
+            r#"forc_pub_http_requests_total{method="GET",route="/widgets/<id>",status="200"} 3"#
+        ));
+        assert!(rendered.contains(
+            r#"forc_pub_http_requests_total{method="GET",route="unmatched",status="404"} 1"#
+        ));
+    }
+}
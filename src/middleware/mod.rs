@@ -1,3 +1,7 @@
+pub mod admin_auth;
+pub mod client_info;
 pub mod cors;
+pub mod metrics;
+pub mod rate_limit;
 pub mod session_auth;
 pub mod token_auth;
@@ -0,0 +1,528 @@
+use crate::db::api_token::PlainToken;
+use crate::db::error::DatabaseError;
+use crate::db::Database;
+use crate::middleware::session_auth::SESSION_COOKIE_NAME;
+use crate::util::load_env;
+use chrono::{DateTime, TimeZone, Utc};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::hyper::header;
+use rocket::http::{Method, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Data, Request};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::{env, fmt};
+use thiserror::Error;
+use tokio::task;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+/// The number of buckets the in-memory store holds before windows that have ended are
+/// dropped.
+const MAX_MEMORY_BUCKETS: usize = 100_000;
+
+#[derive(Error, Debug)]
+pub enum RateLimitError {
+    #[error("Invalid rate limit store: {0}")]
+    InvalidStore(String),
+
+    #[error("Invalid rate limit quota {0}: expected <requests>/<window seconds>, got '{1}'")]
+    InvalidQuota(String, String),
+}
+
+/// A class of routes that share a quota.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteClass {
+    /// Uploading a project, which triggers a build.
+    Upload,
+    /// Publishing an upload.
+    Publish,
+    /// Searching packages.
+    Search,
+}
+
+impl RouteClass {
+    /// Returns the class of the route that a request with the given method and path is
+    /// for, or `None` if the route isn't rate limited.
+    pub fn of(method: Method, path: &str) -> Option<Self> {
+        match (method, path) {
+            (Method::Post, "/upload_project") => Some(RouteClass::Upload),
+            (Method::Post, "/publish") => Some(RouteClass::Publish),
+            (Method::Get, "/search") => Some(RouteClass::Search),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RouteClass::Upload => "upload",
+            RouteClass::Publish => "publish",
+            RouteClass::Search => "search",
+        }
+    }
+}
+
+impl fmt::Display for RouteClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The number of requests a bucket may make in each window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    pub requests: i64,
+    pub window_secs: i64,
+}
+
+impl Quota {
+    pub const fn new(requests: i64, window_secs: i64) -> Self {
+        Self {
+            requests,
+            window_secs,
+        }
+    }
+}
+
+/// Parses a quota in the form `<requests>/<seconds>`.
+impl FromStr for Quota {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (requests, window_secs) = s.split_once('/').ok_or(())?;
+        let requests = requests.trim().parse().map_err(|_| ())?;
+        let window_secs = window_secs.trim().parse().map_err(|_| ())?;
+        if requests < 0 || window_secs <= 0 {
+            return Err(());
+        }
+        Ok(Quota::new(requests, window_secs))
+    }
+}
+
+/// The quota of each route class.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitQuotas {
+    pub upload: Quota,
+    pub publish: Quota,
+    pub search: Quota,
+}
+
+impl Default for RateLimitQuotas {
+    fn default() -> Self {
+        Self {
+            upload: Quota::new(20, 3600),
+            publish: Quota::new(30, 3600),
+            search: Quota::new(60, 60),
+        }
+    }
+}
+
+impl RateLimitQuotas {
+    /// Returns the quotas set with `RATE_LIMIT_UPLOAD`, `RATE_LIMIT_PUBLISH` and
+    /// `RATE_LIMIT_SEARCH` in the `<requests>/<window seconds>` format. Routes whose variable
+    /// is unset or empty get the default quota. A malformed quota fails startup, since
+    /// falling back to the default could silently loosen a limit an operator tightened.
+    pub fn from_env() -> Result<Self, RateLimitError> {
+        load_env();
+        let var = |name: &str| match env::var(name) {
+            Ok(value) if !value.is_empty() => value
+                .parse()
+                .map(Some)
+                .map_err(|_| RateLimitError::InvalidQuota(name.to_string(), value)),
+            _ => Ok(None),
+        };
+        let default = Self::default();
+        Ok(Self {
+            upload: var("RATE_LIMIT_UPLOAD")?.unwrap_or(default.upload),
+            publish: var("RATE_LIMIT_PUBLISH")?.unwrap_or(default.publish),
+            search: var("RATE_LIMIT_SEARCH")?.unwrap_or(default.search),
+        })
+    }
+
+    pub fn quota(&self, class: RouteClass) -> Quota {
+        match class {
+            RouteClass::Upload => self.upload,
+            RouteClass::Publish => self.publish,
+            RouteClass::Search => self.search,
+        }
+    }
+}
+
+/// A bucket that requests are counted in. A request is counted in the buckets of its
+/// client IP, and of its API token or session user, if any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Bucket {
+    Ip(String),
+    Token(Uuid),
+    User(Uuid),
+}
+
+impl fmt::Display for Bucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bucket::Ip(ip) => write!(f, "ip:{ip}"),
+            Bucket::Token(id) => write!(f, "token:{id}"),
+            Bucket::User(id) => write!(f, "user:{id}"),
+        }
+    }
+}
+
+/// Where the request counts of rate limit windows are kept.
+pub trait RateLimitStore: Send + Sync {
+    /// Counts a request in the window of the bucket with the given key, and returns the
+    /// number of requests counted in the window so far.
+    fn hit(
+        &self,
+        key: &str,
+        window_start: DateTime<Utc>,
+        window_end: DateTime<Utc>,
+    ) -> Result<i64, DatabaseError>;
+}
+
+#[derive(Debug)]
+struct MemoryWindow {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    count: i64,
+}
+
+/// Keeps request counts in memory, so each server instance has its own quotas.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    windows: Mutex<HashMap<String, MemoryWindow>>,
+}
+
+impl RateLimitStore for MemoryStore {
+    fn hit(
+        &self,
+        key: &str,
+        window_start: DateTime<Utc>,
+        window_end: DateTime<Utc>,
+    ) -> Result<i64, DatabaseError> {
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        if windows.len() >= MAX_MEMORY_BUCKETS {
+            windows.retain(|_, window| window.end > window_start);
+        }
+        let window = windows
+            .entry(key.to_string())
+            .or_insert_with(|| MemoryWindow {
+                start: window_start,
+                end: window_end,
+                count: 0,
+            });
+        if window.start != window_start {
+            *window = MemoryWindow {
+                start: window_start,
+                end: window_end,
+                count: 0,
+            };
+        }
+        window.count += 1;
+        Ok(window.count)
+    }
+}
+
+/// Keeps request counts in Postgres, so quotas are shared by all server instances.
+pub struct PostgresStore {
+    db: Database,
+}
+
+impl PostgresStore {
+    pub fn new(db: Database) -> Self {
+        Self { db }
+    }
+}
+
+impl RateLimitStore for PostgresStore {
+    fn hit(
+        &self,
+        key: &str,
+        window_start: DateTime<Utc>,
+        window_end: DateTime<Utc>,
+    ) -> Result<i64, DatabaseError> {
+        self.db
+            .transaction(|conn| conn.hit_rate_limit_window(key, window_start, window_end))
+    }
+}
+
+/// The outcome of counting a request, cached for the [RateLimit] guard.
+#[derive(Debug, Clone, Copy, Default)]
+struct RateLimitDecision {
+    /// The seconds until the request may be retried, if it exceeded a quota.
+    retry_after: Option<u64>,
+}
+
+/// A fairing that counts requests to rate limited routes in per-token, per-user and per-IP
+/// buckets, with a separate quota for each [RouteClass]. Requests that exceed a quota are
+/// refused by the [RateLimit] guard of the route.
+///
+/// The store is selected with `RATE_LIMIT_STORE`: `memory` (default) or `postgres`.
+#[derive(Clone)]
+pub struct RateLimiter {
+    quotas: RateLimitQuotas,
+    store: Arc<dyn RateLimitStore>,
+}
+
+/// The parts of a request that identify its buckets.
+#[derive(Debug)]
+struct RequestIdentity {
+    ip: Option<String>,
+    token: Option<String>,
+    session_id: Option<Uuid>,
+}
+
+impl RequestIdentity {
+    fn of(request: &Request<'_>) -> Self {
+        Self {
+            ip: request.client_ip().map(|ip| ip.to_string()),
+            token: request
+                .headers()
+                .get_one(header::AUTHORIZATION.as_str())
+                .and_then(|auth| auth.strip_prefix("Bearer "))
+                .map(str::to_string),
+            session_id: request
+                .cookies()
+                .get(SESSION_COOKIE_NAME)
+                .and_then(|cookie| Uuid::parse_str(cookie.value()).ok()),
+        }
+    }
+}
+
+impl RateLimiter {
+    pub fn new(quotas: RateLimitQuotas, store: Box<dyn RateLimitStore>) -> Self {
+        Self {
+            quotas,
+            store: Arc::from(store),
+        }
+    }
+
+    pub fn from_env(db: &Database) -> Result<Self, RateLimitError> {
+        load_env();
+        let store: Box<dyn RateLimitStore> =
+            match env::var("RATE_LIMIT_STORE").unwrap_or_default().as_str() {
+                "" | "memory" => Box::new(MemoryStore::default()),
+                "postgres" => Box::new(PostgresStore::new(db.clone())),
+                other => return Err(RateLimitError::InvalidStore(other.to_string())),
+            };
+        Ok(Self::new(RateLimitQuotas::from_env()?, store))
+    }
+
+    /// Counts a request of the route class in each of the buckets, and returns the seconds
+    /// until the request may be retried if it exceeded the quota of any of them.
+    ///
+    /// Windows are fixed, so a bucket's count restarts at each multiple of the window
+    /// length. Requests are allowed if the store fails.
+    pub fn check(&self, class: RouteClass, buckets: &[Bucket], now: DateTime<Utc>) -> Option<u64> {
+        let quota = self.quotas.quota(class);
+        let start_secs = now.timestamp() - now.timestamp().rem_euclid(quota.window_secs);
+        let window_start = Utc.timestamp_opt(start_secs, 0).single()?;
+        let window_end = Utc
+            .timestamp_opt(start_secs + quota.window_secs, 0)
+            .single()?;
+
+        let mut exceeded = false;
+        for bucket in buckets {
+            let key = format!("{class}:{bucket}");
+            match self.store.hit(&key, window_start, window_end) {
+                Ok(count) if count > quota.requests => {
+                    warn!("Rate limit of {} exceeded by {}", class, bucket);
+                    exceeded = true;
+                }
+                Ok(_) => {}
+                Err(err) => error!("Failed to count request for rate limit: {err}"),
+            }
+        }
+
+        exceeded.then(|| (window_end - now).num_seconds().max(1) as u64)
+    }
+
+    /// Returns the buckets that the request is counted in. This looks up the token or
+    /// session of the request, so it blocks.
+    fn buckets(db: Option<&Database>, identity: RequestIdentity) -> Vec<Bucket> {
+        let mut buckets = Vec::new();
+        if let Some(ip) = identity.ip {
+            buckets.push(Bucket::Ip(ip));
+        }
+
+        let Some(db) = db else {
+            return buckets;
+        };
+
+        // Unknown tokens and sessions are refused by the route, so they only count
+        // towards the IP bucket.
+        if let Some(token) = identity.token {
+            if let Ok(token) = db.transaction(|conn| conn.get_token(PlainToken::from(token))) {
+                buckets.push(Bucket::Token(token.id));
+                buckets.push(Bucket::User(token.user_id));
+            }
+        } else if let Some(session_id) = identity.session_id {
+            if let Ok(user) = db.transaction(|conn| conn.get_user_for_session(session_id)) {
+                buckets.push(Bucket::User(user.id));
+            }
+        }
+        buckets
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for RateLimiter {
+    fn info(&self) -> Info {
+        Info {
+            name: "Rate limiter",
+            kind: Kind::Request,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        let Some(class) = RouteClass::of(request.method(), request.uri().path().as_str()) else {
+            return;
+        };
+        let identity = RequestIdentity::of(request);
+        let db = request.rocket().state::<Database>().cloned();
+        let limiter = self.clone();
+        // Looking up the buckets and counting the request may wait for the database.
+        let retry_after = task::spawn_blocking(move || {
+            let buckets = Self::buckets(db.as_ref(), identity);
+            limiter.check(class, &buckets, Utc::now())
+        })
+        .await
+        .unwrap_or_else(|err| {
+            error!("Failed to count request for rate limit: {err}");
+            None
+        });
+        request.local_cache(|| RateLimitDecision { retry_after });
+    }
+}
+
+/// Returns the seconds until a request that exceeded a rate limit may be retried.
+pub fn retry_after(request: &Request<'_>) -> Option<u64> {
+    request.local_cache(RateLimitDecision::default).retry_after
+}
+
+/// A request guard for rate limited routes, which fails with `429 Too Many Requests` if
+/// the [RateLimiter] found that the request exceeded a quota.
+pub struct RateLimit;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RateLimit {
+    type Error = u64;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match retry_after(request) {
+            Some(retry_after) => Outcome::Error((Status::TooManyRequests, retry_after)),
+            None => Outcome::Success(RateLimit),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(search: Quota) -> RateLimiter {
+        RateLimiter::new(
+            RateLimitQuotas {
+                search,
+                ..RateLimitQuotas::default()
+            },
+            Box::new(MemoryStore::default()),
+        )
+    }
+
+    #[test]
+    fn test_quota_from_str() {
+        assert_eq!("10/60".parse(), Ok(Quota::new(10, 60)));
+        assert_eq!(" 0 / 1 ".parse(), Ok(Quota::new(0, 1)));
+        for quota in ["10", "10/0", "-1/60", "ten/60", ""] {
+            assert_eq!(quota.parse::<Quota>(), Err(()), "{quota}");
+        }
+    }
+
+    #[test]
+    fn test_route_class_of() {
+        assert_eq!(
+            RouteClass::of(Method::Post, "/upload_project"),
+            Some(RouteClass::Upload)
+        );
+        assert_eq!(
+            RouteClass::of(Method::Post, "/publish"),
+            Some(RouteClass::Publish)
+        );
+        assert_eq!(
+            RouteClass::of(Method::Get, "/search"),
+            Some(RouteClass::Search)
+        );
+        assert_eq!(RouteClass::of(Method::Get, "/publish"), None);
+        assert_eq!(RouteClass::of(Method::Get, "/packages"), None);
+    }
+
+    #[test]
+    fn test_check_limits_each_bucket() {
+        let limiter = limiter(Quota::new(2, 60));
+        let now = Utc.timestamp_opt(1_000_040, 0).unwrap();
+        let ip = Bucket::Ip("127.0.0.1".to_string());
+        let user = Bucket::User(Uuid::new_v4());
+
+        assert_eq!(limiter.check(RouteClass::Search, &[ip.clone()], now), None);
+        assert_eq!(
+            limiter.check(RouteClass::Search, &[ip.clone(), user.clone()], now),
+            None
+        );
+
+        // The IP bucket is over its quota until the window ends, 40 seconds later.
+        assert_eq!(
+            limiter.check(RouteClass::Search, &[ip.clone(), user.clone()], now),
+            Some(40)
+        );
+
+        // Other route classes have their own quotas.
+        assert_eq!(limiter.check(RouteClass::Upload, &[ip.clone()], now), None);
+
+        // The count restarts in the next window.
+        let later = Utc.timestamp_opt(1_000_080, 0).unwrap();
+        assert_eq!(limiter.check(RouteClass::Search, &[ip, user], later), None);
+    }
+
+    #[test]
+    fn test_check_zero_quota_refuses_all_requests() {
+        let limiter = limiter(Quota::new(0, 60));
+        let now = Utc.timestamp_opt(999_960, 0).unwrap();
+        let ip = Bucket::Ip("127.0.0.1".to_string());
+        assert_eq!(limiter.check(RouteClass::Search, &[ip], now), Some(60));
+    }
+
+    #[rocket::get("/search")]
+    fn search(_limit: RateLimit) -> &'static str {
+        "ok"
+    }
+
+    #[rocket::catch(429)]
+    fn too_many_requests(req: &Request<'_>) -> String {
+        retry_after(req).expect("retry after").to_string()
+    }
+
+    #[test]
+    fn test_rate_limit_guard_refuses_requests_over_quota() {
+        use rocket::local::blocking::Client;
+
+        let rocket = rocket::build()
+            .attach(limiter(Quota::new(1, 3600)))
+            .mount("/", rocket::routes![search])
+            .register("/", rocket::catchers![too_many_requests]);
+        let client = Client::tracked(rocket).expect("valid rocket");
+        let request = || {
+            client
+                .get("/search")
+                .remote("127.0.0.1:8000".parse().unwrap())
+        };
+
+        let response = request().dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let response = request().dispatch();
+        assert_eq!(response.status(), Status::TooManyRequests);
+        let retry_after: u64 = response.into_string().unwrap().parse().unwrap();
+        assert!((1..=3600).contains(&retry_after));
+    }
+}
@@ -1,9 +1,9 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use diesel::prelude::*;
 use diesel::sql_types::BigInt;
 use diesel::sql_types::{Array, Nullable, Text, Timestamptz};
 use diesel::QueryableByName;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 #[derive(Queryable, Selectable, Debug, Clone)]
@@ -19,6 +19,7 @@ pub struct User {
     pub email: Option<String>,
     pub is_admin: bool,
     pub created_at: DateTime<Utc>,
+    pub publish_blocked: bool,
 }
 
 #[derive(Insertable)]
@@ -59,6 +60,8 @@ pub struct ApiToken {
     pub friendly_name: String,
     pub expires_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
+    pub scopes: Vec<String>,
+    pub package_glob: Option<String>,
 }
 
 #[derive(Insertable)]
@@ -68,6 +71,8 @@ pub struct NewApiToken {
     pub friendly_name: String,
     pub token: Vec<u8>,
     pub expires_at: Option<DateTime<Utc>>,
+    pub scopes: Vec<String>,
+    pub package_glob: Option<String>,
 }
 
 #[derive(Queryable, Selectable, Debug, Clone)]
@@ -83,9 +88,40 @@ pub struct Upload {
     pub forc_manifest: String,
     pub docs_ipfs_hash: Option<String>,
     pub created_at: DateTime<Utc>,
+    pub workspace_upload_id: Option<Uuid>,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone, PartialEq, Eq)]
+#[diesel(table_name = crate::schema::upload_jobs)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct UploadJob {
+    pub id: Uuid,
+    pub forc_version: String,
+    pub status: String,
+    pub message: Option<String>,
+    pub error: Option<String>,
+    pub diagnostics: Option<String>,
+    pub dry_run: bool,
+    pub report: Option<String>,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub run_at: DateTime<Utc>,
+    pub locked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::upload_jobs)]
+pub struct NewUploadJob {
+    pub id: Uuid,
+    pub forc_version: String,
+    pub tarball: Option<Vec<u8>>,
+    pub message: Option<String>,
+    pub dry_run: bool,
+}
+
+#[derive(Insertable, Serialize, Deserialize, Debug, Clone)]
 #[diesel(table_name = crate::schema::uploads)]
 pub struct NewUpload {
     pub id: Uuid,
@@ -96,6 +132,7 @@ pub struct NewUpload {
     pub readme: Option<String>,
     pub forc_manifest: String,
     pub docs_ipfs_hash: Option<String>,
+    pub workspace_upload_id: Option<Uuid>,
 }
 
 #[derive(Queryable, Selectable, Debug, Clone)]
@@ -117,6 +154,17 @@ pub struct NewPackageDep {
     pub dependency_version_req: String,
 }
 
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageDependent {
+    /// The name of the dependent package.
+    pub name: String,
+    /// The latest version of the dependent package.
+    pub version: String,
+    /// The version requirement the dependent package uses for the dependency.
+    pub version_req: String,
+}
+
 #[derive(Queryable, Selectable, Debug, Clone)]
 #[diesel(table_name = crate::schema::packages)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
@@ -126,6 +174,8 @@ pub struct Package {
     pub package_name: String,
     pub default_version: Option<Uuid>,
     pub created_at: DateTime<Utc>,
+    pub downloads: i64,
+    pub normalized_name: String,
 }
 
 #[derive(Insertable, Debug)]
@@ -133,6 +183,87 @@ pub struct Package {
 pub struct NewPackage {
     pub user_owner: Uuid,
     pub package_name: String,
+    pub normalized_name: String,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::audit_events)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AuditEvent {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub event: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub payload: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::audit_events)]
+pub struct NewAuditEvent {
+    pub user_id: Uuid,
+    pub event: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub payload: serde_json::Value,
+}
+
+#[derive(Queryable, QueryableByName, Selectable, Debug, Clone, PartialEq)]
+#[diesel(table_name = crate::schema::package_name_flags)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PackageNameFlag {
+    pub id: Uuid,
+    pub package_id: Uuid,
+    pub similar_package_id: Uuid,
+    pub similarity: f32,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone, Eq, PartialEq)]
+#[diesel(table_name = crate::schema::package_owners)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PackageOwner {
+    pub id: Uuid,
+    pub package_id: Uuid,
+    pub user_id: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::package_owners)]
+pub struct NewPackageOwner {
+    pub package_id: Uuid,
+    pub user_id: Uuid,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone, Eq, PartialEq)]
+#[diesel(table_name = crate::schema::package_owner_invites)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct PackageOwnerInvite {
+    pub id: Uuid,
+    pub package_id: Uuid,
+    pub invited_user_id: Uuid,
+    pub invited_by: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::schema::package_owner_invites)]
+pub struct NewPackageOwnerInvite {
+    pub package_id: Uuid,
+    pub invited_user_id: Uuid,
+    pub invited_by: Uuid,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageOwnerInviteInfo {
+    pub id: Uuid,
+    pub package_name: String,
+    pub invited_by: AuthorInfo,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Queryable, Selectable, Debug, Clone, Eq, PartialEq)]
@@ -151,6 +282,7 @@ pub struct PackageVersion {
     pub urls: Vec<Option<String>>,
     pub license: Option<String>,
     pub created_at: DateTime<Utc>,
+    pub yanked: bool,
 }
 
 #[derive(Insertable, Debug)]
@@ -216,6 +348,8 @@ pub struct PackagePreview {
     pub created_at: DateTime<Utc>,
     #[diesel(sql_type = Timestamptz)]
     pub updated_at: DateTime<Utc>,
+    #[diesel(sql_type = BigInt)]
+    pub downloads: i64,
 }
 
 #[derive(QueryableByName, Debug, Clone)]
@@ -249,6 +383,12 @@ pub struct FullPackage {
     pub created_at: DateTime<Utc>,
     #[diesel(sql_type = Timestamptz)]
     pub updated_at: DateTime<Utc>,
+    #[diesel(sql_type = BigInt)]
+    pub downloads: i64,
+
+    // Downloads of this version
+    #[diesel(sql_type = BigInt)]
+    pub version_downloads: i64,
 
     // Metadata from Uploads table
     #[diesel(sql_type = Nullable<Text>)]
@@ -294,13 +434,40 @@ pub struct CountResult {
     pub count: i64,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AuthorInfo {
     pub full_name: String,
     pub github_login: String,
 }
 
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEventInfo {
+    pub id: Uuid,
+    pub user: AuthorInfo,
+    pub event: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub payload: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(QueryableByName, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageNameFlagInfo {
+    #[diesel(sql_type = diesel::sql_types::Uuid)]
+    pub id: Uuid,
+    #[diesel(sql_type = Text)]
+    pub package_name: String,
+    #[diesel(sql_type = Text)]
+    pub similar_package_name: String,
+    #[diesel(sql_type = diesel::sql_types::Float4)]
+    pub similarity: f32,
+    #[diesel(sql_type = Timestamptz)]
+    pub created_at: DateTime<Utc>,
+}
+
 #[derive(Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct PackageVersionInfo {
@@ -308,4 +475,20 @@ pub struct PackageVersionInfo {
     pub author: AuthorInfo,
     pub license: Option<String>,
     pub created_at: DateTime<Utc>,
+    pub yanked: bool,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionDownloads {
+    pub version: String,
+    pub downloads: i64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyVersionDownloads {
+    pub date: NaiveDate,
+    pub version: String,
+    pub downloads: i64,
 }
@@ -8,6 +8,20 @@ diesel::table! {
         token -> Bytea,
         expires_at -> Nullable<Timestamptz>,
         created_at -> Timestamptz,
+        scopes -> Array<Text>,
+        package_glob -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    audit_events (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        event -> Varchar,
+        ip_address -> Nullable<Varchar>,
+        user_agent -> Nullable<Varchar>,
+        payload -> Jsonb,
+        created_at -> Timestamptz,
     }
 }
 
@@ -39,6 +53,44 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    package_name_flags (id) {
+        id -> Uuid,
+        package_id -> Uuid,
+        similar_package_id -> Uuid,
+        similarity -> Float4,
+        reviewed_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    package_owner_invites (id) {
+        id -> Uuid,
+        package_id -> Uuid,
+        invited_user_id -> Uuid,
+        invited_by -> Uuid,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    package_owners (id) {
+        id -> Uuid,
+        package_id -> Uuid,
+        user_id -> Uuid,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    package_version_downloads (package_version_id, date) {
+        package_version_id -> Uuid,
+        date -> Date,
+        downloads -> Int8,
+    }
+}
+
 diesel::table! {
     package_versions (id) {
         id -> Uuid,
@@ -54,6 +106,7 @@ diesel::table! {
         urls -> Array<Nullable<Text>>,
         license -> Nullable<Varchar>,
         created_at -> Timestamptz,
+        yanked -> Bool,
     }
 }
 
@@ -64,6 +117,27 @@ diesel::table! {
         package_name -> Varchar,
         default_version -> Nullable<Uuid>,
         created_at -> Timestamptz,
+        downloads -> Int8,
+        normalized_name -> Varchar,
+    }
+}
+
+diesel::table! {
+    rate_limit_windows (key) {
+        key -> Varchar,
+        window_start -> Timestamptz,
+        window_end -> Timestamptz,
+        count -> Int8,
+    }
+}
+
+diesel::table! {
+    removed_package_versions (id) {
+        id -> Uuid,
+        package_id -> Uuid,
+        num -> Varchar,
+        removed_by -> Uuid,
+        created_at -> Timestamptz,
     }
 }
 
@@ -76,6 +150,26 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    upload_jobs (id) {
+        id -> Uuid,
+        forc_version -> Varchar,
+        tarball -> Nullable<Bytea>,
+        status -> Varchar,
+        message -> Nullable<Text>,
+        error -> Nullable<Text>,
+        diagnostics -> Nullable<Text>,
+        dry_run -> Bool,
+        report -> Nullable<Text>,
+        attempts -> Int4,
+        max_attempts -> Int4,
+        run_at -> Timestamptz,
+        locked_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     uploads (id) {
         id -> Uuid,
@@ -87,6 +181,7 @@ diesel::table! {
         readme -> Nullable<Varchar>,
         forc_manifest -> Varchar,
         docs_ipfs_hash -> Nullable<Varchar>,
+        workspace_upload_id -> Nullable<Uuid>,
     }
 }
 
@@ -101,27 +196,43 @@ diesel::table! {
         is_admin -> Bool,
         created_at -> Timestamptz,
         github_id -> Varchar,
+        publish_blocked -> Bool,
     }
 }
 
 diesel::joinable!(api_tokens -> users (user_id));
+diesel::joinable!(audit_events -> users (user_id));
 diesel::joinable!(package_categories -> packages (package_id));
 diesel::joinable!(package_dependencies -> package_versions (dependent_package_version_id));
 diesel::joinable!(package_keywords -> packages (package_id));
+diesel::joinable!(package_owner_invites -> packages (package_id));
+diesel::joinable!(package_owners -> packages (package_id));
+diesel::joinable!(package_owners -> users (user_id));
+diesel::joinable!(package_version_downloads -> package_versions (package_version_id));
 diesel::joinable!(package_versions -> api_tokens (publish_token));
 diesel::joinable!(package_versions -> uploads (upload_id));
 diesel::joinable!(package_versions -> users (published_by));
 diesel::joinable!(packages -> users (user_owner));
+diesel::joinable!(removed_package_versions -> packages (package_id));
+diesel::joinable!(removed_package_versions -> users (removed_by));
 diesel::joinable!(sessions -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     api_tokens,
+    audit_events,
     package_categories,
     package_dependencies,
     package_keywords,
+    package_name_flags,
+    package_owner_invites,
+    package_owners,
+    package_version_downloads,
     package_versions,
     packages,
+    rate_limit_windows,
+    removed_package_versions,
     sessions,
+    upload_jobs,
     uploads,
     users,
 );
@@ -3,14 +3,23 @@
 
 use std::vec;
 
-use chrono::Utc;
+use chrono::{SubsecRound, Utc};
 use diesel::RunQueryDsl as _;
 use forc_pub::api;
 use forc_pub::api::pagination::Pagination;
+use forc_pub::api::publish::PublishRequest;
+use forc_pub::db::api_token::TokenScope;
+use forc_pub::db::error::DatabaseError;
+use forc_pub::db::upload_job::UploadJobStatus;
 use forc_pub::db::Database;
-use forc_pub::handlers::publish::PublishInfo;
+use forc_pub::handlers::dependency_graph::{handle_dependency_graph, DependencyStatus};
+use forc_pub::handlers::publish::{
+    handle_publish, handle_publish_dry_run, PublishError, PublishInfo,
+};
+use forc_pub::handlers::upload::UploadError;
+use forc_pub::middleware::client_info::ClientInfo;
 use forc_pub::models::FullPackageWithCategories;
-use forc_pub::models::{FullPackage, NewUpload, PackageVersion};
+use forc_pub::models::{FullPackage, NewPackageDep, NewUpload, PackageDependent, PackageVersion};
 use semver::Version;
 use serial_test::serial;
 use url::Url;
@@ -52,7 +61,10 @@ fn clear_tables(db: &mut Database) {
         diesel::delete(forc_pub::schema::packages::table).execute(conn.inner())?;
         diesel::delete(forc_pub::schema::api_tokens::table).execute(conn.inner())?;
         diesel::delete(forc_pub::schema::sessions::table).execute(conn.inner())?;
+        diesel::delete(forc_pub::schema::audit_events::table).execute(conn.inner())?;
         diesel::delete(forc_pub::schema::users::table).execute(conn.inner())?;
+        diesel::delete(forc_pub::schema::upload_jobs::table).execute(conn.inner())?;
+        diesel::delete(forc_pub::schema::rate_limit_windows::table).execute(conn.inner())?;
         Ok::<(), diesel::result::Error>(())
     })
     .expect("clear tables");
@@ -163,6 +175,135 @@ fn test_api_tokens() {
     });
 }
 
+#[test]
+#[serial]
+fn test_scoped_api_tokens() {
+    let db = &mut setup_db();
+    let (full_token, update_token, yank_token, upload) = db
+        .transaction(|conn| {
+            // Set up session, user, tokens, and upload.
+            let session = conn
+                .new_user_session(&mock_user_1(), 1000)
+                .expect("session is ok");
+            let user = conn.get_user_for_session(session.id).expect("user is ok");
+            let (full_token, _) = conn
+                .new_token(user.id, TEST_TOKEN_NAME_1.into())
+                .expect("token is ok");
+            let expires_at = (Utc::now() + chrono::Duration::days(30)).trunc_subsecs(0);
+            let (update_token, _) = conn
+                .new_scoped_token(
+                    user.id,
+                    TEST_TOKEN_NAME_2.into(),
+                    Some(expires_at),
+                    &[TokenScope::PublishUpdate],
+                    Some("test-*".into()),
+                )
+                .expect("token is ok");
+            assert_eq!(update_token.expires_at, Some(expires_at));
+            assert_eq!(update_token.scopes, vec!["publish-update".to_string()]);
+            assert_eq!(update_token.package_glob, Some("test-*".to_string()));
+            let (yank_token, _) = conn
+                .new_scoped_token(
+                    user.id,
+                    "yank token".into(),
+                    None,
+                    &[TokenScope::Yank],
+                    None,
+                )
+                .expect("token is ok");
+
+            // Invalid package globs are rejected.
+            let result = conn.new_scoped_token(
+                user.id,
+                "bad token".into(),
+                None,
+                &[TokenScope::Yank],
+                Some("test/*".into()),
+            );
+            assert!(result.is_err());
+
+            let upload = conn
+                .new_upload(&NewUpload {
+                    id: uuid::Uuid::new_v4(),
+                    forc_version: TEST_VERSION_1.into(),
+                    source_code_ipfs_hash: "test-ipfs-hash".into(),
+                    abi_ipfs_hash: None,
+                    bytecode_identifier: None,
+                    readme: None,
+                    forc_manifest: TEST_MANIFEST.into(),
+                    docs_ipfs_hash: None,
+                    workspace_upload_id: None,
+                })
+                .expect("upload is ok");
+            Ok::<_, diesel::result::Error>((full_token, update_token, yank_token, upload))
+        })
+        .unwrap();
+
+    let publish_info = |name: &str, version: &str| PublishInfo {
+        package_name: name.into(),
+        upload_id: upload.id,
+        num: Version::parse(version).unwrap(),
+        package_description: None,
+        repository: None,
+        documentation: None,
+        homepage: None,
+        urls: vec![],
+        readme: None,
+        license: None,
+    };
+
+    let _ = db.transaction(|conn| {
+        // A token without the publish-new scope can't publish a new package.
+        let result = conn.new_package_version(
+            &update_token,
+            &publish_info(TEST_PACKAGE_NAME, TEST_VERSION_1),
+        );
+        assert!(result.is_err());
+        conn.new_package_version(
+            &full_token,
+            &publish_info(TEST_PACKAGE_NAME, TEST_VERSION_1),
+        )
+        .expect("version result is ok");
+
+        // A token with the publish-update scope can publish new versions of matching packages.
+        conn.new_package_version(
+            &update_token,
+            &publish_info(TEST_PACKAGE_NAME, TEST_VERSION_2),
+        )
+        .expect("version result is ok");
+        conn.new_package_version(&full_token, &publish_info("other-package", TEST_VERSION_1))
+            .expect("version result is ok");
+        let result = conn.new_package_version(
+            &update_token,
+            &publish_info("other-package", TEST_VERSION_2),
+        );
+        assert!(result.is_err());
+
+        // Only tokens with the yank scope can yank.
+        let result = conn.set_package_version_yanked(
+            &update_token,
+            TEST_PACKAGE_NAME.into(),
+            TEST_VERSION_2.into(),
+            true,
+        );
+        assert!(result.is_err());
+        let result = conn.new_package_version(
+            &yank_token,
+            &publish_info(TEST_PACKAGE_NAME, TEST_VERSION_3),
+        );
+        assert!(result.is_err());
+        conn.set_package_version_yanked(
+            &yank_token,
+            TEST_PACKAGE_NAME.into(),
+            TEST_VERSION_2.into(),
+            true,
+        )
+        .expect("yank is ok");
+
+        Ok::<(), diesel::result::Error>(())
+    });
+}
+
 #[test]
 #[serial]
 fn test_package_versions() {
@@ -187,6 +328,7 @@ fn test_package_versions() {
                     readme: Some(TEST_README.into()),
                     forc_manifest: TEST_MANIFEST.into(),
                     docs_ipfs_hash: Some("test-docs-hash".into()),
+                    workspace_upload_id: None,
                 })
                 .expect("upload is ok");
             Ok::<_, diesel::result::Error>((token, user, upload))
@@ -227,6 +369,7 @@ fn test_package_versions() {
                     urls: vec![Some(TEST_URL_OTHER.into())],
                     license: request.license,
                     created_at: version_result.created_at,
+                    yanked: false,
                 }
             );
 
@@ -258,6 +401,8 @@ fn test_package_versions() {
                 license: Some(TEST_LICENSE.into()),
                 created_at: result.created_at,
                 updated_at: version_result.created_at,
+                downloads: 0,
+                version_downloads: 0,
                 bytecode_identifier: upload.bytecode_identifier,
                 forc_version: upload.forc_version,
                 source_code_ipfs_hash: upload.source_code_ipfs_hash,
@@ -266,166 +411,1671 @@ fn test_package_versions() {
             }
         );
 
-        Ok::<(), diesel::result::Error>(())
-    });
-    let request = PublishInfo {
-        package_name: TEST_PACKAGE_NAME.into(),
-        upload_id: upload.id,
-        num: Version::parse(TEST_VERSION_2).unwrap(),
-        package_description: Some("test description 2".into()),
-        repository: Url::parse(TEST_URL_REPO).ok(),
-        documentation: Url::parse(TEST_URL_DOC).ok(),
-        homepage: Url::parse(TEST_URL_HOME).ok(),
-        urls: vec![Url::parse(TEST_URL_OTHER).expect("other url")],
-        readme: Some("test readme 2".into()),
-        license: Some("test licens 2".into()),
+        Ok::<(), diesel::result::Error>(())
+    });
+    let request = PublishInfo {
+        package_name: TEST_PACKAGE_NAME.into(),
+        upload_id: upload.id,
+        num: Version::parse(TEST_VERSION_2).unwrap(),
+        package_description: Some("test description 2".into()),
+        repository: Url::parse(TEST_URL_REPO).ok(),
+        documentation: Url::parse(TEST_URL_DOC).ok(),
+        homepage: Url::parse(TEST_URL_HOME).ok(),
+        urls: vec![Url::parse(TEST_URL_OTHER).expect("other url")],
+        readme: Some("test readme 2".into()),
+        license: Some("test licens 2".into()),
+    };
+
+    let _ = db.transaction(|conn| {
+        // Insert a package version for a package that already exists
+        let version_result = conn
+            .new_package_version(&token, &request)
+            .expect("version result is ok");
+        assert_eq!(
+            version_result,
+            PackageVersion {
+                id: version_result.id,
+                package_id: version_result.package_id,
+                published_by: user.id,
+                upload_id: upload.id,
+                num: TEST_VERSION_2.into(),
+                package_description: request.package_description,
+                repository: Some(TEST_URL_REPO.into()),
+                documentation: Some(TEST_URL_DOC.into()),
+                homepage: Some(TEST_URL_HOME.into()),
+                urls: vec![Some(TEST_URL_OTHER.into())],
+                license: request.license,
+                created_at: version_result.created_at,
+                yanked: false,
+            }
+        );
+        let pkg_result = conn
+            .get_package_by_id(version_result.package_id)
+            .expect("pkg result is ok");
+        assert_eq!(pkg_result.package_name, TEST_PACKAGE_NAME);
+        assert_eq!(pkg_result.user_owner, user.id);
+        assert_eq!(pkg_result.default_version, Some(version_result.id));
+        Ok::<(), diesel::result::Error>(())
+    });
+    let _ = db.transaction(|conn| {
+        // Test get_full_packages page 1
+        let result = conn
+            .get_full_packages(
+                None,
+                Pagination {
+                    page: Some(1),
+                    per_page: Some(1),
+                },
+            )
+            .expect("get_full_packages result is ok");
+
+        assert_eq!(result.current_page, 1);
+        assert_eq!(result.per_page, 1);
+        assert_eq!(result.total_pages, 2);
+        assert_eq!(result.total_count, 2);
+        assert_eq!(result.data.len(), 1);
+        assert_eq!(result.data[0].version, TEST_VERSION_2); // latest version
+
+        // Test get_full_packages page 2
+        let result = conn
+            .get_full_packages(
+                None,
+                Pagination {
+                    page: Some(2),
+                    per_page: Some(1),
+                },
+            )
+            .expect("get_full_packages result is ok");
+
+        assert_eq!(result.current_page, 2);
+        assert_eq!(result.per_page, 1);
+        assert_eq!(result.total_pages, 2);
+        assert_eq!(result.total_count, 2);
+        assert_eq!(result.data.len(), 1);
+        assert_eq!(result.data[0].version, TEST_VERSION_1); // latest version
+
+        // Test get_full_packages date filter (should return no results)
+        let result = conn
+            .get_full_packages(
+                Some(Utc::now()),
+                Pagination {
+                    page: Some(1),
+                    per_page: Some(1),
+                },
+            )
+            .expect("get_full_packages result is ok");
+        assert_eq!(result.current_page, 1);
+        assert_eq!(result.per_page, 1);
+        assert_eq!(result.total_pages, 0);
+        assert_eq!(result.total_count, 0);
+        assert_eq!(result.data.len(), 0);
+        Ok::<(), diesel::result::Error>(())
+    });
+}
+
+#[test]
+#[serial]
+fn test_workspace_uploads() {
+    let db = &mut setup_db();
+    let _ = db.transaction(|conn| {
+        let new_upload = |id, workspace_upload_id| NewUpload {
+            id,
+            forc_version: TEST_VERSION_1.into(),
+            source_code_ipfs_hash: "test-ipfs-hash".into(),
+            abi_ipfs_hash: None,
+            bytecode_identifier: None,
+            readme: None,
+            forc_manifest: TEST_MANIFEST.into(),
+            docs_ipfs_hash: None,
+            workspace_upload_id,
+        };
+
+        // A package upload is published on its own.
+        let package_upload = conn
+            .new_upload(&new_upload(uuid::Uuid::new_v4(), None))
+            .expect("upload is ok");
+        let uploads = conn
+            .get_uploads_to_publish(package_upload.id)
+            .expect("uploads are ok");
+        assert_eq!(uploads.len(), 1);
+        assert_eq!(uploads[0].id, package_upload.id);
+
+        // A workspace upload publishes all of its members.
+        let workspace_upload_id = uuid::Uuid::new_v4();
+        let member_1 = conn
+            .new_upload(&new_upload(uuid::Uuid::new_v4(), Some(workspace_upload_id)))
+            .expect("upload is ok");
+        let member_2 = conn
+            .new_upload(&new_upload(uuid::Uuid::new_v4(), Some(workspace_upload_id)))
+            .expect("upload is ok");
+        let mut upload_ids = conn
+            .get_uploads_to_publish(workspace_upload_id)
+            .expect("uploads are ok")
+            .into_iter()
+            .map(|upload| upload.id)
+            .collect::<Vec<_>>();
+        upload_ids.sort();
+        let mut expected = vec![member_1.id, member_2.id];
+        expected.sort();
+        assert_eq!(upload_ids, expected);
+
+        // Unknown upload IDs are not found.
+        assert!(conn.get_uploads_to_publish(uuid::Uuid::new_v4()).is_err());
+
+        Ok::<(), diesel::result::Error>(())
+    });
+}
+
+#[test]
+#[serial]
+fn test_package_categories_keywords() {
+    let db = &mut setup_db();
+    let _ = db.transaction(|conn| {
+        // Set up session, user, token, and upload.
+        let session = conn
+            .new_user_session(&mock_user_1(), 1000)
+            .expect("session is ok");
+        let user = conn.get_user_for_session(session.id).expect("user is ok");
+        let (token, _) = conn
+            .new_token(user.id, "test token".to_string())
+            .expect("token is ok");
+        let upload = conn
+            .new_upload(&NewUpload {
+                id: uuid::Uuid::new_v4(),
+                forc_version: TEST_VERSION_1.into(),
+                source_code_ipfs_hash: "test-ipfs-hash".into(),
+                abi_ipfs_hash: None,
+                bytecode_identifier: None,
+                readme: None,
+                forc_manifest: TEST_MANIFEST.into(),
+                docs_ipfs_hash: Some("test-docs-hash".into()),
+                workspace_upload_id: None,
+            })
+            .expect("upload is ok");
+
+        let request = PublishInfo {
+            package_name: TEST_PACKAGE_NAME.into(),
+            upload_id: upload.id,
+            num: Version::parse(TEST_VERSION_3).unwrap(),
+            package_description: None,
+            repository: None,
+            documentation: None,
+            homepage: None,
+            urls: vec![],
+            readme: None,
+            license: None,
+        };
+
+        let version_result = conn
+            .new_package_version(&token, &request)
+            .expect("version result is ok");
+
+        // Insert categories
+        let categories = vec!["cat1".to_string(), "cat2".to_string()];
+        let cat_count = conn
+            .insert_categories(version_result.package_id, &categories)
+            .expect("insert categories is ok");
+        assert_eq!(cat_count, 2);
+
+        // Insert keywords
+        let keywords = vec!["key1".to_string(), "key2".to_string()];
+        let key_count = conn
+            .insert_keywords(version_result.package_id, &keywords)
+            .expect("insert keywords is ok");
+        assert_eq!(key_count, 2);
+
+        Ok::<(), diesel::result::Error>(())
+    });
+}
+
+#[test]
+#[serial]
+fn test_yank_package_versions() {
+    let db = &mut setup_db();
+    let (token, other_token, upload) = db
+        .transaction(|conn| {
+            // Set up sessions, users, tokens, and upload.
+            let session = conn
+                .new_user_session(&mock_user_1(), 1000)
+                .expect("session is ok");
+            let user = conn.get_user_for_session(session.id).expect("user is ok");
+            let (token, _) = conn
+                .new_token(user.id, "test token".to_string())
+                .expect("token is ok");
+            let other_session = conn
+                .new_user_session(&mock_user_2(), 1000)
+                .expect("session is ok");
+            let other_user = conn
+                .get_user_for_session(other_session.id)
+                .expect("user is ok");
+            let (other_token, _) = conn
+                .new_token(other_user.id, "other token".to_string())
+                .expect("token is ok");
+            let upload = conn
+                .new_upload(&NewUpload {
+                    id: uuid::Uuid::new_v4(),
+                    forc_version: TEST_VERSION_1.into(),
+                    source_code_ipfs_hash: "test-ipfs-hash".into(),
+                    abi_ipfs_hash: None,
+                    bytecode_identifier: None,
+                    readme: None,
+                    forc_manifest: TEST_MANIFEST.into(),
+                    docs_ipfs_hash: None,
+                    workspace_upload_id: None,
+                })
+                .expect("upload is ok");
+            Ok::<_, diesel::result::Error>((token, other_token, upload))
+        })
+        .unwrap();
+
+    // Publish each version in its own transaction so they get distinct timestamps.
+    let versions: Vec<PackageVersion> = [TEST_VERSION_1, TEST_VERSION_2]
+        .into_iter()
+        .map(|version| {
+            let request = PublishInfo {
+                package_name: TEST_PACKAGE_NAME.into(),
+                upload_id: upload.id,
+                num: Version::parse(version).unwrap(),
+                package_description: None,
+                repository: None,
+                documentation: None,
+                homepage: None,
+                urls: vec![],
+                readme: None,
+                license: None,
+            };
+            db.transaction(|conn| conn.new_package_version(&token, &request))
+                .expect("version result is ok")
+        })
+        .collect();
+    assert!(versions.iter().all(|v| !v.yanked));
+
+    let _ = db.transaction(|conn| {
+        // Only the package owner can yank a version.
+        let result = conn.set_package_version_yanked(
+            &other_token,
+            TEST_PACKAGE_NAME.into(),
+            TEST_VERSION_2.into(),
+            true,
+        );
+        assert!(result.is_err());
+
+        // Yanking a version that doesn't exist fails.
+        let result = conn.set_package_version_yanked(
+            &token,
+            TEST_PACKAGE_NAME.into(),
+            TEST_VERSION_3.into(),
+            true,
+        );
+        assert!(result.is_err());
+
+        // Yank the latest version, the default version falls back to the previous one.
+        let yanked = conn
+            .set_package_version_yanked(
+                &token,
+                TEST_PACKAGE_NAME.into(),
+                TEST_VERSION_2.into(),
+                true,
+            )
+            .expect("yank is ok");
+        assert!(yanked.yanked);
+        assert_eq!(yanked.id, versions[1].id);
+        let package = conn
+            .get_package_by_id(yanked.package_id)
+            .expect("package is ok");
+        assert_eq!(package.default_version, Some(versions[0].id));
+
+        let version_infos = conn
+            .get_package_versions(TEST_PACKAGE_NAME.into())
+            .expect("versions are ok");
+        assert_eq!(version_infos.len(), 2);
+        assert_eq!(version_infos[0].version, TEST_VERSION_2);
+        assert!(version_infos[0].yanked);
+        assert!(!version_infos[1].yanked);
+
+        // Un-yank the latest version, it becomes the default version again.
+        let unyanked = conn
+            .set_package_version_yanked(
+                &token,
+                TEST_PACKAGE_NAME.into(),
+                TEST_VERSION_2.into(),
+                false,
+            )
+            .expect("unyank is ok");
+        assert!(!unyanked.yanked);
+        let package = conn
+            .get_package_by_id(unyanked.package_id)
+            .expect("package is ok");
+        assert_eq!(package.default_version, Some(versions[1].id));
+
+        Ok::<(), diesel::result::Error>(())
+    });
+}
+
+#[test]
+#[serial]
+fn test_package_owners() {
+    let db = &mut setup_db();
+    let (user, token, other_user, other_token, upload) = db
+        .transaction(|conn| {
+            // Set up sessions, users, tokens, and upload.
+            let session = conn
+                .new_user_session(&mock_user_1(), 1000)
+                .expect("session is ok");
+            let user = conn.get_user_for_session(session.id).expect("user is ok");
+            let (token, _) = conn
+                .new_token(user.id, "test token".to_string())
+                .expect("token is ok");
+            let other_session = conn
+                .new_user_session(&mock_user_2(), 1000)
+                .expect("session is ok");
+            let other_user = conn
+                .get_user_for_session(other_session.id)
+                .expect("user is ok");
+            let (other_token, _) = conn
+                .new_token(other_user.id, "other token".to_string())
+                .expect("token is ok");
+            let upload = conn
+                .new_upload(&NewUpload {
+                    id: uuid::Uuid::new_v4(),
+                    forc_version: TEST_VERSION_1.into(),
+                    source_code_ipfs_hash: "test-ipfs-hash".into(),
+                    abi_ipfs_hash: None,
+                    bytecode_identifier: None,
+                    readme: None,
+                    forc_manifest: TEST_MANIFEST.into(),
+                    docs_ipfs_hash: None,
+                    workspace_upload_id: None,
+                })
+                .expect("upload is ok");
+            Ok::<_, diesel::result::Error>((user, token, other_user, other_token, upload))
+        })
+        .unwrap();
+
+    let publish_info = |version: &str| PublishInfo {
+        package_name: TEST_PACKAGE_NAME.into(),
+        upload_id: upload.id,
+        num: Version::parse(version).unwrap(),
+        package_description: None,
+        repository: None,
+        documentation: None,
+        homepage: None,
+        urls: vec![],
+        readme: None,
+        license: None,
+    };
+
+    let _ = db.transaction(|conn| {
+        // The publisher of a new package becomes its only owner.
+        conn.new_package_version(&token, &publish_info(TEST_VERSION_1))
+            .expect("version result is ok");
+        let owners = conn
+            .get_package_owners(TEST_PACKAGE_NAME.into())
+            .expect("owners are ok");
+        assert_eq!(owners.len(), 1);
+        assert_eq!(owners[0].github_login, TEST_LOGIN_1);
+
+        // Other users can't publish, invite owners or remove owners.
+        let result = conn.new_package_version(&other_token, &publish_info(TEST_VERSION_2));
+        assert!(result.is_err());
+        let result = conn.new_package_owner_invite(
+            other_user.id,
+            TEST_PACKAGE_NAME.into(),
+            TEST_LOGIN_2.into(),
+        );
+        assert!(result.is_err());
+        let result =
+            conn.remove_package_owner(other_user.id, TEST_PACKAGE_NAME.into(), TEST_LOGIN_1.into());
+        assert!(result.is_err());
+
+        // The last owner can't be removed.
+        let result =
+            conn.remove_package_owner(user.id, TEST_PACKAGE_NAME.into(), TEST_LOGIN_1.into());
+        assert!(result.is_err());
+
+        // Inviting an unknown user or an existing owner fails.
+        let result =
+            conn.new_package_owner_invite(user.id, TEST_PACKAGE_NAME.into(), "unknown".into());
+        assert!(result.is_err());
+        let result =
+            conn.new_package_owner_invite(user.id, TEST_PACKAGE_NAME.into(), TEST_LOGIN_1.into());
+        assert!(result.is_err());
+
+        // Invite the other user.
+        let invite = conn
+            .new_package_owner_invite(user.id, TEST_PACKAGE_NAME.into(), TEST_LOGIN_2.into())
+            .expect("invite is ok");
+        assert!(conn
+            .get_package_owner_invites_for_user(user.id)
+            .expect("invites are ok")
+            .is_empty());
+        let invites = conn
+            .get_package_owner_invites_for_user(other_user.id)
+            .expect("invites are ok");
+        assert_eq!(invites.len(), 1);
+        assert_eq!(invites[0].id, invite.id);
+        assert_eq!(invites[0].package_name, TEST_PACKAGE_NAME);
+        assert_eq!(invites[0].invited_by.github_login, TEST_LOGIN_1);
+
+        // Only the invited user can accept the invite.
+        let result = conn.accept_package_owner_invite(user.id, invite.id.to_string());
+        assert!(result.is_err());
+        conn.accept_package_owner_invite(other_user.id, invite.id.to_string())
+            .expect("accept is ok");
+        assert!(conn
+            .get_package_owner_invites_for_user(other_user.id)
+            .expect("invites are ok")
+            .is_empty());
+
+        // The new owner can publish.
+        let owners = conn
+            .get_package_owners(TEST_PACKAGE_NAME.into())
+            .expect("owners are ok");
+        assert_eq!(owners.len(), 2);
+        conn.new_package_version(&other_token, &publish_info(TEST_VERSION_2))
+            .expect("version result is ok");
+
+        // The new owner can remove the original owner, who can no longer publish.
+        conn.remove_package_owner(other_user.id, TEST_PACKAGE_NAME.into(), TEST_LOGIN_1.into())
+            .expect("remove is ok");
+        let owners = conn
+            .get_package_owners(TEST_PACKAGE_NAME.into())
+            .expect("owners are ok");
+        assert_eq!(owners.len(), 1);
+        assert_eq!(owners[0].github_login, TEST_LOGIN_2);
+        let result = conn.new_package_version(&token, &publish_info(TEST_VERSION_3));
+        assert!(result.is_err());
+
+        // Only the invited user can decline an invite, and only once.
+        let invite = conn
+            .new_package_owner_invite(other_user.id, TEST_PACKAGE_NAME.into(), TEST_LOGIN_1.into())
+            .expect("invite is ok");
+        let result = conn.decline_package_owner_invite(other_user.id, invite.id.to_string());
+        assert!(matches!(result, Err(DatabaseError::NotFound(..))));
+        conn.decline_package_owner_invite(user.id, invite.id.to_string())
+            .expect("decline is ok");
+        let result = conn.decline_package_owner_invite(user.id, invite.id.to_string());
+        assert!(matches!(result, Err(DatabaseError::NotFound(..))));
+
+        // Accepting an invite after becoming an owner fails.
+        let invite = conn
+            .new_package_owner_invite(other_user.id, TEST_PACKAGE_NAME.into(), TEST_LOGIN_1.into())
+            .expect("invite is ok");
+        let package = conn
+            .get_package_by_name(TEST_PACKAGE_NAME.into())
+            .expect("package is ok");
+        conn.add_package_owner(package.id, user.id)
+            .expect("add owner is ok");
+        let result = conn.accept_package_owner_invite(user.id, invite.id.to_string());
+        assert!(matches!(
+            result,
+            Err(DatabaseError::AlreadyPackageOwner(..))
+        ));
+
+        Ok::<(), diesel::result::Error>(())
+    });
+}
+
+#[test]
+#[serial]
+fn test_package_downloads_semver_order() {
+    let db = &mut setup_db();
+    let (token, upload) = db
+        .transaction(|conn| {
+            let session = conn
+                .new_user_session(&mock_user_1(), 1000)
+                .expect("session is ok");
+            let user = conn.get_user_for_session(session.id).expect("user is ok");
+            let (token, _) = conn
+                .new_token(user.id, "test token".to_string())
+                .expect("token is ok");
+            let upload = conn
+                .new_upload(&NewUpload {
+                    id: uuid::Uuid::new_v4(),
+                    forc_version: TEST_VERSION_1.into(),
+                    source_code_ipfs_hash: "test-ipfs-hash".into(),
+                    abi_ipfs_hash: None,
+                    bytecode_identifier: None,
+                    readme: None,
+                    forc_manifest: TEST_MANIFEST.into(),
+                    docs_ipfs_hash: None,
+                    workspace_upload_id: None,
+                })
+                .expect("upload is ok");
+            Ok::<_, diesel::result::Error>((token, upload))
+        })
+        .unwrap();
+
+    let publish_info = |version: &str| PublishInfo {
+        package_name: TEST_PACKAGE_NAME.into(),
+        upload_id: upload.id,
+        num: Version::parse(version).unwrap(),
+        package_description: Some(TEST_DESCRIPTION.into()),
+        repository: None,
+        documentation: None,
+        homepage: None,
+        urls: vec![],
+        readme: None,
+        license: None,
+    };
+
+    let _ = db.transaction(|conn| {
+        // Versions are published out of order, and sort differently as strings.
+        for version in ["0.10.0", "0.2.0", "0.9.0"] {
+            conn.new_package_version(&token, &publish_info(version))
+                .expect("version result is ok");
+            conn.record_download(TEST_PACKAGE_NAME.into(), version.into())
+                .expect("record download is ok");
+        }
+
+        let versions = conn
+            .get_version_downloads(TEST_PACKAGE_NAME.into())
+            .expect("version downloads are ok");
+        assert_eq!(
+            versions
+                .iter()
+                .map(|v| v.version.as_str())
+                .collect::<Vec<_>>(),
+            vec!["0.10.0", "0.9.0", "0.2.0"]
+        );
+
+        let daily = conn
+            .get_daily_downloads(TEST_PACKAGE_NAME.into(), Utc::now().date_naive())
+            .expect("daily downloads are ok");
+        assert_eq!(
+            daily.iter().map(|d| d.version.as_str()).collect::<Vec<_>>(),
+            vec!["0.2.0", "0.9.0", "0.10.0"]
+        );
+
+        Ok::<(), diesel::result::Error>(())
+    });
+}
+
+#[test]
+#[serial]
+fn test_package_downloads() {
+    let db = &mut setup_db();
+    let (token, upload) = db
+        .transaction(|conn| {
+            let session = conn
+                .new_user_session(&mock_user_1(), 1000)
+                .expect("session is ok");
+            let user = conn.get_user_for_session(session.id).expect("user is ok");
+            let (token, _) = conn
+                .new_token(user.id, "test token".to_string())
+                .expect("token is ok");
+            let upload = conn
+                .new_upload(&NewUpload {
+                    id: uuid::Uuid::new_v4(),
+                    forc_version: TEST_VERSION_1.into(),
+                    source_code_ipfs_hash: "test-ipfs-hash".into(),
+                    abi_ipfs_hash: None,
+                    bytecode_identifier: None,
+                    readme: None,
+                    forc_manifest: TEST_MANIFEST.into(),
+                    docs_ipfs_hash: None,
+                    workspace_upload_id: None,
+                })
+                .expect("upload is ok");
+            Ok::<_, diesel::result::Error>((token, upload))
+        })
+        .unwrap();
+
+    let publish_info = |version: &str| PublishInfo {
+        package_name: TEST_PACKAGE_NAME.into(),
+        upload_id: upload.id,
+        num: Version::parse(version).unwrap(),
+        package_description: Some(TEST_DESCRIPTION.into()),
+        repository: None,
+        documentation: None,
+        homepage: None,
+        urls: vec![],
+        readme: None,
+        license: None,
+    };
+
+    let _ = db.transaction(|conn| {
+        conn.new_package_version(&token, &publish_info(TEST_VERSION_1))
+            .expect("version result is ok");
+        conn.new_package_version(&token, &publish_info(TEST_VERSION_2))
+            .expect("version result is ok");
+
+        for _ in 0..3 {
+            conn.record_download(TEST_PACKAGE_NAME.into(), TEST_VERSION_1.into())
+                .expect("record download is ok");
+        }
+        conn.record_download(TEST_PACKAGE_NAME.into(), TEST_VERSION_2.into())
+            .expect("record download is ok");
+
+        // Downloads of unknown versions aren't recorded.
+        let result = conn.record_download(TEST_PACKAGE_NAME.into(), TEST_VERSION_3.into());
+        assert!(result.is_err());
+
+        let package = conn
+            .get_package_by_name(TEST_PACKAGE_NAME.into())
+            .expect("package is ok");
+        assert_eq!(package.downloads, 4);
+
+        let versions = conn
+            .get_version_downloads(TEST_PACKAGE_NAME.into())
+            .expect("version downloads are ok");
+        assert_eq!(
+            versions
+                .iter()
+                .map(|v| (v.version.as_str(), v.downloads))
+                .collect::<Vec<_>>(),
+            vec![(TEST_VERSION_2, 1), (TEST_VERSION_1, 3)]
+        );
+
+        // Downloads are rolled up into one row per version per day.
+        let today = Utc::now().date_naive();
+        let daily = conn
+            .get_daily_downloads(TEST_PACKAGE_NAME.into(), today)
+            .expect("daily downloads are ok");
+        assert_eq!(daily.len(), 2);
+        assert_eq!(daily[0].date, today);
+        assert_eq!(daily[0].version, TEST_VERSION_1);
+        assert_eq!(daily[0].downloads, 3);
+        assert_eq!(daily[1].version, TEST_VERSION_2);
+        assert_eq!(daily[1].downloads, 1);
+        let daily = conn
+            .get_daily_downloads(TEST_PACKAGE_NAME.into(), today.succ_opt().unwrap())
+            .expect("daily downloads are ok");
+        assert!(daily.is_empty());
+
+        // Full packages and search results include the download counts.
+        let full_package = conn
+            .get_full_package_version(TEST_PACKAGE_NAME.into(), TEST_VERSION_1.into())
+            .expect("full package is ok");
+        assert_eq!(full_package.downloads, 4);
+        assert_eq!(full_package.version_downloads, 3);
+        let results = conn
+            .search_packages(
+                TEST_PACKAGE_NAME.into(),
+                Pagination {
+                    page: None,
+                    per_page: None,
+                },
+            )
+            .expect("search is ok");
+        assert_eq!(results.data.len(), 1);
+        assert_eq!(results.data[0].downloads, 4);
+
+        Ok::<(), diesel::result::Error>(())
+    });
+}
+
+#[test]
+#[serial]
+fn test_package_dependents() {
+    let db = &mut setup_db();
+    let (token, upload) = db
+        .transaction(|conn| {
+            let session = conn
+                .new_user_session(&mock_user_1(), 1000)
+                .expect("session is ok");
+            let user = conn.get_user_for_session(session.id).expect("user is ok");
+            let (token, _) = conn
+                .new_token(user.id, "test token".to_string())
+                .expect("token is ok");
+            let upload = conn
+                .new_upload(&NewUpload {
+                    id: uuid::Uuid::new_v4(),
+                    forc_version: TEST_VERSION_1.into(),
+                    source_code_ipfs_hash: "test-ipfs-hash".into(),
+                    abi_ipfs_hash: None,
+                    bytecode_identifier: None,
+                    readme: None,
+                    forc_manifest: TEST_MANIFEST.into(),
+                    docs_ipfs_hash: None,
+                    workspace_upload_id: None,
+                })
+                .expect("upload is ok");
+            Ok::<_, diesel::result::Error>((token, upload))
+        })
+        .unwrap();
+
+    let publish_info = |name: &str, version: &str| PublishInfo {
+        package_name: name.into(),
+        upload_id: upload.id,
+        num: Version::parse(version).unwrap(),
+        package_description: None,
+        repository: None,
+        documentation: None,
+        homepage: None,
+        urls: vec![],
+        readme: None,
+        license: None,
+    };
+    let pagination = |page| Pagination {
+        page: Some(page),
+        per_page: Some(1),
+    };
+
+    let _ = db.transaction(|conn| {
+        let mut publish = |name: &str, version: &str, deps: &[(&str, &str)]| {
+            let package_version = conn
+                .new_package_version(&token, &publish_info(name, version))
+                .expect("version result is ok");
+            conn.insert_dependencies(
+                deps.iter()
+                    .map(|(dep_name, version_req)| NewPackageDep {
+                        dependent_package_version_id: package_version.id,
+                        dependency_package_name: dep_name.to_string(),
+                        dependency_version_req: version_req.to_string(),
+                    })
+                    .collect(),
+            )
+            .expect("insert dependencies is ok");
+        };
+        publish("std-lib", TEST_VERSION_1, &[]);
+        publish("std-lib", TEST_VERSION_2, &[]);
+        publish("token", TEST_VERSION_1, &[("std-lib", "^0.1.0")]);
+        publish("token", TEST_VERSION_2, &[("std-lib", "^0.2.0")]);
+        publish(
+            "amm",
+            TEST_VERSION_1,
+            &[("std-lib", "^0.1.0"), ("token", "^0.2.0")],
+        );
+        // The latest version of this package no longer depends on std-lib.
+        publish("oracle", TEST_VERSION_1, &[("std-lib", "^0.1.0")]);
+        publish("oracle", TEST_VERSION_2, &[]);
+
+        let dependents = conn
+            .get_package_dependents("std-lib".into(), pagination(1))
+            .expect("dependents are ok");
+        assert_eq!(dependents.total_count, 2);
+        assert_eq!(dependents.total_pages, 2);
+        assert_eq!(
+            dependents.data,
+            vec![PackageDependent {
+                name: "amm".into(),
+                version: TEST_VERSION_1.into(),
+                version_req: "^0.1.0".into(),
+            }]
+        );
+        let dependents = conn
+            .get_package_dependents("std-lib".into(), pagination(2))
+            .expect("dependents are ok");
+        assert_eq!(
+            dependents.data,
+            vec![PackageDependent {
+                name: "token".into(),
+                version: TEST_VERSION_2.into(),
+                version_req: "^0.2.0".into(),
+            }]
+        );
+
+        let dependents = conn
+            .get_package_dependents("amm".into(), pagination(1))
+            .expect("dependents are ok");
+        assert_eq!(dependents.total_count, 0);
+        assert!(dependents.data.is_empty());
+
+        let result = conn.get_package_dependents("unknown".into(), pagination(1));
+        assert!(result.is_err());
+
+        Ok::<(), diesel::result::Error>(())
+    });
+}
+
+#[test]
+#[serial]
+fn test_dependency_graph() {
+    let db = &mut setup_db();
+    let (token, upload) = db
+        .transaction(|conn| {
+            let session = conn
+                .new_user_session(&mock_user_1(), 1000)
+                .expect("session is ok");
+            let user = conn.get_user_for_session(session.id).expect("user is ok");
+            let (token, _) = conn
+                .new_token(user.id, "test token".to_string())
+                .expect("token is ok");
+            let upload = conn
+                .new_upload(&NewUpload {
+                    id: uuid::Uuid::new_v4(),
+                    forc_version: TEST_VERSION_1.into(),
+                    source_code_ipfs_hash: "test-ipfs-hash".into(),
+                    abi_ipfs_hash: None,
+                    bytecode_identifier: None,
+                    readme: None,
+                    forc_manifest: TEST_MANIFEST.into(),
+                    docs_ipfs_hash: None,
+                    workspace_upload_id: None,
+                })
+                .expect("upload is ok");
+            Ok::<_, diesel::result::Error>((token, upload))
+        })
+        .unwrap();
+
+    let publish_info = |name: &str, version: &str| PublishInfo {
+        package_name: name.into(),
+        upload_id: upload.id,
+        num: Version::parse(version).unwrap(),
+        package_description: None,
+        repository: None,
+        documentation: None,
+        homepage: None,
+        urls: vec![],
+        readme: None,
+        license: None,
+    };
+
+    let _ = db.transaction(|conn| {
+        let mut publish = |name: &str, version: &str, deps: &[(&str, &str)]| {
+            let package_version = conn
+                .new_package_version(&token, &publish_info(name, version))
+                .expect("version result is ok");
+            conn.insert_dependencies(
+                deps.iter()
+                    .map(|(dep_name, version_req)| NewPackageDep {
+                        dependent_package_version_id: package_version.id,
+                        dependency_package_name: dep_name.to_string(),
+                        dependency_version_req: version_req.to_string(),
+                    })
+                    .collect(),
+            )
+            .expect("insert dependencies is ok");
+        };
+        publish("std-lib", "0.1.0", &[]);
+        publish("std-lib", "0.1.1", &[]);
+        publish("math", "0.1.0", &[("std-lib", "=0.1.0")]);
+        publish(
+            "token",
+            "0.1.0",
+            &[("std-lib", "^0.1.0"), ("math", "^0.1.0")],
+        );
+        publish(
+            "app",
+            "0.1.0",
+            &[
+                ("token", "^0.1.0"),
+                ("std-lib", "^0.1.0"),
+                ("unknown", "^1.0.0"),
+            ],
+        );
+        conn.set_package_version_yanked(&token, "std-lib".into(), "0.1.0".into(), true)
+            .expect("yank is ok");
+        Ok::<(), diesel::result::Error>(())
+    });
+
+    let graph = handle_dependency_graph(db, "app".into(), "0.1.0".into()).expect("graph is ok");
+    let nodes: Vec<_> = graph
+        .nodes
+        .iter()
+        .map(|node| (node.name.as_str(), node.version.as_deref(), node.status))
+        .collect();
+    assert_eq!(
+        nodes,
+        vec![
+            ("app", Some("0.1.0"), DependencyStatus::Resolved),
+            ("token", Some("0.1.0"), DependencyStatus::Resolved),
+            ("std-lib", Some("0.1.1"), DependencyStatus::Resolved),
+            ("unknown", None, DependencyStatus::Missing),
+            ("math", Some("0.1.0"), DependencyStatus::Resolved),
+            ("std-lib", Some("0.1.0"), DependencyStatus::Yanked),
+        ]
+    );
+    assert_eq!(graph.root, 0);
+    let edges: Vec<_> = graph
+        .edges
+        .iter()
+        .map(|edge| (edge.from, edge.to, edge.version_req.as_str()))
+        .collect();
+    assert_eq!(
+        edges,
+        vec![
+            (0, 1, "^0.1.0"),
+            (0, 2, "^0.1.0"),
+            (0, 3, "^1.0.0"),
+            (1, 2, "^0.1.0"),
+            (1, 4, "^0.1.0"),
+            (4, 5, "=0.1.0"),
+        ]
+    );
+
+    let result = handle_dependency_graph(db, "app".into(), "0.2.0".into());
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_publish_dependency_requirements() {
+    std::env::set_var("RUN_ENV", "local");
+    let db = &mut setup_db();
+    let manifest = |name: &str, deps: &str| {
+        format!(
+            "[project]\nauthors = [\"Fuel Labs\"]\nentry = \"lib.sw\"\nimplicit-std = false\nlicense = \"Apache-2.0\"\nname = \"{name}\"\nversion = \"0.1.0\"\n\n[dependencies]\n{deps}"
+        )
+    };
+    let (token, app_upload, bad_upload) = db
+        .transaction(|conn| {
+            let session = conn
+                .new_user_session(&mock_user_1(), 1000)
+                .expect("session is ok");
+            let user = conn.get_user_for_session(session.id).expect("user is ok");
+            let (token, _) = conn
+                .new_token(user.id, "test token".to_string())
+                .expect("token is ok");
+            let mut new_upload = |forc_manifest: String| {
+                conn.new_upload(&NewUpload {
+                    id: uuid::Uuid::new_v4(),
+                    forc_version: TEST_VERSION_1.into(),
+                    source_code_ipfs_hash: "test-ipfs-hash".into(),
+                    abi_ipfs_hash: None,
+                    bytecode_identifier: None,
+                    readme: None,
+                    forc_manifest,
+                    docs_ipfs_hash: None,
+                    workspace_upload_id: None,
+                })
+                .expect("upload is ok")
+            };
+            let std_upload = new_upload(manifest("std-lib", ""));
+            let app_upload = new_upload(manifest("app", "std-lib = \"~0.1\"\n"));
+            let bad_upload = new_upload(manifest(
+                "bad",
+                "missing = \"1.0\"\noops = \"abc\"\nstd-lib = \"^0.2\"\n",
+            ));
+
+            for version in [TEST_VERSION_1, TEST_VERSION_2] {
+                conn.new_package_version(
+                    &token,
+                    &PublishInfo {
+                        package_name: "std-lib".into(),
+                        upload_id: std_upload.id,
+                        num: Version::parse(version).unwrap(),
+                        package_description: None,
+                        repository: None,
+                        documentation: None,
+                        homepage: None,
+                        urls: vec![],
+                        readme: None,
+                        license: None,
+                    },
+                )
+                .expect("version result is ok");
+            }
+            conn.set_package_version_yanked(&token, "std-lib".into(), TEST_VERSION_2.into(), true)
+                .expect("yank is ok");
+            Ok::<_, diesel::result::Error>((token, app_upload, bad_upload))
+        })
+        .unwrap();
+
+    // Ranges are published with their normalized requirement.
+    let request = |upload_id| PublishRequest {
+        upload_id,
+        urls: None,
+        dry_run: false,
+    };
+    handle_publish(db, &request(app_upload.id), &token, &ClientInfo::default())
+        .await
+        .expect("publish is ok");
+    let _ = db.transaction(|conn| {
+        let app = conn
+            .get_package_version("app".into(), TEST_VERSION_1.into())
+            .expect("app version is ok");
+        let deps = conn
+            .get_dependencies_for_package_version(app.id)
+            .expect("dependencies are ok");
+        assert_eq!(deps.len(), 1);
+        assert_eq!(deps[0].dependency_package_name, "std-lib");
+        assert_eq!(deps[0].dependency_version_req, "~0.1");
+        Ok::<(), diesel::result::Error>(())
+    });
+
+    // Every unsatisfiable dependency is reported.
+    let result = handle_publish(db, &request(bad_upload.id), &token, &ClientInfo::default()).await;
+    let Err(PublishError::UnsatisfiableDependencies(unsatisfied)) = result else {
+        panic!("expected unsatisfiable dependencies, got {result:?}");
+    };
+    let unsatisfied: Vec<_> = unsatisfied
+        .iter()
+        .map(|dep| (dep.name.as_str(), dep.version_req.as_str()))
+        .collect();
+    assert_eq!(
+        unsatisfied,
+        vec![("missing", "1.0"), ("oops", "abc"), ("std-lib", "^0.2")]
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn test_publish_workspace_is_atomic() {
+    std::env::set_var("RUN_ENV", "local");
+    let db = &mut setup_db();
+    let manifest = |name: &str| {
+        format!(
+            "[project]\nauthors = [\"Fuel Labs\"]\nentry = \"lib.sw\"\nimplicit-std = false\nlicense = \"Apache-2.0\"\nname = \"{name}\"\nversion = \"0.1.0\"\n"
+        )
+    };
+    let workspace_upload_id = uuid::Uuid::new_v4();
+    let token = db
+        .transaction(|conn| {
+            let session = conn
+                .new_user_session(&mock_user_1(), 1000)
+                .expect("session is ok");
+            let user = conn.get_user_for_session(session.id).expect("user is ok");
+            let (token, _) = conn
+                .new_token(user.id, "test token".to_string())
+                .expect("token is ok");
+            // The second member can't be stored, because it has the same name and version
+            // as the first one.
+            for name in ["lib_a", "lib_a"] {
+                conn.new_upload(&NewUpload {
+                    id: uuid::Uuid::new_v4(),
+                    forc_version: TEST_VERSION_1.into(),
+                    source_code_ipfs_hash: "test-ipfs-hash".into(),
+                    abi_ipfs_hash: None,
+                    bytecode_identifier: None,
+                    readme: None,
+                    forc_manifest: manifest(name),
+                    docs_ipfs_hash: None,
+                    workspace_upload_id: Some(workspace_upload_id),
+                })
+                .expect("upload is ok");
+            }
+            Ok::<_, diesel::result::Error>(token)
+        })
+        .unwrap();
+
+    // No member is published if any of them fails.
+    let request = PublishRequest {
+        upload_id: workspace_upload_id,
+        urls: None,
+        dry_run: false,
+    };
+    let result = handle_publish(db, &request, &token, &ClientInfo::default()).await;
+    assert!(result.is_err());
+    let published = db
+        .transaction(|conn| conn.get_published_versions("lib_a".into()))
+        .expect("versions are ok");
+    assert!(published.is_empty());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_publish_dry_run() {
+    std::env::set_var("RUN_ENV", "local");
+    let db = &mut setup_db();
+    let lease = chrono::Duration::minutes(30);
+    let new_upload = |id| {
+        NewUpload {
+        id,
+        forc_version: TEST_VERSION_1.into(),
+        source_code_ipfs_hash: "test-ipfs-hash".into(),
+        abi_ipfs_hash: None,
+        bytecode_identifier: None,
+        readme: Some(TEST_README.into()),
+        forc_manifest: "[project]\nauthors = [\"Fuel Labs\"]\nentry = \"lib.sw\"\nimplicit-std = false\nlicense = \"Apache-2.0\"\nname = \"app\"\nversion = \"0.1.0\"\nkeywords = [\"token\"]\n".into(),
+        docs_ipfs_hash: None,
+        workspace_upload_id: None,
+    }
+    };
+    let token = db
+        .transaction(|conn| {
+            let session = conn
+                .new_user_session(&mock_user_1(), 1000)
+                .expect("session is ok");
+            let user = conn.get_user_for_session(session.id).expect("user is ok");
+            let (token, _) = conn
+                .new_token(user.id, "test token".to_string())
+                .expect("token is ok");
+            Ok::<_, diesel::result::Error>(token)
+        })
+        .unwrap();
+    let request = |upload_id, dry_run| PublishRequest {
+        upload_id,
+        urls: None,
+        dry_run,
+    };
+
+    // A dry-run job reports its uploads without storing them.
+    let job_id = uuid::Uuid::new_v4();
+    db.transaction(|conn| {
+        conn.new_upload_job(job_id, TEST_VERSION_1.into(), vec![], "Queued".into(), true)
+    })
+    .expect("job is ok");
+    let job = db
+        .transaction(|conn| conn.claim_upload_job(lease))
+        .expect("claim is ok")
+        .expect("job is claimed");
+    assert!(job.dry_run);
+    let job = db
+        .transaction(|conn| conn.complete_dry_run_upload_job(job_id, &[new_upload(job_id)]))
+        .expect("complete is ok");
+    assert_eq!(job.status().unwrap(), UploadJobStatus::Succeeded);
+    let report = job.dry_run_report().expect("report is ok");
+    assert_eq!(report.len(), 1);
+    assert_eq!(report[0].id, job_id);
+    assert!(db.transaction(|conn| conn.get_upload(job_id)).is_err());
+
+    // The report of a dry-run job can be validated, but not published.
+    let packages =
+        handle_publish_dry_run(db, &request(job_id, true), &token).expect("dry run is ok");
+    assert_eq!(packages.len(), 1);
+    assert_eq!(packages[0].name, "app");
+    assert_eq!(packages[0].version, Version::parse(TEST_VERSION_1).unwrap());
+    assert_eq!(packages[0].upload_id, job_id);
+    assert_eq!(packages[0].readme.as_deref(), Some(TEST_README));
+    assert_eq!(packages[0].keywords, vec!["token".to_string()]);
+    assert!(
+        handle_publish(db, &request(job_id, false), &token, &ClientInfo::default())
+            .await
+            .is_err()
+    );
+
+    // A dry-run publish of a stored upload doesn't publish anything.
+    let upload_id = uuid::Uuid::new_v4();
+    db.transaction(|conn| conn.new_upload(&new_upload(upload_id)))
+        .expect("upload is ok");
+    handle_publish_dry_run(db, &request(upload_id, true), &token).expect("dry run is ok");
+    assert!(db
+        .transaction(|conn| conn.get_package_version("app".into(), TEST_VERSION_1.into()))
+        .is_err());
+
+    // Once the version is published, it can't be published again.
+    handle_publish(
+        db,
+        &request(upload_id, false),
+        &token,
+        &ClientInfo::default(),
+    )
+    .await
+    .expect("publish is ok");
+    let result = handle_publish_dry_run(db, &request(upload_id, true), &token);
+    assert!(
+        matches!(result, Err(PublishError::VersionExists { ref name, ref version }) if name == "app" && version.to_string() == TEST_VERSION_1),
+        "expected version exists, got {result:?}"
+    );
+    let result = handle_publish(
+        db,
+        &request(upload_id, false),
+        &token,
+        &ClientInfo::default(),
+    )
+    .await;
+    assert!(matches!(result, Err(PublishError::VersionExists { .. })));
+}
+
+#[tokio::test]
+#[serial]
+async fn test_publish_package_name_policy() {
+    use diesel::ExpressionMethods as _;
+    use forc_pub::handlers::package_name::PackageNameError;
+
+    std::env::set_var("RUN_ENV", "local");
+    let db = &mut setup_db();
+    let manifest = |name: &str| {
+        format!(
+            "[project]\nauthors = [\"Fuel Labs\"]\nentry = \"lib.sw\"\nimplicit-std = false\nlicense = \"Apache-2.0\"\nname = \"{name}\"\nversion = \"0.1.0\"\n"
+        )
+    };
+    let (token, uploads) = db
+        .transaction(|conn| {
+            let session = conn
+                .new_user_session(&mock_user_1(), 1000)
+                .expect("session is ok");
+            let user = conn.get_user_for_session(session.id).expect("user is ok");
+            let (token, _) = conn
+                .new_token(user.id, "test token".to_string())
+                .expect("token is ok");
+            let mut new_upload = |name: &str| {
+                conn.new_upload(&NewUpload {
+                    id: uuid::Uuid::new_v4(),
+                    forc_version: TEST_VERSION_1.into(),
+                    source_code_ipfs_hash: "test-ipfs-hash".into(),
+                    abi_ipfs_hash: None,
+                    bytecode_identifier: None,
+                    readme: None,
+                    forc_manifest: manifest(name),
+                    docs_ipfs_hash: None,
+                    workspace_upload_id: None,
+                })
+                .expect("upload is ok")
+                .id
+            };
+            let popular_upload = new_upload("my_token");
+            let long_name = "a".repeat(100);
+            let uploads = [
+                "std",
+                &long_name,
+                "My-Token",
+                "my-tokens",
+                "modern",
+                "modem",
+            ]
+            .map(new_upload);
+
+            conn.new_package_version(
+                &token,
+                &PublishInfo {
+                    package_name: "my_token".into(),
+                    upload_id: popular_upload,
+                    num: Version::parse(TEST_VERSION_1).unwrap(),
+                    package_description: None,
+                    repository: None,
+                    documentation: None,
+                    homepage: None,
+                    urls: vec![],
+                    readme: None,
+                    license: None,
+                },
+            )
+            .expect("version result is ok");
+            diesel::update(forc_pub::schema::packages::table)
+                .set(forc_pub::schema::packages::downloads.eq(5000))
+                .execute(conn.inner())?;
+            Ok::<_, diesel::result::Error>((token, uploads))
+        })
+        .unwrap();
+    let request = |upload_id| PublishRequest {
+        upload_id,
+        urls: None,
+        dry_run: false,
+    };
+
+    // Reserved and invalid names are refused.
+    let result = handle_publish(db, &request(uploads[0]), &token, &ClientInfo::default()).await;
+    assert!(matches!(
+        result,
+        Err(PublishError::PackageName(PackageNameError::Reserved(_)))
+    ));
+    let result = handle_publish(db, &request(uploads[1]), &token, &ClientInfo::default()).await;
+    assert!(matches!(
+        result,
+        Err(PublishError::PackageName(PackageNameError::InvalidLength(
+            _
+        )))
+    ));
+
+    // Names that only differ by case and separators collide.
+    let result = handle_publish(db, &request(uploads[2]), &token, &ClientInfo::default()).await;
+    let Err(PublishError::PackageName(PackageNameError::Taken { name, existing })) = result else {
+        panic!("expected name collision, got {result:?}");
     };
+    assert_eq!((name.as_str(), existing.as_str()), ("My-Token", "my_token"));
+
+    // Near-duplicates of popular packages are published, but flagged for review.
+    handle_publish(db, &request(uploads[3]), &token, &ClientInfo::default())
+        .await
+        .expect("publish is ok");
+    let flags = db
+        .transaction(|conn| conn.get_package_name_flags_for_review())
+        .expect("flags are ok");
+    assert_eq!(flags.len(), 1);
+    assert_eq!(
+        (
+            flags[0].package_name.as_str(),
+            flags[0].similar_package_name.as_str()
+        ),
+        ("my-tokens", "my_token")
+    );
+    assert!(flags[0].similarity >= 0.5);
+
+    // ASCII lookalikes of any package are published, but flagged for review.
+    handle_publish(db, &request(uploads[4]), &token, &ClientInfo::default())
+        .await
+        .expect("publish is ok");
+    handle_publish(db, &request(uploads[5]), &token, &ClientInfo::default())
+        .await
+        .expect("publish is ok");
+    let flags = db
+        .transaction(|conn| conn.get_package_name_flags_for_review())
+        .expect("flags are ok");
+    assert_eq!(flags.len(), 2);
+    assert_eq!(
+        (
+            flags[0].package_name.as_str(),
+            flags[0].similar_package_name.as_str()
+        ),
+        ("modem", "modern")
+    );
+    assert_eq!(flags[0].similarity, 1.0);
+}
 
-    let _ = db.transaction(|conn| {
-        // Insert a package version for a package that already exists
-        let version_result = conn
-            .new_package_version(&token, &request)
-            .expect("version result is ok");
-        assert_eq!(
-            version_result,
-            PackageVersion {
-                id: version_result.id,
-                package_id: version_result.package_id,
-                published_by: user.id,
-                upload_id: upload.id,
-                num: TEST_VERSION_2.into(),
-                package_description: request.package_description,
-                repository: Some(TEST_URL_REPO.into()),
-                documentation: Some(TEST_URL_DOC.into()),
-                homepage: Some(TEST_URL_HOME.into()),
-                urls: vec![Some(TEST_URL_OTHER.into())],
-                license: request.license,
-                created_at: version_result.created_at,
+#[tokio::test]
+#[serial]
+async fn test_admin_moderation() {
+    use forc_pub::db::audit_event::AuditEventKind;
+    use forc_pub::db::error::DatabaseError;
+    use forc_pub::handlers::admin::{
+        handle_admin_yank, handle_block_user, handle_remove_version, handle_transfer_ownership,
+    };
+
+    std::env::set_var("RUN_ENV", "local");
+    let db = &mut setup_db();
+    let (admin, token) = db
+        .transaction(|conn| {
+            let session = conn
+                .new_user_session(&mock_user_1(), 1000)
+                .expect("session is ok");
+            let admin = conn.get_user_for_session(session.id).expect("user is ok");
+            let session = conn
+                .new_user_session(&mock_user_2(), 1000)
+                .expect("session is ok");
+            let user = conn.get_user_for_session(session.id).expect("user is ok");
+            let (token, _) = conn
+                .new_token(user.id, "test token".to_string())
+                .expect("token is ok");
+
+            for version in [TEST_VERSION_1, TEST_VERSION_2] {
+                let upload = conn
+                    .new_upload(&NewUpload {
+                        id: uuid::Uuid::new_v4(),
+                        forc_version: TEST_VERSION_1.into(),
+                        source_code_ipfs_hash: "test-ipfs-hash".into(),
+                        abi_ipfs_hash: None,
+                        bytecode_identifier: None,
+                        readme: None,
+                        forc_manifest: TEST_MANIFEST.into(),
+                        docs_ipfs_hash: None,
+                        workspace_upload_id: None,
+                    })
+                    .expect("upload is ok");
+                conn.new_package_version(
+                    &token,
+                    &PublishInfo {
+                        package_name: TEST_PACKAGE_NAME.into(),
+                        upload_id: upload.id,
+                        num: Version::parse(version).unwrap(),
+                        package_description: None,
+                        repository: None,
+                        documentation: None,
+                        homepage: None,
+                        urls: vec![],
+                        readme: None,
+                        license: None,
+                    },
+                )
+                .expect("version result is ok");
             }
-        );
-        let pkg_result = conn
-            .get_package_by_id(version_result.package_id)
-            .expect("pkg result is ok");
-        assert_eq!(pkg_result.package_name, TEST_PACKAGE_NAME);
-        assert_eq!(pkg_result.user_owner, user.id);
-        assert_eq!(pkg_result.default_version, Some(version_result.id));
-        Ok::<(), diesel::result::Error>(())
-    });
-    let _ = db.transaction(|conn| {
-        // Test get_full_packages page 1
-        let result = conn
-            .get_full_packages(
-                None,
-                Pagination {
-                    page: Some(1),
-                    per_page: Some(1),
-                },
-            )
-            .expect("get_full_packages result is ok");
+            Ok::<_, diesel::result::Error>((admin, token))
+        })
+        .unwrap();
 
-        assert_eq!(result.current_page, 1);
-        assert_eq!(result.per_page, 1);
-        assert_eq!(result.total_pages, 2);
-        assert_eq!(result.total_count, 2);
-        assert_eq!(result.data.len(), 1);
-        assert_eq!(result.data[0].version, TEST_VERSION_2); // latest version
+    // Blocked users can't publish until they are unblocked.
+    let user = handle_block_user(
+        db,
+        &admin,
+        &ClientInfo::default(),
+        TEST_LOGIN_2.into(),
+        true,
+    )
+    .expect("block is ok");
+    assert!(user.publish_blocked);
+    let result = db.transaction(|conn| conn.authorize_publish(&token, TEST_PACKAGE_NAME));
+    assert!(matches!(result, Err(DatabaseError::PublishBlocked)));
+    handle_block_user(
+        db,
+        &admin,
+        &ClientInfo::default(),
+        TEST_LOGIN_2.into(),
+        false,
+    )
+    .expect("unblock is ok");
+    db.transaction(|conn| conn.authorize_publish(&token, TEST_PACKAGE_NAME))
+        .expect("authorize is ok");
+
+    // Admins can yank versions of packages they don't own.
+    let version = handle_admin_yank(
+        db,
+        &admin,
+        &ClientInfo::default(),
+        TEST_PACKAGE_NAME.into(),
+        TEST_VERSION_2.into(),
+        true,
+    )
+    .await
+    .expect("yank is ok");
+    assert!(version.yanked);
+
+    // Removing a version deletes it, and the default version moves to the remaining one.
+    let removed = handle_remove_version(
+        db,
+        &admin,
+        &ClientInfo::default(),
+        TEST_PACKAGE_NAME.into(),
+        TEST_VERSION_1.into(),
+    )
+    .await
+    .expect("remove is ok");
+    assert_eq!(removed.num, TEST_VERSION_1);
+    let (versions, package) = db
+        .transaction(|conn| {
+            let versions = conn.get_package_versions(TEST_PACKAGE_NAME.into())?;
+            let package = conn.get_package_by_name(TEST_PACKAGE_NAME.into())?;
+            Ok::<_, DatabaseError>((versions, package))
+        })
+        .expect("package is ok");
+    assert_eq!(versions.len(), 1);
+    assert_eq!(versions[0].version, TEST_VERSION_2);
+    assert_eq!(package.default_version, Some(version.id));
+
+    // Removed versions can't be published again, and can't be removed twice.
+    let removed_versions = db
+        .transaction(|conn| conn.get_removed_versions(TEST_PACKAGE_NAME.into()))
+        .expect("removed versions are ok");
+    assert_eq!(removed_versions, vec![TEST_VERSION_1.to_string()]);
+    let result = handle_remove_version(
+        db,
+        &admin,
+        &ClientInfo::default(),
+        TEST_PACKAGE_NAME.into(),
+        TEST_VERSION_1.into(),
+    )
+    .await;
+    assert!(matches!(
+        result,
+        Err(forc_pub::handlers::admin::AdminError::Database(
+            DatabaseError::NotFound(..)
+        ))
+    ));
+
+    // Transferring ownership replaces the owners.
+    let previous_owners = handle_transfer_ownership(
+        db,
+        &admin,
+        &ClientInfo::default(),
+        TEST_PACKAGE_NAME.into(),
+        TEST_LOGIN_1.into(),
+    )
+    .expect("transfer is ok");
+    assert_eq!(previous_owners, vec![TEST_LOGIN_2.to_string()]);
+    let owners = db
+        .transaction(|conn| conn.get_package_owners(TEST_PACKAGE_NAME.into()))
+        .expect("owners are ok");
+    assert_eq!(owners.len(), 1);
+    assert_eq!(owners[0].github_login, TEST_LOGIN_1);
+
+    let pagination = Pagination {
+        page: Some(1),
+        per_page: Some(10),
+    };
+    let users = db
+        .transaction(|conn| conn.search_users(Some("FOO".into()), pagination.clone()))
+        .expect("users are ok");
+    assert_eq!(users.total_count, 1);
+    assert_eq!(users.data[0].github_login, TEST_LOGIN_2);
+    assert!(!users.data[0].publish_blocked);
+
+    // Every action is recorded in the audit log, most recent first.
+    let events = db
+        .transaction(|conn| conn.get_admin_audit_events(pagination.clone()))
+        .expect("events are ok");
+    let recorded: Vec<_> = events
+        .data
+        .iter()
+        .map(|event| event.event.as_str())
+        .collect();
+    assert_eq!(
+        recorded,
+        vec![
+            AuditEventKind::AdminTransferOwnership.as_str(),
+            AuditEventKind::AdminRemoveVersion.as_str(),
+            AuditEventKind::AdminYank.as_str(),
+            AuditEventKind::AdminUnblockUser.as_str(),
+            AuditEventKind::AdminBlockUser.as_str(),
+        ]
+    );
+    assert_eq!(events.data[0].user.github_login, TEST_LOGIN_1);
+    assert_eq!(events.data[1].payload["name"], TEST_PACKAGE_NAME);
+    assert_eq!(events.data[1].payload["version"], TEST_VERSION_1);
+    assert_eq!(events.data[4].payload["githubLogin"], TEST_LOGIN_2);
+
+    // Admin actions are also part of the audit log of the admin.
+    let events = db
+        .transaction(|conn| conn.get_audit_events(Some(admin.id), pagination))
+        .expect("events are ok");
+    assert_eq!(events.total_count, 5);
+}
 
-        // Test get_full_packages page 2
-        let result = conn
-            .get_full_packages(
-                None,
-                Pagination {
-                    page: Some(2),
-                    per_page: Some(1),
-                },
+#[tokio::test]
+#[serial]
+async fn test_audit_events() {
+    use forc_pub::db::audit_event::AuditEventKind;
+    use forc_pub::handlers::yank::handle_yank;
+
+    std::env::set_var("RUN_ENV", "local");
+    let db = &mut setup_db();
+    let client = ClientInfo {
+        ip_address: Some("127.0.0.1".into()),
+        user_agent: Some("forc-publish/0.1.0".into()),
+    };
+    let (user_id, token, upload_id) = db
+        .transaction(|conn| {
+            let session = conn
+                .new_user_session(&mock_user_1(), 1000)
+                .expect("session is ok");
+            let user = conn.get_user_for_session(session.id).expect("user is ok");
+            conn.record_audit_event(user.id, AuditEventKind::Login, &client, serde_json::json!({}))
+                .expect("event is ok");
+            let other = conn
+                .new_user_session(&mock_user_2(), 1000)
+                .expect("session is ok");
+            conn.record_audit_event(
+                other.user_id,
+                AuditEventKind::Login,
+                &ClientInfo::default(),
+                serde_json::json!({}),
             )
-            .expect("get_full_packages result is ok");
+            .expect("event is ok");
+            let (token, _) = conn
+                .new_token(user.id, "test token".to_string())
+                .expect("token is ok");
+            let upload = conn
+                .new_upload(&NewUpload {
+                    id: uuid::Uuid::new_v4(),
+                    forc_version: TEST_VERSION_1.into(),
+                    source_code_ipfs_hash: "test-ipfs-hash".into(),
+                    abi_ipfs_hash: None,
+                    bytecode_identifier: None,
+                    readme: None,
+                    forc_manifest: format!(
+                        "[project]\nauthors = [\"Fuel Labs\"]\nentry = \"lib.sw\"\nimplicit-std = false\nlicense = \"Apache-2.0\"\nname = \"{TEST_PACKAGE_NAME}\"\nversion = \"{TEST_VERSION_1}\"\n"
+                    ),
+                    docs_ipfs_hash: None,
+                    workspace_upload_id: None,
+                })
+                .expect("upload is ok");
+            Ok::<_, diesel::result::Error>((user.id, token, upload.id))
+        })
+        .unwrap();
 
-        assert_eq!(result.current_page, 2);
-        assert_eq!(result.per_page, 1);
-        assert_eq!(result.total_pages, 2);
-        assert_eq!(result.total_count, 2);
-        assert_eq!(result.data.len(), 1);
-        assert_eq!(result.data[0].version, TEST_VERSION_1); // latest version
+    let request = PublishRequest {
+        upload_id,
+        urls: None,
+        dry_run: false,
+    };
+    handle_publish(db, &request, &token, &client)
+        .await
+        .expect("publish is ok");
+    handle_yank(
+        db,
+        TEST_PACKAGE_NAME.into(),
+        TEST_VERSION_1.into(),
+        true,
+        &token,
+        &client,
+    )
+    .await
+    .expect("yank is ok");
+
+    let pagination = Pagination {
+        page: Some(1),
+        per_page: Some(10),
+    };
 
-        // Test get_full_packages date filter (should return no results)
-        let result = conn
-            .get_full_packages(
-                Some(Utc::now()),
-                Pagination {
-                    page: Some(1),
-                    per_page: Some(1),
-                },
-            )
-            .expect("get_full_packages result is ok");
-        assert_eq!(result.current_page, 1);
-        assert_eq!(result.per_page, 1);
-        assert_eq!(result.total_pages, 0);
-        assert_eq!(result.total_count, 0);
-        assert_eq!(result.data.len(), 0);
-        Ok::<(), diesel::result::Error>(())
-    });
+    // Users see their own events, most recent first.
+    let events = db
+        .transaction(|conn| conn.get_audit_events(Some(user_id), pagination.clone()))
+        .expect("events are ok");
+    let recorded: Vec<_> = events.data.iter().map(|e| e.event.as_str()).collect();
+    assert_eq!(recorded, vec!["yank", "publish", "login"]);
+    let publish = &events.data[1];
+    assert_eq!(publish.user.github_login, TEST_LOGIN_1);
+    assert_eq!(publish.ip_address.as_deref(), Some("127.0.0.1"));
+    assert_eq!(publish.user_agent.as_deref(), Some("forc-publish/0.1.0"));
+    assert_eq!(publish.payload["name"], TEST_PACKAGE_NAME);
+    assert_eq!(publish.payload["version"], TEST_VERSION_1);
+    assert_eq!(publish.payload["tokenId"], token.id.to_string());
+
+    // Without a user, the events of all users are returned.
+    let events = db
+        .transaction(|conn| conn.get_audit_events(None, pagination))
+        .expect("events are ok");
+    assert_eq!(events.total_count, 4);
 }
 
 #[test]
 #[serial]
-fn test_package_categories_keywords() {
-    let db = &mut setup_db();
-    let _ = db.transaction(|conn| {
-        // Set up session, user, token, and upload.
-        let session = conn
-            .new_user_session(&mock_user_1(), 1000)
-            .expect("session is ok");
-        let user = conn.get_user_for_session(session.id).expect("user is ok");
-        let (token, _) = conn
-            .new_token(user.id, "test token".to_string())
-            .expect("token is ok");
-        let upload = conn
-            .new_upload(&NewUpload {
-                id: uuid::Uuid::new_v4(),
-                forc_version: TEST_VERSION_1.into(),
-                source_code_ipfs_hash: "test-ipfs-hash".into(),
-                abi_ipfs_hash: None,
-                bytecode_identifier: None,
-                readme: None,
-                forc_manifest: TEST_MANIFEST.into(),
-                docs_ipfs_hash: Some("test-docs-hash".into()),
-            })
-            .expect("upload is ok");
-
-        let request = PublishInfo {
-            package_name: TEST_PACKAGE_NAME.into(),
-            upload_id: upload.id,
-            num: Version::parse(TEST_VERSION_3).unwrap(),
-            package_description: None,
-            repository: None,
-            documentation: None,
-            homepage: None,
-            urls: vec![],
-            readme: None,
-            license: None,
-        };
+fn test_rate_limit_windows() {
+    use diesel::QueryDsl as _;
+    use forc_pub::middleware::rate_limit::{
+        Bucket, PostgresStore, Quota, RateLimitQuotas, RateLimiter, RouteClass,
+    };
 
-        let version_result = conn
-            .new_package_version(&token, &request)
-            .expect("version result is ok");
+    let db = &mut setup_db();
+    let start = Utc::now().trunc_subsecs(0);
+    let end = start + chrono::Duration::seconds(60);
+    let next_end = end + chrono::Duration::seconds(60);
 
-        // Insert categories
-        let categories = vec!["cat1".to_string(), "cat2".to_string()];
-        let cat_count = conn
-            .insert_categories(version_result.package_id, &categories)
-            .expect("insert categories is ok");
-        assert_eq!(cat_count, 2);
+    db.transaction(|conn| {
+        // Requests in the same window are counted together, per bucket.
+        assert_eq!(conn.hit_rate_limit_window("search:ip:a", start, end)?, 1);
+        assert_eq!(conn.hit_rate_limit_window("search:ip:a", start, end)?, 2);
+        assert_eq!(conn.hit_rate_limit_window("search:ip:b", start, end)?, 1);
 
-        // Insert keywords
-        let keywords = vec!["key1".to_string(), "key2".to_string()];
-        let key_count = conn
-            .insert_keywords(version_result.package_id, &keywords)
-            .expect("insert keywords is ok");
-        assert_eq!(key_count, 2);
+        // The count restarts in the next window, and ended windows are removed.
+        assert_eq!(conn.hit_rate_limit_window("search:ip:a", end, next_end)?, 1);
+        assert_eq!(
+            conn.hit_rate_limit_window("search:ip:c", next_end, next_end)?,
+            1
+        );
+        let remaining = forc_pub::schema::rate_limit_windows::table
+            .count()
+            .get_result::<i64>(conn.inner())?;
+        assert_eq!(remaining, 2);
+        Ok::<_, forc_pub::db::error::DatabaseError>(())
+    })
+    .expect("rate limit windows are ok");
 
-        Ok::<(), diesel::result::Error>(())
-    });
+    // The Postgres store shares quotas between limiters, like server instances do.
+    let quotas = RateLimitQuotas {
+        publish: Quota::new(1, 3600),
+        ..RateLimitQuotas::default()
+    };
+    let limiter_1 = RateLimiter::new(quotas.clone(), Box::new(PostgresStore::new(db.clone())));
+    let limiter_2 = RateLimiter::new(quotas, Box::new(PostgresStore::new(db.clone())));
+    let user = [Bucket::User(uuid::Uuid::new_v4())];
+    assert_eq!(
+        limiter_1.check(RouteClass::Publish, &user, Utc::now()),
+        None
+    );
+    assert!(limiter_2
+        .check(RouteClass::Publish, &user, Utc::now())
+        .is_some());
 }
 
 // Tests for ABI inlining functionality
@@ -433,15 +2083,14 @@ fn test_package_categories_keywords() {
 #[serial]
 async fn test_abi_inlining_with_mock_pinata() {
     use forc_pub::api::search::FullPackage;
-    use forc_pub::file_uploader::pinata::PinataClient;
+    use forc_pub::file_uploader::storage::{FileStorage, FileUrls};
     use forc_pub::handlers::upload::UploadError;
     use forc_pub::models;
-    use std::env;
     use std::path::Path;
     // Create a test mock client that returns mock ABI content
     struct TestMockPinataClient;
 
-    impl PinataClient for TestMockPinataClient {
+    impl FileStorage for TestMockPinataClient {
         async fn new() -> Result<Self, UploadError> {
             Ok(TestMockPinataClient)
         }
@@ -456,8 +2105,7 @@ async fn test_abi_inlining_with_mock_pinata() {
         }
     }
 
-    // Set up environment variables
-    env::set_var("PINATA_URL", "https://test-pinata.com");
+    let file_urls = FileUrls::Gateway("https://test-pinata.com".to_string());
 
     // Create a mock FullPackage from database model (simulating what we'd get from DB)
     let db_full_package = models::FullPackage {
@@ -466,6 +2114,8 @@ async fn test_abi_inlining_with_mock_pinata() {
         description: Some("Test package with ABI".to_string()),
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
+        downloads: 0,
+        version_downloads: 0,
         bytecode_identifier: None,
         forc_version: "0.68.0".to_string(),
         source_code_ipfs_hash: "source_hash_123".to_string(),
@@ -480,21 +2130,27 @@ async fn test_abi_inlining_with_mock_pinata() {
     };
 
     // Test 1: Convert without ABI inlining (default behavior)
-    let full_package_without_abi = FullPackage::from(FullPackageWithCategories {
-        package: db_full_package.clone(),
-        categories: vec![],
-        keywords: vec![],
-    });
+    let full_package_without_abi = FullPackage::new(
+        FullPackageWithCategories {
+            package: db_full_package.clone(),
+            categories: vec![],
+            keywords: vec![],
+        },
+        &file_urls,
+    );
     assert!(full_package_without_abi.abi_ipfs_url.is_some());
     assert!(full_package_without_abi.abi.is_none());
 
     // Test 2: Simulate ABI inlining process
     let mock_client = TestMockPinataClient;
-    let mut full_package_with_abi = FullPackage::from(FullPackageWithCategories {
-        package: db_full_package.clone(),
-        categories: vec![],
-        keywords: vec![],
-    });
+    let mut full_package_with_abi = FullPackage::new(
+        FullPackageWithCategories {
+            package: db_full_package.clone(),
+            categories: vec![],
+            keywords: vec![],
+        },
+        &file_urls,
+    );
 
     // Simulate the inline_abi=true logic from the endpoint
     if let Some(abi_hash) = db_full_package.abi_ipfs_hash {
@@ -549,6 +2205,7 @@ fn test_full_package_abi_field_serialization() {
         description: Some("Test description".to_string()),
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
+        downloads: 0,
     };
 
     let package_preview_2 = PackagePreview {
@@ -557,11 +2214,13 @@ fn test_full_package_abi_field_serialization() {
         description: Some("Test description".to_string()),
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
+        downloads: 0,
     };
 
     // Test without ABI field
     let full_package_without_abi = FullPackage {
         package_preview: package_preview_1,
+        version_downloads: 0,
         bytecode_identifier: None,
         forc_version: "0.68.0".to_string(),
         source_code_ipfs_url: "https://example.com/source".to_string(),
@@ -590,6 +2249,7 @@ fn test_full_package_abi_field_serialization() {
 
     let full_package_with_abi = FullPackage {
         package_preview: package_preview_2,
+        version_downloads: 0,
         bytecode_identifier: None,
         forc_version: "0.68.0".to_string(),
         source_code_ipfs_url: "https://example.com/source".to_string(),
@@ -635,6 +2295,7 @@ fn test_search_with_categories() {
                 readme: None,
                 forc_manifest: TEST_MANIFEST.into(),
                 docs_ipfs_hash: Some("test-docs-hash".into()),
+                workspace_upload_id: None,
             })
             .expect("upload is ok");
 
@@ -736,11 +2397,10 @@ fn test_search_with_categories() {
 #[serial]
 fn test_full_package_conversion_maintains_abi_none() {
     use forc_pub::api::search::FullPackage;
+    use forc_pub::file_uploader::storage::FileUrls;
     use forc_pub::models;
-    use std::env;
 
-    // Set required environment variable for the test
-    env::set_var("PINATA_URL", "https://test-pinata.com");
+    let file_urls = FileUrls::Gateway("https://test-pinata.com".to_string());
 
     let db_full_package = models::FullPackage {
         name: "test".to_string(),
@@ -748,6 +2408,8 @@ fn test_full_package_conversion_maintains_abi_none() {
         description: Some("test".to_string()),
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
+        downloads: 0,
+        version_downloads: 0,
         bytecode_identifier: None,
         forc_version: "0.68.0".to_string(),
         source_code_ipfs_hash: "source123".to_string(),
@@ -761,11 +2423,14 @@ fn test_full_package_conversion_maintains_abi_none() {
         license: None,
     };
 
-    let api_full_package = FullPackage::from(FullPackageWithCategories {
-        package: db_full_package,
-        categories: vec![],
-        keywords: vec![],
-    });
+    let api_full_package = FullPackage::new(
+        FullPackageWithCategories {
+            package: db_full_package,
+            categories: vec![],
+            keywords: vec![],
+        },
+        &file_urls,
+    );
 
     // Should have abi_ipfs_url but abi should be None by default
     assert!(api_full_package.abi_ipfs_url.is_some());
@@ -795,6 +2460,7 @@ fn test_filter_by_category() {
                 readme: None,
                 forc_manifest: TEST_MANIFEST.into(),
                 docs_ipfs_hash: Some("test-docs-hash".into()),
+                workspace_upload_id: None,
             })
             .expect("upload is ok");
 
@@ -920,6 +2586,7 @@ fn test_get_full_package_with_categories() {
                 readme: Some("Test README".into()),
                 forc_manifest: TEST_MANIFEST.into(),
                 docs_ipfs_hash: Some("test-docs-hash".into()),
+                workspace_upload_id: None,
             })
             .expect("upload is ok");
 
@@ -1003,6 +2670,7 @@ fn test_get_categories_and_keywords_for_packages() {
                 readme: None,
                 forc_manifest: TEST_MANIFEST.into(),
                 docs_ipfs_hash: Some("test-docs-hash".into()),
+                workspace_upload_id: None,
             })
             .expect("upload is ok");
 
@@ -1115,6 +2783,7 @@ fn test_documentation_functionality() {
                     readme: Some(TEST_README.to_string()),
                     forc_manifest: TEST_MANIFEST.to_string(),
                     docs_ipfs_hash: Some("QmDocsHash789".to_string()),
+                    workspace_upload_id: None,
                 })
                 .expect("upload is ok");
             Ok::<_, diesel::result::Error>((token, user, upload))
@@ -1177,6 +2846,7 @@ fn test_documentation_functionality() {
             readme: None,
             forc_manifest: TEST_MANIFEST.to_string(),
             docs_ipfs_hash: None, // No documentation
+            workspace_upload_id: None,
         };
         let saved_upload_no_docs = conn.new_upload(&upload_without_docs)?;
 
@@ -1196,6 +2866,7 @@ fn test_documentation_functionality() {
 #[serial]
 fn test_api_documentation_serialization() {
     use forc_pub::api::search::FullPackage as ApiFullPackage;
+    use forc_pub::file_uploader::storage::FileUrls;
 
     let db = setup_db();
 
@@ -1219,6 +2890,7 @@ fn test_api_documentation_serialization() {
                     readme: Some(TEST_README.to_string()),
                     forc_manifest: TEST_MANIFEST.to_string(),
                     docs_ipfs_hash: Some("QmDocsHash789".to_string()),
+                    workspace_upload_id: None,
                 })
                 .expect("upload is ok");
             Ok::<_, diesel::result::Error>((token, user, upload))
@@ -1249,7 +2921,8 @@ fn test_api_documentation_serialization() {
         )?;
 
         // Convert to API representation
-        let api_package = ApiFullPackage::from(db_package);
+        let file_urls = FileUrls::Gateway("https://test-pinata.com".to_string());
+        let api_package = ApiFullPackage::new(db_package, &file_urls);
 
         // Verify that docs_ipfs_url is correctly generated
         assert!(api_package.docs_ipfs_url.is_some());
@@ -1273,3 +2946,184 @@ fn test_api_documentation_serialization() {
     })
     .unwrap();
 }
+
+#[test]
+#[serial]
+fn test_upload_jobs() {
+    let db = &mut setup_db();
+    let lease = chrono::Duration::minutes(30);
+    let new_upload = |id| NewUpload {
+        id,
+        forc_version: TEST_VERSION_1.into(),
+        source_code_ipfs_hash: "test-ipfs-hash".into(),
+        abi_ipfs_hash: None,
+        bytecode_identifier: None,
+        readme: None,
+        forc_manifest: TEST_MANIFEST.into(),
+        docs_ipfs_hash: None,
+        workspace_upload_id: None,
+    };
+
+    // A new job is pending and keeps its tarball.
+    let job_id = uuid::Uuid::new_v4();
+    let job = db
+        .transaction(|conn| {
+            conn.new_upload_job(
+                job_id,
+                TEST_VERSION_1.into(),
+                vec![1, 2, 3],
+                "Queued".into(),
+                false,
+            )
+        })
+        .expect("job is ok");
+    assert_eq!(job.status().unwrap(), UploadJobStatus::Pending);
+    assert_eq!(job.attempts, 0);
+    assert_eq!(
+        db.transaction(|conn| conn.get_upload_job_tarball(job_id))
+            .expect("tarball is ok"),
+        vec![1, 2, 3]
+    );
+
+    // Claiming the job marks it as running, and it can't be claimed again.
+    let job = db
+        .transaction(|conn| conn.claim_upload_job(lease))
+        .expect("claim is ok")
+        .expect("job is claimed");
+    assert_eq!(job.id, job_id);
+    assert_eq!(job.status().unwrap(), UploadJobStatus::Running);
+    assert_eq!(job.attempts, 1);
+    assert!(job.locked_at.is_some());
+    assert!(db
+        .transaction(|conn| conn.claim_upload_job(lease))
+        .expect("claim is ok")
+        .is_none());
+
+    // Progress messages are visible to readers.
+    db.transaction(|conn| conn.set_upload_job_message(job_id, "Building".into()))
+        .expect("message is ok");
+    let job = db
+        .transaction(|conn| conn.get_upload_job(job_id.to_string()))
+        .expect("job is ok");
+    assert_eq!(job.message.as_deref(), Some("Building"));
+
+    // Renewing the lease keeps the message, and keeps the job from being claimed again.
+    let locked_at = job.locked_at;
+    db.transaction(|conn| conn.renew_upload_job_lease(job_id))
+        .expect("renewal is ok");
+    let job = db
+        .transaction(|conn| conn.get_upload_job(job_id.to_string()))
+        .expect("job is ok");
+    assert_eq!(job.message.as_deref(), Some("Building"));
+    assert!(job.locked_at > locked_at);
+    assert!(db
+        .transaction(|conn| conn.claim_upload_job(lease))
+        .expect("claim is ok")
+        .is_none());
+
+    // A job that is retried later isn't claimed before then.
+    let retry_at = Utc::now() + chrono::Duration::minutes(5);
+    let job = db
+        .transaction(|conn| {
+            conn.fail_upload_job(job_id, "\"SaveFile\"".into(), None, Some(retry_at))
+        })
+        .expect("fail is ok");
+    assert_eq!(job.status().unwrap(), UploadJobStatus::Pending);
+    assert!(job.locked_at.is_none());
+    assert!(db
+        .transaction(|conn| conn.claim_upload_job(lease))
+        .expect("claim is ok")
+        .is_none());
+
+    // A job that is retried now is claimed again.
+    db.transaction(|conn| {
+        conn.fail_upload_job(job_id, "\"SaveFile\"".into(), None, Some(Utc::now()))
+    })
+    .expect("fail is ok");
+    let job = db
+        .transaction(|conn| conn.claim_upload_job(lease))
+        .expect("claim is ok")
+        .expect("job is claimed");
+    assert_eq!(job.attempts, 2);
+
+    // A running job whose lease expired is claimed again.
+    let job = db
+        .transaction(|conn| conn.claim_upload_job(chrono::Duration::seconds(-1)))
+        .expect("claim is ok")
+        .expect("job is claimed");
+    assert_eq!(job.id, job_id);
+    assert_eq!(job.attempts, 3);
+
+    // Completing the job stores its uploads and clears the tarball.
+    let upload_id = uuid::Uuid::new_v4();
+    let job = db
+        .transaction(|conn| conn.complete_upload_job(job_id, &[new_upload(upload_id)]))
+        .expect("complete is ok");
+    assert_eq!(job.status().unwrap(), UploadJobStatus::Succeeded);
+    assert!(job.error.is_none());
+    assert!(db
+        .transaction(|conn| conn.get_upload_job_tarball(job_id))
+        .is_err());
+    db.transaction(|conn| conn.get_upload(upload_id))
+        .expect("upload is ok");
+
+    // A job that fails permanently keeps its error and isn't claimed again.
+    let job_id = uuid::Uuid::new_v4();
+    db.transaction(|conn| {
+        conn.new_upload_job(
+            job_id,
+            TEST_VERSION_1.into(),
+            vec![],
+            "Queued".into(),
+            false,
+        )
+    })
+    .expect("job is ok");
+    db.transaction(|conn| conn.claim_upload_job(lease))
+        .expect("claim is ok")
+        .expect("job is claimed");
+    let err = UploadError::FailedToCompile("error: expected `;`".into());
+    let job = db
+        .transaction(|conn| {
+            conn.fail_upload_job(
+                job_id,
+                serde_json::to_string(&err).unwrap(),
+                err.diagnostics().map(str::to_string),
+                None,
+            )
+        })
+        .expect("fail is ok");
+    assert_eq!(job.status().unwrap(), UploadJobStatus::Failed);
+    assert_eq!(job.upload_error(), Some(err));
+    assert_eq!(job.diagnostics.as_deref(), Some("error: expected `;`"));
+    assert!(db
+        .transaction(|conn| conn.claim_upload_job(lease))
+        .expect("claim is ok")
+        .is_none());
+
+    // Unknown jobs are not found.
+    assert!(db
+        .transaction(|conn| conn.get_upload_job(uuid::Uuid::new_v4().to_string()))
+        .is_err());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_readiness_checks_database() {
+    use forc_pub::api::readiness::{ComponentReadiness, ComponentStatus};
+    use forc_pub::handlers::readiness::check_database;
+
+    let db = setup_db();
+    assert_eq!(check_database(&db).await, ComponentReadiness::ok());
+
+    // An unreachable database is reported instead of panicking.
+    let manager = diesel::r2d2::ConnectionManager::new("postgres://postgres@127.0.0.1:1/none");
+    let unreachable = Database {
+        pool: diesel::r2d2::Pool::builder().build_unchecked(manager),
+    };
+    let readiness = check_database(&unreachable).await;
+    assert_eq!(readiness.status, ComponentStatus::Unavailable);
+    assert!(readiness
+        .error
+        .is_some_and(|err| err.contains("Failed to connect to the database")));
+}